@@ -0,0 +1,141 @@
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::policy::Cidr;
+
+// Accepted files are a YAML list of `HOST:PORT` findings that are known and approved.
+// Hosts may be CIDR groups, ports may be ranges, and IPv6 hosts are written in brackets:
+//
+// # jump host, approved in ticket-1234
+// - 192.168.1.10:22
+// - 10.0.0.0/24:8000-8010
+// - "[2001:db8::1]:443"
+
+/// Findings that are known and approved, suppressed from watch changes and policy
+/// violations.
+#[derive(Debug)]
+pub struct Accepted {
+    entries: Vec<(Cidr, u16, u16)>,
+}
+
+impl Accepted {
+    /// Loads and parses an accepted file.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to read accepted file" if the file cannot be read.
+    /// * Any error returned by `Accepted::parse`.
+    pub fn load(path: &str) -> Result<Accepted, &'static str> {
+        let contents = fs::read_to_string(path).map_err(|_| "failed to read accepted file")?;
+        Accepted::parse(&contents)
+    }
+
+    /// Parses the contents of an accepted file.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid line in accepted file" if a line is not a `- HOST:PORT` item.
+    /// * "not a valid IP address or CIDR" if a host cannot be parsed.
+    /// * "invalid port in accepted file" if a port or port range cannot be parsed.
+    pub fn parse(contents: &str) -> Result<Accepted, &'static str> {
+        let mut entries = vec![];
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let item = line
+                .strip_prefix('-')
+                .ok_or("invalid line in accepted file")?
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'');
+            let (host, ports) = item
+                .rsplit_once(':')
+                .ok_or("invalid line in accepted file")?;
+            let host = host
+                .strip_prefix('[')
+                .and_then(|host| host.strip_suffix(']'))
+                .unwrap_or(host);
+
+            let cidr = Cidr::from_str(host)?;
+            let (first, last) = match ports.split_once('-') {
+                Some((first, last)) => (first.trim(), last.trim()),
+                None => (ports, ports),
+            };
+            let first: u16 = first.parse().map_err(|_| "invalid port in accepted file")?;
+            let last: u16 = last.parse().map_err(|_| "invalid port in accepted file")?;
+
+            if first == 0 || first > last {
+                return Err("invalid port in accepted file");
+            }
+            entries.push((cidr, first, last));
+        }
+
+        Ok(Accepted { entries })
+    }
+
+    /// Returns whether a finding on `port` of `addr` is accepted.
+    pub fn contains(&self, addr: IpAddr, port: u16) -> bool {
+        self.entries
+            .iter()
+            .any(|(cidr, first, last)| cidr.contains(addr) && (*first..=*last).contains(&port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_accepted_findings() {
+        let accepted = Accepted::parse(
+            "# jump host, approved in ticket-1234\n\
+             - 192.168.1.10:22\n\
+             \n\
+             - 10.0.0.0/24:8000-8010  # dev servers\n\
+             - \"[2001:db8::1]:443\"\n\
+             - '172.16.0.1:80'\n",
+        )
+        .unwrap();
+
+        assert!(accepted.contains(addr("192.168.1.10"), 22));
+        assert!(!accepted.contains(addr("192.168.1.10"), 23));
+        assert!(!accepted.contains(addr("192.168.1.11"), 22));
+        assert!(accepted.contains(addr("10.0.0.200"), 8000));
+        assert!(accepted.contains(addr("10.0.0.200"), 8010));
+        assert!(!accepted.contains(addr("10.0.0.200"), 8011));
+        assert!(!accepted.contains(addr("10.0.1.1"), 8005));
+        assert!(accepted.contains(addr("2001:db8::1"), 443));
+        assert!(accepted.contains(addr("172.16.0.1"), 80));
+    }
+
+    #[test]
+    fn accepts_empty_files() {
+        let accepted = Accepted::parse("# nothing yet\n\n").unwrap();
+        assert!(!accepted.contains(addr("10.0.0.1"), 22));
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let cases = [
+            ("192.168.1.10:22", "invalid line in accepted file"),
+            ("- 192.168.1.10", "invalid line in accepted file"),
+            ("- 192.168.1:22", "not a valid IP address or CIDR"),
+            ("- 192.168.1.10:0", "invalid port in accepted file"),
+            ("- 192.168.1.10:65536", "invalid port in accepted file"),
+            ("- 192.168.1.10:ssh", "invalid port in accepted file"),
+            ("- 192.168.1.10:90-80", "invalid port in accepted file"),
+        ];
+        for (line, error) in cases {
+            assert_eq!(Accepted::parse(line).err(), Some(error), "{}", line);
+        }
+    }
+}
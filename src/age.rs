@@ -0,0 +1,274 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use ip_sniffer::base64;
+
+use crate::crypto::{self, BASEPOINT};
+use crate::hash;
+
+// `--encrypt-to` writes the scan's output as an age file (age-encryption.org/v1), which
+// the `age` and `rage` tools decrypt with the recipient's identity:
+//
+// age-encryption.org/v1
+// -> X25519 <ephemeral public key>       one stanza per recipient, wrapping the file key
+// <wrapped file key>
+// --- <header MAC>
+// <16-byte nonce><payload>
+//
+// The payload is cut into 64 KiB chunks, each sealed with ChaCha20-Poly1305 under a key
+// derived from the file key and the nonce; the last chunk is marked, so a truncated file
+// does not decrypt. Only X25519 recipients (`age1...`) are supported.
+
+const INTRO: &str = "age-encryption.org/v1";
+const X25519_INFO: &[u8] = b"age-encryption.org/v1/X25519";
+
+/// Plaintext bytes in each payload chunk.
+const CHUNK: usize = 64 * 1024;
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+/// An age X25519 recipient: the public key an output is encrypted to.
+#[derive(Clone, Debug)]
+pub struct Recipient([u8; 32]);
+
+impl FromStr for Recipient {
+    type Err = &'static str;
+
+    /// Parses a Bech32 public key such as `age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p`.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid recipient; must be an age X25519 public key (age1...)" if it is not one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "invalid recipient; must be an age X25519 public key (age1...)";
+        let data = s
+            .to_ascii_lowercase()
+            .strip_prefix("age1")
+            .ok_or(invalid)?
+            .bytes()
+            .map(|c| BECH32_CHARSET.iter().position(|&d| d == c).map(|i| i as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(invalid)?;
+        if data.len() < 6 {
+            return Err(invalid);
+        }
+
+        let mut checked = vec![3, 3, 3, 0, 1, 7, 5];
+        checked.extend(&data);
+        if bech32_polymod(&checked) != 1 {
+            return Err(invalid);
+        }
+
+        // Regroup the 5-bit values, less the checksum, into bytes.
+        let mut key = vec![];
+        let (mut acc, mut bits) = (0u32, 0);
+        for value in &data[..data.len() - 6] {
+            acc = (acc << 5) | *value as u32;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                key.push((acc >> bits) as u8);
+            }
+        }
+        key.try_into().map(Recipient).map_err(|_| invalid)
+    }
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in BECH32_GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Reads the recipients of a file with one public key per line, as `age -R` does. Blank
+/// lines and lines starting with `#` are skipped.
+///
+/// # Errors
+///
+/// * "failed to read recipients file" if the file cannot be read.
+/// * "no recipients in recipients file" if it has none.
+/// * Any error returned by `Recipient::from_str`.
+pub fn load_recipients(path: &str) -> Result<Vec<Recipient>, &'static str> {
+    let contents = fs::read_to_string(path).map_err(|_| "failed to read recipients file")?;
+    let recipients = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Recipient::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if recipients.is_empty() {
+        return Err("no recipients in recipients file");
+    }
+    Ok(recipients)
+}
+
+/// Encrypts everything read from `input` to `recipients`, writing the age file to
+/// `output` a chunk at a time.
+///
+/// # Errors
+///
+/// * "failed to read random bytes from /dev/urandom" if no keys can be generated.
+/// * "encryption is only supported on Unix" on other systems, which have no
+///   /dev/urandom.
+/// * "failed to read output to encrypt" if `input` fails.
+/// * "failed to write encrypted output" if `output` fails.
+pub fn encrypt(
+    recipients: &[Recipient],
+    input: &mut impl Read,
+    output: &mut impl Write,
+) -> Result<(), &'static str> {
+    let write_failed = "failed to write encrypted output";
+    let file_key: [u8; 16] = random()?;
+
+    let mut header = format!("{}\n", INTRO);
+    for recipient in recipients {
+        let ephemeral: [u8; 32] = random()?;
+        let share = crypto::x25519(&ephemeral, &BASEPOINT);
+        let shared = crypto::x25519(&ephemeral, &recipient.0);
+
+        let mut salt = share.to_vec();
+        salt.extend_from_slice(&recipient.0);
+        let wrap_key = hash::hkdf_sha256(&shared, &salt, X25519_INFO);
+        let wrapped = crypto::chacha20poly1305_seal(&wrap_key, &[0; 12], &file_key);
+
+        header.push_str(&format!("-> X25519 {}\n", unpadded(&share)));
+        header.push_str(&format!("{}\n", unpadded(&wrapped)));
+    }
+    header.push_str("---");
+    let mac_key = hash::hkdf_sha256(&file_key, &[], b"header");
+    let mac = hash::hmac_sha256(&mac_key, header.as_bytes());
+    header.push_str(&format!(" {}\n", unpadded(&mac)));
+
+    let nonce: [u8; 16] = random()?;
+    let payload_key = hash::hkdf_sha256(&file_key, &nonce, b"payload");
+    output
+        .write_all(header.as_bytes())
+        .and_then(|_| output.write_all(&nonce))
+        .map_err(|_| write_failed)?;
+
+    // A chunk is only sealed once more input follows it, so the last one can be marked.
+    let mut pending = vec![];
+    let mut buf = vec![0; CHUNK];
+    let mut counter = 0u64;
+    loop {
+        let n = input
+            .read(&mut buf)
+            .map_err(|_| "failed to read output to encrypt")?;
+        if n == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buf[..n]);
+
+        while pending.len() > CHUNK {
+            let chunk: Vec<u8> = pending.drain(..CHUNK).collect();
+            let sealed = seal_chunk(&payload_key, counter, false, &chunk);
+            output.write_all(&sealed).map_err(|_| write_failed)?;
+            output.flush().map_err(|_| write_failed)?;
+            counter += 1;
+        }
+    }
+
+    let sealed = seal_chunk(&payload_key, counter, true, &pending);
+    output
+        .write_all(&sealed)
+        .and_then(|_| output.flush())
+        .map_err(|_| write_failed)
+}
+
+/// Seals a payload chunk; its nonce is the chunk's 11-byte counter and a last-chunk flag.
+fn seal_chunk(key: &[u8; 32], counter: u64, last: bool, chunk: &[u8]) -> Vec<u8> {
+    let mut nonce = [0; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    crypto::chacha20poly1305_seal(key, &nonce, chunk)
+}
+
+/// Encodes `data` as base64 without padding, as age headers are.
+fn unpadded(data: &[u8]) -> String {
+    base64::encode(data).trim_end_matches('=').to_string()
+}
+
+#[cfg(unix)]
+fn random<const N: usize>() -> Result<[u8; N], &'static str> {
+    use std::fs::File;
+
+    let mut bytes = [0; N];
+    File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .map_err(|_| "failed to read random bytes from /dev/urandom")?;
+    Ok(bytes)
+}
+
+#[cfg(not(unix))]
+fn random<const N: usize>() -> Result<[u8; N], &'static str> {
+    Err("encryption is only supported on Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks a Bech32 string as BIP-173 does: the human-readable part, expanded, and the
+    /// data part must have a checksum of 1.
+    fn bech32_valid(s: &str) -> bool {
+        let s = s.to_ascii_lowercase();
+        let Some((hrp, data)) = s.rsplit_once('1') else {
+            return false;
+        };
+        let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+        values.push(0);
+        values.extend(hrp.bytes().map(|c| c & 31));
+        for c in data.bytes() {
+            match BECH32_CHARSET.iter().position(|&d| d == c) {
+                Some(value) => values.push(value as u8),
+                None => return false,
+            }
+        }
+        data.len() >= 6 && bech32_polymod(&values) == 1
+    }
+
+    #[test]
+    fn bech32_bip173_vectors() {
+        let valid = [
+            "A12UEL5L",
+            "a12uel5l",
+            "an83characterlonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1tt5tgs",
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+            "11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqc8247j",
+            "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+            "?1ezyfcl",
+        ];
+        for s in valid {
+            assert!(bech32_valid(s), "{}", s);
+        }
+        // Changing any one character breaks the checksum.
+        assert!(!bech32_valid("a12uel5m"));
+        assert!(!bech32_valid(
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxx"
+        ));
+    }
+
+    #[test]
+    fn parses_age_recipients() {
+        let recipient: Recipient = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"
+            .parse()
+            .unwrap();
+        assert_eq!(recipient.0[0], 0x07);
+
+        let invalid = "invalid recipient; must be an age X25519 public key (age1...)";
+        let typo = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8q";
+        assert_eq!(typo.parse::<Recipient>().unwrap_err(), invalid);
+        assert_eq!("npub1xyz".parse::<Recipient>().unwrap_err(), invalid);
+    }
+}
@@ -0,0 +1,162 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::notify;
+use crate::output::json_string;
+use crate::risk::{Finding, Level};
+
+// `--alert LEVEL=SERVICE:KEY` opens an incident for every open port whose risk level is
+// LEVEL or above:
+//
+// critical=pagerduty:ROUTING_KEY    Events API v2 trigger, with a PagerDuty severity
+// high=opsgenie:API_KEY             Alert API alert, with a P1-P5 priority
+//
+// Incidents are keyed by host and port, so a port that stays open across scans keeps one
+// incident. In watch mode, a port that closes resolves its incident. Both APIs are HTTPS
+// only, so requests are posted with curl, as `--notify` does; keys are given to curl on
+// stdin, never on its command line.
+
+const PAGERDUTY_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const OPSGENIE_URL: &str = "https://api.opsgenie.com/v2/alerts";
+
+/// An incident management service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Service {
+    PagerDuty,
+    Opsgenie,
+}
+
+/// A rule opening incidents for findings at or above a risk level.
+#[derive(Clone, Debug)]
+pub struct Alert {
+    pub level: Level,
+    service: Service,
+    /// The PagerDuty routing key or Opsgenie API key.
+    key: String,
+}
+
+impl FromStr for Alert {
+    type Err = &'static str;
+
+    /// Parses `LEVEL=pagerduty:ROUTING_KEY` or `LEVEL=opsgenie:API_KEY`.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid alert; must be LEVEL=pagerduty:KEY or LEVEL=opsgenie:KEY" if it is not.
+    /// * Any error returned by `Level::from_str` if the level is unknown.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "invalid alert; must be LEVEL=pagerduty:KEY or LEVEL=opsgenie:KEY";
+        let (level, target) = s.split_once('=').ok_or(invalid)?;
+        let (service, key) = target.split_once(':').ok_or(invalid)?;
+
+        let service = match service {
+            "pagerduty" => Service::PagerDuty,
+            "opsgenie" => Service::Opsgenie,
+            _ => return Err(invalid),
+        };
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(invalid);
+        }
+
+        Ok(Alert {
+            level: Level::from_str(level)?,
+            service,
+            key: key.to_string(),
+        })
+    }
+}
+
+impl Alert {
+    /// Returns whether `finding` is severe enough to open an incident.
+    pub fn applies_to(&self, finding: &Finding) -> bool {
+        finding.level >= self.level
+    }
+
+    /// Opens an incident for an open port of `addr`, or updates the one already open.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to run curl; is it installed?" if curl cannot be started.
+    /// * "incident service rejected the alert" if the request fails.
+    pub fn trigger(&self, addr: IpAddr, finding: &Finding) -> Result<(), &'static str> {
+        let summary = format!(
+            "port {} is open on {} ({} risk)",
+            finding.port, addr, finding.level
+        );
+
+        match self.service {
+            Service::PagerDuty => {
+                let severity = match finding.level {
+                    Level::Critical => "critical",
+                    Level::High => "error",
+                    Level::Medium => "warning",
+                    Level::Low | Level::Info => "info",
+                };
+                let body = format!(
+                    r#"{{"routing_key":{},"event_action":"trigger","dedup_key":{},"payload":{{"summary":{},"source":"{}","severity":"{}","component":"port {}","class":"open port","custom_details":{{"risk":"{}"}}}}}}"#,
+                    json_string(&self.key),
+                    json_string(&incident_key(addr, finding.port)),
+                    json_string(&summary),
+                    addr,
+                    severity,
+                    finding.port,
+                    finding.level
+                );
+                notify::post(PAGERDUTY_URL, &[], &body, REJECTED)
+            }
+            Service::Opsgenie => {
+                let priority = match finding.level {
+                    Level::Critical => "P1",
+                    Level::High => "P2",
+                    Level::Medium => "P3",
+                    Level::Low => "P4",
+                    Level::Info => "P5",
+                };
+                let body = format!(
+                    r#"{{"message":{},"alias":{},"source":"ip-sniffer","entity":"{}","priority":"{}","tags":["ip-sniffer","risk:{}"]}}"#,
+                    json_string(&summary),
+                    json_string(&incident_key(addr, finding.port)),
+                    addr,
+                    priority,
+                    finding.level
+                );
+                let auth = format!("Authorization: GenieKey {}", self.key);
+                notify::post(OPSGENIE_URL, &[&auth], &body, REJECTED)
+            }
+        }
+    }
+
+    /// Resolves the incident of a port of `addr` that has closed.
+    ///
+    /// # Errors
+    ///
+    /// As `trigger`.
+    pub fn resolve(&self, addr: IpAddr, port: u16) -> Result<(), &'static str> {
+        let key = incident_key(addr, port);
+
+        match self.service {
+            Service::PagerDuty => {
+                let body = format!(
+                    r#"{{"routing_key":{},"event_action":"resolve","dedup_key":{}}}"#,
+                    json_string(&self.key),
+                    json_string(&key)
+                );
+                notify::post(PAGERDUTY_URL, &[], &body, REJECTED)
+            }
+            Service::Opsgenie => {
+                let url = format!("{}/{}/close?identifierType=alias", OPSGENIE_URL, key);
+                let body = format!(r#"{{"source":"ip-sniffer","note":"port {} closed"}}"#, port);
+                let auth = format!("Authorization: GenieKey {}", self.key);
+                notify::post(&url, &[&auth], &body, REJECTED)
+            }
+        }
+    }
+}
+
+const REJECTED: &str = "incident service rejected the alert";
+
+/// Returns the key incidents of a port are deduplicated by, e.g. `ip-sniffer-10.0.0.5-3389`.
+fn incident_key(addr: IpAddr, port: u16) -> String {
+    // Opsgenie aliases go into a URL path, so IPv6 colons are replaced.
+    format!("ip-sniffer-{}-{}", addr.to_string().replace(':', "."), port)
+}
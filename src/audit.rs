@@ -0,0 +1,232 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ip_sniffer::events::Event;
+
+use crate::check::{self, Status};
+use crate::i18n::Lang;
+
+// `--audit-log` appends every probe of a scan, and every port found open, to a file so
+// the scan can be re-issued later with `replay`. Each scan starts a block:
+//
+// # ip-sniffer audit log, started 1791279167.042
+// 0 probe 10.0.0.5 1
+// 0 probe 10.0.0.5 2
+// 14 open 10.0.0.5 22
+//
+// The first field is milliseconds since the start of the block, whose header gives the
+// UNIX time it started at. Retries and retransmits are probes of their own. Jobs and
+// multi-target scans run each target as its own scan, so they append a block per
+// target.
+//
+// A probe is identified by its block, destination and time; there is no probe ID on
+// the wire. A connect scan cannot learn the source port of a SYN before the kernel
+// sends it, nor at all if the connection fails, so the packets of a probe in a
+// `--pcap` capture are found as the SYNs to its address and port at the block's start
+// plus its time.
+
+/// How long a replayed probe may take before the port is reported as filtered.
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Records the probes of a scan as they are made.
+///
+/// # Usage
+///
+/// * `AuditLog::open(path)` - Start a block at the end of an audit log.
+/// * `log.record(&event)` - Log a `ProbeSent` or `PortOpen` event; others are ignored.
+pub struct AuditLog {
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path` for appending, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to open audit log" if the file cannot be opened or written.
+    pub fn open(path: &str) -> Result<AuditLog, &'static str> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|_| "failed to open audit log")?;
+        let mut file = BufWriter::new(file);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let (secs, millis) = (now.as_secs(), now.subsec_millis());
+        writeln!(
+            file,
+            "# ip-sniffer audit log, started {}.{:03}",
+            secs, millis
+        )
+        .map_err(|_| "failed to open audit log")?;
+
+        Ok(AuditLog {
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        let (kind, addr, port) = match event {
+            Event::ProbeSent { addr, port } => ("probe", addr, *port),
+            Event::PortOpen { addr, open } => ("open", addr, open.port),
+            // The scan may exit without dropping the log, e.g. on policy violations.
+            Event::HostDone { .. } => {
+                self.file.flush().ok();
+                return;
+            }
+            _ => return,
+        };
+        let elapsed = self.started.elapsed().as_millis();
+        // A failed write must not stop the scan; what was logged is still replayable.
+        writeln!(self.file, "{} {} {} {}", elapsed, kind, addr, port).ok();
+    }
+}
+
+/// A probe read back from an audit log.
+struct Probe {
+    /// Milliseconds since the start of its block.
+    at: u64,
+    /// The number of its block in the log.
+    block: usize,
+    target: SocketAddr,
+}
+
+/// Runs the `replay` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `replay` on the command line.
+/// * `lang` - The language results are printed in.
+///
+/// # Returns
+///
+/// The exit code: 0 if every port is in the state the log recorded, 2 if any is not.
+///
+/// # Errors
+///
+/// * "missing audit log" if no log, or more than one, is given.
+/// * "failed to read audit log" if the log cannot be read.
+/// * "invalid line in audit log" if a line is not a probe or open port record.
+///
+/// # Usage
+///
+/// * `replay <FILE>` - Re-issue every probe of an audit log, in order.
+/// * `replay <FILE> --only-open` - Re-issue only the probes of ports the log found open.
+/// * `replay <FILE> --timing` - Wait between probes as long as the logged scan did.
+///
+/// # Description
+///
+/// Probes are re-issued one at a time, so a replay of a whole scan takes far longer than
+/// the scan; `--only-open` replays just the findings. A port probed more than once in a
+/// block, by retries and retransmits, is replayed once, at its first probe. Each port is
+/// compared with what its own block logged. Every port found open and every port whose
+/// state differs from the log is printed.
+pub fn replay(args: &[String], lang: Lang) -> Result<i32, &'static str> {
+    let mut path = None;
+    let mut only_open = false;
+    let mut timing = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--only-open" => only_open = true,
+            "--timing" => timing = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err("missing audit log"),
+        }
+    }
+    let contents = fs::read_to_string(path.ok_or("missing audit log")?)
+        .map_err(|_| "failed to read audit log")?;
+
+    let mut probes = vec![];
+    let mut logged_open = BTreeSet::new();
+    // Retries and retransmits probe a port again within its block.
+    let mut probed = BTreeSet::new();
+    let mut block = 0;
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with('#') {
+            block += 1;
+            continue;
+        }
+        let (at, kind, target) = parse_line(line).ok_or("invalid line in audit log")?;
+        match kind {
+            "probe" => {
+                if probed.insert((block, target)) {
+                    probes.push(Probe { at, block, target });
+                }
+            }
+            _ => {
+                logged_open.insert((block, target));
+            }
+        }
+    }
+    if only_open {
+        probes.retain(|probe| logged_open.contains(&(probe.block, probe.target)));
+    }
+
+    let mut differ = 0;
+    // When the block being replayed started, shifted so its first probe is due now.
+    let mut clock = (usize::MAX, Instant::now());
+    for probe in &probes {
+        if timing {
+            if probe.block != clock.0 {
+                clock = (
+                    probe.block,
+                    Instant::now() - Duration::from_millis(probe.at),
+                );
+            }
+            let due = clock.1 + Duration::from_millis(probe.at);
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+        }
+
+        let was_open = logged_open.contains(&(probe.block, probe.target));
+        let state = match check::probe(probe.target, REPLAY_TIMEOUT) {
+            Ok((Status::Open, _)) => "open",
+            Ok((Status::Closed, _)) => "closed",
+            Ok((Status::Filtered, _)) => "filtered",
+            Err(err) => {
+                println!("{}: {}", probe.target, lang.tr(err));
+                continue;
+            }
+        };
+
+        let logged = if was_open { "open" } else { "not open" };
+        if (state == "open") != was_open {
+            differ += 1;
+            let message = "{} is {}, logged {}";
+            let (state, logged) = (lang.tr(state), lang.tr(logged));
+            println!(
+                "{}",
+                lang.format(message, &[&probe.target, &state, &logged])
+            );
+        } else if was_open {
+            println!("{}", lang.format("{} is open, as logged", &[&probe.target]));
+        }
+    }
+
+    let message = "\n{} probe(s) replayed, {} differ from the audit log";
+    println!("{}", lang.format(message, &[&probes.len(), &differ]));
+    Ok(if differ == 0 { 0 } else { 2 })
+}
+
+/// Parses a `MS KIND ADDR PORT` line of an audit log.
+fn parse_line(line: &str) -> Option<(u64, &str, SocketAddr)> {
+    let mut fields = line.split_whitespace();
+    let at = fields.next()?.parse().ok()?;
+    let kind = fields.next().filter(|k| *k == "probe" || *k == "open")?;
+    let addr = IpAddr::from_str(fields.next()?).ok()?;
+    let port = fields.next()?.parse().ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some((at, kind, SocketAddr::new(addr, port)))
+}
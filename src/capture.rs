@@ -0,0 +1,70 @@
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for the capture process to report that it is listening.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A running packet capture of the scanner's traffic, written to a pcap file.
+///
+/// The capture is delegated to `tcpdump`, which uses libpcap, so no raw-socket
+/// privileges are needed by the scanner itself; `tcpdump` does need them.
+pub struct Capture {
+    child: Child,
+}
+
+impl Capture {
+    /// Starts capturing all traffic to and from `addrs` into `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The pcap file to write.
+    /// * `addrs` - The IP addresses being scanned; used as the capture filter.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to start tcpdump" if `tcpdump` is not installed or could not be run.
+    /// * "tcpdump did not start listening" if it exited early, e.g. for lack of privileges.
+    pub fn start(path: &str, addrs: &[IpAddr]) -> Result<Capture, &'static str> {
+        let filter: Vec<String> = addrs.iter().map(|addr| format!("host {}", addr)).collect();
+        let mut child = Command::new("tcpdump")
+            .args(["-i", "any", "-U", "-n", "-w", path])
+            .arg(filter.join(" or "))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|_| "failed to start tcpdump")?;
+
+        // tcpdump announces "listening on ..." on stderr once packets are being captured;
+        // wait for it so the first probes are not missed.
+        let stderr = child.stderr.take().ok_or("failed to start tcpdump")?;
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                if line.contains("listening on") {
+                    let _ = tx.send(());
+                }
+            }
+        });
+
+        if rx.recv_timeout(STARTUP_TIMEOUT).is_err() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("tcpdump did not start listening");
+        }
+
+        Ok(Capture { child })
+    }
+
+    /// Stops the capture. Packets are written as they arrive (`-U`), so the file
+    /// is complete up to this point.
+    pub fn stop(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
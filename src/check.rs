@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ip_sniffer::scan;
+
+use crate::i18n::Lang;
+use crate::target;
+
+/// How long a port may take to answer before it is reported as filtered.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Maximum number of ports checked at the same time in batch mode.
+const BATCH_CONCURRENCY: usize = 64;
+
+/// The state of a checked port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Open,
+    Closed,
+    Filtered,
+}
+
+impl Status {
+    /// Exit code of `check` for a port in this state.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Status::Open => 0,
+            Status::Closed => 1,
+            Status::Filtered => 2,
+        }
+    }
+}
+
+/// Runs the `check` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `check` on the command line.
+/// * `lang` - The language results are printed in.
+///
+/// # Returns
+///
+/// The exit code: for a single port 0 if it is open, 1 if it is closed and 2 if it is
+/// filtered; for a batch 0 if every port is open and 1 otherwise.
+///
+/// # Errors
+///
+/// * "missing HOST:PORT" if no target is given.
+/// * "missing check file" if `--file` has no file name.
+/// * "failed to read check file" if the file cannot be read.
+/// * "too many arguments" if more than one target is given.
+/// * Any error returned by `target::parse_host_port` or `probe`.
+///
+/// # Usage
+///
+/// * `check <HOST:PORT>` - Connect to one port and print its state and latency.
+/// * `check --file <FILE>` - Check every `HOST:PORT` line of a file, or of stdin if
+///   `FILE` is `-`, concurrently and print a pass/fail line for each.
+pub fn run(args: &[String], lang: Lang) -> Result<i32, &'static str> {
+    let target = match args {
+        [flag, path] if flag == "--file" => return run_batch(path, lang),
+        [flag] if flag == "--file" => return Err("missing check file"),
+        [target] => target,
+        [] => return Err("missing HOST:PORT"),
+        _ => return Err("too many arguments"),
+    };
+
+    let addr = target::parse_host_port(target)?;
+    let (status, latency) = probe(addr, TIMEOUT)?;
+
+    let latency = format!("{:.1}", latency.as_secs_f64() * 1000.0);
+    let message = match status {
+        Status::Open => lang.format("{} is open ({}ms)", &[target, &latency]),
+        Status::Closed => lang.format("{} is closed ({}ms)", &[target, &latency]),
+        Status::Filtered => lang.format(
+            "{} is filtered (no answer within {}s)",
+            &[target, &TIMEOUT.as_secs()],
+        ),
+    };
+    println!("{}", message);
+
+    Ok(status.exit_code())
+}
+
+/// Checks every entry of a batch file and prints a pass/fail line for each, in file order.
+fn run_batch(path: &str, lang: Lang) -> Result<i32, &'static str> {
+    let contents = if path == "-" {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents).map(|_| contents)
+    } else {
+        fs::read_to_string(path)
+    }
+    .map_err(|_| "failed to read check file")?;
+
+    let targets: Vec<&str> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let mut failed = 0;
+
+    for chunk in targets.chunks(BATCH_CONCURRENCY) {
+        let results: Vec<Result<(Status, Duration), &str>> = thread::scope(|s| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|target| s.spawn(move || probe(target::parse_host_port(target)?, TIMEOUT)))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or(Err("check failed")))
+                .collect()
+        });
+
+        for (target, result) in chunk.iter().zip(results) {
+            let message = match result {
+                Ok((Status::Open, latency)) => {
+                    let latency = format!("{:.1}", latency.as_secs_f64() * 1000.0);
+                    lang.format("PASS {} is open ({}ms)", &[target, &latency])
+                }
+                Ok((Status::Closed, _)) => lang.format("FAIL {} is closed", &[target]),
+                Ok((Status::Filtered, _)) => lang.format("FAIL {} is filtered", &[target]),
+                Err(err) => lang.format("FAIL {}: {}", &[target, &lang.tr(err)]),
+            };
+            println!("{}", message);
+
+            if !matches!(result, Ok((Status::Open, _))) {
+                failed += 1;
+            }
+        }
+    }
+
+    let passed = targets.len() - failed;
+    println!(
+        "{}",
+        lang.format("\n{} passed, {} failed", &[&passed, &failed])
+    );
+    Ok(if failed == 0 { 0 } else { 1 })
+}
+
+/// Connects to `addr` once.
+///
+/// # Returns
+///
+/// The state of the port and how long it took to find out.
+///
+/// # Errors
+///
+/// * "local network error" if the connection failed because of our own network, in
+///   which case nothing is known about the port.
+pub fn probe(addr: SocketAddr, timeout: Duration) -> Result<(Status, Duration), &'static str> {
+    let started = Instant::now();
+
+    let status = match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(_) => Status::Open,
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => Status::Closed,
+        Err(err) if scan::is_local_error(&err) => return Err("local network error"),
+        Err(_) => Status::Filtered,
+    };
+
+    Ok((status, started.elapsed()))
+}
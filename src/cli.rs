@@ -0,0 +1,130 @@
+use crate::HELP;
+
+// The help text is the definition of the command line: every line of its first section is
+// `NAMES [<VALUE>] to DESCRIPTION`, and every line of the later sections is
+// `COMMAND [SUBCOMMAND] [<ARG>...] to DESCRIPTION`. Completions and the man page are
+// generated from it so they cannot drift from what `-h` shows.
+
+/// An option of the scan, as described in the help text.
+pub struct Flag {
+    /// Every spelling of the option, e.g. `-h` and `-help`.
+    pub names: Vec<&'static str>,
+    /// The placeholder of the option's value, e.g. `FILE` or `text|jsonl`.
+    pub value: Option<&'static str>,
+    pub description: &'static str,
+}
+
+/// What kind of value an option takes, for completion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueKind {
+    None,
+    File,
+    Choices,
+    Free,
+}
+
+impl Flag {
+    pub fn value_kind(&self) -> ValueKind {
+        match self.value {
+            None => ValueKind::None,
+            Some("FILE") => ValueKind::File,
+            Some(value) if value.contains('|') => ValueKind::Choices,
+            Some(_) => ValueKind::Free,
+        }
+    }
+
+    /// The values the option accepts, if it takes one of a fixed set.
+    pub fn choices(&self) -> Vec<&'static str> {
+        match self.value_kind() {
+            ValueKind::Choices => self.value.unwrap_or_default().split('|').collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// A subcommand, as described in the help text.
+pub struct Command {
+    /// The words naming the command, e.g. `project` and `add-target`.
+    pub words: Vec<&'static str>,
+    /// The placeholders of the command's arguments, e.g. `<DIR>` or `[--note <TEXT>]`.
+    pub args: &'static str,
+    pub description: &'static str,
+}
+
+/// Returns the options listed in the help text.
+pub fn flags() -> Vec<Flag> {
+    sections()
+        .into_iter()
+        .next()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(usage, description)| {
+            let mut names = vec![];
+            let mut value = None;
+
+            for word in usage.split_whitespace() {
+                if word.starts_with('-') {
+                    names.push(word);
+                } else if let Some(placeholder) = word.strip_prefix('<') {
+                    value = placeholder.strip_suffix('>');
+                }
+            }
+
+            Flag {
+                names,
+                value,
+                description,
+            }
+        })
+        .collect()
+}
+
+/// Returns the subcommands listed in the help text.
+pub fn commands() -> Vec<Command> {
+    sections()
+        .into_iter()
+        .skip(1)
+        .flatten()
+        .map(|(usage, description)| {
+            let args = usage.find(['<', '[']).map_or("", |i| usage[i..].trim_end());
+            let words = usage[..usage.len() - args.len()]
+                .split_whitespace()
+                .collect();
+
+            Command {
+                words,
+                args,
+                description,
+            }
+        })
+        .collect()
+}
+
+/// Returns the names of the top-level subcommands, e.g. `project`.
+pub fn command_names() -> Vec<&'static str> {
+    let mut names: Vec<&str> = commands().iter().map(|c| c.words[0]).collect();
+    names.dedup();
+    names
+}
+
+/// Returns the subcommands of `command`, e.g. `init` for `project`.
+pub fn subcommands(command: &str) -> Vec<&'static str> {
+    commands()
+        .iter()
+        .filter(|c| c.words[0] == command)
+        .filter_map(|c| c.words.get(1).copied())
+        .collect()
+}
+
+/// Splits the help text into its blank-line separated sections of `(usage, description)`
+/// pairs, leaving out the section titles.
+fn sections() -> Vec<Vec<(&'static str, &'static str)>> {
+    HELP.split("\n\n")
+        .map(|section| {
+            section
+                .lines()
+                .filter_map(|line| line.split_once(" to "))
+                .collect()
+        })
+        .collect()
+}
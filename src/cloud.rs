@@ -0,0 +1,248 @@
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::json::Json;
+use crate::provider::{Target, TargetProvider};
+
+// Cloud inventories are listed with the provider's own command-line tool, which must be
+// installed and logged in, so no credentials pass through the scanner. `--cloud` takes
+// the provider and optional settings:
+//
+// aws:profile=prod,region=eu-west-1      aws ec2 describe-instances / describe-addresses
+// gcp:project=my-project                 gcloud compute instances list / addresses list
+// azure:subscription=1234-abcd           az network public-ip list
+
+/// Prefix of the links GCP gives its resources, dropped from resource IDs.
+const GCP_LINK_PREFIX: &str = "https://www.googleapis.com/compute/v1/";
+
+/// A cloud provider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Provider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+/// The public IP addresses of a cloud account, read with the provider's CLI.
+#[derive(Debug)]
+pub struct Inventory {
+    provider: Provider,
+    /// Settings passed to the CLI, e.g. `("profile", "prod")`.
+    settings: Vec<(String, String)>,
+}
+
+impl FromStr for Inventory {
+    type Err = &'static str;
+
+    /// Parses a `--cloud` value such as `aws:profile=prod,region=eu-west-1`.
+    ///
+    /// # Errors
+    ///
+    /// * "unknown cloud; must be aws, gcp or azure" if the provider is not one of those.
+    /// * "unknown cloud setting; aws takes profile and region, gcp takes project, azure
+    ///   takes subscription" if a setting does not apply to the provider.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, settings) = s.split_once(':').unwrap_or((s, ""));
+        let provider = match name {
+            "aws" => Provider::Aws,
+            "gcp" => Provider::Gcp,
+            "azure" => Provider::Azure,
+            _ => return Err("unknown cloud; must be aws, gcp or azure"),
+        };
+        let keys: &[&str] = match provider {
+            Provider::Aws => &["profile", "region"],
+            Provider::Gcp => &["project"],
+            Provider::Azure => &["subscription"],
+        };
+
+        let mut inventory = Inventory {
+            provider,
+            settings: vec![],
+        };
+        for setting in settings.split(',').filter(|s| !s.is_empty()) {
+            match setting.split_once('=') {
+                Some((key, value)) if keys.contains(&key) && !value.is_empty() => inventory
+                    .settings
+                    .push((key.to_string(), value.to_string())),
+                _ => {
+                    return Err("unknown cloud setting; aws takes profile and region, gcp \
+                                takes project, azure takes subscription")
+                }
+            }
+        }
+        Ok(inventory)
+    }
+}
+
+impl TargetProvider for Inventory {
+    fn describe(&self) -> String {
+        let name = match self.provider {
+            Provider::Aws => "aws",
+            Provider::Gcp => "gcp",
+            Provider::Azure => "azure",
+        };
+        let settings: Vec<String> = self
+            .settings
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        match settings.is_empty() {
+            true => name.to_string(),
+            false => format!("{}:{}", name, settings.join(",")),
+        }
+    }
+
+    /// Returns every public IP address of the account, with the ID of the resource it is
+    /// attached to, e.g. `aws i-0abc123`.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to run the cloud CLI; is it installed and logged in?" if the CLI cannot
+    ///   be started or exits with an error.
+    /// * "unexpected output from the cloud CLI" if its output is not the expected JSON.
+    ///
+    /// # Description
+    ///
+    /// * aws - the public IPv4 and IPv6 addresses of EC2 instances, and Elastic IPs,
+    ///   including those not attached to an instance.
+    /// * gcp - the external IPv4 and IPv6 addresses of Compute Engine instances, and
+    ///   reserved external addresses.
+    /// * azure - every public IP address resource that has an address assigned.
+    fn targets(&self) -> Result<Vec<Target>, &'static str> {
+        let mut found: Vec<(IpAddr, String)> = vec![];
+
+        match self.provider {
+            Provider::Aws => {
+                let instances = self.run("aws", &["ec2", "describe-instances"])?;
+                for reservation in field(&instances, "Reservations").as_array() {
+                    for instance in field(reservation, "Instances").as_array() {
+                        let id = text(instance, "InstanceId");
+                        let v6 = field(instance, "NetworkInterfaces")
+                            .as_array()
+                            .iter()
+                            .flat_map(|nic| field(nic, "Ipv6Addresses").as_array())
+                            .map(|addr| text(addr, "Ipv6Address"));
+                        for addr in [text(instance, "PublicIpAddress")].into_iter().chain(v6) {
+                            push(&mut found, addr, &format!("aws {}", id));
+                        }
+                    }
+                }
+
+                let addresses = self.run("aws", &["ec2", "describe-addresses"])?;
+                for address in field(&addresses, "Addresses").as_array() {
+                    let id = match text(address, "InstanceId") {
+                        "" => text(address, "AllocationId"),
+                        instance => instance,
+                    };
+                    push(
+                        &mut found,
+                        text(address, "PublicIp"),
+                        &format!("aws {}", id),
+                    );
+                }
+            }
+            Provider::Gcp => {
+                let instances = self.run("gcloud", &["compute", "instances", "list"])?;
+                for instance in instances.as_array() {
+                    let id = gcp_id(instance);
+                    for nic in field(instance, "networkInterfaces").as_array() {
+                        for config in field(nic, "accessConfigs").as_array() {
+                            push(&mut found, text(config, "natIP"), &id);
+                        }
+                        for config in field(nic, "ipv6AccessConfigs").as_array() {
+                            push(&mut found, text(config, "externalIpv6"), &id);
+                        }
+                    }
+                }
+
+                let addresses = self.run("gcloud", &["compute", "addresses", "list"])?;
+                for address in addresses.as_array() {
+                    if text(address, "addressType") == "EXTERNAL" {
+                        push(&mut found, text(address, "address"), &gcp_id(address));
+                    }
+                }
+            }
+            Provider::Azure => {
+                let addresses = self.run("az", &["network", "public-ip", "list"])?;
+                for address in addresses.as_array() {
+                    let id = format!("azure {}", text(address, "id"));
+                    push(&mut found, text(address, "ipAddress"), &id);
+                }
+            }
+        }
+
+        Ok(found
+            .into_iter()
+            .map(|(addr, resource)| Target {
+                name: addr.to_string(),
+                resource: Some(resource),
+            })
+            .collect())
+    }
+}
+
+impl Inventory {
+    /// Runs a command of the provider's CLI with JSON output and the inventory's settings.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to run the cloud CLI; is it installed and logged in?" if it cannot be
+    ///   started or exits with an error.
+    /// * "unexpected output from the cloud CLI" if its output is not JSON.
+    fn run(&self, program: &str, args: &[&str]) -> Result<Json, &'static str> {
+        let mut command = Command::new(program);
+        command.args(args);
+        command.arg(match self.provider {
+            Provider::Gcp => "--format=json",
+            Provider::Aws | Provider::Azure => "--output=json",
+        });
+        for (key, value) in &self.settings {
+            command.arg(format!("--{}={}", key, value));
+        }
+
+        let output = command
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .ok_or("failed to run the cloud CLI; is it installed and logged in?")?;
+
+        let text =
+            String::from_utf8(output.stdout).map_err(|_| "unexpected output from the cloud CLI")?;
+        Json::parse(&text).map_err(|_| "unexpected output from the cloud CLI")
+    }
+}
+
+/// Returns the member `key` of an object, or `null` if it has none.
+fn field<'a>(value: &'a Json, key: &str) -> &'a Json {
+    value.get(key).unwrap_or(&Json::Null)
+}
+
+/// Returns the string member `key` of an object, or an empty string if it has none.
+fn text<'a>(value: &'a Json, key: &str) -> &'a str {
+    field(value, key).as_str().unwrap_or("")
+}
+
+/// Returns the resource ID of a GCP resource, e.g.
+/// `gcp projects/my-project/zones/europe-west1-b/instances/web-1`.
+fn gcp_id(resource: &Json) -> String {
+    let link = text(resource, "selfLink");
+    let id = match link.strip_prefix(GCP_LINK_PREFIX) {
+        Some(id) => id,
+        None if link.is_empty() => text(resource, "name"),
+        None => link,
+    };
+    format!("gcp {}", id)
+}
+
+/// Adds `addr` with the resource it belongs to, unless it is not an address or was
+/// already found.
+fn push(found: &mut Vec<(IpAddr, String)>, addr: &str, resource: &str) {
+    if let Ok(addr) = IpAddr::from_str(addr) {
+        if !found.iter().any(|(known, _)| *known == addr) {
+            found.push((addr, resource.to_string()));
+        }
+    }
+}
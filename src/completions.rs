@@ -0,0 +1,272 @@
+use crate::cli::{self, Flag, ValueKind};
+
+/// Name the completion scripts register for.
+const PROGRAM: &str = "ip-sniffer";
+
+/// Prints the completion script for a shell.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `completions` on the command line.
+///
+/// # Errors
+///
+/// * "missing shell; must be bash, zsh, fish or powershell" if no shell is given.
+/// * "unknown shell; must be bash, zsh, fish or powershell" if the shell is not supported.
+/// * "too many arguments" if anything follows the shell.
+///
+/// # Description
+///
+/// The scripts are generated from the help text, so every option, the values of options
+/// that take one of a fixed set, file arguments and the subcommands are completed.
+pub fn run(args: &[String]) -> Result<(), &'static str> {
+    let script = match args {
+        [] => return Err("missing shell; must be bash, zsh, fish or powershell"),
+        [shell] => match shell.as_str() {
+            "bash" => bash(),
+            "zsh" => zsh(),
+            "fish" => fish(),
+            "powershell" => powershell(),
+            _ => return Err("unknown shell; must be bash, zsh, fish or powershell"),
+        },
+        _ => return Err("too many arguments"),
+    };
+
+    print!("{}", script);
+    Ok(())
+}
+
+/// Returns the names of the options taking a value of `kind`.
+fn names_of(flags: &[Flag], kind: ValueKind) -> Vec<&'static str> {
+    flags
+        .iter()
+        .filter(|f| f.value_kind() == kind)
+        .flat_map(|f| f.names.iter().copied())
+        .collect()
+}
+
+/// Returns every option name.
+fn all_names(flags: &[Flag]) -> Vec<&'static str> {
+    flags.iter().flat_map(|f| f.names.iter().copied()).collect()
+}
+
+/// Returns the values of the `completions` subcommand, e.g. `bash`.
+fn shells() -> Vec<&'static str> {
+    cli::commands()
+        .iter()
+        .find(|c| c.words == ["completions"])
+        .map(|c| c.args.trim_matches(['<', '>']).split('|').collect())
+        .unwrap_or_default()
+}
+
+fn bash() -> String {
+    let flags = cli::flags();
+    let mut script = String::new();
+
+    script += "_ip_sniffer() {\n";
+    script += "    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n";
+    script += "    local prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n\n";
+    script += "    if [ \"$COMP_CWORD\" -ge 2 ]; then\n";
+    script += "        case \"${COMP_WORDS[1]}\" in\n";
+    script += &format!(
+        "            project)\n                if [ \"$COMP_CWORD\" -eq 2 ]; then\n                    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n                else\n                    COMPREPLY=($(compgen -d -- \"$cur\"))\n                fi\n                return ;;\n",
+        cli::subcommands("project").join(" ")
+    );
+    script += &format!(
+        "            completions)\n                [ \"$COMP_CWORD\" -eq 2 ] && COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n                return ;;\n",
+        shells().join(" ")
+    );
+    script += "        esac\n    fi\n\n";
+    script += "    case \"$prev\" in\n";
+
+    for flag in flags
+        .iter()
+        .filter(|f| f.value_kind() == ValueKind::Choices)
+    {
+        script += &format!(
+            "        {})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            return ;;\n",
+            flag.names.join("|"),
+            flag.choices().join(" ")
+        );
+    }
+    script += &format!(
+        "        {})\n            COMPREPLY=($(compgen -f -- \"$cur\"))\n            return ;;\n",
+        names_of(&flags, ValueKind::File).join("|")
+    );
+    script += &format!(
+        "        {})\n            return ;;\n",
+        names_of(&flags, ValueKind::Free).join("|")
+    );
+    script += "    esac\n\n";
+    script += &format!(
+        "    if [[ \"$cur\" == -* ]]; then\n        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n",
+        all_names(&flags).join(" ")
+    );
+    script += &format!(
+        "    elif [ \"$COMP_CWORD\" -eq 1 ]; then\n        COMPREPLY=($(compgen -W \"{}\" -A hostname -- \"$cur\"))\n",
+        cli::command_names().join(" ")
+    );
+    script += "    else\n        COMPREPLY=($(compgen -A hostname -- \"$cur\"))\n    fi\n}\n\n";
+    script += &format!("complete -F _ip_sniffer {}\n", PROGRAM);
+    script
+}
+
+/// Escapes a description for an `_arguments` spec inside single quotes.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh() -> String {
+    let flags = cli::flags();
+    let mut script = format!("#compdef {}\n\n", PROGRAM);
+
+    script += "_ip_sniffer() {\n    local state\n\n";
+    script += &format!(
+        "    case $words[2] in\n        project)\n            if (( CURRENT == 3 )); then\n                compadd {}\n            else\n                _files -/\n            fi\n            return ;;\n",
+        cli::subcommands("project").join(" ")
+    );
+    script += &format!(
+        "        completions)\n            (( CURRENT == 3 )) && compadd {}\n            return ;;\n    esac\n\n",
+        shells().join(" ")
+    );
+    script += "    _arguments \\\n";
+
+    for flag in &flags {
+        let description = zsh_escape(flag.description);
+        let action = match flag.value_kind() {
+            ValueKind::None => String::new(),
+            ValueKind::File => format!(":{}:_files", flag.value.unwrap_or_default()),
+            ValueKind::Choices => format!(":value:({})", flag.choices().join(" ")),
+            ValueKind::Free => format!(":{}: ", flag.value.unwrap_or_default()),
+        };
+
+        for name in &flag.names {
+            script += &format!("        '{}[{}]{}' \\\n", name, description, action);
+        }
+    }
+    script += "        '1:target:->target'\n\n";
+    script += &format!(
+        "    if [[ $state == target ]]; then\n        compadd {}\n        _hosts\n    fi\n}}\n\n",
+        cli::command_names().join(" ")
+    );
+    script += "_ip_sniffer \"$@\"\n";
+    script
+}
+
+/// Escapes text for a single-quoted fish string.
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish() -> String {
+    let flags = cli::flags();
+    let mut script = format!("complete -c {} -f\n", PROGRAM);
+
+    script += &format!(
+        "complete -c {} -n __fish_use_subcommand -a '{}'\n",
+        PROGRAM,
+        cli::command_names().join(" ")
+    );
+    script += &format!(
+        "complete -c {} -n '__fish_seen_subcommand_from project' -a '{}'\n",
+        PROGRAM,
+        cli::subcommands("project").join(" ")
+    );
+    script += &format!(
+        "complete -c {} -n '__fish_seen_subcommand_from completions' -a '{}'\n",
+        PROGRAM,
+        shells().join(" ")
+    );
+
+    for flag in &flags {
+        let value = match flag.value_kind() {
+            ValueKind::None => String::new(),
+            ValueKind::File => " -r -F".to_string(),
+            ValueKind::Choices => format!(" -x -a '{}'", flag.choices().join(" ")),
+            ValueKind::Free => " -x".to_string(),
+        };
+
+        for name in &flag.names {
+            let option = match name.strip_prefix("--") {
+                Some(long) => format!("-l {}", long),
+                None if name.len() == 2 => format!("-s {}", &name[1..]),
+                None => format!("-o {}", &name[1..]),
+            };
+            script += &format!(
+                "complete -c {} {}{} -d '{}'\n",
+                PROGRAM,
+                option,
+                value,
+                fish_escape(flag.description)
+            );
+        }
+    }
+
+    script
+}
+
+/// Formats words as a PowerShell array of single-quoted strings.
+fn powershell_list(words: &[&str]) -> String {
+    let words: Vec<String> = words.iter().map(|w| format!("'{}'", w)).collect();
+    format!("@({})", words.join(", "))
+}
+
+fn powershell() -> String {
+    let flags = cli::flags();
+    let mut script = format!(
+        "Register-ArgumentCompleter -Native -CommandName {} -ScriptBlock {{\n",
+        PROGRAM
+    );
+
+    script += "    param($wordToComplete, $commandAst, $cursorPosition)\n\n";
+    script += "    $words = @($commandAst.CommandElements | ForEach-Object { $_.ToString() })\n";
+    script += "    $index = if ($wordToComplete) { $words.Count - 1 } else { $words.Count }\n";
+    script += "    $prev = $words[$index - 1]\n\n";
+    script += "    $candidates = if ($index -ge 2 -and $words[1] -eq 'project') {\n";
+    script += &format!(
+        "        if ($index -eq 2) {{ {} }} else {{ return }}\n",
+        powershell_list(&cli::subcommands("project"))
+    );
+    script += "    } elseif ($index -ge 2 -and $words[1] -eq 'completions') {\n";
+    script += &format!(
+        "        if ($index -eq 2) {{ {} }} else {{ @() }}\n",
+        powershell_list(&shells())
+    );
+    script += "    } else {\n        switch ($prev) {\n";
+
+    for flag in flags
+        .iter()
+        .filter(|f| f.value_kind() == ValueKind::Choices)
+    {
+        for name in &flag.names {
+            script += &format!(
+                "            '{}' {{ {}; break }}\n",
+                name,
+                powershell_list(&flag.choices())
+            );
+        }
+    }
+    for name in names_of(&flags, ValueKind::File) {
+        script += &format!("            '{}' {{ return }}\n", name);
+    }
+    for name in names_of(&flags, ValueKind::Free) {
+        script += &format!("            '{}' {{ @(); break }}\n", name);
+    }
+
+    let words = all_names(&flags);
+    script += &format!(
+        "            default {{\n                if ($index -eq 1) {{ {} }} else {{ {} }}\n            }}\n",
+        powershell_list(&[cli::command_names(), words.clone()].concat()),
+        powershell_list(&words)
+    );
+    script += "        }\n    }\n\n";
+    script +=
+        "    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n";
+    script +=
+        "        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n";
+    script += "    }\n}\n";
+    script
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::policy::Cidr;
+use crate::probe::Identity;
+
+// Config files hold `key = value` settings, one per line:
+//
+// # identifying strings sent by application-layer probes
+// http_user_agent = Mozilla/5.0 (X11; Linux x86_64)
+// smtp_ehlo = scanner.lab.example
+// ssh_banner = SSH-2.0-OpenSSH_9.6
+//
+// # scan defaults, overridden by -j and --adaptive-timeout
+// threads = 200
+// timeout_ms = 500
+//
+// # addresses that must never be scanned
+// exclude = 10.0.0.0/8, 192.168.1.1
+
+/// Bounds of `timeout_ms`.
+const TIMEOUT_RANGE_MS: (u64, u64) = (10, 60_000);
+
+/// Settings loaded with `--config`.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Identifying strings sent by probes.
+    pub identity: Identity,
+    /// Number of scan threads when `-j` is not given.
+    pub threads: Option<u16>,
+    /// Connect timeout when `--adaptive-timeout` is not given.
+    pub timeout: Option<Duration>,
+    /// Networks that are never scanned.
+    pub exclude: Vec<Cidr>,
+}
+
+/// A problem found while loading a config file.
+#[derive(Debug)]
+pub struct Problem {
+    /// The 1-based line the problem is on, if it is about a line.
+    pub line: Option<usize>,
+    pub message: &'static str,
+}
+
+impl Config {
+    /// Loads and parses a config file.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to read config file" if the file cannot be read.
+    /// * Any problems returned by `Config::parse`.
+    pub fn load(path: &str) -> Result<Config, Vec<Problem>> {
+        let contents = fs::read_to_string(path).map_err(|_| {
+            vec![Problem {
+                line: None,
+                message: "failed to read config file",
+            }]
+        })?;
+        Config::parse(&contents)
+    }
+
+    /// Parses and validates the contents of a config file.
+    ///
+    /// # Errors
+    ///
+    /// Every problem in the file, in line order:
+    ///
+    /// * "invalid line in config" if a line is not a `key = value` pair.
+    /// * "config value must not be empty" if a value is missing.
+    /// * "threads must be a number from 1 to 65535" if `threads` is out of range.
+    /// * "timeout_ms must be a number from 10 to 60000" if `timeout_ms` is out of range.
+    /// * Any error returned by `Cidr::from_str` for an entry of `exclude`.
+    /// * "setting is given more than once" if a key is repeated, except for `exclude`.
+    /// * "unknown setting in config" if the key is not recognized.
+    pub fn parse(contents: &str) -> Result<Config, Vec<Problem>> {
+        let mut config = Config::default();
+        let mut problems = vec![];
+        let mut seen = vec![];
+
+        for (i, line) in contents.lines().map(str::trim).enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut problem = |message| {
+                problems.push(Problem {
+                    line: Some(i + 1),
+                    message,
+                })
+            };
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim().to_string()),
+                None => {
+                    problem("invalid line in config");
+                    continue;
+                }
+            };
+            if value.is_empty() {
+                problem("config value must not be empty");
+                continue;
+            }
+            if key != "exclude" && seen.contains(&key) {
+                problem("setting is given more than once");
+            }
+            seen.push(key);
+
+            match key {
+                "http_user_agent" => config.identity.user_agent = value,
+                "smtp_ehlo" => config.identity.ehlo = value,
+                "ssh_banner" if value.starts_with("SSH-") => config.identity.ssh_banner = value,
+                "ssh_banner" => config.identity.ssh_banner = format!("SSH-2.0-{}", value),
+                "threads" => match value.parse::<u16>() {
+                    Ok(threads) if threads > 0 => config.threads = Some(threads),
+                    _ => problem("threads must be a number from 1 to 65535"),
+                },
+                "timeout_ms" => match value.parse::<u64>() {
+                    Ok(ms) if (TIMEOUT_RANGE_MS.0..=TIMEOUT_RANGE_MS.1).contains(&ms) => {
+                        config.timeout = Some(Duration::from_millis(ms))
+                    }
+                    _ => problem("timeout_ms must be a number from 10 to 60000"),
+                },
+                "exclude" => {
+                    for item in value.split(',').map(str::trim) {
+                        match Cidr::from_str(item) {
+                            Ok(cidr) => config.exclude.push(cidr),
+                            Err(err) => problem(err),
+                        }
+                    }
+                }
+                _ => problem("unknown setting in config"),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Returns whether `addr` lies within a network of `exclude`.
+    pub fn excludes(&self, addr: IpAddr) -> bool {
+        self.exclude.iter().any(|cidr| cidr.contains(addr))
+    }
+}
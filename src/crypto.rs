@@ -0,0 +1,437 @@
+// X25519 (RFC 7748) and ChaCha20-Poly1305 (RFC 8439), used to encrypt reports.
+// Implemented here to keep the crate free of dependencies. X25519 follows TweetNaCl,
+// with field elements as sixteen 16-bit limbs; Poly1305 follows poly1305-donna with
+// five 26-bit limbs.
+
+/// An element of the field of integers modulo 2^255 - 19.
+type Field = [i64; 16];
+
+const A24: Field = [0xdb41, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+/// The u-coordinate of the Curve25519 base point.
+pub const BASEPOINT: [u8; 32] = {
+    let mut point = [0; 32];
+    point[0] = 9;
+    point
+};
+
+fn carry(o: &mut Field) {
+    for i in 0..16 {
+        o[i] += 1 << 16;
+        let c = o[i] >> 16;
+        if i < 15 {
+            o[i + 1] += c - 1;
+        } else {
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c << 16;
+    }
+}
+
+/// Swaps `p` and `q` if `b` is 1, in constant time.
+fn swap(p: &mut Field, q: &mut Field, b: i64) {
+    let mask = !(b - 1);
+    for i in 0..16 {
+        let t = mask & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+fn add(a: Field, b: Field) -> Field {
+    let mut o = [0; 16];
+    for i in 0..16 {
+        o[i] = a[i] + b[i];
+    }
+    o
+}
+
+fn sub(a: Field, b: Field) -> Field {
+    let mut o = [0; 16];
+    for i in 0..16 {
+        o[i] = a[i] - b[i];
+    }
+    o
+}
+
+fn mul(a: Field, b: Field) -> Field {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut o = [0; 16];
+    o.copy_from_slice(&t[..16]);
+    carry(&mut o);
+    carry(&mut o);
+    o
+}
+
+fn invert(i: Field) -> Field {
+    let mut c = i;
+    for a in (0..=253).rev() {
+        c = mul(c, c);
+        if a != 2 && a != 4 {
+            c = mul(c, i);
+        }
+    }
+    c
+}
+
+fn unpack(n: &[u8; 32]) -> Field {
+    let mut o = [0; 16];
+    for i in 0..16 {
+        o[i] = n[2 * i] as i64 + ((n[2 * i + 1] as i64) << 8);
+    }
+    o[15] &= 0x7fff;
+    o
+}
+
+fn pack(n: Field) -> [u8; 32] {
+    let mut t = n;
+    carry(&mut t);
+    carry(&mut t);
+    carry(&mut t);
+
+    // Subtract the modulus once or twice to fully reduce t.
+    for _ in 0..2 {
+        let mut m = [0; 16];
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let borrow = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        swap(&mut t, &mut m, 1 - borrow);
+    }
+
+    let mut o = [0; 32];
+    for i in 0..16 {
+        o[2 * i] = t[i] as u8;
+        o[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    o
+}
+
+/// Multiplies the Curve25519 point with u-coordinate `point` by `scalar`.
+///
+/// # Arguments
+///
+/// * `scalar` - A secret key; it is clamped as RFC 7748 requires.
+/// * `point` - A public key, or `BASEPOINT` to compute the public key of `scalar`.
+///
+/// # Returns
+///
+/// The u-coordinate of the product: a public key or a shared secret.
+pub fn x25519(scalar: &[u8; 32], point: &[u8; 32]) -> [u8; 32] {
+    let mut z = *scalar;
+    z[31] = (z[31] & 127) | 64;
+    z[0] &= 248;
+
+    let x = unpack(point);
+    let mut a = [0; 16];
+    let mut b = x;
+    let mut c = [0; 16];
+    let mut d = [0; 16];
+    a[0] = 1;
+    d[0] = 1;
+
+    for i in (0..=254).rev() {
+        let bit = ((z[i >> 3] >> (i & 7)) & 1) as i64;
+        swap(&mut a, &mut b, bit);
+        swap(&mut c, &mut d, bit);
+
+        let e = add(a, c);
+        a = sub(a, c);
+        c = add(b, d);
+        b = sub(b, d);
+        d = mul(e, e);
+        let f = mul(a, a);
+        a = mul(c, a);
+        c = mul(b, e);
+        let e = add(a, c);
+        a = sub(a, c);
+        b = mul(a, a);
+        c = sub(d, f);
+        a = mul(c, A24);
+        a = add(a, d);
+        c = mul(c, a);
+        a = mul(d, f);
+        d = mul(b, x);
+        b = mul(e, e);
+
+        swap(&mut a, &mut b, bit);
+        swap(&mut c, &mut d, bit);
+    }
+
+    pack(mul(a, invert(c)))
+}
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Computes the ChaCha20 block of `key` and `nonce` at `counter`.
+fn chacha20_block(key: &[u8; 32], counter: u32, nonce: &[u8; 12]) -> [u8; 64] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for i in 0..8 {
+        state[4 + i] = le32(&key[4 * i..]);
+    }
+    state[12] = counter;
+    for i in 0..3 {
+        state[13 + i] = le32(&nonce[4 * i..]);
+    }
+
+    let mut s = state;
+    for _ in 0..10 {
+        quarter_round(&mut s, 0, 4, 8, 12);
+        quarter_round(&mut s, 1, 5, 9, 13);
+        quarter_round(&mut s, 2, 6, 10, 14);
+        quarter_round(&mut s, 3, 7, 11, 15);
+        quarter_round(&mut s, 0, 5, 10, 15);
+        quarter_round(&mut s, 1, 6, 11, 12);
+        quarter_round(&mut s, 2, 7, 8, 13);
+        quarter_round(&mut s, 3, 4, 9, 14);
+    }
+
+    let mut block = [0; 64];
+    for i in 0..16 {
+        block[4 * i..4 * i + 4].copy_from_slice(&s[i].wrapping_add(state[i]).to_le_bytes());
+    }
+    block
+}
+
+/// Computes the Poly1305 tag of `message` under the one-time `key`.
+fn poly1305(key: &[u8; 32], message: &[u8]) -> [u8; 16] {
+    const MASK: u32 = 0x3ffffff;
+
+    let r = [
+        le32(&key[0..]) & 0x3ffffff,
+        (le32(&key[3..]) >> 2) & 0x3ffff03,
+        (le32(&key[6..]) >> 4) & 0x3ffc0ff,
+        (le32(&key[9..]) >> 6) & 0x3f03fff,
+        (le32(&key[12..]) >> 8) & 0x00fffff,
+    ]
+    .map(u64::from);
+    let s = [r[1] * 5, r[2] * 5, r[3] * 5, r[4] * 5];
+    let mut h = [0u32; 5];
+
+    for chunk in message.chunks(16) {
+        let mut block = [0; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        // A full block has its 2^128 bit set; a short one is padded with a 1 byte.
+        let high = if chunk.len() == 16 {
+            1 << 24
+        } else {
+            block[chunk.len()] = 1;
+            0
+        };
+
+        h[0] += le32(&block[0..]) & MASK;
+        h[1] += (le32(&block[3..]) >> 2) & MASK;
+        h[2] += (le32(&block[6..]) >> 4) & MASK;
+        h[3] += (le32(&block[9..]) >> 6) & MASK;
+        h[4] += (le32(&block[12..]) >> 8) | high;
+
+        let h64 = h.map(u64::from);
+        let mut d = [
+            h64[0] * r[0] + h64[1] * s[3] + h64[2] * s[2] + h64[3] * s[1] + h64[4] * s[0],
+            h64[0] * r[1] + h64[1] * r[0] + h64[2] * s[3] + h64[3] * s[2] + h64[4] * s[1],
+            h64[0] * r[2] + h64[1] * r[1] + h64[2] * r[0] + h64[3] * s[3] + h64[4] * s[2],
+            h64[0] * r[3] + h64[1] * r[2] + h64[2] * r[1] + h64[3] * r[0] + h64[4] * s[3],
+            h64[0] * r[4] + h64[1] * r[3] + h64[2] * r[2] + h64[3] * r[1] + h64[4] * r[0],
+        ];
+        for i in 0..4 {
+            d[i + 1] += d[i] >> 26;
+            h[i] = d[i] as u32 & MASK;
+        }
+        h[4] = d[4] as u32 & MASK;
+        h[0] += (d[4] >> 26) as u32 * 5;
+        h[1] += h[0] >> 26;
+        h[0] &= MASK;
+    }
+
+    // Fully carry h, then subtract 2^130 - 5 if h is at least that.
+    for i in 1..4 {
+        h[i + 1] += h[i] >> 26;
+        h[i] &= MASK;
+    }
+    h[0] += (h[4] >> 26) * 5;
+    h[4] &= MASK;
+    h[1] += h[0] >> 26;
+    h[0] &= MASK;
+
+    let mut g = [0u32; 5];
+    let mut c = 5;
+    for i in 0..4 {
+        g[i] = h[i] + c;
+        c = g[i] >> 26;
+        g[i] &= MASK;
+    }
+    g[4] = h[4].wrapping_add(c).wrapping_sub(1 << 26);
+    let keep_g = (g[4] >> 31).wrapping_sub(1);
+    for i in 0..5 {
+        h[i] = (h[i] & !keep_g) | (g[i] & keep_g);
+    }
+
+    let words = [
+        h[0] | (h[1] << 26),
+        (h[1] >> 6) | (h[2] << 20),
+        (h[2] >> 12) | (h[3] << 14),
+        (h[3] >> 18) | (h[4] << 8),
+    ];
+    let mut tag = [0; 16];
+    let mut f = 0u64;
+    for i in 0..4 {
+        f += words[i] as u64 + le32(&key[16 + 4 * i..]) as u64;
+        tag[4 * i..4 * i + 4].copy_from_slice(&(f as u32).to_le_bytes());
+        f >>= 32;
+    }
+    tag
+}
+
+/// Encrypts `plaintext` with ChaCha20-Poly1305 and no associated data.
+///
+/// # Returns
+///
+/// The ciphertext followed by its 16-byte tag.
+pub fn chacha20poly1305_seal(key: &[u8; 32], nonce: &[u8; 12], plaintext: &[u8]) -> Vec<u8> {
+    seal(key, nonce, &[], plaintext)
+}
+
+/// Encrypts `plaintext` with ChaCha20-Poly1305, authenticating `aad` along with it.
+fn seal(key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut sealed = Vec::with_capacity(plaintext.len() + 16);
+    for (i, chunk) in plaintext.chunks(64).enumerate() {
+        let stream = chacha20_block(key, i as u32 + 1, nonce);
+        sealed.extend(chunk.iter().zip(stream).map(|(byte, key)| byte ^ key));
+    }
+
+    let mut one_time_key = [0; 32];
+    one_time_key.copy_from_slice(&chacha20_block(key, 0, nonce)[..32]);
+
+    // The associated data and the ciphertext, each padded to 16 bytes, then their lengths.
+    let mut authenticated = aad.to_vec();
+    authenticated.resize(aad.len().div_ceil(16) * 16, 0);
+    authenticated.extend_from_slice(&sealed);
+    authenticated.resize(
+        authenticated.len() + sealed.len().div_ceil(16) * 16 - sealed.len(),
+        0,
+    );
+    authenticated.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    authenticated.extend_from_slice(&(sealed.len() as u64).to_le_bytes());
+
+    sealed.extend_from_slice(&poly1305(&one_time_key, &authenticated));
+    sealed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn key(s: &str) -> [u8; 32] {
+        hex(s).try_into().unwrap()
+    }
+
+    #[test]
+    fn x25519_rfc7748_vectors() {
+        // RFC 7748, section 5.2.
+        let vectors = [
+            (
+                "a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4",
+                "e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c",
+                "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552",
+            ),
+            (
+                "4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d",
+                "e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493",
+                "95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957",
+            ),
+        ];
+        for (scalar, point, product) in vectors {
+            assert_eq!(x25519(&key(scalar), &key(point)), key(product));
+        }
+    }
+
+    #[test]
+    fn x25519_rfc7748_iterated() {
+        // RFC 7748, section 5.2, after one iteration; the 1,000-iteration value is left
+        // out, as it takes seconds unoptimized.
+        let k = x25519(&BASEPOINT, &BASEPOINT);
+        let expected = "422c8e7a6227d7bca1350b3e2bb7279f7897b87bb6854b783c60e80311ae3079";
+        assert_eq!(k, key(expected));
+    }
+
+    #[test]
+    fn x25519_rfc7748_key_agreement() {
+        // RFC 7748, section 6.1.
+        let alice = key("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = key("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let alice_public = x25519(&alice, &BASEPOINT);
+        let bob_public = x25519(&bob, &BASEPOINT);
+
+        let expected = "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a";
+        assert_eq!(alice_public, key(expected));
+        let expected = "de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f";
+        assert_eq!(bob_public, key(expected));
+        let expected = "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742";
+        assert_eq!(x25519(&alice, &bob_public), key(expected));
+        assert_eq!(x25519(&bob, &alice_public), key(expected));
+    }
+
+    #[test]
+    fn chacha20poly1305_rfc8439_vector() {
+        // RFC 8439, section 2.8.2.
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+            only one tip for the future, sunscreen would be it.";
+        let key = key("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let nonce: [u8; 12] = hex("070000004041424344454647").try_into().unwrap();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+
+        let sealed = seal(&key, &nonce, &aad, plaintext);
+        let expected = hex(concat!(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+            "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+            "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+            "3ff4def08e4b7a9de576d26586cec64b6116",
+            "1ae10b594f09e26a7e902ecbd0600691",
+        ));
+        assert_eq!(sealed, expected);
+    }
+
+    #[test]
+    fn chacha20poly1305_without_associated_data() {
+        let key = [7; 32];
+        let nonce = [9; 12];
+        assert_eq!(
+            chacha20poly1305_seal(&key, &nonce, b"report"),
+            seal(&key, &nonce, &[], b"report")
+        );
+        assert_eq!(chacha20poly1305_seal(&key, &nonce, &[]).len(), 16);
+    }
+}
@@ -0,0 +1,138 @@
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::json::Json;
+
+/// Docker socket used when `DOCKER_HOST` does not name a Unix socket.
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// A container port published on the host.
+#[derive(Debug)]
+pub struct Published {
+    /// The container's name, without the leading `/`.
+    pub container: String,
+    /// The image the container runs, e.g. `nginx:1.25`.
+    pub image: String,
+    /// The host address the port is bound to; `0.0.0.0` and `::` mean every address.
+    pub bound: IpAddr,
+    /// The host port.
+    pub port: u16,
+    /// The port inside the container.
+    pub container_port: u16,
+}
+
+impl Published {
+    /// Returns the address to connect to for the port: the bound address, or loopback
+    /// if it is bound to every address.
+    pub fn addr(&self) -> IpAddr {
+        match self.bound {
+            IpAddr::V4(v4) if v4.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(v6) if v6.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            addr => addr,
+        }
+    }
+}
+
+/// Returns the path of the Docker socket: the `unix://` path of `DOCKER_HOST` if set,
+/// `/var/run/docker.sock` otherwise.
+pub fn socket_path() -> String {
+    env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|host| host.strip_prefix("unix://").map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_SOCKET.to_string())
+}
+
+/// Lists the TCP ports that running containers publish on the host.
+///
+/// # Arguments
+///
+/// * `socket` - The path of the Docker socket.
+///
+/// # Returns
+///
+/// Every published port, ordered by host port; a port published on both IPv4 and IPv6
+/// is listed once per address.
+///
+/// # Errors
+///
+/// * "failed to connect to the Docker socket" if it cannot be opened, e.g. because the
+///   daemon is not running or we lack permission.
+/// * "unexpected answer from the Docker socket" if the answer is not a container list.
+/// * "the Docker socket is only supported on Unix" on other platforms.
+pub fn published(socket: &str) -> Result<Vec<Published>, &'static str> {
+    let body = get(socket, "/containers/json")?;
+    let containers = Json::parse(&body).map_err(|_| "unexpected answer from the Docker socket")?;
+    let mut published = vec![];
+
+    for container in containers.as_array() {
+        let name = container
+            .get("Names")
+            .map_or(&[][..], Json::as_array)
+            .first()
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .trim_start_matches('/');
+        let image = container.get("Image").and_then(Json::as_str).unwrap_or("");
+
+        for port in container.get("Ports").map_or(&[][..], Json::as_array) {
+            let number = |key| port.get(key).and_then(Json::as_f64).map(|n| n as u16);
+            let tcp = port.get("Type").and_then(Json::as_str) == Some("tcp");
+            let bound = port
+                .get("IP")
+                .and_then(Json::as_str)
+                .and_then(|ip| IpAddr::from_str(ip).ok());
+
+            if let (true, Some(bound), Some(port), Some(container_port)) =
+                (tcp, bound, number("PublicPort"), number("PrivatePort"))
+            {
+                published.push(Published {
+                    container: name.to_string(),
+                    image: image.to_string(),
+                    bound,
+                    port,
+                    container_port,
+                });
+            }
+        }
+    }
+
+    published.sort_by_key(|p| (p.port, p.bound));
+    Ok(published)
+}
+
+/// Sends a GET request to the Docker API over its Unix socket.
+///
+/// # Returns
+///
+/// The body of the response.
+#[cfg(unix)]
+fn get(socket: &str, path: &str) -> Result<String, &'static str> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream =
+        UnixStream::connect(socket).map_err(|_| "failed to connect to the Docker socket")?;
+    // HTTP/1.0 keeps the body from being chunked and closes the connection after it.
+    let request = format!("GET {} HTTP/1.0\r\nHost: docker\r\n\r\n", path);
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|_| "failed to connect to the Docker socket")?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|_| "unexpected answer from the Docker socket")?;
+
+    match response.split_once("\r\n\r\n") {
+        Some((head, body)) if head.starts_with("HTTP/1.") && head.get(9..12) == Some("200") => {
+            Ok(body.to_string())
+        }
+        _ => Err("unexpected answer from the Docker socket"),
+    }
+}
+
+#[cfg(not(unix))]
+fn get(_socket: &str, _path: &str) -> Result<String, &'static str> {
+    Err("the Docker socket is only supported on Unix")
+}
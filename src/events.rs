@@ -0,0 +1,84 @@
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::scan::OpenPort;
+
+/// Something that happened during a scan, published on a `Bus`.
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    /// A connection attempt to `port` is about to be made. Retries after local errors
+    /// and retransmits of timed-out ports are published again. Only published when the
+    /// scan's `Controls::probe_events` is set.
+    ProbeSent { addr: IpAddr, port: u16 },
+    /// A port accepted the connection.
+    PortOpen { addr: IpAddr, open: OpenPort },
+    /// Every port of `addr` was probed, or the scan of it stopped early.
+    HostDone {
+        addr: IpAddr,
+        open: usize,
+        closed: usize,
+        filtered: usize,
+    },
+    /// At least half of the last `probes` probes failed because of our own network,
+    /// with errors such as `kind`, so the scan pauses for `pause` before retrying them.
+    LocalErrors {
+        errors: usize,
+        probes: usize,
+        kind: io::ErrorKind,
+        pause: Duration,
+    },
+    /// At least half of the last `probes` connections were reset by the target, which may be rate
+    /// limiting the scan, so the scan pauses for `pause`.
+    Resets {
+        resets: usize,
+        probes: usize,
+        pause: Duration,
+    },
+    /// After a pause, a scan with `Controls::auto_backoff` went down to `threads` threads.
+    BackedOff { threads: usize },
+    /// The last `probes` probes of `addr` timed out after it had answered, so the scan
+    /// checks whether it is still up; if it is not, its `HostScan` has `host_down` set.
+    HostSilent { addr: IpAddr, probes: usize },
+    /// `addr` still answered that check, and its scan resumes.
+    HostAnswers { addr: IpAddr },
+    /// Every host of the scan is done; published by the application, which knows when
+    /// the last host has been scanned.
+    ScanDone { hosts: usize, elapsed: Duration },
+}
+
+/// A consumer of scan events, e.g. an output writer.
+pub type Sink<'a> = Box<dyn FnMut(&Event) + 'a>;
+
+/// Delivers scan events to every subscribed sink, in the order they subscribed.
+///
+/// # Usage
+///
+/// * `Bus::new()` - Create a bus without sinks.
+/// * `bus.subscribe(sink)` - Add a sink, e.g. an output writer or a progress display.
+/// * `bus.publish(&event)` - Hand an event to every sink.
+///
+/// Sinks are called on the thread publishing the event; scans publish from the thread
+/// that started them, never from their workers, so sinks need no locking.
+#[derive(Default)]
+pub struct Bus<'a> {
+    sinks: Vec<Sink<'a>>,
+}
+
+impl<'a> Bus<'a> {
+    pub fn new() -> Bus<'a> {
+        Bus::default()
+    }
+
+    /// Adds a sink that is called with every event published from now on.
+    pub fn subscribe(&mut self, sink: impl FnMut(&Event) + 'a) -> &mut Bus<'a> {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    pub fn publish(&mut self, event: &Event) {
+        for sink in &mut self.sinks {
+            sink(event);
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+
+use crate::json::Json;
+use crate::risk;
+
+// `--geoip` looks up public targets in MaxMind DB (.mmdb) files, e.g. GeoLite2-City and
+// GeoLite2-ASN, and reports where they are and which network they belong to:
+//
+// location of 93.184.216.34: Norwell, United States (US), AS15133 EDGECAST
+//
+// An .mmdb file is a binary search tree over the bits of the address, whose leaves point
+// into a data section of typed values (maps, strings, numbers, ...), followed by a
+// metadata map describing the tree:
+//
+// [ search tree ][ 16 zero bytes ][ data section ][ \xAB\xCD\xEFMaxMind.com ][ metadata ]
+//
+// Records are decoded into `Json` values; only the country, city and autonomous system
+// members are read from them.
+
+/// Marks the start of the metadata section.
+const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+
+/// How far from the end of the file the metadata may start.
+const METADATA_MAX_SIZE: usize = 128 * 1024;
+
+/// How many pointers and nested maps or arrays a value may go through, so a corrupt
+/// file cannot send the decoder into a loop.
+const MAX_DEPTH: u8 = 32;
+
+const INVALID: &str = "invalid GeoIP database; must be a MaxMind .mmdb file";
+
+/// An .mmdb file loaded into memory.
+pub struct Database {
+    bytes: Vec<u8>,
+    node_count: u32,
+    /// Bits per record of a node: 24, 28 or 32.
+    record_size: u16,
+    ip_version: u16,
+    /// Where the data section starts and ends in `bytes`.
+    data: (usize, usize),
+}
+
+/// What the databases know about an address.
+#[derive(Debug, Default, PartialEq)]
+pub struct Location {
+    /// ISO 3166-1 alpha-2 code, e.g. `DE`.
+    pub country: Option<String>,
+    /// English name of the country, e.g. `Germany`.
+    pub country_name: Option<String>,
+    /// English name of the city, e.g. `Frankfurt am Main`.
+    pub city: Option<String>,
+    /// Number of the autonomous system announcing the address.
+    pub asn: Option<u32>,
+    /// Organization of the autonomous system.
+    pub as_org: Option<String>,
+}
+
+impl Database {
+    /// Loads an .mmdb file.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to read GeoIP database" if the file cannot be read.
+    /// * "invalid GeoIP database; must be a MaxMind .mmdb file" if it is not one.
+    pub fn load(path: &str) -> Result<Database, &'static str> {
+        let bytes = fs::read(path).map_err(|_| "failed to read GeoIP database")?;
+
+        let tail = bytes.len().saturating_sub(METADATA_MAX_SIZE);
+        let marker = bytes[tail..]
+            .windows(METADATA_MARKER.len())
+            .rposition(|window| window == METADATA_MARKER)
+            .map(|at| tail + at)
+            .ok_or(INVALID)?;
+        let metadata = Decoder(&bytes[marker + METADATA_MARKER.len()..])
+            .decode(0, 0)
+            .map(|(metadata, _)| metadata)
+            .ok_or(INVALID)?;
+        let number = |key: &str| metadata.get(key).and_then(Json::as_f64);
+
+        let node_count = number("node_count").ok_or(INVALID)? as u32;
+        let record_size = number("record_size").ok_or(INVALID)? as u16;
+        let ip_version = number("ip_version").ok_or(INVALID)? as u16;
+        if ![24, 28, 32].contains(&record_size) || ![4, 6].contains(&ip_version) {
+            return Err(INVALID);
+        }
+
+        let tree_size = node_count as usize * record_size as usize / 4;
+        let data_start = tree_size + 16;
+        if data_start > marker {
+            return Err(INVALID);
+        }
+
+        Ok(Database {
+            bytes,
+            node_count,
+            record_size,
+            ip_version,
+            data: (data_start, marker),
+        })
+    }
+
+    /// Returns the record of the network `addr` is in, or `None` if it is in none.
+    fn lookup(&self, addr: IpAddr) -> Option<Json> {
+        // IPv4 addresses are stored in the ::/96 subtree of IPv6 databases.
+        let (key, bits) = match (addr, self.ip_version) {
+            (IpAddr::V4(v4), 4) => (u32::from(v4) as u128, 32),
+            (IpAddr::V4(v4), _) => (u32::from(v4) as u128, 128),
+            (IpAddr::V6(v6), 6) => (u128::from(v6), 128),
+            (IpAddr::V6(_), _) => return None,
+        };
+
+        let mut node = 0;
+        for i in (0..bits).rev() {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.record(node, (key >> i) & 1 == 1)?;
+        }
+        if node <= self.node_count {
+            return None;
+        }
+
+        let (start, end) = self.data;
+        let offset = ((node - self.node_count) as usize).checked_sub(16)?;
+        Decoder(&self.bytes[start..end])
+            .decode(offset, 0)
+            .map(|(record, _)| record)
+    }
+
+    /// Returns the left or right record of a node of the search tree.
+    fn record(&self, node: u32, right: bool) -> Option<u32> {
+        let size = self.record_size as usize / 4;
+        let at = node as usize * size;
+        let b = self.bytes.get(at..at + size)?;
+        let be = |bytes: &[u8]| bytes.iter().fold(0, |n, &b| (n << 8) | b as u32);
+
+        Some(match (self.record_size, right) {
+            (24, false) => be(&b[0..3]),
+            (24, true) => be(&b[3..6]),
+            (28, false) => ((b[3] as u32 >> 4) << 24) | be(&b[0..3]),
+            (28, true) => ((b[3] as u32 & 0x0f) << 24) | be(&b[4..7]),
+            (_, false) => be(&b[0..4]),
+            (_, true) => be(&b[4..8]),
+        })
+    }
+}
+
+/// Looks up a public address in every database, the first to know a field providing it.
+///
+/// # Returns
+///
+/// `None` if the address is not globally routable or no database knows anything of it.
+pub fn locate(databases: &[Database], addr: IpAddr) -> Option<Location> {
+    if !risk::is_internet(addr) {
+        return None;
+    }
+
+    let mut location = Location::default();
+    for record in databases.iter().filter_map(|db| db.lookup(addr)) {
+        let text = |path: &[&str]| -> Option<String> {
+            path.iter()
+                .try_fold(&record, |value, key| value.get(key))
+                .and_then(Json::as_str)
+                .map(str::to_string)
+        };
+        let country = ["country", "registered_country"]
+            .into_iter()
+            .find(|key| record.get(key).is_some())
+            .unwrap_or("country");
+
+        location.country = location.country.or(text(&[country, "iso_code"]));
+        location.country_name = location.country_name.or(text(&[country, "names", "en"]));
+        location.city = location.city.or(text(&["city", "names", "en"]));
+        location.asn = location.asn.or(record
+            .get("autonomous_system_number")
+            .and_then(Json::as_f64)
+            .map(|asn| asn as u32));
+        location.as_org = location
+            .as_org
+            .or(text(&["autonomous_system_organization"]));
+    }
+
+    (location != Location::default()).then_some(location)
+}
+
+impl fmt::Display for Location {
+    /// Formats the location as e.g. `Frankfurt am Main, Germany (DE), AS64500 Example GmbH`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let place: Vec<&str> = [&self.city, &self.country_name]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let mut parts = vec![];
+        match (&self.country, place.is_empty()) {
+            (Some(code), false) => parts.push(format!("{} ({})", place.join(", "), code)),
+            (Some(code), true) => parts.push(code.clone()),
+            (None, false) => parts.push(place.join(", ")),
+            (None, true) => {}
+        }
+        match (self.asn, &self.as_org) {
+            (Some(asn), Some(org)) => parts.push(format!("AS{} {}", asn, org)),
+            (Some(asn), None) => parts.push(format!("AS{}", asn)),
+            (None, Some(org)) => parts.push(org.clone()),
+            (None, None) => {}
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Decodes the typed values of a data or metadata section.
+struct Decoder<'a>(&'a [u8]);
+
+impl Decoder<'_> {
+    /// Decodes the value at `at`.
+    ///
+    /// # Returns
+    ///
+    /// The value and where the next one starts, or `None` if the section is corrupt.
+    /// Byte strings and unknown types decode to `Json::Null`.
+    fn decode(&self, at: usize, depth: u8) -> Option<(Json, usize)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let control = *self.0.get(at)?;
+        let mut at = at + 1;
+
+        let mut kind = control >> 5;
+        if kind == 1 {
+            return self.pointer(control, at, depth);
+        }
+        if kind == 0 {
+            kind = self.0.get(at)?.checked_add(7)?;
+            at += 1;
+        }
+
+        let mut size = (control & 0x1f) as usize;
+        if size >= 29 {
+            let extra = size - 28;
+            let n = self.uint(at, extra)? as usize;
+            size = match extra {
+                1 => 29 + n,
+                2 => 285 + n,
+                _ => 65_821 + n,
+            };
+            at += extra;
+        }
+
+        let value = match kind {
+            // Maps and arrays hold `size` entries rather than bytes.
+            7 => {
+                let mut members = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let (key, next) = self.decode(at, depth + 1)?;
+                    let (value, next) = self.decode(next, depth + 1)?;
+                    members.push((key.as_str()?.to_string(), value));
+                    at = next;
+                }
+                return Some((Json::Object(members), at));
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let (item, next) = self.decode(at, depth + 1)?;
+                    items.push(item);
+                    at = next;
+                }
+                return Some((Json::Array(items), at));
+            }
+            // Booleans hold their value in the size.
+            14 => return Some((Json::Bool(size != 0), at)),
+            2 => Json::String(String::from_utf8_lossy(self.0.get(at..at + size)?).into_owned()),
+            3 if size == 8 => {
+                Json::Number(f64::from_be_bytes(self.0.get(at..at + 8)?.try_into().ok()?))
+            }
+            15 if size == 4 => {
+                Json::Number(f32::from_be_bytes(self.0.get(at..at + 4)?.try_into().ok()?) as f64)
+            }
+            5 | 6 | 9 | 10 if size <= 16 => Json::Number(self.uint(at, size)? as f64),
+            8 if size <= 4 => Json::Number(self.uint(at, size)? as u32 as i32 as f64),
+            _ => Json::Null,
+        };
+        Some((value, at + size))
+    }
+
+    /// Follows a pointer to a value elsewhere in the section.
+    fn pointer(&self, control: u8, at: usize, depth: u8) -> Option<(Json, usize)> {
+        let high = (control & 0x07) as usize;
+        let (target, len) = match (control >> 3) & 0x03 {
+            0 => ((high << 8) | self.uint(at, 1)? as usize, 1),
+            1 => (((high << 16) | self.uint(at, 2)? as usize) + 2048, 2),
+            2 => (((high << 24) | self.uint(at, 3)? as usize) + 526_336, 3),
+            _ => (self.uint(at, 4)? as usize, 4),
+        };
+        let (value, _) = self.decode(target, depth + 1)?;
+        Some((value, at + len))
+    }
+
+    /// Reads a big-endian unsigned integer of `len` bytes.
+    fn uint(&self, at: usize, len: usize) -> Option<u128> {
+        let bytes = self.0.get(at..at + len)?;
+        Some(bytes.iter().fold(0, |n, &b| (n << 8) | b as u128))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes a string of the data section.
+    fn string(s: &str) -> Vec<u8> {
+        [vec![0x40 | s.len() as u8], s.as_bytes().to_vec()].concat()
+    }
+
+    /// Encodes a map of `n` members, followed by their keys and values.
+    fn map(n: u8, members: &[Vec<u8>]) -> Vec<u8> {
+        [vec![0xe0 | n], members.concat()].concat()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Json> {
+        Decoder(bytes).decode(0, 0).map(|(value, _)| value)
+    }
+
+    #[test]
+    fn decodes_data_types() {
+        assert_eq!(decode(&string("DE")), Some(Json::String("DE".into())));
+        // uint16 and uint32.
+        assert_eq!(decode(&[0xa2, 0x01, 0x00]), Some(Json::Number(256.0)));
+        assert_eq!(
+            decode(&[0xc3, 0x00, 0xfb, 0xf4]),
+            Some(Json::Number(64500.0))
+        );
+        // int32 -1 and uint64 through the extended type byte.
+        assert_eq!(
+            decode(&[0x04, 0x01, 0xff, 0xff, 0xff, 0xff]),
+            Some(Json::Number(-1.0))
+        );
+        assert_eq!(decode(&[0x01, 0x02, 0x2a]), Some(Json::Number(42.0)));
+        // A boolean and a double.
+        assert_eq!(decode(&[0x01, 0x07]), Some(Json::Bool(true)));
+        let double = [vec![0x68], 1.5f64.to_be_bytes().to_vec()].concat();
+        assert_eq!(decode(&double), Some(Json::Number(1.5)));
+        assert_eq!(
+            decode(&map(1, &[string("en"), string("Germany")])),
+            Some(Json::Object(vec![(
+                "en".into(),
+                Json::String("Germany".into())
+            )]))
+        );
+        // An array of two strings.
+        let array = [vec![0x02, 0x04], string("a"), string("b")].concat();
+        assert_eq!(
+            decode(&array),
+            Some(Json::Array(vec![
+                Json::String("a".into()),
+                Json::String("b".into())
+            ]))
+        );
+    }
+
+    #[test]
+    fn decodes_long_strings() {
+        let text = "x".repeat(300);
+        let bytes = [vec![0x5e, 0x00, 0x0f], text.as_bytes().to_vec()].concat();
+        assert_eq!(decode(&bytes), Some(Json::String(text)));
+    }
+
+    #[test]
+    fn follows_pointers() {
+        // A map whose value points back to the string after it.
+        let bytes = [map(1, &[string("k"), vec![0x20, 0x05]]), string("v")].concat();
+        let (value, next) = Decoder(&bytes).decode(0, 0).unwrap();
+        assert_eq!(
+            value,
+            Json::Object(vec![("k".into(), Json::String("v".into()))])
+        );
+        assert_eq!(next, 5);
+    }
+
+    #[test]
+    fn rejects_corrupt_sections() {
+        // An extended type byte that overflows.
+        assert_eq!(decode(&[0x01, 0xf9]), None);
+        // A pointer to itself.
+        assert_eq!(decode(&[0x20, 0x00]), None);
+        // A string running past the end.
+        assert_eq!(decode(&[0x45, b'a']), None);
+        // A map key that is not a string.
+        assert_eq!(decode(&[0xe1, 0xa1, 0x01, 0xa1, 0x01]), None);
+    }
+
+    /// Writes an IPv4 database with one node: addresses below 128.0.0.0 are in Germany,
+    /// AS64500, the others are in no network.
+    fn database(name: &str) -> String {
+        let mut bytes = vec![0, 0, 17, 0, 0, 1];
+        bytes.extend([0; 16]);
+        bytes.extend(map(
+            2,
+            &[
+                string("country"),
+                map(
+                    2,
+                    &[
+                        string("iso_code"),
+                        string("DE"),
+                        string("names"),
+                        map(1, &[string("en"), string("Germany")]),
+                    ],
+                ),
+                string("autonomous_system_number"),
+                vec![0xc2, 0xfb, 0xf4],
+            ],
+        ));
+        bytes.extend(METADATA_MARKER);
+        bytes.extend(map(
+            3,
+            &[
+                string("node_count"),
+                vec![0xc1, 1],
+                string("record_size"),
+                vec![0xa1, 24],
+                string("ip_version"),
+                vec![0xa1, 4],
+            ],
+        ));
+        let path = std::env::temp_dir().join(format!("ip-sniffer-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn locates_addresses() {
+        let path = database("geo.mmdb");
+        let databases = [Database::load(&path).unwrap()];
+        fs::remove_file(path).unwrap();
+
+        let location = locate(&databases, "8.8.8.8".parse().unwrap()).unwrap();
+        assert_eq!(location.to_string(), "Germany (DE), AS64500");
+        assert_eq!(locate(&databases, "193.0.0.1".parse().unwrap()), None);
+        assert_eq!(locate(&databases, "10.0.0.1".parse().unwrap()), None);
+        assert_eq!(locate(&databases, "2001:4860::8888".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("ip-sniffer-{}-not.mmdb", std::process::id()));
+        fs::write(&path, b"not a database").unwrap();
+        let result = Database::load(&path.to_string_lossy());
+        fs::remove_file(path).unwrap();
+        assert_eq!(result.err(), Some(INVALID));
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use ip_sniffer::scan::OpenPort;
+
+use crate::i18n::Lang;
+
+/// Number of open ports above which a host is considered to be answering on "everything",
+/// which is how LaBrea-style tarpits behave.
+const OPEN_PORT_LIMIT: usize = 1000;
+
+/// Minimum number of open ports before the latency and banner heuristics are applied.
+const MIN_SAMPLE: usize = 10;
+
+/// Coefficient of variation of connect latencies below which timing is considered uniform.
+const UNIFORM_LATENCY: f64 = 0.05;
+
+/// Maximum number of open ports whose banners are read.
+const BANNER_SAMPLE: usize = 20;
+
+/// How long to wait for a service to send its greeting.
+const BANNER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Fraction of sampled ports that must share one banner for it to be suspicious.
+const SHARED_BANNER_RATIO: f64 = 0.5;
+
+/// A heuristic that matched, with what it measured.
+pub enum Reason {
+    /// This many ports accepted connections.
+    OpenEverywhere(usize),
+    /// The mean connect latency was this many milliseconds across this many ports, with
+    /// little variation.
+    UniformLatency(f64, usize),
+    /// This many of this many sampled ports sent this same banner.
+    SharedBanner(usize, usize, String),
+}
+
+impl Reason {
+    /// Describes the reason for people, in the given language.
+    pub fn text(&self, lang: Lang) -> String {
+        match self {
+            Reason::OpenEverywhere(open) => {
+                let message =
+                    "{} ports accepted connections, which suggests a tarpit answering on every port";
+                lang.format(message, &[open])
+            }
+            Reason::UniformLatency(ms, ports) => {
+                let message = "connect latency is nearly constant ({}ms) across {} ports";
+                lang.format(message, &[&format!("{:.1}", ms), ports])
+            }
+            Reason::SharedBanner(count, sampled, banner) => {
+                let message = "{} of {} sampled ports sent the same banner {}";
+                lang.format(message, &[count, sampled, &format!("{:?}", banner)])
+            }
+        }
+    }
+}
+
+/// Checks the results of a scan for signs of a honeypot or tarpit.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `open` - The open ports found on the host.
+///
+/// # Returns
+///
+/// A reason for every heuristic that matched. An empty vector means the host looks
+/// genuine.
+///
+/// # Description
+///
+/// Three heuristics are applied:
+///
+/// * The host accepts connections on an implausible number of ports.
+/// * Connect latency is nearly constant across many ports, as when a single process
+///   emulates every service.
+/// * Many ports send the exact same greeting banner.
+///
+/// Window-size tricks used by LaBrea are not visible to a connect scan, but such tarpits
+/// are still caught by the first heuristic since they answer on every port.
+pub fn assess(addr: IpAddr, open: &[OpenPort]) -> Vec<Reason> {
+    let mut reasons = vec![];
+
+    if open.len() > OPEN_PORT_LIMIT {
+        reasons.push(Reason::OpenEverywhere(open.len()));
+    }
+
+    if open.len() < MIN_SAMPLE {
+        return reasons;
+    }
+
+    let latencies: Vec<f64> = open.iter().map(|p| p.latency.as_secs_f64()).collect();
+    let mean = latencies.iter().sum::<f64>() / latencies.len() as f64;
+    let variance =
+        latencies.iter().map(|l| (l - mean).powi(2)).sum::<f64>() / latencies.len() as f64;
+
+    if mean > 0.0 && variance.sqrt() / mean < UNIFORM_LATENCY {
+        reasons.push(Reason::UniformLatency(mean * 1000.0, open.len()));
+    }
+
+    let banners = grab_banners(addr, open);
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+
+    for banner in banners.iter().flatten() {
+        *counts.entry(banner.as_str()).or_default() += 1;
+    }
+
+    if let Some((banner, count)) = counts.into_iter().max_by_key(|(_, count)| *count) {
+        if count > 1 && count as f64 >= banners.len() as f64 * SHARED_BANNER_RATIO {
+            reasons.push(Reason::SharedBanner(
+                count,
+                banners.len(),
+                banner.to_string(),
+            ));
+        }
+    }
+
+    reasons
+}
+
+/// Reads the greeting banner of up to `BANNER_SAMPLE` open ports in parallel.
+///
+/// # Returns
+///
+/// One entry per sampled port; `None` if the port sent nothing before `BANNER_TIMEOUT`.
+fn grab_banners(addr: IpAddr, open: &[OpenPort]) -> Vec<Option<String>> {
+    thread::scope(|s| {
+        let handles: Vec<_> = open
+            .iter()
+            .take(BANNER_SAMPLE)
+            .map(|p| s.spawn(move || grab_banner(SocketAddr::new(addr, p.port))))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or(None))
+            .collect()
+    })
+}
+
+/// Connects to `target` and returns whatever the service sends first, trimmed.
+fn grab_banner(target: SocketAddr) -> Option<String> {
+    let mut stream = TcpStream::connect_timeout(&target, BANNER_TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(BANNER_TIMEOUT)).ok()?;
+
+    let mut buf = [0; 256];
+    let n = stream.read(&mut buf).ok()?;
+    let banner = String::from_utf8_lossy(&buf[..n]).trim().to_string();
+
+    if banner.is_empty() {
+        None
+    } else {
+        Some(banner)
+    }
+}
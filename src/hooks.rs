@@ -0,0 +1,120 @@
+use std::io;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use ip_sniffer::scan::OpenPort;
+
+use crate::{Arguments, DEFAULT_THREADS};
+
+/// Environment passed to hooks, as shown in the man page.
+pub const ENVIRONMENT: &[(&str, &str)] = &[
+    ("IP_SNIFFER_PHASE", "`pre` or `post`"),
+    ("IP_SNIFFER_TARGET", "the IP address being scanned"),
+    ("IP_SNIFFER_THREADS", "the number of scan threads"),
+    ("IP_SNIFFER_NOTE", "the text given with --note, if any"),
+    (
+        "IP_SNIFFER_OPEN_PORTS",
+        "comma-separated open ports (post-hook only)",
+    ),
+    (
+        "IP_SNIFFER_OPEN_COUNT",
+        "number of open ports (post-hook only)",
+    ),
+    (
+        "IP_SNIFFER_DURATION_MS",
+        "scan duration in milliseconds (post-hook only)",
+    ),
+];
+
+/// Runs the pre-scan hook.
+///
+/// # Arguments
+///
+/// * `command` - The shell command to run.
+/// * `arguments` - The parsed command-line arguments, exposed to the hook as environment variables.
+///
+/// # Errors
+///
+/// * "failed to start hook" if the shell could not be started.
+/// * "hook exited with a failure status" if the command did not succeed.
+pub fn run_pre(command: &str, arguments: &Arguments) -> Result<(), &'static str> {
+    let mut cmd = shell(command);
+    cmd.env("IP_SNIFFER_PHASE", "pre");
+    common_env(&mut cmd, arguments);
+    execute(cmd)
+}
+
+/// Runs the post-scan hook.
+///
+/// # Arguments
+///
+/// * `command` - The shell command to run.
+/// * `arguments` - The parsed command-line arguments, exposed to the hook as environment variables.
+/// * `open` - The open ports found by the scan.
+/// * `duration` - How long the scan took.
+///
+/// # Errors
+///
+/// * "failed to start hook" if the shell could not be started.
+/// * "hook exited with a failure status" if the command did not succeed.
+pub fn run_post(
+    command: &str,
+    arguments: &Arguments,
+    open: &[OpenPort],
+    duration: Duration,
+) -> Result<(), &'static str> {
+    let mut ports: Vec<u16> = open.iter().map(|p| p.port).collect();
+    ports.sort();
+    let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+
+    let mut cmd = shell(command);
+    cmd.env("IP_SNIFFER_PHASE", "post")
+        .env("IP_SNIFFER_OPEN_PORTS", ports.join(","))
+        .env("IP_SNIFFER_OPEN_COUNT", ports.len().to_string())
+        .env("IP_SNIFFER_DURATION_MS", duration.as_millis().to_string());
+    common_env(&mut cmd, arguments);
+    execute(cmd)
+}
+
+fn common_env(cmd: &mut Command, arguments: &Arguments) {
+    cmd.env("IP_SNIFFER_TARGET", arguments.ipaddr.to_string())
+        .env(
+            "IP_SNIFFER_THREADS",
+            arguments.threads.unwrap_or(DEFAULT_THREADS).to_string(),
+        );
+
+    if let Some(note) = &arguments.note {
+        cmd.env("IP_SNIFFER_NOTE", note);
+    }
+}
+
+/// Builds a command that runs `command` through the platform's shell.
+pub fn shell(command: &str) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    cmd
+}
+
+/// Runs `cmd` to completion. Its standard output is sent to our standard error so that
+/// hooks can never corrupt machine-readable scan output.
+fn execute(mut cmd: Command) -> Result<(), &'static str> {
+    let status = cmd
+        .stdin(Stdio::null())
+        .stdout(io::stderr())
+        .status()
+        .map_err(|_| "failed to start hook")?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err("hook exited with a failure status")
+    }
+}
@@ -0,0 +1,296 @@
+// Internationalized domain names are resolved by their ASCII form, in which every label
+// with non-ASCII characters is replaced by `xn--` and its Punycode (RFC 3492) encoding:
+//
+// bücher.example    <->    xn--bcher-kva.example
+//
+// Labels are lowercased before encoding, which covers the case mapping of IDNA for the
+// scripts hostnames commonly use; the full Unicode normalization tables are not applied.
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// Prefix of labels encoded with Punycode.
+const ACE_PREFIX: &str = "xn--";
+
+/// Longest label DNS allows, in bytes.
+const MAX_LABEL: usize = 63;
+
+/// Converts a hostname to the ASCII form it is resolved by.
+///
+/// # Returns
+///
+/// `name` unchanged if it is already ASCII, otherwise the name with each non-ASCII label
+/// lowercased and encoded, e.g. `xn--bcher-kva.example` for `Bücher.example`. Ideographic
+/// full stops, e.g. `。`, separate labels as dots do.
+///
+/// # Errors
+///
+/// * "invalid internationalized hostname" if an encoded label is longer than 63 bytes.
+pub fn to_ascii(name: &str) -> Result<String, &'static str> {
+    if name.is_ascii() {
+        return Ok(name.to_string());
+    }
+
+    let name = name.replace(['\u{3002}', '\u{ff0e}', '\u{ff61}'], ".");
+    let labels: Result<Vec<String>, &'static str> = name
+        .split('.')
+        .map(|label| {
+            let label = label.to_lowercase();
+            if label.is_ascii() {
+                return Ok(label);
+            }
+            let chars: Vec<u32> = label.chars().map(u32::from).collect();
+            match encode(&chars) {
+                Some(encoded) if ACE_PREFIX.len() + encoded.len() <= MAX_LABEL => {
+                    Ok(format!("{}{}", ACE_PREFIX, encoded))
+                }
+                _ => Err("invalid internationalized hostname"),
+            }
+        })
+        .collect();
+    Ok(labels?.join("."))
+}
+
+/// Converts a hostname to the Unicode form people read.
+///
+/// # Returns
+///
+/// `name` with every `xn--` label decoded, e.g. `bücher.example` for
+/// `xn--bcher-kva.example`. Labels that are not valid Punycode are left as they are.
+pub fn to_unicode(name: &str) -> String {
+    let labels: Vec<String> = name
+        .split('.')
+        .map(|label| {
+            let encoded = label
+                .get(..ACE_PREFIX.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(ACE_PREFIX))
+                .and_then(|_| decode(&label[ACE_PREFIX.len()..]));
+            encoded.unwrap_or_else(|| label.to_string())
+        })
+        .collect();
+    labels.join(".")
+}
+
+/// Encodes the code points of a label as Punycode, without the `xn--` prefix.
+///
+/// # Returns
+///
+/// `None` if the encoding overflows, which only very long labels can cause.
+fn encode(input: &[u32]) -> Option<String> {
+    let mut output: String = input
+        .iter()
+        .filter(|&&c| c < 0x80)
+        .filter_map(|&c| char::from_u32(c))
+        .collect();
+    let basic = output.len() as u32;
+    let mut handled = basic;
+    if basic > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    while (handled as usize) < input.len() {
+        // The smallest code point not yet handled.
+        let m = input.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+
+        for &c in input {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta = delta.checked_add(1)?;
+        n = n.checked_add(1)?;
+    }
+    Some(output)
+}
+
+/// Decodes a Punycode label, without the `xn--` prefix.
+///
+/// # Returns
+///
+/// `None` if it is not valid Punycode.
+fn decode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(at) => (&input[..at], &input[at + 1..]),
+        None => ("", input),
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut output: Vec<char> = basic.chars().collect();
+
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.bytes();
+    while digits.len() > 0 {
+        let old_i = i;
+        let mut weight: u32 = 1;
+        let mut k = BASE;
+        loop {
+            let d = value(digits.next()?)?;
+            i = i.checked_add(d.checked_mul(weight)?)?;
+            let t = threshold(k, bias);
+            if d < t {
+                break;
+            }
+            weight = weight.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+
+        let length = output.len() as u32 + 1;
+        bias = adapt(i - old_i, length, old_i == 0);
+        n = n.checked_add(i / length)?;
+        i %= length;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+/// Returns the threshold of the digit at position `k`.
+fn threshold(k: u32, bias: u32) -> u32 {
+    k.saturating_sub(bias).clamp(T_MIN, T_MAX)
+}
+
+/// Adapts the bias after a code point is encoded or decoded.
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+/// Returns the character of a Punycode digit: `a` to `z` for 0 to 25, `0` to `9` for 26
+/// to 35.
+fn digit(d: u32) -> char {
+    match d {
+        0..=25 => (b'a' + d as u8) as char,
+        _ => (b'0' + (d - 26) as u8) as char,
+    }
+}
+
+/// Returns the value of a Punycode digit, in either case.
+fn value(c: u8) -> Option<u32> {
+    match c {
+        b'a'..=b'z' => Some((c - b'a') as u32),
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'0'..=b'9' => Some((c - b'0') as u32 + 26),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples from RFC 3492, section 7.1: the code points and their Punycode.
+    const SAMPLES: &[(&[u32], &str)] = &[
+        // (A) Arabic (Egyptian)
+        (
+            &[
+                0x0644, 0x064A, 0x0647, 0x0645, 0x0627, 0x0628, 0x062A, 0x0643, 0x0644, 0x0645,
+                0x0648, 0x0634, 0x0639, 0x0631, 0x0628, 0x064A, 0x061F,
+            ],
+            "egbpdaj6bu4bxfgehfvwxn",
+        ),
+        // (B) Chinese (simplified)
+        (
+            &[
+                0x4ED6, 0x4EEC, 0x4E3A, 0x4EC0, 0x4E48, 0x4E0D, 0x8BF4, 0x4E2D, 0x6587,
+            ],
+            "ihqwcrb4cv8a8dqg056pqjye",
+        ),
+        // (L) 3<nen>B<gumi><kinpachi><sensei>
+        (
+            &[
+                0x0033, 0x5E74, 0x0042, 0x7D44, 0x91D1, 0x516B, 0x5148, 0x751F,
+            ],
+            "3B-ww4c5e180e575a65lsy2b",
+        ),
+        // (R) <sono><supiido><de>
+        (
+            &[0x305D, 0x306E, 0x30B9, 0x30D4, 0x30FC, 0x30C9, 0x3067],
+            "d9juau41awczczp",
+        ),
+        // (S) -> $1.00 <-
+        (
+            &[
+                0x002D, 0x003E, 0x0020, 0x0024, 0x0031, 0x002E, 0x0030, 0x0030, 0x0020, 0x003C,
+                0x002D,
+            ],
+            "-> $1.00 <--",
+        ),
+    ];
+
+    #[test]
+    fn encodes_rfc3492_samples() {
+        for (input, expected) in SAMPLES {
+            assert_eq!(encode(input).as_deref(), Some(*expected));
+        }
+    }
+
+    #[test]
+    fn decodes_rfc3492_samples() {
+        for (expected, input) in SAMPLES {
+            let expected: String = expected
+                .iter()
+                .map(|&c| char::from_u32(c).unwrap())
+                .collect();
+            assert_eq!(decode(input), Some(expected));
+        }
+    }
+
+    #[test]
+    fn converts_hostnames() {
+        assert_eq!(to_ascii("Bücher.example").unwrap(), "xn--bcher-kva.example");
+        assert_eq!(
+            to_ascii("bücher。example").unwrap(),
+            "xn--bcher-kva.example"
+        );
+        assert_eq!(to_ascii("example.com").unwrap(), "example.com");
+        assert_eq!(to_unicode("xn--bcher-kva.example"), "bücher.example");
+        assert_eq!(to_unicode("XN--bcher-kva.example"), "bücher.example");
+        // Not valid Punycode, so left as it is.
+        assert_eq!(
+            to_unicode("xn--99999999999.example"),
+            "xn--99999999999.example"
+        );
+    }
+
+    #[test]
+    fn rejects_overlong_labels() {
+        assert!(to_ascii(&"ü".repeat(60)).is_err());
+    }
+}
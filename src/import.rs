@@ -0,0 +1,282 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use ip_sniffer::events::Bus;
+use ip_sniffer::scan::{self, Confidence, Connector, OpenPort, PortOrder};
+
+use crate::config::Config;
+use crate::i18n::Lang;
+use crate::json::Json;
+use crate::output::{self, OutputFormat};
+
+/// Open ports per host, as recorded by another scanner.
+pub type Imported = BTreeMap<IpAddr, BTreeSet<u16>>;
+
+/// Loads the hosts and open TCP ports from an nmap or masscan output file.
+///
+/// # Arguments
+///
+/// * `path` - An nmap XML file (`-oX`), or a masscan JSON (`-oJ`) or list (`-oL`) file.
+///
+/// # Returns
+///
+/// The open ports of every host in the file. Hosts without open ports are kept with an
+/// empty set so they are still scanned.
+///
+/// # Errors
+///
+/// * "failed to read import file" if the file cannot be read.
+/// * "invalid JSON" if a masscan JSON file cannot be parsed.
+/// * "unrecognized import file; must be nmap XML or masscan JSON or list output" if the
+///   format is not recognized.
+pub fn load(path: &str) -> Result<Imported, &'static str> {
+    let contents = fs::read_to_string(path).map_err(|_| "failed to read import file")?;
+    let trimmed = contents.trim_start();
+
+    if trimmed.starts_with('<') {
+        Ok(parse_nmap(&contents))
+    } else if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        parse_masscan_json(&contents)
+    } else if trimmed.starts_with('#') || trimmed.starts_with("open ") {
+        Ok(parse_masscan_list(&contents))
+    } else {
+        Err("unrecognized import file; must be nmap XML or masscan JSON or list output")
+    }
+}
+
+/// Parses nmap XML output, taking `<port>` elements whose state is `open`.
+fn parse_nmap(xml: &str) -> Imported {
+    let mut imported = Imported::new();
+
+    for host in elements(xml, "host") {
+        let addr = tags(host, "address")
+            .into_iter()
+            .filter(|tag| matches!(attr(tag, "addrtype"), Some("ipv4" | "ipv6")))
+            .find_map(|tag| IpAddr::from_str(attr(tag, "addr")?).ok());
+        let Some(addr) = addr else { continue };
+
+        let ports = imported.entry(addr).or_default();
+
+        for port in elements(host, "port") {
+            let open = tags(port, "state")
+                .into_iter()
+                .any(|tag| attr(tag, "state") == Some("open"));
+
+            if open && attr(port, "protocol") == Some("tcp") {
+                if let Some(number) = attr(port, "portid").and_then(|p| p.parse().ok()) {
+                    ports.insert(number);
+                }
+            }
+        }
+    }
+
+    imported
+}
+
+/// Returns every `<name ...>...</name>` element in `xml`, starting at its opening tag.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}", name), format!("</{}>", name));
+
+    xml.match_indices(&open)
+        .map(|(i, _)| &xml[i..])
+        .filter(|element| element[open.len()..].starts_with([' ', '>']))
+        .map(|element| element.split(&close).next().unwrap_or_default())
+        .collect()
+}
+
+/// Returns the attribute text of every `<name ...>` tag in `xml`.
+fn tags<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    xml.match_indices(&format!("<{} ", name))
+        .filter_map(|(i, _)| xml[i..].split_once('>').map(|(tag, _)| tag))
+        .collect()
+}
+
+/// Returns the value of attribute `name` in a tag, e.g. `22` for `portid` in
+/// `<port protocol="tcp" portid="22">`.
+fn attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+/// Parses masscan JSON output: an array of `{"ip": ..., "ports": [{"port": ..., "proto": ...,
+/// "status": ...}]}` records. Older masscan versions write one record per line with a
+/// trailing comma, so each line is also tried on its own.
+fn parse_masscan_json(contents: &str) -> Result<Imported, &'static str> {
+    let records = match Json::parse(contents) {
+        Ok(Json::Array(records)) => records,
+        Ok(record) => vec![record],
+        Err(err) => {
+            let lines: Vec<&str> = contents
+                .lines()
+                .map(|line| line.trim().trim_end_matches(','))
+                .filter(|line| line.starts_with('{'))
+                .collect();
+            if lines.is_empty() {
+                return Err(err);
+            }
+            lines
+                .into_iter()
+                .map(Json::parse)
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    let mut imported = Imported::new();
+
+    for record in &records {
+        let Some(addr) = record
+            .get("ip")
+            .and_then(Json::as_str)
+            .and_then(|ip| IpAddr::from_str(ip).ok())
+        else {
+            continue;
+        };
+        let ports = imported.entry(addr).or_default();
+
+        for port in record.get("ports").map(Json::as_array).unwrap_or_default() {
+            let tcp = port.get("proto").and_then(Json::as_str) == Some("tcp");
+            let open = port.get("status").and_then(Json::as_str) == Some("open");
+            let number = port.get("port").and_then(Json::as_f64);
+
+            if let (true, true, Some(number)) = (tcp, open, number) {
+                ports.insert(number as u16);
+            }
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Parses masscan list output: `open tcp PORT IPADDR TIMESTAMP` lines.
+fn parse_masscan_list(contents: &str) -> Imported {
+    let mut imported = Imported::new();
+
+    for line in contents.lines() {
+        if let ["open", "tcp", port, ip, ..] = line.split_whitespace().collect::<Vec<_>>()[..] {
+            if let (Ok(port), Ok(addr)) = (port.parse(), IpAddr::from_str(ip)) {
+                imported.entry(addr).or_default().insert(port);
+            }
+        }
+    }
+
+    imported
+}
+
+/// Settings of re-scanning imported hosts.
+pub struct Rescan<'a> {
+    /// The number of threads used for full scans.
+    pub threads: u16,
+    pub connector: &'a Connector,
+    /// Hosts in its `exclude` networks are skipped.
+    pub config: &'a Config,
+    pub output: OutputFormat,
+    /// How long a full scan of one host may take.
+    pub host_timeout: Option<Duration>,
+    /// When to stop; hosts not yet re-checked are skipped.
+    pub deadline: Option<Instant>,
+    /// The language the text output is printed in.
+    pub lang: Lang,
+}
+
+impl Rescan<'_> {
+    /// Re-scans imported hosts: known open ports are verified, and hosts the import has no
+    /// open ports for get a full scan.
+    ///
+    /// # Arguments
+    ///
+    /// * `imported` - The hosts and open ports loaded with `load`.
+    pub fn run(&self, imported: &Imported) {
+        let Rescan {
+            threads,
+            connector,
+            config,
+            output: format,
+            host_timeout,
+            deadline,
+            lang,
+        } = *self;
+        let mut skipped = 0;
+
+        for (&addr, known) in imported {
+            if config.excludes(addr) {
+                let message = "{}: skipped, excluded by the config";
+                eprintln!("{}", lang.format(message, &[&addr]));
+                continue;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                skipped += 1;
+                continue;
+            }
+
+            let (open, dropped): (Vec<u16>, Vec<u16>) = if known.is_empty() {
+                let host_deadline = crate::host_deadline(host_timeout, deadline);
+                let mut bus = Bus::new();
+                bus.subscribe(crate::health_warnings(lang, false));
+                let host_scan = scan::scan_host_with(
+                    addr,
+                    threads,
+                    connector.clone(),
+                    PortOrder::Sequential,
+                    host_deadline,
+                    &mut bus,
+                );
+                if host_scan.deadline_reached {
+                    let message = "{}: deadline reached; {} port(s) were not probed";
+                    eprintln!("{}", lang.format(message, &[&addr, &host_scan.unprobed]));
+                }
+                let mut open: Vec<u16> = host_scan.open.iter().map(|p| p.port).collect();
+                open.sort();
+                (open, vec![])
+            } else {
+                let known: Vec<OpenPort> = known
+                    .iter()
+                    .map(|&port| OpenPort {
+                        port,
+                        latency: Default::default(),
+                        confidence: Confidence::Low,
+                    })
+                    .collect();
+                let (confirmed, dropped) = scan::verify(addr, &known, connector);
+                (
+                    confirmed.iter().map(|p| p.port).collect(),
+                    dropped.iter().map(|p| p.port).collect(),
+                )
+            };
+
+            match format {
+                OutputFormat::Jsonl => {
+                    for &port in &open {
+                        println!("{}", output::jsonl_record(addr, port));
+                    }
+                    for &port in &dropped {
+                        println!("{}", output::jsonl_unverified_record(addr, port));
+                    }
+                }
+                OutputFormat::Text if known.is_empty() => {
+                    let ports: Vec<String> = open.iter().map(u16::to_string).collect();
+                    let message = "{}: no open ports in the import; full scan found: {}";
+                    println!("{}", lang.format(message, &[&addr, &ports.join(", ")]));
+                }
+                OutputFormat::Text => {
+                    let message = "{}: {} of {} imported port(s) still open";
+                    println!(
+                        "{}",
+                        lang.format(message, &[&addr, &open.len(), &known.len()])
+                    );
+                    for port in &dropped {
+                        println!("{}", lang.format("  - {} is no longer open", &[port]));
+                    }
+                }
+            }
+        }
+
+        if skipped > 0 {
+            let message = "scan deadline reached; {} imported host(s) were not re-checked";
+            eprintln!("{}", lang.format(message, &[&skipped]));
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Job files are a small subset of YAML listing named scan jobs. Each job scans its
+// targets one after the other with its own options, writing their results to its own
+// file, and `parallel` jobs run at the same time:
+//
+// parallel: 2
+//
+// dmz:
+//   targets: [203.0.113.5, www.example.com]
+//   options: -j 200 --adaptive-timeout --service-detect --output jsonl
+//   output: results/dmz.jsonl
+// lan:
+//   targets: 192.168.1.1
+//   options: --host-timeout 5m --note "quarterly review"
+
+/// A scan job of a job file.
+#[derive(Debug, Default)]
+pub struct Job {
+    pub name: String,
+    /// Targets as given on the command line, i.e. IP addresses or hostnames.
+    pub targets: Vec<String>,
+    /// Options passed to the scan of every target, after the global ones.
+    pub options: Vec<String>,
+    /// File the results of every target are written to; standard output if `None`.
+    pub output: Option<PathBuf>,
+}
+
+/// The jobs of a job file and how many of them run at the same time.
+#[derive(Debug)]
+pub struct Plan {
+    pub parallel: usize,
+    pub jobs: Vec<Job>,
+}
+
+/// The outcome of a job.
+#[derive(Debug)]
+pub struct Outcome {
+    pub name: String,
+    /// Number of targets scanned.
+    pub scanned: usize,
+    /// Number of those whose scan could not be started or exited with a non-zero status.
+    pub failed: usize,
+}
+
+impl Plan {
+    /// Loads and parses a job file.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to read jobs file" if the file cannot be read.
+    /// * Any error returned by `Plan::parse`.
+    pub fn load(path: &str) -> Result<Plan, &'static str> {
+        let contents = fs::read_to_string(path).map_err(|_| "failed to read jobs file")?;
+        Plan::parse(&contents)
+    }
+
+    /// Parses the contents of a job file.
+    ///
+    /// # Errors
+    ///
+    /// * "parallel must be a positive number" if `parallel` is not a number above 0.
+    /// * "job setting outside of a job in jobs file" if an indented line has no job above it.
+    /// * "unknown job setting; must be targets, options or output" if a job key is not
+    ///   recognized.
+    /// * "unterminated quote in job options" if a quote in `options` is not closed.
+    /// * "job has no targets" if a job lacks `targets`.
+    /// * "jobs file has no jobs" if no job is defined.
+    /// * "invalid line in jobs file" for anything else that is not understood.
+    pub fn parse(contents: &str) -> Result<Plan, &'static str> {
+        let mut plan = Plan {
+            parallel: 1,
+            jobs: vec![],
+        };
+
+        for line in contents.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let indented = line.starts_with([' ', '\t']);
+            let (key, value) = line
+                .trim()
+                .split_once(':')
+                .ok_or("invalid line in jobs file")?;
+            let value = value.trim();
+
+            if !indented {
+                match (key, value) {
+                    ("parallel", value) => {
+                        plan.parallel = match value.parse() {
+                            Ok(parallel) if parallel > 0 => parallel,
+                            _ => return Err("parallel must be a positive number"),
+                        }
+                    }
+                    (name, "") => plan.jobs.push(Job {
+                        name: name.trim().to_string(),
+                        ..Job::default()
+                    }),
+                    _ => return Err("invalid line in jobs file"),
+                }
+                continue;
+            }
+
+            let job = plan
+                .jobs
+                .last_mut()
+                .ok_or("job setting outside of a job in jobs file")?;
+            let unquoted = value.trim_matches(|c| c == '"' || c == '\'');
+
+            match key {
+                "targets" => {
+                    let list = value
+                        .strip_prefix('[')
+                        .and_then(|v| v.strip_suffix(']'))
+                        .unwrap_or(unquoted);
+                    job.targets = list
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|target| !target.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                }
+                "options" => job.options = split_options(value)?,
+                "output" if !unquoted.is_empty() => job.output = Some(PathBuf::from(unquoted)),
+                "output" => return Err("invalid line in jobs file"),
+                _ => return Err("unknown job setting; must be targets, options or output"),
+            }
+        }
+
+        if plan.jobs.is_empty() {
+            return Err("jobs file has no jobs");
+        }
+        if plan.jobs.iter().any(|job| job.targets.is_empty()) {
+            return Err("job has no targets");
+        }
+        Ok(plan)
+    }
+
+    /// Runs every job, up to `parallel` at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `global` - Options passed to every scan before the job's own, e.g. `--lang es`.
+    /// * `on_done` - Called with the outcome of each job as it finishes.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of every job, in the order of the file.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to locate the ip-sniffer executable" if the binary cannot be re-run.
+    ///
+    /// # Description
+    ///
+    /// Each target is scanned by running this executable again with the global options,
+    /// the job's options and the target, so jobs accept every option of a single scan.
+    /// A job's output file is truncated before its first target; its targets' results
+    /// follow each other in it. Messages on standard error are not redirected.
+    pub fn run(
+        &self,
+        global: &[String],
+        on_done: impl Fn(&Outcome) + Sync,
+    ) -> Result<Vec<Outcome>, &'static str> {
+        let exe = env::current_exe().map_err(|_| "failed to locate the ip-sniffer executable")?;
+        let next = AtomicUsize::new(0);
+        let outcomes: Mutex<Vec<Option<Outcome>>> =
+            Mutex::new(self.jobs.iter().map(|_| None).collect());
+
+        thread::scope(|scope| {
+            for _ in 0..self.parallel.min(self.jobs.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(job) = self.jobs.get(i) else {
+                        break;
+                    };
+
+                    let outcome = run_job(&exe, global, job);
+                    on_done(&outcome);
+                    outcomes.lock().unwrap()[i] = Some(outcome);
+                });
+            }
+        });
+
+        Ok(outcomes
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
+
+/// Scans the targets of `job` one after the other.
+fn run_job(exe: &Path, global: &[String], job: &Job) -> Outcome {
+    let mut outcome = Outcome {
+        name: job.name.clone(),
+        scanned: 0,
+        failed: 0,
+    };
+
+    let file = match &job.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(file),
+            Err(_) => {
+                outcome.failed = job.targets.len();
+                return outcome;
+            }
+        },
+        None => None,
+    };
+
+    for target in &job.targets {
+        let stdout = match file.as_ref().map(File::try_clone) {
+            Some(Ok(file)) => Stdio::from(file),
+            Some(Err(_)) => Stdio::null(),
+            None => Stdio::inherit(),
+        };
+        let status = Command::new(exe)
+            .args(global)
+            .args(&job.options)
+            .arg(target)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .status();
+
+        outcome.scanned += 1;
+        if !status.is_ok_and(|status| status.success()) {
+            outcome.failed += 1;
+        }
+    }
+    outcome
+}
+
+/// Splits job options on whitespace, keeping `"quoted text"` and `'quoted text'` together.
+///
+/// # Errors
+///
+/// * "unterminated quote in job options" if a quote is not closed.
+fn split_options(text: &str) -> Result<Vec<String>, &'static str> {
+    let mut options = vec![];
+    let mut current: Option<String> = None;
+    let mut quote = None;
+
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => options.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote in job options");
+    }
+    options.extend(current);
+    Ok(options)
+}
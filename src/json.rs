@@ -0,0 +1,175 @@
+use crate::output::parse_json_string;
+
+/// How deeply arrays and objects may nest, so hostile input cannot overflow the stack.
+const MAX_DEPTH: u8 = 128;
+
+/// A parsed JSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members in the order they appear in the document.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses a complete JSON document.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid JSON" if the text is not a single valid JSON value, or nests arrays and
+    ///   objects more than `MAX_DEPTH` deep.
+    pub fn parse(text: &str) -> Result<Json, &'static str> {
+        let (value, rest) = parse_value(text.trim_start(), 0).ok_or("invalid JSON")?;
+
+        if !rest.trim().is_empty() {
+            return Err("invalid JSON");
+        }
+        Ok(value)
+    }
+
+    /// Returns the member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of an array; anything else has none.
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+/// Parses one value at the start of `s`, returning it and the text after it.
+fn parse_value(s: &str, depth: u8) -> Option<(Json, &str)> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let s = s.trim_start();
+
+    match s.chars().next()? {
+        '{' => {
+            let mut members = vec![];
+            let mut rest = s[1..].trim_start();
+
+            if let Some(rest) = rest.strip_prefix('}') {
+                return Some((Json::Object(members), rest));
+            }
+            loop {
+                let (key, after) = parse_json_string(rest.trim_start())?;
+                let after = after.trim_start().strip_prefix(':')?;
+                let (value, after) = parse_value(after, depth + 1)?;
+                members.push((key, value));
+
+                let after = after.trim_start();
+                match after.chars().next()? {
+                    ',' => rest = &after[1..],
+                    '}' => return Some((Json::Object(members), &after[1..])),
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            let mut items = vec![];
+            let mut rest = s[1..].trim_start();
+
+            if let Some(rest) = rest.strip_prefix(']') {
+                return Some((Json::Array(items), rest));
+            }
+            loop {
+                let (value, after) = parse_value(rest, depth + 1)?;
+                items.push(value);
+
+                let after = after.trim_start();
+                match after.chars().next()? {
+                    ',' => rest = &after[1..],
+                    ']' => return Some((Json::Array(items), &after[1..])),
+                    _ => return None,
+                }
+            }
+        }
+        '"' => parse_json_string(s).map(|(string, rest)| (Json::String(string), rest)),
+        't' => s.strip_prefix("true").map(|rest| (Json::Bool(true), rest)),
+        'f' => s
+            .strip_prefix("false")
+            .map(|rest| (Json::Bool(false), rest)),
+        'n' => s.strip_prefix("null").map(|rest| (Json::Null, rest)),
+        _ => {
+            let end = s
+                .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                .unwrap_or(s.len());
+            let number = s[..end].parse().ok()?;
+            Some((Json::Number(number), &s[end..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let json = Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "d\u00e9"}} "#);
+        let expected = Json::Object(vec![
+            (
+                "a".to_string(),
+                Json::Array(vec![
+                    Json::Number(1.0),
+                    Json::Number(-25.0),
+                    Json::Bool(true),
+                    Json::Null,
+                ]),
+            ),
+            (
+                "b".to_string(),
+                Json::Object(vec![("c".to_string(), Json::String("dé".to_string()))]),
+            ),
+        ]);
+        assert_eq!(json, Ok(expected));
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        for text in [
+            "",
+            "[1,]",
+            "{\"a\" 1}",
+            "[1] 2",
+            "tru",
+            "{\"a\":1",
+            "\"open",
+        ] {
+            assert_eq!(Json::parse(text), Err("invalid JSON"), "{}", text);
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH as usize)).is_ok());
+        assert_eq!(Json::parse(&nested(100_000)), Err("invalid JSON"));
+    }
+}
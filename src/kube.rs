@@ -0,0 +1,244 @@
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::json::Json;
+use crate::provider::{Target, TargetProvider};
+
+/// A service of a Kubernetes cluster.
+#[derive(Debug)]
+struct Service {
+    /// `namespace/name`.
+    name: String,
+    /// `ClusterIP`, `NodePort`, `LoadBalancer` or `ExternalName`.
+    kind: String,
+    /// TCP node ports allocated to the service.
+    node_ports: Vec<u16>,
+    /// Load balancer ingress IPs and hostnames, and the service's external IPs.
+    endpoints: Vec<String>,
+}
+
+/// A node of a Kubernetes cluster with an external address.
+#[derive(Debug)]
+struct Node {
+    name: String,
+    addrs: Vec<IpAddr>,
+}
+
+/// A node port of a service on a node's external address.
+#[derive(Debug)]
+pub struct NodePort {
+    /// `namespace/name` of the service.
+    pub service: String,
+    /// The type of the service, e.g. `NodePort`.
+    pub kind: String,
+    pub node: String,
+    pub addr: IpAddr,
+    pub port: u16,
+}
+
+/// The services and nodes of a cluster, listed with `kubectl`.
+///
+/// # Usage
+///
+/// * `Cluster::load(context)` - List the services and nodes of a kubectl context.
+/// * `cluster.targets()` - The externally reachable endpoints, as a `TargetProvider`.
+/// * `cluster.node_ports()` - Every node port on every node's external address.
+#[derive(Debug)]
+pub struct Cluster {
+    context: String,
+    services: Vec<Service>,
+    nodes: Vec<Node>,
+}
+
+impl Cluster {
+    /// Lists the services of every namespace and the nodes of a kubectl context.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to run kubectl; is it installed and is the context valid?" if kubectl
+    ///   cannot be started or exits with an error.
+    /// * "unexpected output from kubectl" if its output is not the expected JSON.
+    pub fn load(context: &str) -> Result<Cluster, &'static str> {
+        let services = kubectl(context, &["get", "services", "--all-namespaces"])?;
+        let nodes = kubectl(context, &["get", "nodes"])?;
+
+        let services = items(&services)?.iter().map(service).collect();
+
+        let nodes = items(&nodes)?
+            .iter()
+            .map(|item| Node {
+                name: text(item.get("metadata").and_then(|m| m.get("name"))).to_string(),
+                addrs: list(
+                    item.get("status")
+                        .and_then(|status| status.get("addresses")),
+                )
+                .iter()
+                .filter(|addr| text(addr.get("type")) == "ExternalIP")
+                .filter_map(|addr| IpAddr::from_str(text(addr.get("address"))).ok())
+                .collect(),
+            })
+            .filter(|node| !node.addrs.is_empty())
+            .collect();
+
+        Ok(Cluster {
+            context: context.to_string(),
+            services,
+            nodes,
+        })
+    }
+
+    /// Returns every node port of every service on every node's external address.
+    ///
+    /// # Description
+    ///
+    /// Load balancer services are allocated node ports too, so their ports are
+    /// reachable on the nodes as well unless the nodes are firewalled.
+    pub fn node_ports(&self) -> Vec<NodePort> {
+        let mut node_ports = vec![];
+
+        for service in &self.services {
+            for &port in &service.node_ports {
+                for node in &self.nodes {
+                    for &addr in &node.addrs {
+                        node_ports.push(NodePort {
+                            service: service.name.clone(),
+                            kind: service.kind.clone(),
+                            node: node.name.clone(),
+                            addr,
+                            port,
+                        });
+                    }
+                }
+            }
+        }
+        node_ports
+    }
+}
+
+impl TargetProvider for Cluster {
+    fn describe(&self) -> String {
+        self.context.clone()
+    }
+
+    /// Returns the load balancer and external IPs of every service, then the external
+    /// addresses of the nodes if any service has node ports, each with the service or
+    /// node it belongs to, e.g. `kube service default/web`.
+    fn targets(&self) -> Result<Vec<Target>, &'static str> {
+        let mut targets: Vec<Target> = vec![];
+        let mut push = |name: String, resource: String| {
+            if !targets.iter().any(|target| target.name == name) {
+                targets.push(Target {
+                    name,
+                    resource: Some(resource),
+                });
+            }
+        };
+
+        for service in &self.services {
+            for endpoint in &service.endpoints {
+                push(endpoint.clone(), format!("kube service {}", service.name));
+            }
+        }
+        if self
+            .services
+            .iter()
+            .any(|service| !service.node_ports.is_empty())
+        {
+            for node in &self.nodes {
+                for addr in &node.addrs {
+                    push(addr.to_string(), format!("kube node {}", node.name));
+                }
+            }
+        }
+        Ok(targets)
+    }
+}
+
+/// Runs a kubectl command against `context` with JSON output.
+///
+/// # Errors
+///
+/// * "failed to run kubectl; is it installed and is the context valid?" if kubectl
+///   cannot be started or exits with an error.
+/// * "unexpected output from kubectl" if its output is not JSON.
+fn kubectl(context: &str, args: &[&str]) -> Result<Json, &'static str> {
+    let output = Command::new("kubectl")
+        .arg(format!("--context={}", context))
+        .args(args)
+        .arg("--output=json")
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .ok_or("failed to run kubectl; is it installed and is the context valid?")?;
+
+    let text = String::from_utf8(output.stdout).map_err(|_| "unexpected output from kubectl")?;
+    Json::parse(&text).map_err(|_| "unexpected output from kubectl")
+}
+
+/// Reads a service of `kubectl get services`.
+fn service(item: &Json) -> Service {
+    let spec = item.get("spec");
+    let metadata = item.get("metadata");
+
+    let ingress = list(
+        item.get("status")
+            .and_then(|status| status.get("loadBalancer"))
+            .and_then(|lb| lb.get("ingress")),
+    );
+    let endpoints = ingress
+        .iter()
+        .flat_map(|ingress| [ingress.get("ip"), ingress.get("hostname")])
+        .chain(
+            list(spec.and_then(|spec| spec.get("externalIPs")))
+                .iter()
+                .map(Some),
+        )
+        .map(text)
+        .filter(|endpoint| !endpoint.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let node_ports = list(spec.and_then(|spec| spec.get("ports")))
+        .iter()
+        .filter(|port| matches!(text(port.get("protocol")), "" | "TCP"))
+        .filter_map(|port| port.get("nodePort")?.as_f64())
+        .map(|port| port as u16)
+        .filter(|&port| port > 0)
+        .collect();
+
+    Service {
+        name: format!(
+            "{}/{}",
+            text(metadata.and_then(|m| m.get("namespace"))),
+            text(metadata.and_then(|m| m.get("name")))
+        ),
+        kind: text(spec.and_then(|spec| spec.get("type"))).to_string(),
+        node_ports,
+        endpoints,
+    }
+}
+
+/// Returns a string value, or an empty string if it is missing or not a string.
+fn text(value: Option<&Json>) -> &str {
+    value.and_then(Json::as_str).unwrap_or("")
+}
+
+/// Returns the elements of an array value, or none if it is missing.
+fn list(value: Option<&Json>) -> &[Json] {
+    value.map_or(&[], Json::as_array)
+}
+
+/// Returns the `items` of a kubectl list.
+///
+/// # Errors
+///
+/// * "unexpected output from kubectl" if it has none.
+fn items(list: &Json) -> Result<&[Json], &'static str> {
+    match list.get("items") {
+        Some(Json::Array(items)) => Ok(items),
+        _ => Err("unexpected output from kubectl"),
+    }
+}
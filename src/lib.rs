@@ -0,0 +1,16 @@
+//! A TCP connect port scanner.
+//!
+//! The `scan` module can be embedded in other applications: `scan::scan_host` scans a
+//! host to completion, `scan::scan_hosts` scans several hosts with one pool of threads,
+//! and `scan::ScanHandle` runs a scan in the background and lets the application pause,
+//! resume or cancel it. `scan::Connector` controls how ports are connected to,
+//! directly or through a `proxy::Proxy`. `scan::scan_host_with` publishes the probes,
+//! open ports, pauses and finished hosts of a scan as `events::Event`s on an
+//! `events::Bus`, so output writers, progress displays and notifiers can each subscribe
+//! as a sink; the library prints nothing itself.
+
+pub mod base64;
+pub mod events;
+pub mod idna;
+pub mod proxy;
+pub mod scan;
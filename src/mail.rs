@@ -0,0 +1,254 @@
+use std::io::{BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ip_sniffer::base64;
+
+use crate::probe::smtp;
+
+// `--email-report` mails the reports of watch mode through an SMTP server:
+//
+// 220 <- greeting          EHLO -> 250          AUTH PLAIN -> 235 (with credentials)
+// MAIL FROM -> 250         RCPT TO -> 250 each  DATA -> 354, message, "." -> 250
+//
+// The session is plaintext; servers that require TLS are reached through a local relay.
+
+/// Default port of SMTP servers.
+const SMTP_PORT: u16 = 25;
+
+/// How long the server may take to accept the connection or answer a command.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sender of the reports when `--email-from` is not given.
+pub const DEFAULT_FROM: &str = "ip-sniffer@localhost";
+
+/// An SMTP server that reports are sent through.
+#[derive(Clone, Debug)]
+pub struct Smtp {
+    /// `HOST:PORT` of the server.
+    server: String,
+    /// The user name and password of `AUTH PLAIN`, if given.
+    credentials: Option<(String, String)>,
+}
+
+impl FromStr for Smtp {
+    type Err = &'static str;
+
+    /// Parses `smtp://[USER:PASSWORD@]HOST[:PORT]`; the port defaults to 25.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("smtp://")
+            .ok_or("SMTP server must be an smtp:// URL")?;
+        let rest = rest.trim_end_matches('/');
+
+        let (credentials, host) = match rest.rsplit_once('@') {
+            Some((credentials, host)) => {
+                let (user, password) = credentials
+                    .split_once(':')
+                    .ok_or("SMTP credentials must be USER:PASSWORD")?;
+                (Some((user.to_string(), password.to_string())), host)
+            }
+            None => (None, rest),
+        };
+        if host.is_empty() {
+            return Err("SMTP server must be an smtp:// URL");
+        }
+
+        let has_port = match host.rsplit_once(':') {
+            Some((name, port)) => !name.ends_with(':') && port.parse::<u16>().is_ok(),
+            None => false,
+        };
+        let server = if has_port {
+            host.to_string()
+        } else {
+            format!("{}:{}", host, SMTP_PORT)
+        };
+
+        Ok(Smtp {
+            server,
+            credentials,
+        })
+    }
+}
+
+/// Sends reports by mail.
+///
+/// # Usage
+///
+/// * `Mailer { smtp, from, to, ehlo }` - Mail reports from `from` to every address of `to`.
+/// * `mailer.send(subject, body)` - Send one report.
+pub struct Mailer {
+    pub smtp: Smtp,
+    pub from: String,
+    pub to: Vec<String>,
+    /// The name sent with `EHLO`.
+    pub ehlo: String,
+}
+
+impl Mailer {
+    /// Sends a plain text message to every recipient.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to reach SMTP server" if it cannot be resolved or connected to.
+    /// * "SMTP server did not greet us" if its first reply is not 220.
+    /// * "SMTP server rejected EHLO" if neither `EHLO` nor `HELO` is accepted.
+    /// * "SMTP authentication failed" if the credentials are refused.
+    /// * "SMTP server rejected the sender" or "SMTP server rejected a recipient" if
+    ///   `MAIL FROM` or an `RCPT TO` is refused.
+    /// * "SMTP server rejected the message" if `DATA` or the message is refused.
+    /// * "SMTP server closed the connection" if it stops answering.
+    pub fn send(&self, subject: &str, body: &str) -> Result<(), &'static str> {
+        let unreachable = "failed to reach SMTP server";
+        let addr = self
+            .smtp
+            .server
+            .to_socket_addrs()
+            .map_err(|_| unreachable)?
+            .next()
+            .ok_or(unreachable)?;
+        let stream = TcpStream::connect_timeout(&addr, SMTP_TIMEOUT).map_err(|_| unreachable)?;
+        stream
+            .set_read_timeout(Some(SMTP_TIMEOUT))
+            .map_err(|_| unreachable)?;
+        let mut session = Session {
+            writer: stream.try_clone().map_err(|_| unreachable)?,
+            reader: BufReader::new(stream),
+        };
+
+        if session.reply()? != 220 {
+            return Err("SMTP server did not greet us");
+        }
+        if session.command(&format!("EHLO {}", self.ehlo))? != 250
+            && session.command(&format!("HELO {}", self.ehlo))? != 250
+        {
+            return Err("SMTP server rejected EHLO");
+        }
+
+        if let Some((user, password)) = &self.smtp.credentials {
+            let token = base64::encode(format!("\0{}\0{}", user, password).as_bytes());
+            if session.command(&format!("AUTH PLAIN {}", token))? != 235 {
+                return Err("SMTP authentication failed");
+            }
+        }
+
+        if session.command(&format!("MAIL FROM:<{}>", self.from))? != 250 {
+            return Err("SMTP server rejected the sender");
+        }
+        for to in &self.to {
+            if !matches!(session.command(&format!("RCPT TO:<{}>", to))?, 250 | 251) {
+                return Err("SMTP server rejected a recipient");
+            }
+        }
+        if session.command("DATA")? != 354 {
+            return Err("SMTP server rejected the message");
+        }
+        if session.command(&self.message(subject, body))? != 250 {
+            return Err("SMTP server rejected the message");
+        }
+
+        let _ = session.command("QUIT");
+        Ok(())
+    }
+
+    /// Builds the message of `DATA`, up to and including the terminating `.` line.
+    fn message(&self, subject: &str, body: &str) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\n\
+             MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.from,
+            self.to.join(", "),
+            subject,
+            date(now)
+        );
+
+        // Lines starting with a dot are escaped so they do not end the message.
+        for line in body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push('.');
+        message
+    }
+}
+
+/// Parses the comma-separated recipients of `--email-report`.
+///
+/// # Errors
+///
+/// * "invalid email address" if a recipient is not a plain `USER@DOMAIN` address.
+pub fn parse_recipients(list: &str) -> Result<Vec<String>, &'static str> {
+    list.split(',')
+        .map(str::trim)
+        .map(|to| {
+            let valid = matches!(to.split_once('@'), Some((user, domain))
+                if !user.is_empty() && !domain.is_empty())
+                && !to.contains(|c: char| c.is_whitespace() || "<>\",;".contains(c));
+            valid.then(|| to.to_string()).ok_or("invalid email address")
+        })
+        .collect()
+}
+
+/// An SMTP session on one connection.
+struct Session {
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Session {
+    /// Sends a command and returns the code of the reply.
+    fn command(&mut self, command: &str) -> Result<u16, &'static str> {
+        self.writer
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .map_err(|_| "SMTP server closed the connection")?;
+        self.reply()
+    }
+
+    fn reply(&mut self) -> Result<u16, &'static str> {
+        smtp::read_reply(&mut self.reader)
+            .map(|(code, _)| code)
+            .ok_or("SMTP server closed the connection")
+    }
+}
+
+/// Formats a UNIX timestamp as an RFC 5322 date in UTC, e.g.
+/// `Tue, 14 Nov 2023 22:13:20 +0000`.
+pub fn date(secs: u64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let days = secs / 86400;
+    let time = secs % 86400;
+
+    // Converts days since 1970-01-01 to a civil date, counting in 400-year eras that
+    // start on March 1st so leap days fall at the end of a year.
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} +0000",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
@@ -0,0 +1,138 @@
+use crate::cli;
+use crate::hooks;
+
+const SUMMARY: &str = "TCP connect port scanner";
+
+const DESCRIPTION: &str = "ip-sniffer connects to every TCP port of a host and reports \
+                           the ports that accept connections. Open ports are printed as \
+                           they are found and summarized once the scan completes. Options \
+                           add probes that identify the services behind open ports, compare \
+                           the results against a policy, or run hooks around the scan. A \
+                           target given as an http:// or https:// URL is only scanned on \
+                           the URL's port, which is probed with HTTP or a TLS fingerprint.";
+
+/// Exit statuses and what they mean.
+const EXIT_STATUS: &[(&str, &str)] = &[
+    (
+        "0",
+        "The scan completed, or the help text or an argument error was shown.",
+    ),
+    (
+        "1",
+        "A file, the proxy, packet capture or the pre-hook failed before the scan.",
+    ),
+    (
+        "2",
+        "The scan found violations of the policy given with --policy.",
+    ),
+];
+
+/// Environment variables read by ip-sniffer.
+const ENVIRONMENT: &[(&str, &str)] = &[(
+    "LC_ALL, LC_MESSAGES, LANG",
+    "Select the language of messages unless --lang is given.",
+)];
+
+/// Renders the man page in roff format.
+///
+/// # Description
+///
+/// The synopsis, options and commands are generated from the help text, and the hook
+/// environment from `hooks::ENVIRONMENT`, so the page always matches the binary.
+pub fn render() -> String {
+    let mut page = format!(
+        ".TH IP-SNIFFER 1 \"\" \"ip-sniffer {}\" \"User Commands\"\n",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    page += &format!(".SH NAME\nip\\-sniffer \\- {}\n", SUMMARY);
+
+    page += ".SH SYNOPSIS\n";
+    page += ".B ip\\-sniffer\n[\\fIOPTIONS\\fR] \\fIIPADDR\\fR|\\fIHOSTNAME\\fR|\\fIURL\\fR\n";
+    for command in cli::commands() {
+        page += &format!(
+            ".br\n.B ip\\-sniffer {}\n",
+            escape(&command.words.join(" "))
+        );
+        if !command.args.is_empty() {
+            page += &format!("{}\n", italic_args(command.args));
+        }
+    }
+
+    page += &format!(".SH DESCRIPTION\n{}\n", escape(DESCRIPTION));
+
+    page += ".SH OPTIONS\n";
+    for flag in cli::flags() {
+        let names: Vec<String> = flag
+            .names
+            .iter()
+            .map(|name| format!("\\fB{}\\fR", escape(name)))
+            .collect();
+        let value = flag
+            .value
+            .map(|value| format!(" \\fI{}\\fR", escape(value)))
+            .unwrap_or_default();
+
+        page += &format!(
+            ".TP\n{}{}\n{}\n",
+            names.join(", "),
+            value,
+            sentence(flag.description)
+        );
+    }
+
+    page += ".SH COMMANDS\n";
+    for command in cli::commands() {
+        page += &format!(
+            ".TP\n\\fB{}\\fR {}\n{}\n",
+            escape(&command.words.join(" ")),
+            italic_args(command.args),
+            sentence(command.description)
+        );
+    }
+
+    page += ".SH ENVIRONMENT\n";
+    for (name, description) in ENVIRONMENT {
+        page += &format!(".TP\n\\fB{}\\fR\n{}\n", escape(name), escape(description));
+    }
+    page += ".PP\nHooks run with the following variables set:\n";
+    for (name, description) in hooks::ENVIRONMENT {
+        page += &format!(
+            ".TP\n\\fB{}\\fR\n{}\n",
+            escape(name),
+            sentence(&description.replace('`', ""))
+        );
+    }
+
+    page += ".SH EXIT STATUS\n";
+    for (status, description) in EXIT_STATUS {
+        page += &format!(".TP\n\\fB{}\\fR\n{}\n", status, escape(description));
+    }
+
+    page
+}
+
+/// Escapes text for roff: backslashes, hyphens and a leading control character.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+
+    if text.starts_with(['.', '\'']) {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+/// Turns a help description into a sentence, e.g. `select the output format` into
+/// `Select the output format.`
+fn sentence(description: &str) -> String {
+    let mut chars = description.chars();
+    let first = chars.next().map(|c| c.to_uppercase().collect::<String>());
+
+    format!("{}{}.", first.unwrap_or_default(), escape(chars.as_str()))
+}
+
+/// Renders the `<PLACEHOLDER>`s of a command's arguments in italics.
+fn italic_args(args: &str) -> String {
+    escape(args).replace('<', "\\fI").replace('>', "\\fR")
+}
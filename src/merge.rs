@@ -0,0 +1,199 @@
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::i18n::Lang;
+use crate::json::Json;
+use crate::output;
+
+// `merge` combines the JSON Lines reports of several runs, e.g. shards of a distributed
+// scan, into one. Reports are taken as of:
+//
+// <TS>.jsonl                  the UNIX timestamp in the name, as project runs store them
+// anything else               the time the file was last modified
+//
+// Each host is taken whole from the freshest report that has it, since a port missing
+// from a newer scan is as meaningful as one listed. Every host's records are preceded
+// by a source record naming the report they came from and the reports they replaced;
+// merging merged reports again keeps those.
+
+/// Where records came from.
+#[derive(Clone)]
+struct Source {
+    name: String,
+    time: u64,
+    /// Reports the records replaced.
+    superseded: Vec<String>,
+}
+
+/// The records of one host, or of a whole run if `host` is `None`.
+struct Part {
+    host: Option<String>,
+    source: Source,
+    lines: Vec<String>,
+}
+
+/// Runs the `merge` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `merge` on the command line.
+/// * `lang` - The language the summary is printed in.
+///
+/// # Returns
+///
+/// The exit code, 0.
+///
+/// # Errors
+///
+/// * "missing report files" if fewer than two reports are given.
+/// * "missing output file" if `-o` has no file name.
+/// * "failed to read report file" if a report cannot be read.
+/// * "invalid record in report file; must be JSON Lines" if a line is not a JSON object.
+/// * "failed to write merged report" if the output cannot be written.
+///
+/// # Usage
+///
+/// * `merge <FILE>... -o <FILE>` - Merge reports into a file.
+/// * `merge <FILE>...` - Merge reports to stdout.
+pub fn run(args: &[String], lang: Lang) -> Result<i32, &'static str> {
+    let mut inputs = vec![];
+    let mut out = None;
+    let mut rest = args.iter();
+
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-o" | "--output" => out = Some(rest.next().ok_or("missing output file")?),
+            path => inputs.push(path),
+        }
+    }
+    if inputs.len() < 2 {
+        return Err("missing report files");
+    }
+
+    let mut parts = vec![];
+    for path in &inputs {
+        parts.extend(load(path)?);
+    }
+
+    let mut merged = vec![];
+    let mut hosts: Vec<&str> = vec![];
+    for part in &parts {
+        match &part.host {
+            Some(host) if !hosts.contains(&host.as_str()) => hosts.push(host),
+            Some(_) => {}
+            None => {
+                merged.push(source_record(None, &part.source));
+                merged.extend(part.lines.iter().cloned());
+            }
+        }
+    }
+
+    let mut conflicts = 0;
+    for host in hosts {
+        let mut holders: Vec<&Part> = parts
+            .iter()
+            .filter(|part| part.host.as_deref() == Some(host))
+            .collect();
+        // The last of equally fresh reports wins, as it was given last.
+        holders.sort_by_key(|part| part.source.time);
+        let Some(freshest) = holders.pop() else {
+            continue;
+        };
+
+        let mut source = freshest.source.clone();
+        for older in &holders {
+            source.superseded.push(older.source.name.clone());
+            source
+                .superseded
+                .extend(older.source.superseded.iter().cloned());
+        }
+        if !holders.is_empty() {
+            conflicts += 1;
+        }
+        merged.push(source_record(Some(host), &source));
+        merged.extend(freshest.lines.iter().cloned());
+    }
+
+    let text: String = merged.iter().map(|line| format!("{}\n", line)).collect();
+    match out {
+        Some(path) => fs::write(path, text).map_err(|_| "failed to write merged report")?,
+        None => print!("{}", text),
+    }
+    let message =
+        "merged {} report(s); {} host(s) were in more than one and taken from the freshest";
+    eprintln!("{}", lang.format(message, &[&inputs.len(), &conflicts]));
+    Ok(0)
+}
+
+fn source_record(host: Option<&str>, source: &Source) -> String {
+    output::jsonl_source_record(host, &source.name, source.time, &source.superseded)
+}
+
+/// Reads a report and splits its records by host, in the order each host first appears.
+fn load(path: &str) -> Result<Vec<Part>, &'static str> {
+    let contents = fs::read_to_string(path).map_err(|_| "failed to read report file")?;
+    let file = Path::new(path);
+
+    let named = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok());
+    let modified = || {
+        fs::metadata(file)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs())
+    };
+    let report = Source {
+        name: path.to_string(),
+        time: named.unwrap_or_else(modified),
+        superseded: vec![],
+    };
+
+    let mut parts: Vec<Part> = vec![];
+    // The source record of a merged report that the next records came from.
+    let mut origin: Option<(Option<String>, Source)> = None;
+
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let record = match Json::parse(line) {
+            Ok(record @ Json::Object(_)) => record,
+            _ => return Err("invalid record in report file; must be JSON Lines"),
+        };
+        let host = ["ip", "host"]
+            .iter()
+            .find_map(|key| record.get(key).and_then(Json::as_str))
+            .map(str::to_string);
+
+        if let Some(name) = record.get("source").and_then(Json::as_str) {
+            let source = Source {
+                name: name.to_string(),
+                time: record.get("time").and_then(Json::as_f64).unwrap_or(0.0) as u64,
+                superseded: record
+                    .get("superseded")
+                    .map_or(&[][..], Json::as_array)
+                    .iter()
+                    .filter_map(Json::as_str)
+                    .map(str::to_string)
+                    .collect(),
+            };
+            origin = Some((host, source));
+            continue;
+        }
+
+        let source = match &origin {
+            Some((origin_host, source)) if *origin_host == host => source.clone(),
+            _ => report.clone(),
+        };
+        match parts.iter_mut().find(|p| host.is_some() && p.host == host) {
+            Some(part) => part.lines.push(line.to_string()),
+            None => parts.push(Part {
+                host,
+                source,
+                lines: vec![line.to_string()],
+            }),
+        }
+    }
+    Ok(parts)
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use ip_sniffer::scan::HostScan;
+
+/// How long a metrics client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Totals of every scan run in watch mode, exported in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    scans: u64,
+    probes: u64,
+    open_ports: BTreeMap<IpAddr, usize>,
+    errors: BTreeMap<String, u64>,
+    local_errors: u64,
+    duration_sum: f64,
+    last_duration: f64,
+    sink_stalls: u64,
+    sink_wait: f64,
+}
+
+impl Metrics {
+    /// Adds a completed scan of `addr` to the totals.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The IP address that was scanned.
+    /// * `scan` - The results of the scan.
+    /// * `open` - The number of open ports after verification, if any.
+    /// * `duration` - How long the scan took.
+    pub fn record(&self, addr: IpAddr, scan: &HostScan, open: usize, duration: Duration) {
+        let mut counters = self.inner.lock().unwrap();
+
+        counters.scans += 1;
+        counters.probes += (scan.open.len()
+            + scan.closed
+            + scan.filtered
+            + scan.local_errors
+            + scan.retries
+            + scan.retransmits) as u64;
+        counters.open_ports.insert(addr, open);
+        counters.local_errors += scan.local_errors as u64;
+        for (error, count) in &scan.errors {
+            *counters.errors.entry(error.clone()).or_default() += *count as u64;
+        }
+        counters.duration_sum += duration.as_secs_f64();
+        counters.last_duration = duration.as_secs_f64();
+        counters.sink_stalls += scan.sink_stalls as u64;
+        counters.sink_wait += scan.sink_wait.as_secs_f64();
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let counters = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        out += "# HELP ip_sniffer_scans_total Completed scans.\n";
+        out += "# TYPE ip_sniffer_scans_total counter\n";
+        out += &format!("ip_sniffer_scans_total {}\n", counters.scans);
+
+        out += "# HELP ip_sniffer_probes_total Connection attempts, including retries.\n";
+        out += "# TYPE ip_sniffer_probes_total counter\n";
+        out += &format!("ip_sniffer_probes_total {}\n", counters.probes);
+
+        out += "# HELP ip_sniffer_open_ports Open ports found by the latest scan.\n";
+        out += "# TYPE ip_sniffer_open_ports gauge\n";
+        for (addr, open) in &counters.open_ports {
+            out += &format!("ip_sniffer_open_ports{{target=\"{}\"}} {}\n", addr, open);
+        }
+
+        out += "# HELP ip_sniffer_connect_errors_total Failed connection attempts by error.\n";
+        out += "# TYPE ip_sniffer_connect_errors_total counter\n";
+        for (error, count) in &counters.errors {
+            out += &format!(
+                "ip_sniffer_connect_errors_total{{error=\"{}\"}} {}\n",
+                error.replace('\\', "\\\\").replace('"', "\\\""),
+                count
+            );
+        }
+
+        out += "# HELP ip_sniffer_local_errors_total Ports not probed because of local network errors.\n";
+        out += "# TYPE ip_sniffer_local_errors_total counter\n";
+        out += &format!("ip_sniffer_local_errors_total {}\n", counters.local_errors);
+
+        out += "# HELP ip_sniffer_scan_duration_seconds How long scans took.\n";
+        out += "# TYPE ip_sniffer_scan_duration_seconds summary\n";
+        out += &format!(
+            "ip_sniffer_scan_duration_seconds_sum {:.3}\n",
+            counters.duration_sum
+        );
+        out += &format!(
+            "ip_sniffer_scan_duration_seconds_count {}\n",
+            counters.scans
+        );
+
+        out += "# HELP ip_sniffer_last_scan_duration_seconds How long the latest scan took.\n";
+        out += "# TYPE ip_sniffer_last_scan_duration_seconds gauge\n";
+        out += &format!(
+            "ip_sniffer_last_scan_duration_seconds {:.3}\n",
+            counters.last_duration
+        );
+
+        out += "# HELP ip_sniffer_sink_stalls_total Times the scan waited for slow output sinks.\n";
+        out += "# TYPE ip_sniffer_sink_stalls_total counter\n";
+        out += &format!("ip_sniffer_sink_stalls_total {}\n", counters.sink_stalls);
+
+        out += "# HELP ip_sniffer_sink_wait_seconds_total Time the scan waited for slow output sinks.\n";
+        out += "# TYPE ip_sniffer_sink_wait_seconds_total counter\n";
+        out += &format!(
+            "ip_sniffer_sink_wait_seconds_total {:.3}\n",
+            counters.sink_wait
+        );
+
+        out
+    }
+
+    /// Serves the metrics over HTTP on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `listen` - The address to listen on, e.g. `127.0.0.1:9100`, or `:9100` for
+    ///   every interface.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to listen on metrics address" if the address is invalid or in use.
+    pub fn serve(self: &Arc<Self>, listen: &str) -> Result<(), &'static str> {
+        let listen = match listen.strip_prefix(':') {
+            Some(port) => format!("0.0.0.0:{}", port),
+            None => listen.to_string(),
+        };
+        let listener =
+            TcpListener::bind(listen).map_err(|_| "failed to listen on metrics address")?;
+        let metrics = Arc::clone(self);
+
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                respond(stream, &metrics);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Answers one HTTP request: the metrics on `GET /metrics`, and 404 otherwise.
+fn respond(mut stream: TcpStream, metrics: &Metrics) {
+    let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT));
+
+    let mut buf = [0; 1024];
+    let n = stream.read(&mut buf).unwrap_or(0);
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = if path == "/metrics" {
+        ("200 OK", metrics.render())
+    } else {
+        ("404 Not Found", "not found; try /metrics\n".to_string())
+    };
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
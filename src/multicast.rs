@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::hash;
+use crate::probe::dns;
+use crate::provider::{Target, TargetProvider};
+
+// `--discover-multicast` finds devices on the local network by asking them to announce
+// themselves, the way printers, cameras and media servers expect to be found:
+//
+// mDNS            224.0.0.251:5353      which DNS-SD service types each device offers
+// SSDP            239.255.255.250:1900  which UPnP device and service types it is
+// WS-Discovery    239.255.255.250:3702  which WS types it is, e.g. an ONVIF camera
+//
+// The queries are sent once; answers are collected until DISCOVERY_WINDOW ends.
+
+/// How long to collect answers to the discovery queries.
+const DISCOVERY_WINDOW: Duration = Duration::from_secs(3);
+
+const MDNS: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+const SSDP: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+const WS_DISCOVERY: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 3702);
+
+/// The DNS-SD name listing every service type offered on the link.
+const MDNS_SERVICES: &str = "_services._dns-sd._udp.local";
+
+const TYPE_PTR: u16 = 12;
+
+/// Asks for unicast answers, so they reach our ephemeral port (RFC 6762 5.4).
+const CLASS_UNICAST_RESPONSE: u16 = 0x8000;
+
+const SSDP_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+                           HOST: 239.255.255.250:1900\r\n\
+                           MAN: \"ssdp:discover\"\r\n\
+                           MX: 2\r\n\
+                           ST: ssdp:all\r\n\r\n";
+
+/// Devices on the local network found with mDNS, SSDP and WS-Discovery.
+pub struct Multicast;
+
+impl TargetProvider for Multicast {
+    fn describe(&self) -> String {
+        "multicast".to_string()
+    }
+
+    /// Returns every device that answered, with the services it advertised, e.g.
+    /// `multicast mdns _ipp._tcp, ssdp urn:schemas-upnp-org:device:MediaServer:1`.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to send multicast discovery queries" if no query could be sent, e.g.
+    ///   because there is no network interface with multicast.
+    fn targets(&self) -> Result<Vec<Target>, &'static str> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|_| "failed to send multicast discovery queries")?;
+
+        let mdns = dns::build_query(
+            0,
+            MDNS_SERVICES,
+            TYPE_PTR,
+            dns::CLASS_IN | CLASS_UNICAST_RESPONSE,
+            false,
+        );
+        let queries = [
+            (mdns, MDNS),
+            (SSDP_SEARCH.as_bytes().to_vec(), SSDP),
+            (ws_discovery_probe().into_bytes(), WS_DISCOVERY),
+        ];
+        let sent = queries
+            .iter()
+            .filter(|(query, group)| socket.send_to(query, group).is_ok())
+            .count();
+        if sent == 0 {
+            return Err("failed to send multicast discovery queries");
+        }
+
+        let mut found: BTreeMap<IpAddr, Vec<String>> = BTreeMap::new();
+        let deadline = Instant::now() + DISCOVERY_WINDOW;
+        let mut buf = vec![0; 9000];
+
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            if left.is_zero() || socket.set_read_timeout(Some(left)).is_err() {
+                break;
+            }
+            let (n, from) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(_) => break,
+            };
+
+            let services = found.entry(from.ip()).or_default();
+            for service in advertised(&buf[..n]) {
+                if !services.contains(&service) {
+                    services.push(service);
+                }
+            }
+        }
+
+        Ok(found
+            .into_iter()
+            .map(|(addr, services)| Target {
+                name: addr.to_string(),
+                resource: Some(match services.is_empty() {
+                    true => "multicast".to_string(),
+                    false => format!("multicast {}", services.join(", ")),
+                }),
+            })
+            .collect())
+    }
+}
+
+/// Returns the services advertised in an answer to one of the discovery queries.
+fn advertised(answer: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(answer);
+
+    if text.starts_with("HTTP/1.1 200") || text.starts_with("NOTIFY ") {
+        return header(&text, "ST")
+            .or_else(|| header(&text, "NT"))
+            .map(|st| vec![format!("ssdp {}", st)])
+            .unwrap_or_default();
+    }
+    if text.contains("ProbeMatches") {
+        return element(&text, "Types")
+            .map(|types| {
+                types
+                    .split_whitespace()
+                    .map(|t| format!("wsd {}", t))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    // mDNS: the PTR records name the service types, e.g. `_ipp._tcp.local`.
+    dns::answers(answer)
+        .unwrap_or_default()
+        .iter()
+        .filter(|record| record.rtype == TYPE_PTR)
+        .filter_map(|record| dns::read_name(answer, record.offset))
+        .map(|(name, _)| format!("mdns {}", name.trim_end_matches(".local")))
+        .collect()
+}
+
+/// Returns the value of an HTTP header, matched case-insensitively.
+fn header<'a>(message: &'a str, name: &str) -> Option<&'a str> {
+    message.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+            .filter(|value| !value.is_empty())
+    })
+}
+
+/// Returns the text of the first XML element with the local name `name`, whatever
+/// its namespace prefix.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = xml.find(&format!(":{}>", name))? + name.len() + 2;
+    let close = xml[open..].find("</")?;
+    Some(xml[open..open + close].trim())
+}
+
+/// Builds a WS-Discovery Probe for every device type.
+fn ws_discovery_probe() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let hex: String = hash::sha256(&nanos.to_be_bytes())[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let id = format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    );
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <s:Envelope xmlns:s=\"http://www.w3.org/2003/05/soap-envelope\" \
+         xmlns:a=\"http://schemas.xmlsoap.org/ws/2004/08/addressing\" \
+         xmlns:d=\"http://schemas.xmlsoap.org/ws/2005/04/discovery\">\
+         <s:Header>\
+         <a:Action>http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe</a:Action>\
+         <a:MessageID>urn:uuid:{}</a:MessageID>\
+         <a:To>urn:schemas-xmlsoap-org:ws:2005:04:discovery</a:To>\
+         </s:Header>\
+         <s:Body><d:Probe/></s:Body>\
+         </s:Envelope>",
+        id
+    )
+}
@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::output::json_string;
+
+// `--notify` posts scan summaries and watch-mode change alerts to chat webhooks, in each
+// service's own rich message format:
+//
+// slack://hooks.slack.com/services/T000/B000/XXXX        Block Kit header and section
+// discord://discord.com/api/webhooks/ID/TOKEN             an embed, red for alerts
+// teams://example.webhook.office.com/webhookb2/...        a MessageCard, red for alerts
+//
+// The scheme is replaced by https://. Webhooks only accept HTTPS, so messages are posted
+// with curl, which must be installed.
+
+/// Longest message text sent; Slack refuses sections over 3000 characters.
+const MAX_TEXT: usize = 3000;
+
+/// How long a webhook may take to accept a message, in seconds.
+const POST_TIMEOUT: &str = "30";
+
+/// Colors of alerts and other messages, as `RRGGBB`.
+const ALERT_COLOR: u32 = 0xd93025;
+const INFO_COLOR: u32 = 0x1e8e3e;
+
+/// A chat service webhooks are posted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Service {
+    Slack,
+    Discord,
+    Teams,
+}
+
+/// A chat webhook messages are posted to.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    service: Service,
+    /// The `https://` URL of the webhook.
+    url: String,
+}
+
+impl FromStr for Notifier {
+    type Err = &'static str;
+
+    /// Parses `slack://`, `discord://` or `teams://` followed by the webhook's URL
+    /// without its `https://`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or("notify URL must start with slack://, discord:// or teams://")?;
+        let service = match scheme {
+            "slack" => Service::Slack,
+            "discord" => Service::Discord,
+            "teams" => Service::Teams,
+            _ => return Err("notify URL must start with slack://, discord:// or teams://"),
+        };
+        if rest.is_empty() || rest.contains(char::is_whitespace) {
+            return Err("invalid webhook in notify URL");
+        }
+
+        Ok(Notifier {
+            service,
+            url: format!("https://{}", rest),
+        })
+    }
+}
+
+/// A message to post.
+#[derive(Debug)]
+pub struct Message {
+    pub title: String,
+    /// The body, one item per line.
+    pub text: String,
+    /// Whether it reports something to act on, such as a port that opened.
+    pub alert: bool,
+}
+
+impl Notifier {
+    /// Posts `message` to the webhook.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to run curl; is it installed?" if curl cannot be started.
+    /// * "webhook rejected the message" if curl fails or the webhook answers with an error.
+    pub fn send(&self, message: &Message) -> Result<(), &'static str> {
+        let body = self.payload(message);
+        post(&self.url, &[], &body, "webhook rejected the message")
+    }
+
+    /// Formats `message` as the JSON body the service expects.
+    fn payload(&self, message: &Message) -> String {
+        let title = json_string(&message.title);
+        let color = match message.alert {
+            true => ALERT_COLOR,
+            false => INFO_COLOR,
+        };
+
+        match self.service {
+            Service::Slack => format!(
+                r#"{{"text":{title},"blocks":[{{"type":"header","text":{{"type":"plain_text","text":{title}}}}},{{"type":"section","text":{{"type":"mrkdwn","text":{}}}}}]}}"#,
+                json_string(&truncate(&message.text))
+            ),
+            Service::Discord => format!(
+                r#"{{"embeds":[{{"title":{title},"description":{},"color":{color}}}]}}"#,
+                json_string(&truncate(&message.text))
+            ),
+            // Cards are Markdown, where single line breaks are joined.
+            Service::Teams => format!(
+                r#"{{"@type":"MessageCard","@context":"https://schema.org/extensions","summary":{title},"themeColor":"{color:06x}","title":{title},"text":{}}}"#,
+                json_string(&truncate(&message.text).replace('\n', "\n\n"))
+            ),
+        }
+    }
+}
+
+/// Cuts `text` to `MAX_TEXT` characters, marking the cut.
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_TEXT - 1) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Posts a JSON `body` to an HTTPS `url` with curl.
+///
+/// # Arguments
+///
+/// * `url` - Where to post.
+/// * `headers` - Headers sent besides `Content-Type`, e.g. `Authorization: ...`.
+/// * `body` - The JSON to post.
+/// * `rejected` - The error returned if the post fails.
+///
+/// # Errors
+///
+/// * "failed to run curl; is it installed?" if curl cannot be started.
+/// * `rejected` if curl fails or the server answers with an error.
+///
+/// # Description
+///
+/// Webhook URLs and API keys are secrets, and the command line of a process can be read
+/// by any local user, so the URL, headers and body are given to curl as a config on stdin.
+pub fn post(
+    url: &str,
+    headers: &[&str],
+    body: &str,
+    rejected: &'static str,
+) -> Result<(), &'static str> {
+    let mut config = format!("url = {}\n", config_string(url));
+    for header in ["Content-Type: application/json"].iter().chain(headers) {
+        config += &format!("header = {}\n", config_string(header));
+    }
+    config += &format!("data-raw = {}\n", config_string(body));
+
+    let mut child = Command::new("curl")
+        .args(["-sS", "-f", "-m", POST_TIMEOUT, "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|_| "failed to run curl; is it installed?")?;
+
+    // curl's errors are not printed, as they show the URL, which may hold a secret.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(config.as_bytes());
+    }
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(rejected),
+    }
+}
+
+/// Quotes `value` as a string of a curl config file.
+fn config_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            '\t' => quoted += "\\t",
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,253 @@
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::config::Config;
+use crate::probe::Identity;
+use crate::{Arguments, DEFAULT_THREADS};
+
+// `--opsec-check` prints what a scan with the given options would tell the targets, and
+// anyone watching the network, about the scanner, then exits without scanning:
+//
+// source       the local address the targets would see, or the proxy's
+// stack        the TTL and TCP options of the operating system's own connect()
+// timing       threads, port order, jitter and timeouts
+// payloads     what each enabled probe sends, with the identifying strings it uses
+//
+// Lines marked `!` name the tool or make the scan easy to tell apart from ordinary
+// traffic.
+
+/// What each probe option sends, by option. `{ua}`, `{ehlo}` and `{ssh}` are replaced by
+/// the identifying strings of the config.
+const PAYLOADS: &[(&str, &str)] = &[
+    ("--ssh-probe", "SSH identification '{ssh}' and a key exchange init"),
+    ("--smb-probe", "SMB negotiate and session setup requests"),
+    ("--rdp-probe", "RDP connection requests for each security protocol"),
+    ("--vnc-probe", "an RFB version reply"),
+    ("--dns-probe", "a version.bind CH TXT query and a recursive query for example.com"),
+    ("--jarm", "ten fixed TLS ClientHellos, as the JARM reference tool sends"),
+    (
+        "--service-detect",
+        "HTTP GETs with User-Agent '{ua}', SMTP EHLO '{ehlo}' and MySQL, PostgreSQL, Redis and MongoDB handshakes",
+    ),
+    ("--vhosts", "HTTP GETs with User-Agent '{ua}' and each Host name of the file"),
+    ("--keepalive-probe", "up to 100 HTTP HEADs per port with User-Agent '{ua}'"),
+    (
+        "--starttls-probe",
+        "EHLO '{ehlo}', CAPABILITY, CAPA, FEAT, FTP USER '{ehlo}' and an LDAP bind as cn=ip-sniffer",
+    ),
+    (
+        "--smtp-relay-check",
+        "EHLO '{ehlo}', MAIL FROM ip-sniffer@example.com and RCPT TO relay-check@example.net",
+    ),
+    ("--screenshot", "page loads by headless Chromium, whose User-Agent says HeadlessChrome"),
+    ("--snmp-probe", "SNMP GETs of sysDescr with each community string"),
+    ("--upnp-probe", "an SSDP M-SEARCH and SOAP requests with User-Agent '{ua}'"),
+    ("--amplification-check", "an NTP monlist, a recursive DNS ANY and an SSDP M-SEARCH"),
+    ("--ipmi-probe", "RMCP Get Channel Authentication Capabilities and Open Session requests"),
+    ("--ics-probe", "Modbus Read Device Identification, S7 SZL reads and EtherNet/IP List Identity"),
+    ("--sip-probe", "SIP OPTIONS with User-Agent '{ua}' and From ip-sniffer"),
+];
+
+/// Builds the report of `--opsec-check`.
+///
+/// # Arguments
+///
+/// * `arguments` - The parsed command line.
+/// * `config` - The config file's identifying strings, threads and timeout.
+///
+/// # Returns
+///
+/// The report, one item per line under a heading per section.
+pub fn review(arguments: &Arguments, config: &Config) -> String {
+    let mut out = String::from("What this scan would reveal about the scanner\n");
+    let mut section = |title: &str, items: Vec<(bool, String)>| {
+        out.push_str(&format!("\n{}\n", title));
+        for (flagged, item) in items {
+            let mark = if flagged { "!" } else { " " };
+            out.push_str(&format!("{} {}\n", mark, item));
+        }
+    };
+
+    section("source", source(arguments));
+    section("stack", stack());
+    section("timing", timing(arguments, config));
+    section("payloads", payloads(arguments, &config.identity));
+
+    out.push_str("\nNothing was scanned; run again without --opsec-check to scan.\n");
+    out
+}
+
+fn source(arguments: &Arguments) -> Vec<(bool, String)> {
+    if let Some(proxy) = &arguments.proxy {
+        return vec![(
+            false,
+            format!("targets see the proxy {}, not this host", proxy.addr),
+        )];
+    }
+
+    // Connecting a UDP socket picks the route without sending anything.
+    let local = arguments.addrs.first().and_then(|addr| {
+        let bind = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(bind).ok()?;
+        socket.connect(SocketAddr::new(*addr, 9)).ok()?;
+        Some((socket.local_addr().ok()?.ip(), *addr))
+    });
+    match local {
+        Some((local, target)) => vec![(
+            false,
+            format!(
+                "targets see this host as {} (the route to {})",
+                local, target
+            ),
+        )],
+        None => vec![(false, "targets see this host's own address".to_string())],
+    }
+}
+
+fn stack() -> Vec<(bool, String)> {
+    // A new socket starts with the operating system's default TTL.
+    let ttl = UdpSocket::bind("0.0.0.0:0").and_then(|socket| socket.ttl());
+    let ttl = match ttl {
+        Ok(ttl) => format!(
+            "TTL {}, the operating system's default, which hints at the OS",
+            ttl
+        ),
+        Err(_) => "TTL is the operating system's default".to_string(),
+    };
+
+    vec![
+        (false, ttl),
+        (
+            false,
+            "SYNs come from the operating system's connect(), so their window and options match any other client on this host".to_string(),
+        ),
+        (
+            false,
+            "every open port gets a full handshake and a close, which the service may log".to_string(),
+        ),
+    ]
+}
+
+fn timing(arguments: &Arguments, config: &Config) -> Vec<(bool, String)> {
+    let threads = arguments
+        .threads
+        .or(config.threads)
+        .unwrap_or(DEFAULT_THREADS);
+    let mut items = vec![(threads > 100, format!("{} connections at a time", threads))];
+
+    items.push(if let Some(url) = &arguments.url {
+        (false, format!("only port {}, of the URL", url.port))
+    } else if arguments.randomize {
+        (false, "ports in random order".to_string())
+    } else {
+        (
+            true,
+            "ports in order from 1 to 65535, a plain scan signature".to_string(),
+        )
+    });
+    items.push(match arguments.jitter {
+        Some(jitter) => (
+            false,
+            format!(
+                "a random {:?} to {:?} before each probe of every thread",
+                jitter.min, jitter.max
+            ),
+        ),
+        None => (
+            true,
+            "no jitter; each thread probes as soon as its last probe ends".to_string(),
+        ),
+    });
+    items.push(match (arguments.adaptive_timeout, config.timeout) {
+        (true, _) => (
+            false,
+            "connect timeout from the measured round-trip time, after connects to 80, 443, 22, 445, 3389 and 1".to_string(),
+        ),
+        (false, Some(timeout)) => (false, format!("connect timeout of {:?}", timeout)),
+        (false, None) => (false, "the operating system's connect timeout".to_string()),
+    });
+    if arguments.adaptive_timeout || config.timeout.is_some() {
+        items.push((
+            false,
+            "timed-out ports are connected to once more with a longer timeout".to_string(),
+        ));
+    }
+    if let Some(runs) = arguments.runs {
+        items.push((false, format!("every port is scanned {} times", runs)));
+    }
+    if arguments.verify {
+        items.push((
+            false,
+            "open ports are connected to again, up to twice, to confirm them".to_string(),
+        ));
+    }
+    items
+}
+
+fn payloads(arguments: &Arguments, identity: &Identity) -> Vec<(bool, String)> {
+    let enabled = [
+        arguments.ssh_probe,
+        arguments.smb_probe,
+        arguments.rdp_probe,
+        arguments.vnc_probe,
+        arguments.dns_probe,
+        arguments.jarm,
+        arguments.service_detect,
+        arguments.vhosts.is_some(),
+        arguments.keepalive_probe,
+        arguments.starttls_probe,
+        arguments.smtp_relay_check,
+        arguments.screenshot.is_some(),
+        arguments.snmp_probe,
+        arguments.upnp_probe,
+        arguments.amplification_check,
+        arguments.ipmi_probe,
+        arguments.ics_probe,
+        arguments.sip_probe,
+    ];
+
+    let defaults = Identity::default();
+    let mut items = vec![];
+    let mut configurable = false;
+    for ((option, sends), on) in PAYLOADS.iter().zip(enabled) {
+        if !on {
+            continue;
+        }
+        let text = sends
+            .replace("{ua}", &identity.user_agent)
+            .replace("{ehlo}", &identity.ehlo)
+            .replace("{ssh}", &identity.ssh_banner);
+        // The built-in strings name the tool unless the config replaces them.
+        let default = (sends.contains("{ua}") && identity.user_agent == defaults.user_agent)
+            || (sends.contains("{ehlo}") && identity.ehlo == defaults.ehlo)
+            || (sends.contains("{ssh}") && identity.ssh_banner == defaults.ssh_banner);
+        configurable |= default;
+        items.push((
+            default || text.contains("ip-sniffer"),
+            format!("{}: {}", option, text),
+        ));
+    }
+
+    if arguments.detect_honeypot {
+        items.push((
+            false,
+            "--detect-honeypot: nothing; it only reads what services send first".to_string(),
+        ));
+    }
+    if items.is_empty() {
+        items.push((
+            false,
+            "none; the scan only opens and closes connections".to_string(),
+        ));
+    } else if configurable {
+        items.push((
+            true,
+            "set http_user_agent, smtp_ehlo and ssh_banner in a --config file to stop naming the tool"
+                .to_string(),
+        ));
+    }
+    items
+}
@@ -0,0 +1,231 @@
+use std::env;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::i18n::Lang;
+
+// The first three bytes of a MAC address are the OUI (organizationally unique identifier)
+// the IEEE assigned to the vendor of the network card. `update-oui` downloads the IEEE
+// registry, whose entries look like:
+//
+// 00-50-56   (hex)		VMware, Inc.
+// 005056     (base 16)		VMware, Inc.
+//
+// and caches the `(hex)` lines, as `OUI<TAB>vendor` lines, in the user's cache directory,
+// e.g. ~/.cache/ip-sniffer/oui.txt. Vendors are looked up in the cache, or in a snapshot
+// of common vendors built into the binary if `update-oui` was never run.
+//
+// MAC addresses are only known for hosts on the local network, from the kernel's
+// neighbour table, so `--mac-vendor` only shows vendors for those.
+
+/// Where the IEEE publishes the registry.
+const IEEE_URL: &str = "https://standards-oui.ieee.org/oui/oui.txt";
+
+/// How long the download may take, in seconds.
+const DOWNLOAD_TIMEOUT: &str = "300";
+
+/// The kernel's IPv4 neighbour table on Linux.
+const ARP_TABLE: &str = "/proc/net/arp";
+
+/// Vendors used when there is no cache, by OUI.
+const SNAPSHOT: &[(&str, &str)] = &[
+    ("00000C", "Cisco Systems, Inc"),
+    ("00005E", "ICANN, IANA Department"),
+    ("000393", "Apple, Inc."),
+    ("0004F2", "Polycom"),
+    ("00044B", "NVIDIA"),
+    ("000569", "VMware, Inc."),
+    ("0007E9", "Intel Corporation"),
+    ("00090F", "Fortinet, Inc."),
+    ("000B82", "Grandstream Networks, Inc."),
+    ("000C29", "VMware, Inc."),
+    ("000D3A", "Microsoft Corp."),
+    ("000E58", "Sonos, Inc."),
+    ("001018", "Broadcom"),
+    ("001132", "Synology Incorporated"),
+    ("00146C", "NETGEAR"),
+    ("00155D", "Microsoft Corporation"),
+    ("00156D", "Ubiquiti Inc"),
+    ("00163E", "Xensource, Inc."),
+    ("001788", "Philips Lighting BV"),
+    ("00180A", "Cisco Meraki"),
+    ("001B17", "Palo Alto Networks"),
+    ("001B21", "Intel Corporate"),
+    ("001C14", "VMware, Inc."),
+    ("001C42", "Parallels, Inc."),
+    ("001E67", "Intel Corporate"),
+    ("002590", "Super Micro Computer, Inc."),
+    ("002722", "Ubiquiti Inc"),
+    ("003048", "Super Micro Computer, Inc."),
+    ("005056", "VMware, Inc."),
+    ("00E04C", "REALTEK SEMICONDUCTOR CORP."),
+    ("080027", "PCS Systemtechnik GmbH"),
+    ("18B430", "Nest Labs Inc."),
+    ("24A43C", "Ubiquiti Inc"),
+    ("3C5AB4", "Google, Inc."),
+    ("B827EB", "Raspberry Pi Foundation"),
+    ("DCA632", "Raspberry Pi Trading Ltd"),
+    ("E45F01", "Raspberry Pi Trading Ltd"),
+];
+
+/// Runs the `update-oui` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `update-oui` on the command line.
+/// * `lang` - The language the result is printed in.
+///
+/// # Returns
+///
+/// The exit code, 0 once the cache is written.
+///
+/// # Errors
+///
+/// * "missing OUI source" if `--from` has no URL or file.
+/// * "too many arguments" if anything else is given.
+/// * "failed to find a cache directory; set XDG_CACHE_HOME or HOME" if there is none.
+/// * "failed to run curl; is it installed?" if curl cannot be started.
+/// * "failed to download the OUI database" if the download fails.
+/// * "failed to read OUI file" if a `--from` file cannot be read.
+/// * "not an OUI database" if the source has no `(hex)` lines.
+/// * "failed to write OUI cache" if the cache cannot be written.
+///
+/// # Usage
+///
+/// * `update-oui` - Download the IEEE registry and replace the cache with it.
+/// * `update-oui --from <URL|FILE>` - Use a mirror or a copy of `oui.txt` instead, e.g.
+///   on a machine without internet access.
+pub fn run(args: &[String], lang: Lang) -> Result<i32, &'static str> {
+    let source = match args {
+        [] => IEEE_URL,
+        [flag, source] if flag == "--from" => source.as_str(),
+        [flag] if flag == "--from" => return Err("missing OUI source"),
+        _ => return Err("too many arguments"),
+    };
+    let path =
+        cache_path().ok_or("failed to find a cache directory; set XDG_CACHE_HOME or HOME")?;
+
+    let registry = if source.starts_with("http://") || source.starts_with("https://") {
+        download(source)?
+    } else {
+        fs::read_to_string(source).map_err(|_| "failed to read OUI file")?
+    };
+    let vendors = parse_registry(&registry);
+    if vendors.is_empty() {
+        return Err("not an OUI database");
+    }
+
+    let cache: String = vendors
+        .iter()
+        .map(|(oui, vendor)| format!("{}\t{}\n", oui, vendor))
+        .collect();
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, cache))
+        .map_err(|_| "failed to write OUI cache")?;
+
+    let message = "{} vendors saved to {}";
+    println!(
+        "{}",
+        lang.format(message, &[&vendors.len(), &path.display()])
+    );
+    Ok(0)
+}
+
+/// Returns the vendor of a MAC address, e.g. `VMware, Inc.` for `00:50:56:c0:00:08`.
+///
+/// # Returns
+///
+/// `None` if the OUI is unknown, or if the address is locally administered, as virtual
+/// machines, containers and phones hiding their hardware address use.
+pub fn vendor(mac: &str) -> Option<String> {
+    let oui: String = mac
+        .chars()
+        .filter(char::is_ascii_hexdigit)
+        .take(6)
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let first = u8::from_str_radix(oui.get(..2)?, 16).ok()?;
+    if oui.len() < 6 || first & 0x02 != 0 {
+        return None;
+    }
+
+    match cache_path().and_then(|path| fs::read_to_string(path).ok()) {
+        Some(cache) => cache
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .find(|(entry, _)| *entry == oui)
+            .map(|(_, vendor)| vendor.to_string()),
+        None => SNAPSHOT
+            .iter()
+            .find(|(entry, _)| *entry == oui)
+            .map(|(_, vendor)| vendor.to_string()),
+    }
+}
+
+/// Returns the MAC address of a host on the local network from the kernel's neighbour
+/// table, e.g. `00:50:56:c0:00:08`.
+///
+/// # Returns
+///
+/// `None` if the host is not in the table, which is the case for hosts behind a router,
+/// for IPv6 hosts, and on systems other than Linux.
+pub fn neighbour(addr: IpAddr) -> Option<String> {
+    let table = fs::read_to_string(ARP_TABLE).ok()?;
+
+    // IP address  HW type  Flags  HW address  Mask  Device; a flag of 0x0 is incomplete.
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            [ip, _, flags, mac, ..]
+                if ip.parse() == Ok(addr) && flags != "0x0" && mac != "00:00:00:00:00:00" =>
+            {
+                Some(mac.to_string())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Returns where the OUI cache is kept: `$XDG_CACHE_HOME/ip-sniffer/oui.txt`,
+/// `%LOCALAPPDATA%\ip-sniffer\oui.txt` on Windows, or `~/.cache/ip-sniffer/oui.txt`.
+fn cache_path() -> Option<PathBuf> {
+    let dir = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+    let base = dir("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dir("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| dir("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(base.join("ip-sniffer").join("oui.txt"))
+}
+
+/// Downloads the registry with curl.
+fn download(url: &str) -> Result<String, &'static str> {
+    let output = Command::new("curl")
+        .args(["-sS", "-f", "-L", "-m", DOWNLOAD_TIMEOUT])
+        .arg(url)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|_| "failed to run curl; is it installed?")?;
+    if !output.status.success() {
+        return Err("failed to download the OUI database");
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses the `(hex)` lines of the IEEE registry into `(OUI, vendor)` pairs, in registry
+/// order, with the OUI as six uppercase hex digits.
+fn parse_registry(registry: &str) -> Vec<(String, String)> {
+    registry
+        .lines()
+        .filter_map(|line| line.split_once("(hex)"))
+        .filter_map(|(oui, vendor)| {
+            let oui: String = oui.trim().split('-').collect();
+            let vendor = vendor.trim();
+            let valid = oui.len() == 6 && oui.chars().all(|c| c.is_ascii_hexdigit());
+            (valid && !vendor.is_empty()).then(|| (oui.to_ascii_uppercase(), vendor.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,777 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ip_sniffer::idna;
+use ip_sniffer::scan::{OpenPort, PortState};
+
+use crate::docker::Published;
+use crate::geoip::Location;
+use crate::i18n::Lang;
+use crate::kube::NodePort;
+use crate::policy::Violation;
+use crate::probe::{ServiceReport, Value};
+use crate::risk::{Finding, Level, Summary};
+use crate::screenshot::Shot;
+use crate::stats::ScanStats;
+use crate::target::MergedPort;
+use crate::tcpclock::Analysis;
+use crate::watch::{Change, Timeline};
+
+/// The format used to report scan results on standard output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Progress dots while scanning, followed by a sorted list of open ports.
+    Text,
+    /// One JSON object per line, emitted as soon as each result is found.
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err("unknown output format"),
+        }
+    }
+}
+
+/// Collapses a sorted list of ports into runs of consecutive ports.
+///
+/// # Arguments
+///
+/// * `ports` - The ports to collapse, sorted in ascending order.
+///
+/// # Returns
+///
+/// One `(first, last)` pair per run; a port with no neighbours is returned as
+/// `(port, port)`. For example `[22, 8000, 8001, 8002]` becomes
+/// `[(22, 22), (8000, 8002)]`.
+pub fn collapse_ranges(ports: &[u16]) -> Vec<(u16, u16)> {
+    let mut ranges: Vec<(u16, u16)> = vec![];
+
+    for &port in ports {
+        match ranges.last_mut() {
+            Some((_, last)) if last.checked_add(1) == Some(port) => *last = port,
+            _ => ranges.push((port, port)),
+        }
+    }
+
+    ranges
+}
+
+/// Formats a single discovered result as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `port` - The open port that was found.
+///
+/// # Returns
+///
+/// A JSON object on a single line, without the trailing newline, e.g.
+/// `{"ip":"192.168.1.1","port":22,"state":"open"}`.
+pub fn jsonl_record(addr: IpAddr, port: u16) -> String {
+    format!(r#"{{"ip":"{}","port":{},"state":"open"}}"#, addr, port)
+}
+
+/// Formats an open port found by the scan as a JSON Lines record, along with how sure
+/// the scan is of it.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `open` - The open port that was found.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","port":22,"state":"open","confidence":"high"}`.
+pub fn jsonl_open_record(addr: IpAddr, open: &OpenPort) -> String {
+    format!(
+        r#"{{"ip":"{}","port":{},"state":"{}","confidence":"{}"}}"#,
+        addr,
+        open.port,
+        PortState::Open,
+        open.confidence
+    )
+}
+
+/// Parses an open-port record previously produced by `jsonl_record` or
+/// `jsonl_open_record`.
+///
+/// # Arguments
+///
+/// * `line` - A single line of JSON Lines output.
+///
+/// # Returns
+///
+/// The IP address and port of the record, or `None` if the line is not an open-port record.
+pub fn parse_jsonl_record(line: &str) -> Option<(IpAddr, u16)> {
+    let rest = line.strip_prefix(r#"{"ip":""#)?;
+    let (ip, rest) = rest.split_once('"')?;
+    let rest = rest.strip_prefix(r#","port":"#)?;
+    let (port, rest) = rest.split_once(',')?;
+
+    let with_confidence = rest
+        .strip_prefix(r#""state":"open","confidence":""#)
+        .is_some_and(|rest| rest.ends_with(r#""}"#));
+
+    if rest != r#""state":"open"}"# && !with_confidence {
+        return None;
+    }
+
+    Some((ip.parse().ok()?, port.parse().ok()?))
+}
+
+/// Formats a correction record for a port that failed the verification pass.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `port` - The port that was previously streamed as open.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","port":22,"state":"unverified"}`.
+pub fn jsonl_unverified_record(addr: IpAddr, port: u16) -> String {
+    format!(
+        r#"{{"ip":"{}","port":{},"state":"unverified"}}"#,
+        addr, port
+    )
+}
+
+/// Formats how many scans of `--runs` found a port open as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `port` - A port found open by at least one scan.
+/// * `observed` - The number of scans that found it open.
+/// * `runs` - The number of scans.
+/// * `kept` - Whether it was open in at least the consensus number of scans.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","port":22,"state":"consensus","observed":2,"runs":3,"open":true}`.
+pub fn jsonl_consensus_record(
+    addr: IpAddr,
+    port: u16,
+    observed: usize,
+    runs: usize,
+    kept: bool,
+) -> String {
+    format!(
+        r#"{{"ip":"{}","port":{},"state":"consensus","observed":{},"runs":{},"open":{}}}"#,
+        addr, port, observed, runs, kept
+    )
+}
+
+/// Formats a host that stopped answering partway through the scan as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `unprobed` - The number of ports left unprobed once the host went down.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","host_down":true,"unprobed":40000}`.
+pub fn jsonl_host_down_record(addr: IpAddr, unprobed: usize) -> String {
+    format!(
+        r#"{{"ip":"{}","host_down":true,"unprobed":{}}}"#,
+        addr, unprobed
+    )
+}
+
+/// Formats a host whose scan was stopped at a deadline as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `deadline` - Which deadline was reached: `host` for `--host-timeout`, `scan` for
+///   `--scan-deadline`.
+/// * `unprobed` - The number of ports left unprobed.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","deadline":"host","unprobed":40000}`.
+pub fn jsonl_deadline_record(addr: IpAddr, deadline: &str, unprobed: usize) -> String {
+    format!(
+        r#"{{"ip":"{}","deadline":"{}","unprobed":{}}}"#,
+        addr, deadline, unprobed
+    )
+}
+
+/// Formats a port that opened or closed between two scans in watch mode as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address being watched.
+/// * `port` - The port that changed.
+/// * `change` - Whether the port opened or closed.
+/// * `timeline` - When the port was first and last seen open, and when it closed.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","port":22,"change":"closed","time":1700000600,
+/// "first_seen":1700000000,"last_seen":1700000300,"closed_at":1700000600}`, where `time`
+/// is when the change was seen. `closed_at` is only given for closed ports.
+pub fn jsonl_change_record(addr: IpAddr, port: u16, change: Change, timeline: &Timeline) -> String {
+    let closed_at = timeline
+        .closed_at
+        .map_or(String::new(), |time| format!(r#","closed_at":{}"#, time));
+    format!(
+        r#"{{"ip":"{}","port":{},"change":"{}","time":{},"first_seen":{},"last_seen":{}{}}}"#,
+        addr,
+        port,
+        change.as_str(),
+        timeline.closed_at.unwrap_or(timeline.first_seen),
+        timeline.first_seen,
+        timeline.last_seen,
+        closed_at
+    )
+}
+
+/// Formats the honeypot/tarpit assessment for a host as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `reasons` - The heuristics that matched; empty if the host looks genuine.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","honeypot":true,"reasons":["..."]}`.
+pub fn jsonl_honeypot_record(addr: IpAddr, reasons: &[String]) -> String {
+    format!(
+        r#"{{"ip":"{}","honeypot":{},"reasons":{}}}"#,
+        addr,
+        !reasons.is_empty(),
+        json_array(reasons)
+    )
+}
+
+/// Formats where a public host is, per the `--geoip` databases, as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `location` - What the databases know of it.
+///
+/// # Returns
+///
+/// A JSON object on a single line, with `null` for what the databases do not know, e.g.
+/// `{"ip":"93.184.216.34","country":"US","country_name":"United States","city":null,"asn":15133,"as_org":"EDGECAST"}`.
+pub fn jsonl_geoip_record(addr: IpAddr, location: &Location) -> String {
+    let text = |value: &Option<String>| value.as_deref().map_or("null".to_string(), json_string);
+    let asn = location
+        .asn
+        .map_or("null".to_string(), |asn| asn.to_string());
+
+    format!(
+        r#"{{"ip":"{}","country":{},"country_name":{},"city":{},"asn":{},"as_org":{}}}"#,
+        addr,
+        text(&location.country),
+        text(&location.country_name),
+        text(&location.city),
+        asn,
+        text(&location.as_org)
+    )
+}
+
+/// Formats what the SYN-ACKs of a host captured by `--pcap` reveal as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `analysis` - The clock and sequence numbers estimated by `--uptime`.
+///
+/// # Returns
+///
+/// A JSON object on a single line, with `null` for what could not be estimated, e.g.
+/// `{"ip":"192.168.1.1","samples":7,"clock_hz":1000,"uptime_secs":3628800,"skew_ppm":-12.4,"skew_error_ppm":1.8,"isn":"random"}`.
+pub fn jsonl_uptime_record(addr: IpAddr, analysis: &Analysis) -> String {
+    let (hz, uptime, skew, error) = match analysis.clock {
+        Some(clock) => (
+            clock.hz.to_string(),
+            clock.uptime.as_secs().to_string(),
+            format!("{:.1}", clock.skew_ppm),
+            format!("{:.1}", clock.skew_error_ppm),
+        ),
+        None => ("null".into(), "null".into(), "null".into(), "null".into()),
+    };
+    let isn = analysis
+        .isn
+        .map_or("null".to_string(), |isn| json_string(&isn.to_string()));
+
+    format!(
+        r#"{{"ip":"{}","samples":{},"clock_hz":{},"uptime_secs":{},"skew_ppm":{},"skew_error_ppm":{},"isn":{}}}"#,
+        addr, analysis.samples, hz, uptime, skew, error, isn
+    )
+}
+
+/// Formats a group of addresses found by `--dedupe-by-skew` to be one host as a JSON Lines
+/// record.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g. `{"same_host":["192.0.2.1","192.0.2.2"]}`.
+pub fn jsonl_same_host_record(addrs: &[IpAddr]) -> String {
+    let addrs: Vec<String> = addrs.iter().map(IpAddr::to_string).collect();
+    format!(r#"{{"same_host":{}}}"#, json_array(&addrs))
+}
+
+/// Formats a policy violation as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `violation` - The difference between the policy and the scan.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","port":23,"violation":"unexpected-open"}`.
+pub fn jsonl_violation_record(addr: IpAddr, violation: &Violation) -> String {
+    format!(
+        r#"{{"ip":"{}","port":{},"violation":"{}"}}"#,
+        addr,
+        violation.port(),
+        violation.kind()
+    )
+}
+
+/// Formats the number of accepted findings left out of a report as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `count` - The number of policy violations or watch changes matching `--accepted`.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g. `{"ip":"192.168.1.1","accepted":2}`.
+pub fn jsonl_accepted_record(addr: IpAddr, count: usize) -> String {
+    format!(r#"{{"ip":"{}","accepted":{}}}"#, addr, count)
+}
+
+/// Formats an open port of a host with several addresses as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `host` - The hostname that was scanned, in its ASCII form.
+/// * `merged` - The port and the addresses it was found open and closed on.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"host":"example.com","port":80,"open_on":["192.0.2.1"],"closed_on":["192.0.2.2"]}`.
+/// An internationalized hostname also has its Unicode form, e.g.
+/// `"host":"xn--bcher-kva.example","host_unicode":"bücher.example"`.
+pub fn jsonl_merged_record(host: &str, merged: &MergedPort) -> String {
+    let addrs = |addrs: &[IpAddr]| -> Vec<String> { addrs.iter().map(IpAddr::to_string).collect() };
+    let unicode = idna::to_unicode(host);
+    let unicode = match unicode == host {
+        true => String::new(),
+        false => format!(r#","host_unicode":{}"#, json_string(&unicode)),
+    };
+
+    format!(
+        r#"{{"host":{}{},"port":{},"open_on":{},"closed_on":{}}}"#,
+        json_string(host),
+        unicode,
+        merged.port,
+        json_array(&addrs(&merged.open_on)),
+        json_array(&addrs(&merged.closed_on))
+    )
+}
+
+/// Formats the statistics of a scan as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `stats` - The statistics of the scan.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","stats":{"open":2,"closed":65533,...,"latency_ms":{"p50":0.12,...},"errors":{"connection refused":65533},"retries":0,"retransmits":0,"sink_stalls":0,"sink_wait_ms":0.000,"confidence":{"open":{"high":2},"closed":{"high":65533}}}}`.
+pub fn jsonl_stats_record(addr: IpAddr, stats: &ScanStats) -> String {
+    let latency: Vec<String> = stats
+        .latency
+        .iter()
+        .map(|(p, latency)| format!(r#""p{}":{:.3}"#, p, latency.as_secs_f64() * 1000.0))
+        .collect();
+    let errors: Vec<String> = stats
+        .errors
+        .iter()
+        .map(|(error, count)| format!("{}:{}", json_string(error), count))
+        .collect();
+    let mut states: Vec<(PortState, Vec<String>)> = vec![];
+    for (&(state, confidence), count) in &stats.states {
+        let entry = format!(r#""{}":{}"#, confidence, count);
+        match states.last_mut() {
+            Some((last, entries)) if *last == state => entries.push(entry),
+            _ => states.push((state, vec![entry])),
+        }
+    }
+    let states: Vec<String> = states
+        .iter()
+        .map(|(state, entries)| format!(r#""{}":{{{}}}"#, state, entries.join(",")))
+        .collect();
+
+    format!(
+        r#"{{"ip":"{}","stats":{{"open":{},"closed":{},"filtered":{},"local_errors":{},"latency_ms":{{{}}},"samples":{},"errors":{{{}}},"retries":{},"retransmits":{},"sink_stalls":{},"sink_wait_ms":{:.3},"confidence":{{{}}}}}}}"#,
+        addr,
+        stats.open,
+        stats.closed,
+        stats.filtered,
+        stats.local_errors,
+        latency.join(","),
+        stats.samples,
+        errors.join(","),
+        stats.retries,
+        stats.retransmits,
+        stats.sink_stalls,
+        stats.sink_wait.as_secs_f64() * 1000.0,
+        states.join(",")
+    )
+}
+
+/// Formats the risk level of an open port as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `finding` - The port and its risk level.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g. `{"ip":"203.0.113.5","port":3389,"risk":"high"}`.
+pub fn jsonl_risk_record(addr: IpAddr, finding: &Finding) -> String {
+    format!(
+        r#"{{"ip":"{}","port":{},"risk":"{}"}}"#,
+        addr, finding.port, finding.level
+    )
+}
+
+/// Formats the risk summary of a host or a whole scan as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned; `None` for the summary of every address
+///   of a host scanned with `--merge-by-host`.
+/// * `summary` - The number of findings at each level.
+///
+/// # Returns
+///
+/// A JSON object on a single line with the most severe level, `null` if there are no
+/// findings, and the count of every level, e.g.
+/// `{"ip":"203.0.113.5","risk_summary":{"highest":"high","critical":0,"high":1,"medium":0,"low":2,"info":1}}`.
+pub fn jsonl_risk_summary_record(addr: Option<IpAddr>, summary: &Summary) -> String {
+    let highest = summary
+        .highest()
+        .map_or("null".to_string(), |level| format!(r#""{}""#, level));
+    let counts: Vec<String> = Level::ALL
+        .iter()
+        .map(|&level| format!(r#""{}":{}"#, level, summary.count(level)))
+        .collect();
+    let record = format!(
+        r#""risk_summary":{{"highest":{},{}}}"#,
+        highest,
+        counts.join(",")
+    );
+
+    match addr {
+        Some(addr) => format!(r#"{{"ip":"{}",{}}}"#, addr, record),
+        None => format!("{{{}}}", record),
+    }
+}
+
+/// Formats a Kubernetes node port that is open to the world as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `node_port` - The node port, with its service and node.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"203.0.113.7","port":30080,"node_port":true,"service":"default/web","type":"NodePort","node":"node-1"}`.
+pub fn jsonl_node_port_record(node_port: &NodePort) -> String {
+    format!(
+        r#"{{"ip":"{}","port":{},"node_port":true,"service":{},"type":{},"node":{}}}"#,
+        node_port.addr,
+        node_port.port,
+        json_string(&node_port.service),
+        json_string(&node_port.kind),
+        json_string(&node_port.node)
+    )
+}
+
+/// Formats a port published by a container as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `published` - The published port, with its container.
+/// * `state` - What connecting to the port found.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"127.0.0.1","port":8080,"state":"open","container":"web","image":"nginx:1.25","container_port":80}`.
+pub fn jsonl_container_record(published: &Published, state: PortState) -> String {
+    format!(
+        r#"{{"ip":"{}","port":{},"state":"{}","container":{},"image":{},"container_port":{}}}"#,
+        published.addr(),
+        published.port,
+        state,
+        json_string(&published.container),
+        json_string(&published.image),
+        published.container_port
+    )
+}
+
+/// Formats a note attached to a scan as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned; `None` for a note on a whole project run.
+/// * `note` - The note, e.g. a ticket number.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g. `{"ip":"192.168.1.1","note":"ticket-1234"}`.
+pub fn jsonl_note_record(addr: Option<IpAddr>, note: &str) -> String {
+    match addr {
+        Some(addr) => format!(r#"{{"ip":"{}","note":{}}}"#, addr, json_string(note)),
+        None => format!(r#"{{"note":{}}}"#, json_string(note)),
+    }
+}
+
+/// Parses a note record previously produced by `jsonl_note_record`.
+///
+/// # Returns
+///
+/// The IP address, if any, and the note; `None` if the line is not a note record.
+pub fn parse_jsonl_note_record(line: &str) -> Option<(Option<IpAddr>, String)> {
+    let (addr, rest) = match line.strip_prefix(r#"{"ip":""#) {
+        Some(rest) => {
+            let (ip, rest) = rest.split_once('"')?;
+            (Some(ip.parse().ok()?), rest.strip_prefix(',')?)
+        }
+        None => (None, line.strip_prefix('{')?),
+    };
+    let (note, rest) = parse_json_string(rest.strip_prefix(r#""note":"#)?)?;
+
+    (rest == "}").then_some((addr, note))
+}
+
+/// Formats where the following records of a merged report came from as a JSON Lines
+/// record.
+///
+/// # Arguments
+///
+/// * `host` - The IP address or host name of the records; `None` for a record about a
+///   whole run, e.g. its note.
+/// * `source` - The report the records were taken from.
+/// * `time` - When that report was taken, in seconds since the UNIX epoch.
+/// * `superseded` - The older reports that also had the host.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","source":"shard-2.jsonl","time":1700000000,"superseded":["shard-1.jsonl"]}`.
+pub fn jsonl_source_record(
+    host: Option<&str>,
+    source: &str,
+    time: u64,
+    superseded: &[String],
+) -> String {
+    let host = match host {
+        Some(host) => json_string(host),
+        None => "null".to_string(),
+    };
+    format!(
+        r#"{{"ip":{},"source":{},"time":{},"superseded":{}}}"#,
+        host,
+        json_string(source),
+        time,
+        json_array(superseded)
+    )
+}
+
+/// Formats what a probe found on a port as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `port` - The port the service runs on.
+/// * `report` - The details collected by the probe.
+///
+/// # Returns
+///
+/// A JSON object on a single line with the `ip`, `port`, `transport` and `service`,
+/// followed by one member per field of the report, e.g.
+/// `{"ip":"192.168.1.1","port":22,"transport":"tcp","service":"ssh","banner":"SSH-2.0-OpenSSH_9.6",...}`.
+pub fn jsonl_service_record(addr: IpAddr, port: u16, report: &ServiceReport) -> String {
+    let mut out = format!(
+        r#"{{"ip":"{}","port":{},"transport":"{}","service":{}"#,
+        addr,
+        port,
+        report.transport,
+        json_string(report.service)
+    );
+
+    for (name, value) in &report.fields {
+        let value = match value {
+            Value::Text(text) => json_string(text),
+            Value::List(items) => json_array(items),
+            Value::Map(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}", json_string(key), json_string(value)))
+                    .collect();
+                format!("{{{}}}", pairs.join(","))
+            }
+        };
+        out += &format!(",{}:{}", json_string(name), value);
+    }
+
+    out.push('}');
+    out
+}
+
+/// Formats a screenshot of a web service as a JSON Lines record.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `shot` - The service's URL and image file.
+///
+/// # Returns
+///
+/// A JSON object on a single line, e.g.
+/// `{"ip":"192.168.1.1","port":80,"url":"http://192.168.1.1:80/","screenshot":"shots/192.168.1.1_80.png"}`,
+/// with a `null` screenshot if it failed.
+pub fn jsonl_screenshot_record(addr: IpAddr, shot: &Shot) -> String {
+    let path = match &shot.path {
+        Some(path) => json_string(&path.to_string_lossy()),
+        None => "null".to_string(),
+    };
+
+    format!(
+        r#"{{"ip":"{}","port":{},"url":{},"screenshot":{}}}"#,
+        addr,
+        shot.port,
+        json_string(&shot.url),
+        path
+    )
+}
+
+/// Formats what a probe found on a port for the text output, naming the fields in the
+/// given language.
+pub fn text_service(port: u16, report: &ServiceReport, lang: Lang) -> String {
+    let mut out = format!("{}/{} {}\n", port, report.transport, report.service);
+
+    for (name, value) in &report.fields {
+        let name = lang.tr(name);
+        match value {
+            Value::Text(text) => out += &format!("  {}: {}\n", name, text),
+            Value::List(items) => out += &format!("  {}: {}\n", name, items.join(", ")),
+            Value::Map(pairs) => {
+                for (key, value) in pairs {
+                    out += &format!("  {}: {} {}\n", name, key, value);
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Formats a list of strings as a JSON array.
+pub fn json_array(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Quotes and escapes a string for inclusion in a JSON document.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// Parses a JSON string at the start of `s`.
+///
+/// # Returns
+///
+/// The unescaped string and whatever follows its closing quote.
+pub fn parse_json_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut out = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                'u' => {
+                    let hex: String = (0..4)
+                        .filter_map(|_| chars.next().map(|(_, c)| c))
+                        .collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => out.push(c),
+            },
+            c => out.push(c),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_consecutive_ports() {
+        assert_eq!(
+            collapse_ranges(&[22, 8000, 8001, 8002]),
+            [(22, 22), (8000, 8002)]
+        );
+        assert_eq!(collapse_ranges(&[1, 2, 4, 5, 7]), [(1, 2), (4, 5), (7, 7)]);
+        assert_eq!(collapse_ranges(&[65534, 65535]), [(65534, 65535)]);
+        assert_eq!(collapse_ranges(&[]), []);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+// Policy files are a small subset of YAML mapping hosts, CIDR groups or `default`
+// to the ports that are expected to be open:
+//
+// # flow style
+// 192.168.1.10: [22, 443]
+// # block style, with a range
+// 10.0.0.0/24:
+//   - 22
+//   - 8000-8010
+// default: [22]
+
+/// A network given in CIDR notation, e.g. `10.0.0.0/24`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    pub network: IpAddr,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    /// Returns whether `addr` lies within this network.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.network, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = &'static str;
+
+    /// Parses `ADDR/PREFIX`; a bare address is treated as a single-host network.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let network = IpAddr::from_str(addr).map_err(|_| "not a valid IP address or CIDR")?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| "not a valid CIDR prefix")?,
+            None => max,
+        };
+        if prefix > max {
+            return Err("CIDR prefix is too long");
+        }
+
+        Ok(Cidr { network, prefix })
+    }
+}
+
+/// Which hosts a policy entry applies to.
+#[derive(Debug)]
+enum Scope {
+    Network(Cidr),
+    Default,
+}
+
+/// A set of expected open ports per host or group.
+#[derive(Debug)]
+pub struct Policy {
+    entries: Vec<(Scope, BTreeSet<u16>)>,
+}
+
+/// A difference between the policy and what the scan found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The port is open but the policy does not allow it.
+    UnexpectedOpen(u16),
+    /// The policy expects the port to be open but it is not.
+    ExpectedClosed(u16),
+}
+
+impl Violation {
+    pub fn port(&self) -> u16 {
+        match *self {
+            Violation::UnexpectedOpen(port) | Violation::ExpectedClosed(port) => port,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Violation::UnexpectedOpen(_) => "unexpected-open",
+            Violation::ExpectedClosed(_) => "expected-closed",
+        }
+    }
+}
+
+impl Policy {
+    /// Loads and parses a policy file.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to read policy file" if the file cannot be read.
+    /// * Any error returned by `Policy::parse`.
+    pub fn load(path: &str) -> Result<Policy, &'static str> {
+        let contents = fs::read_to_string(path).map_err(|_| "failed to read policy file")?;
+        Policy::parse(&contents)
+    }
+
+    /// Parses the contents of a policy file.
+    ///
+    /// # Errors
+    ///
+    /// * "not a valid IP address or CIDR" if a key is neither a host, a CIDR nor `default`.
+    /// * "invalid port in policy" if a port or port range cannot be parsed.
+    /// * "list item outside of a host entry in policy" if a `- PORT` line has no key above it.
+    /// * "invalid line in policy" for anything else that is not understood.
+    pub fn parse(contents: &str) -> Result<Policy, &'static str> {
+        let mut entries: Vec<(Scope, BTreeSet<u16>)> = vec![];
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(item) = line.strip_prefix('-') {
+                let (_, ports) = entries
+                    .last_mut()
+                    .ok_or("list item outside of a host entry in policy")?;
+                parse_ports(item, ports)?;
+                continue;
+            }
+
+            // Split on the last `: ` (or a trailing `:`) so unquoted IPv6 keys keep their colons.
+            let (key, value) = match line.strip_suffix(':') {
+                Some(key) => (key, ""),
+                None => line.rsplit_once(": ").ok_or("invalid line in policy")?,
+            };
+            let key = key.trim().trim_matches(|c| c == '"' || c == '\'');
+            let value = value.trim();
+
+            let scope = if key == "default" {
+                Scope::Default
+            } else {
+                Scope::Network(Cidr::from_str(key)?)
+            };
+
+            let mut ports = BTreeSet::new();
+
+            if !value.is_empty() {
+                let list = value
+                    .strip_prefix('[')
+                    .and_then(|v| v.strip_suffix(']'))
+                    .ok_or("invalid line in policy")?;
+
+                for item in list.split(',').filter(|item| !item.trim().is_empty()) {
+                    parse_ports(item, &mut ports)?;
+                }
+            }
+
+            entries.push((scope, ports));
+        }
+
+        Ok(Policy { entries })
+    }
+
+    /// Returns the ports expected to be open on `addr`: those of the exact host entry,
+    /// or else the most specific matching group, or else `default`.
+    pub fn expected(&self, addr: IpAddr) -> BTreeSet<u16> {
+        let network = self
+            .entries
+            .iter()
+            .filter_map(|(scope, ports)| match scope {
+                Scope::Network(cidr) if cidr.contains(addr) => Some((cidr.prefix, ports)),
+                _ => None,
+            })
+            .max_by_key(|(prefix, _)| *prefix)
+            .map(|(_, ports)| ports);
+
+        let default = self.entries.iter().find_map(|(scope, ports)| match scope {
+            Scope::Default => Some(ports),
+            _ => None,
+        });
+
+        network.or(default).cloned().unwrap_or_default()
+    }
+
+    /// Compares the open ports of `addr` against the policy.
+    ///
+    /// # Returns
+    ///
+    /// Every violation, ordered by port.
+    pub fn check(&self, addr: IpAddr, open: &[u16]) -> Vec<Violation> {
+        let expected = self.expected(addr);
+        let open: BTreeSet<u16> = open.iter().copied().collect();
+
+        let mut violations: Vec<Violation> = open
+            .difference(&expected)
+            .map(|&port| Violation::UnexpectedOpen(port))
+            .chain(
+                expected
+                    .difference(&open)
+                    .map(|&port| Violation::ExpectedClosed(port)),
+            )
+            .collect();
+        violations.sort_by_key(Violation::port);
+        violations
+    }
+}
+
+/// Parses a single port or an inclusive `FIRST-LAST` range into `ports`.
+fn parse_ports(item: &str, ports: &mut BTreeSet<u16>) -> Result<(), &'static str> {
+    let item = item.trim();
+
+    let (first, last) = match item.split_once('-') {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => (item, item),
+    };
+    let first: u16 = first.parse().map_err(|_| "invalid port in policy")?;
+    let last: u16 = last.parse().map_err(|_| "invalid port in policy")?;
+
+    if first == 0 || first > last {
+        return Err("invalid port in policy");
+    }
+
+    ports.extend(first..=last);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_cidrs() {
+        let cidr: Cidr = "10.0.0.0/24".parse().unwrap();
+        assert!(cidr.contains(addr("10.0.0.255")));
+        assert!(!cidr.contains(addr("10.0.1.0")));
+        assert!(!cidr.contains(addr("::ffff:10.0.0.1")));
+        assert!("0.0.0.0/0"
+            .parse::<Cidr>()
+            .unwrap()
+            .contains(addr("8.8.8.8")));
+        assert!("2001:db8::/32"
+            .parse::<Cidr>()
+            .unwrap()
+            .contains(addr("2001:db8:1::1")));
+        assert_eq!("10.0.0.1".parse::<Cidr>().unwrap().prefix, 32);
+
+        assert_eq!(
+            "10.0.0/24".parse::<Cidr>(),
+            Err("not a valid IP address or CIDR")
+        );
+        assert_eq!("10.0.0.0/x".parse::<Cidr>(), Err("not a valid CIDR prefix"));
+        assert_eq!(
+            "10.0.0.0/33".parse::<Cidr>(),
+            Err("CIDR prefix is too long")
+        );
+    }
+
+    #[test]
+    fn parses_policies() {
+        let policy = Policy::parse(
+            "# flow style\n\
+             192.168.1.10: [22, 443]\n\
+             # block style, with a range\n\
+             10.0.0.0/24:\n\
+             \x20 - 22\n\
+             \x20 - 8000-8002\n\
+             2001:db8::1: [443]\n\
+             \"10.0.0.5\": []\n\
+             default: [22]\n",
+        )
+        .unwrap();
+
+        let expected = |text| policy.expected(addr(text)).into_iter().collect::<Vec<_>>();
+        assert_eq!(expected("192.168.1.10"), [22, 443]);
+        assert_eq!(expected("10.0.0.7"), [22, 8000, 8001, 8002]);
+        // The exact host wins over its group.
+        assert_eq!(expected("10.0.0.5"), []);
+        assert_eq!(expected("2001:db8::1"), [443]);
+        assert_eq!(expected("172.16.0.1"), [22]);
+    }
+
+    #[test]
+    fn reports_violations() {
+        let policy = Policy::parse("10.0.0.1: [22, 443]\n").unwrap();
+        assert_eq!(
+            policy.check(addr("10.0.0.1"), &[80, 22]),
+            [
+                Violation::UnexpectedOpen(80),
+                Violation::ExpectedClosed(443)
+            ]
+        );
+        assert_eq!(policy.check(addr("10.0.0.1"), &[443, 22]), []);
+        // Without a default, nothing is expected of other hosts.
+        assert_eq!(
+            policy.check(addr("10.0.0.2"), &[22]),
+            [Violation::UnexpectedOpen(22)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_policies() {
+        let cases = [
+            ("- 22", "list item outside of a host entry in policy"),
+            ("10.0.0.1 22", "invalid line in policy"),
+            ("10.0.0.1: 22", "invalid line in policy"),
+            ("hosts: [22]", "not a valid IP address or CIDR"),
+            ("10.0.0.1: [0]", "invalid port in policy"),
+            ("10.0.0.1: [443-80]", "invalid port in policy"),
+            ("10.0.0.1:\n  - ssh", "invalid port in policy"),
+        ];
+        for (contents, error) in cases {
+            assert_eq!(Policy::parse(contents).err(), Some(error), "{}", contents);
+        }
+    }
+}
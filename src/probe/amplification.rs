@@ -0,0 +1,227 @@
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use ip_sniffer::scan::PortState;
+
+use crate::probe::{self, dns, upnp, ServiceReport};
+
+// `--amplification-check` sends each UDP query that reflection attacks abuse, once, and
+// weighs every datagram the host sends back within `LISTEN_TIME` against it:
+//
+// ntp/123     monlist (mode 7, MON_GETLIST_1): up to 600 recent clients, 440 bytes each
+// dns/53      recursive ANY for RECURSION_TEST_NAME with a 4096-byte EDNS buffer
+// ssdp/1900   unicast M-SEARCH ssdp:all: one answer per device and service
+//
+// The factor is bytes received over bytes sent, UDP payloads only, so it is what one
+// spoofed query would reflect at a victim.
+
+pub const NTP_PORT: u16 = 123;
+
+/// How long to collect the answers to each query; monlist and SSDP answer with many
+/// datagrams.
+const LISTEN_TIME: Duration = Duration::from_secs(2);
+
+/// The NTP mode 7 `MON_GETLIST_1` request of `ntpdc -c monlist`, padded as ntpdc pads it.
+const MONLIST: [u8; 48] = {
+    let mut request = [0; 48];
+    request[0] = 0x17; // version 2, mode 7
+    request[2] = 0x03; // XNTPD implementation
+    request[3] = 0x2a; // MON_GETLIST_1
+    request
+};
+
+/// The domain queried for ANY records through open resolvers.
+const RECURSION_TEST_NAME: &str = "isc.org";
+
+const TYPE_ANY: u16 = 255;
+
+const SSDP_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+                           HOST: 239.255.255.250:1900\r\n\
+                           MAN: \"ssdp:discover\"\r\n\
+                           MX: 1\r\n\
+                           ST: ssdp:all\r\n\r\n";
+
+/// A query a host answered, and how much it answered with.
+#[derive(Debug)]
+pub struct AmplificationInfo {
+    pub service: &'static str,
+    /// The query sent, e.g. `monlist`.
+    pub vector: &'static str,
+    pub request_bytes: usize,
+    pub response_bytes: usize,
+    pub datagrams: usize,
+    pub weak: Vec<String>,
+}
+
+impl AmplificationInfo {
+    /// Bytes received for every byte sent.
+    pub fn factor(&self) -> f64 {
+        self.response_bytes as f64 / self.request_bytes as f64
+    }
+
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new(self.service)
+            .udp()
+            .text("amplification_vector", self.vector)
+            .text(
+                "amplification",
+                format!(
+                    "{:.1}x ({} bytes in {} datagram(s) for {})",
+                    self.factor(),
+                    self.response_bytes,
+                    self.datagrams,
+                    self.request_bytes
+                ),
+            )
+            .list("weak", &self.weak)
+    }
+}
+
+/// Sends the NTP monlist, recursive DNS ANY and unicast SSDP queries to `addr`.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+///
+/// # Returns
+///
+/// One entry per query, in port order: the service and port queried, and the answer
+/// with its amplification factor, or the state of the port if there was none.
+///
+/// # Description
+///
+/// An NTP answer only counts if it is a monlist answer rather than an error, and a DNS
+/// answer only if the server recursed for it. Any answer to the SSDP search counts, as
+/// devices should only answer searches sent to the multicast group.
+pub fn probe(addr: IpAddr) -> Vec<(&'static str, u16, Result<AmplificationInfo, PortState>)> {
+    let ssdp = SSDP_SEARCH.as_bytes();
+
+    vec![
+        check(
+            addr,
+            "dns",
+            dns::PORT,
+            "recursive ANY",
+            &dns_query(),
+            |answer| {
+                let flags = u16::from_be_bytes([answer[2], answer[3]]);
+                let answers = u16::from_be_bytes([answer[6], answer[7]]);
+                // Recursion available, no error, and answers to the recursive query.
+                flags & 0x0080 != 0 && flags & 0x000f == 0 && answers > 0
+            },
+        ),
+        check(addr, "ntp", NTP_PORT, "monlist", &MONLIST, |answer| {
+            // A mode 7 response to MON_GETLIST_1 without an error code.
+            answer[0] & 0x80 != 0
+                && answer[0] & 0x07 == 7
+                && answer[3] == 0x2a
+                && answer[4] >> 4 == 0
+        }),
+        check(
+            addr,
+            "ssdp",
+            upnp::PORT,
+            "unicast M-SEARCH",
+            ssdp,
+            |answer| answer.starts_with(b"HTTP/1.1 200"),
+        ),
+    ]
+}
+
+/// Sends one amplification query and weighs the answers.
+///
+/// # Returns
+///
+/// The service and port, with the answer or, if no answer that `valid` accepts came
+/// back, the state of the port: `Open` if only other answers did, or as `exchange`
+/// found it if none did.
+fn check(
+    addr: IpAddr,
+    service: &'static str,
+    port: u16,
+    vector: &'static str,
+    request: &[u8],
+    valid: impl Fn(&[u8]) -> bool,
+) -> (&'static str, u16, Result<AmplificationInfo, PortState>) {
+    let answer = exchange(addr, port, request, valid).and_then(|(response_bytes, datagrams)| {
+        if datagrams == 0 {
+            return Err(PortState::Open);
+        }
+
+        let mut info = AmplificationInfo {
+            service,
+            vector,
+            request_bytes: request.len(),
+            response_bytes,
+            datagrams,
+            weak: vec![],
+        };
+        info.weak.push(format!(
+            "answers {} from anyone ({:.1}x); usable for reflection attacks",
+            vector,
+            info.factor()
+        ));
+        Ok(info)
+    });
+    (service, port, answer)
+}
+
+/// Builds the recursive ANY query, with an EDNS OPT record advertising a 4096-byte
+/// buffer so the answer is not truncated at 512 bytes.
+fn dns_query() -> Vec<u8> {
+    let mut query = dns::build_query(0x1003, RECURSION_TEST_NAME, TYPE_ANY, dns::CLASS_IN, true);
+    query[11] = 1; // one additional record
+    query.extend_from_slice(&[0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+    query
+}
+
+/// Sends `request` to udp/`port` of `addr` and collects the answers for `LISTEN_TIME`.
+///
+/// # Returns
+///
+/// The bytes and number of datagrams received, counting only answers of at least a
+/// DNS header's length that `valid` accepts. If nothing at all came back, the state of
+/// the port instead.
+fn exchange(
+    addr: IpAddr,
+    port: u16,
+    request: &[u8],
+    valid: impl Fn(&[u8]) -> bool,
+) -> Result<(usize, usize), PortState> {
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).map_err(|_| PortState::Unknown)?;
+    socket
+        .connect(SocketAddr::new(addr, port))
+        .map_err(|_| PortState::Unknown)?;
+    socket.send(request).map_err(|err| probe::udp_state(&err))?;
+
+    let deadline = Instant::now() + LISTEN_TIME;
+    let mut buf = vec![0; 65536];
+    let (mut bytes, mut datagrams) = (0, 0);
+    let mut answered = false;
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let timeout = remaining.max(Duration::from_millis(1));
+        if socket.set_read_timeout(Some(timeout)).is_err() {
+            break;
+        }
+        let n = match socket.recv(&mut buf) {
+            Ok(n) => n,
+            Err(err) if !answered => return Err(probe::udp_state(&err)),
+            Err(_) => break,
+        };
+        answered = true;
+        if n >= 12 && valid(&buf[..n]) {
+            bytes += n;
+            datagrams += 1;
+        }
+    }
+    match answered {
+        true => Ok((bytes, datagrams)),
+        false => Err(PortState::OpenFiltered),
+    }
+}
@@ -0,0 +1,246 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use ip_sniffer::scan::PortState;
+
+use crate::probe::{self, ServiceReport};
+
+pub const PORT: u16 = 53;
+
+/// How long to wait for an answer to a query.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A name outside any zone the server is likely to be authoritative for, used to test
+/// whether it resolves arbitrary names for us.
+const RECURSION_TEST_NAME: &str = "example.com";
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_TXT: u16 = 16;
+pub const CLASS_IN: u16 = 1;
+pub const CLASS_CH: u16 = 3;
+
+const FLAG_RD: u16 = 0x0100;
+const FLAG_RA: u16 = 0x0080;
+
+/// Details collected from a DNS server.
+#[derive(Debug, Default)]
+pub struct DnsInfo {
+    /// The answer to `version.bind CH TXT`, usually the server software and version.
+    pub version: Option<String>,
+    /// Whether the server resolved a name it is not authoritative for.
+    pub open_resolver: bool,
+    pub weak: Vec<String>,
+}
+
+impl DnsInfo {
+    pub fn report(&self, transport_udp: bool) -> ServiceReport {
+        let report = ServiceReport::new("dns")
+            .maybe("version", self.version.as_ref())
+            .text("open_resolver", self.open_resolver.to_string())
+            .list("weak", &self.weak);
+
+        if transport_udp {
+            report.udp()
+        } else {
+            report
+        }
+    }
+}
+
+/// Probes the DNS server on port 53 of `addr` for its version and open recursion.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+/// * `tcp` - Whether tcp/53 was found open; used when the server does not answer on UDP.
+///
+/// # Returns
+///
+/// The details, and whether they were collected over UDP. If no DNS server answered,
+/// the state of udp/53 instead, e.g. `OpenFiltered` if nothing came back.
+pub fn probe(addr: IpAddr, tcp: bool) -> Result<(DnsInfo, bool), PortState> {
+    let target = SocketAddr::new(addr, PORT);
+    let mut udp = true;
+
+    let version_query = build_query(0x1001, "version.bind", TYPE_TXT, CLASS_CH, false);
+    let version = match query_udp(target, &version_query) {
+        Ok(response) => response,
+        Err(state) if tcp => {
+            udp = false;
+            query_tcp(target, &version_query).ok_or(state)?
+        }
+        Err(state) => return Err(state),
+    };
+
+    let mut info = DnsInfo {
+        version: first_txt(&version),
+        ..DnsInfo::default()
+    };
+
+    let recursion_query = build_query(0x1002, RECURSION_TEST_NAME, TYPE_A, CLASS_IN, true);
+    let recursion = if udp {
+        query_udp(target, &recursion_query).ok()
+    } else {
+        query_tcp(target, &recursion_query)
+    };
+    info.open_resolver = recursion.is_some_and(|r| {
+        let flags = u16::from_be_bytes([r[2], r[3]]);
+        flags & FLAG_RA != 0 && flags & 0x000f == 0 && u16::from_be_bytes([r[6], r[7]]) > 0
+    });
+
+    if info.open_resolver {
+        info.weak.push("open resolver".to_string());
+    }
+    if info.version.is_some() {
+        info.weak.push("version disclosed".to_string());
+    }
+
+    Ok((info, udp))
+}
+
+/// Builds a DNS query for `name`.
+pub fn build_query(id: u16, name: &str, qtype: u16, qclass: u16, recursion: bool) -> Vec<u8> {
+    let flags = if recursion { FLAG_RD } else { 0 };
+
+    let mut packet = id.to_be_bytes().to_vec();
+    packet.extend_from_slice(&flags.to_be_bytes());
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // one question
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&qclass.to_be_bytes());
+    packet
+}
+
+/// Sends `query` over UDP and returns the response with the same ID, or the state of
+/// the port if none came back.
+pub fn query_udp(target: SocketAddr, query: &[u8]) -> Result<Vec<u8>, PortState> {
+    let local = if target.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).map_err(|_| PortState::Unknown)?;
+    socket.connect(target).map_err(|_| PortState::Unknown)?;
+    socket
+        .set_read_timeout(Some(QUERY_TIMEOUT))
+        .map_err(|_| PortState::Unknown)?;
+    socket.send(query).map_err(|err| probe::udp_state(&err))?;
+
+    let mut buf = vec![0; 4096];
+    let mut state = None;
+    loop {
+        let n = match socket.recv(&mut buf) {
+            Ok(n) => n,
+            Err(err) => return Err(state.unwrap_or_else(|| probe::udp_state(&err))),
+        };
+        if n >= 12 && buf[..2] == query[..2] {
+            buf.truncate(n);
+            return Ok(buf);
+        }
+        // Something answered, just not to this query.
+        state = Some(PortState::Open);
+    }
+}
+
+/// Sends `query` over TCP and returns the response.
+pub fn query_tcp(target: SocketAddr, query: &[u8]) -> Option<Vec<u8>> {
+    let mut stream = probe::connect(target).ok()?;
+
+    let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(query);
+    stream.write_all(&framed).ok()?;
+
+    read_tcp_message(&mut stream)
+}
+
+/// Reads one length-prefixed DNS message from a TCP stream.
+pub fn read_tcp_message(stream: &mut impl Read) -> Option<Vec<u8>> {
+    let mut length = [0; 2];
+    stream.read_exact(&mut length).ok()?;
+
+    let mut response = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut response).ok()?;
+    (response.len() >= 12).then_some(response)
+}
+
+/// A resource record from the answer section of a response.
+pub struct Record<'a> {
+    pub rtype: u16,
+    pub data: &'a [u8],
+    /// Offset of `data` in the message, to read compressed names in it.
+    pub offset: usize,
+}
+
+/// Parses the answer section of a DNS response.
+pub fn answers(message: &[u8]) -> Option<Vec<Record<'_>>> {
+    let questions = u16::from_be_bytes([*message.get(4)?, *message.get(5)?]);
+    let count = u16::from_be_bytes([*message.get(6)?, *message.get(7)?]);
+    let mut offset = 12;
+
+    for _ in 0..questions {
+        offset = read_name(message, offset)?.1 + 4;
+    }
+
+    let mut records = vec![];
+    for _ in 0..count {
+        let next = read_name(message, offset)?.1;
+        let rtype = u16::from_be_bytes([*message.get(next)?, *message.get(next + 1)?]);
+        let length = u16::from_be_bytes([*message.get(next + 8)?, *message.get(next + 9)?]);
+        let data_offset = next + 10;
+        let data = message.get(data_offset..data_offset + length as usize)?;
+
+        records.push(Record {
+            rtype,
+            data,
+            offset: data_offset,
+        });
+        offset = data_offset + length as usize;
+    }
+
+    Some(records)
+}
+
+/// Reads a possibly compressed domain name at `offset`.
+///
+/// # Returns
+///
+/// The name, without a trailing dot, and the offset just past it.
+pub fn read_name(message: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = vec![];
+    let mut end = None;
+
+    // Bound the number of jumps so a pointer loop cannot hang us.
+    for _ in 0..128 {
+        let length = *message.get(offset)? as usize;
+
+        if length == 0 {
+            return Some((labels.join("."), end.unwrap_or(offset + 1)));
+        }
+        if length & 0xc0 == 0xc0 {
+            let pointer = (length & 0x3f) << 8 | *message.get(offset + 1)? as usize;
+            end.get_or_insert(offset + 2);
+            offset = pointer;
+            continue;
+        }
+
+        let label = message.get(offset + 1..offset + 1 + length)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        offset += 1 + length;
+    }
+
+    None
+}
+
+/// Returns the first string of the first TXT record in a response.
+fn first_txt(message: &[u8]) -> Option<String> {
+    let records = answers(message)?;
+    let txt = records.iter().find(|r| r.rtype == TYPE_TXT)?;
+    let length = *txt.data.first()? as usize;
+    let text = txt.data.get(1..1 + length)?;
+    Some(String::from_utf8_lossy(text).into_owned())
+}
@@ -0,0 +1,592 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::SocketAddr;
+
+use ip_sniffer::base64;
+
+use crate::hash;
+use crate::probe::{self, Identity, ServiceReport};
+
+/// Ports on which HTTP is probed.
+pub const PORTS: &[u16] = &[80, 8000, 8008, 8080, 8888];
+
+/// Maximum number of response bytes read.
+const MAX_RESPONSE: u64 = 64 * 1024;
+
+/// Where a technology leaves a trace in a response.
+enum Signature {
+    /// A header, by lowercase name, whose value contains the text, ignoring case.
+    Header(&'static str, &'static str),
+    /// Text found in the body, ignoring case.
+    Body(&'static str),
+}
+
+/// Technologies recognized from the response to `/`, with the signatures that reveal
+/// them. A technology is reported if any of its signatures match.
+const TECHNOLOGIES: &[(&str, &[Signature])] = {
+    use Signature::*;
+
+    &[
+        ("Apache", &[Header("server", "apache")]),
+        ("nginx", &[Header("server", "nginx")]),
+        ("IIS", &[Header("server", "microsoft-iis")]),
+        ("Cloudflare", &[Header("server", "cloudflare")]),
+        (
+            "Tomcat",
+            &[Body("apache tomcat"), Header("server", "tomcat")],
+        ),
+        (
+            "PHP",
+            &[
+                Header("x-powered-by", "php"),
+                Header("set-cookie", "phpsessid"),
+            ],
+        ),
+        (
+            "ASP.NET",
+            &[
+                Header("x-powered-by", "asp.net"),
+                Header("x-aspnet-version", ""),
+                Header("set-cookie", "asp.net_sessionid"),
+            ],
+        ),
+        ("Express", &[Header("x-powered-by", "express")]),
+        (
+            "WordPress",
+            &[
+                Body("/wp-content/"),
+                Body("/wp-includes/"),
+                Header("link", "wp-json"),
+            ],
+        ),
+        (
+            "Drupal",
+            &[Header("x-generator", "drupal"), Body("drupal.settings")],
+        ),
+        ("Joomla", &[Body("content=\"joomla"), Body("/media/jui/")]),
+        ("Laravel", &[Header("set-cookie", "laravel_session")]),
+        ("Django", &[Body("csrfmiddlewaretoken")]),
+        (
+            "Grafana",
+            &[Body("grafana-app"), Body("<title>grafana</title>")],
+        ),
+        (
+            "Kibana",
+            &[Header("kbn-name", ""), Body("<title>kibana</title>")],
+        ),
+        ("Prometheus", &[Body("<title>prometheus")]),
+        ("Jenkins", &[Header("x-jenkins", "")]),
+        (
+            "GitLab",
+            &[
+                Header("set-cookie", "_gitlab_session"),
+                Body("content=\"gitlab"),
+            ],
+        ),
+        ("Jira", &[Header("x-arequestid", ""), Body("jira-")]),
+        ("phpMyAdmin", &[Body("phpmyadmin")]),
+    ]
+};
+
+/// Most requests sent on one connection by the keep-alive probe.
+const MAX_KEEPALIVE_REQUESTS: usize = 100;
+
+/// Number of base64 characters per line when hashing a favicon, as Python's
+/// `base64.encodebytes` wraps them; fingerprint databases hash that form.
+const FAVICON_LINE: usize = 76;
+
+/// Details collected from an HTTP server.
+#[derive(Debug, Default)]
+pub struct HttpInfo {
+    /// The `Host` header sent, if it was a name rather than the IP address.
+    pub vhost: Option<String>,
+    /// The path requested, if it was not `/`.
+    pub path: Option<String>,
+    /// The status line, e.g. `HTTP/1.1 200 OK`.
+    pub status: String,
+    /// The `Location` header of a redirect.
+    pub location: Option<String>,
+    /// The `Server` header.
+    pub server: Option<String>,
+    /// The `X-Powered-By` header.
+    pub powered_by: Option<String>,
+    /// The `<title>` of the page.
+    pub title: Option<String>,
+    /// Technologies recognized from the headers and page, e.g. `WordPress`.
+    pub technologies: Vec<String>,
+    /// MurmurHash3 of the base64-encoded `/favicon.ico`, as Shodan computes it.
+    pub favicon_mmh3: Option<i32>,
+    /// SHA-256 of `/favicon.ico`, in hex.
+    pub favicon_sha256: Option<String>,
+}
+
+/// A response to one request.
+pub struct Response {
+    /// The status line, e.g. `HTTP/1.1 200 OK`.
+    pub status: String,
+    /// Header names, lowercased, and values.
+    pub headers: Vec<(String, String)>,
+    /// The body, decoded if it was chunked.
+    pub body: Vec<u8>,
+}
+
+impl HttpInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("http")
+            .maybe("vhost", self.vhost.as_ref())
+            .maybe("path", self.path.as_ref())
+            .text("status", &self.status)
+            .maybe("location", self.location.as_ref())
+            .maybe("server", self.server.as_ref())
+            .maybe("powered_by", self.powered_by.as_ref())
+            .maybe("title", self.title.as_ref())
+            .list("technologies", &self.technologies)
+            .maybe(
+                "favicon_mmh3",
+                self.favicon_mmh3.map(|hash| hash.to_string()).as_ref(),
+            )
+            .maybe("favicon_sha256", self.favicon_sha256.as_ref())
+    }
+}
+
+/// How an HTTP server handles persistent connections.
+#[derive(Debug, Default)]
+pub struct KeepAliveInfo {
+    /// Whether the server answered a second request on the same connection.
+    pub keep_alive: bool,
+    /// Requests answered on one connection before the server closed it, or
+    /// `MAX_KEEPALIVE_REQUESTS` if it never did.
+    pub requests: usize,
+    /// The `Keep-Alive` header of the first response, e.g. `timeout=5, max=100`.
+    pub advertised: Option<String>,
+    /// Whether the server answered two requests sent together in order.
+    pub pipelining: bool,
+}
+
+impl KeepAliveInfo {
+    pub fn report(&self) -> ServiceReport {
+        let requests = if self.requests >= MAX_KEEPALIVE_REQUESTS {
+            format!("{}+", self.requests)
+        } else {
+            self.requests.to_string()
+        };
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+
+        ServiceReport::new("http")
+            .text("keep_alive", yes_no(self.keep_alive))
+            .text("requests_per_connection", requests)
+            .maybe("keep_alive_header", self.advertised.as_ref())
+            .text("pipelining", yes_no(self.pipelining))
+    }
+}
+
+/// Requests `/` and `/favicon.ico` from an HTTP server.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `identity` - Supplies the `User-Agent` header.
+/// * `vhost` - The name sent in the `Host` header; the IP address is sent if `None`.
+///
+/// # Returns
+///
+/// `None` if the port does not speak HTTP.
+pub fn probe(target: SocketAddr, identity: &Identity, vhost: Option<&str>) -> Option<HttpInfo> {
+    probe_path(target, "/", identity, vhost)
+}
+
+/// Requests `path` and `/favicon.ico` from an HTTP server, e.g. the page of a URL target.
+///
+/// # Returns
+///
+/// As `probe`.
+pub fn probe_path(
+    target: SocketAddr,
+    path: &str,
+    identity: &Identity,
+    vhost: Option<&str>,
+) -> Option<HttpInfo> {
+    let response = get(target, path, identity, vhost)?;
+
+    let mut info = HttpInfo {
+        vhost: vhost.map(str::to_string),
+        path: (path != "/").then(|| path.to_string()),
+        status: response.status,
+        ..HttpInfo::default()
+    };
+
+    for (name, value) in &response.headers {
+        match name.as_str() {
+            "server" => info.server = Some(value.clone()),
+            "x-powered-by" => info.powered_by = Some(value.clone()),
+            "location" => info.location = Some(value.clone()),
+            _ => {}
+        }
+    }
+    let body = String::from_utf8_lossy(&response.body);
+    info.title = title(&body);
+    info.technologies = technologies(&response.headers, &body);
+
+    if let Some(Response {
+        status, body: icon, ..
+    }) = get(target, "/favicon.ico", identity, vhost)
+    {
+        if status.split_whitespace().nth(1) == Some("200") && !icon.is_empty() {
+            info.favicon_mmh3 = Some(favicon_mmh3(&icon));
+            let digest = hash::sha256(&icon);
+            info.favicon_sha256 = Some(digest.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+    }
+
+    Some(info)
+}
+
+/// Sends a `GET` request for `path`, with `vhost` or else the IP address as `Host`.
+///
+/// # Returns
+///
+/// `None` if the port does not speak HTTP.
+pub fn get(
+    target: SocketAddr,
+    path: &str,
+    identity: &Identity,
+    vhost: Option<&str>,
+) -> Option<Response> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path,
+        host(target, vhost),
+        identity.user_agent
+    );
+    exchange(target, &request)
+}
+
+/// Sends a `POST` request for `path` with `body` and extra `headers`, with the IP
+/// address as `Host`.
+///
+/// # Returns
+///
+/// `None` if the port does not speak HTTP.
+pub fn post(
+    target: SocketAddr,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+    identity: &Identity,
+) -> Option<Response> {
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        path,
+        host(target, None),
+        identity.user_agent,
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    exchange(target, &request)
+}
+
+/// Returns the `Host` header for a request: `vhost`, or else the IP address.
+fn host(target: SocketAddr, vhost: Option<&str>) -> String {
+    match (vhost, target) {
+        (Some(vhost), _) => vhost.to_string(),
+        (None, SocketAddr::V4(_)) => target.ip().to_string(),
+        (None, SocketAddr::V6(_)) => format!("[{}]", target.ip()),
+    }
+}
+
+/// Sends a request and reads the response.
+///
+/// # Returns
+///
+/// `None` if the port does not speak HTTP.
+fn exchange(target: SocketAddr, request: &str) -> Option<Response> {
+    let mut stream = probe::connect(target).ok()?;
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut response = vec![];
+    // A timeout after some data is fine; servers may ignore `Connection: close`.
+    let _ = stream.take(MAX_RESPONSE).read_to_end(&mut response);
+
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n");
+    let (head, body) = match split {
+        Some(i) => (&response[..i], response[i + 4..].to_vec()),
+        None => (&response[..], vec![]),
+    };
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.lines();
+    let status = lines.next()?.trim().to_string();
+    if !status.starts_with("HTTP/") {
+        return None;
+    }
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let chunked = headers
+        .iter()
+        .any(|(name, value)| name == "transfer-encoding" && value.eq_ignore_ascii_case("chunked"));
+    let body = if chunked { dechunk(&body) } else { body };
+
+    Some(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked`, keeping the chunks read
+/// before any truncation.
+fn dechunk(mut data: &[u8]) -> Vec<u8> {
+    let mut body = vec![];
+
+    while let Some(end) = data.windows(2).position(|w| w == b"\r\n") {
+        let size = String::from_utf8_lossy(&data[..end]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let Ok(size) = usize::from_str_radix(size, 16) else {
+            break;
+        };
+        if size == 0 {
+            break;
+        }
+
+        let chunk = &data[end + 2..];
+        body.extend_from_slice(&chunk[..size.min(chunk.len())]);
+        data = chunk.get(size + 2..).unwrap_or_default();
+    }
+
+    body
+}
+
+/// Hashes a favicon the way Shodan does: MurmurHash3 of its base64 encoding, wrapped
+/// into lines of `FAVICON_LINE` characters, as a signed number.
+fn favicon_mmh3(icon: &[u8]) -> i32 {
+    let encoded = base64::encode(icon);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / FAVICON_LINE + 1);
+
+    for line in encoded.as_bytes().chunks(FAVICON_LINE) {
+        wrapped.push_str(&String::from_utf8_lossy(line));
+        wrapped.push('\n');
+    }
+
+    hash::murmur3_32(wrapped.as_bytes(), 0) as i32
+}
+
+/// Requests `/` with each of `vhosts` as the `Host` header and keeps the answers that
+/// differ from `baseline` in status, redirect or title.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `identity` - Supplies the `User-Agent` header.
+/// * `baseline` - The answer to the scan's own host name or IP address.
+/// * `vhosts` - The names to try.
+///
+/// # Returns
+///
+/// The answers of the names the server treats differently, in the order of `vhosts`.
+pub fn probe_vhosts(
+    target: SocketAddr,
+    identity: &Identity,
+    baseline: &HttpInfo,
+    vhosts: &[String],
+) -> Vec<HttpInfo> {
+    let key = |info: &HttpInfo| {
+        (
+            info.status.clone(),
+            info.location.clone(),
+            info.title.clone(),
+        )
+    };
+
+    vhosts
+        .iter()
+        .filter_map(|vhost| probe(target, identity, Some(vhost)))
+        .filter(|info| key(info) != key(baseline))
+        .collect()
+}
+
+/// Loads the names tried with `--vhosts`, one per line; blank lines and `#` comments
+/// are ignored.
+///
+/// # Errors
+///
+/// * "failed to read vhosts file" if the file cannot be read.
+/// * "vhosts file is empty" if it holds no names.
+pub fn load_vhosts(path: &str) -> Result<Vec<String>, &'static str> {
+    let contents = std::fs::read_to_string(path).map_err(|_| "failed to read vhosts file")?;
+    let vhosts: Vec<String> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+
+    if vhosts.is_empty() {
+        return Err("vhosts file is empty");
+    }
+    Ok(vhosts)
+}
+
+/// Checks whether an HTTP server keeps connections open between requests, how many
+/// requests it answers on one, and whether it answers pipelined requests.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `identity` - Supplies the `User-Agent` header.
+/// * `vhost` - The name sent in the `Host` header; the IP address is sent if `None`.
+///
+/// # Returns
+///
+/// `None` if the port does not speak HTTP.
+///
+/// # Description
+///
+/// `HEAD /` is sent over one connection until the server closes it, answers with
+/// `Connection: close` or `MAX_KEEPALIVE_REQUESTS` have been answered. `HEAD` responses
+/// carry no body, so each ends at its blank line. A second connection then sends two
+/// requests in one write, which a server supporting pipelining answers in turn.
+pub fn probe_keepalive(
+    target: SocketAddr,
+    identity: &Identity,
+    vhost: Option<&str>,
+) -> Option<KeepAliveInfo> {
+    let request = |connection: &str| {
+        format!(
+            "HEAD / HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\nConnection: {}\r\n\r\n",
+            host(target, vhost),
+            identity.user_agent,
+            connection
+        )
+    };
+    let keep = request("keep-alive");
+
+    let mut reader = BufReader::new(probe::connect(target).ok()?);
+    let mut info = KeepAliveInfo::default();
+    while info.requests < MAX_KEEPALIVE_REQUESTS {
+        if reader.get_mut().write_all(keep.as_bytes()).is_err() {
+            break;
+        }
+        let Some(headers) = read_head(&mut reader) else {
+            break;
+        };
+        info.requests += 1;
+
+        if info.requests == 1 {
+            info.advertised = header(&headers, "keep-alive").cloned();
+        }
+        if header(&headers, "connection").is_some_and(|v| v.eq_ignore_ascii_case("close")) {
+            break;
+        }
+    }
+    if info.requests == 0 {
+        return None;
+    }
+    info.keep_alive = info.requests > 1;
+
+    if info.keep_alive {
+        let mut reader = BufReader::new(probe::connect(target).ok()?);
+        let pipelined = format!("{}{}", keep, request("close"));
+        info.pipelining = reader.get_mut().write_all(pipelined.as_bytes()).is_ok()
+            && read_head(&mut reader).is_some()
+            && read_head(&mut reader).is_some();
+    }
+
+    Some(info)
+}
+
+/// Reads the status line and headers of a response to a `HEAD` request, skipping any
+/// interim `1xx` responses.
+///
+/// # Returns
+///
+/// `None` if the connection closed or the response is not HTTP.
+fn read_head(reader: &mut impl BufRead) -> Option<Vec<(String, String)>> {
+    loop {
+        let mut status = String::new();
+        if reader.read_line(&mut status).ok()? == 0 || !status.starts_with("HTTP/") {
+            return None;
+        }
+
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let interim = status
+            .split_whitespace()
+            .nth(1)
+            .is_some_and(|code| code.starts_with('1'));
+        if !interim {
+            return Some(headers);
+        }
+    }
+}
+
+/// Returns the value of the header `name`, which must be lowercase.
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a String> {
+    headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| value)
+}
+
+/// Returns the names of the `TECHNOLOGIES` whose signatures match a response.
+fn technologies(headers: &[(String, String)], body: &str) -> Vec<String> {
+    let body = body.to_ascii_lowercase();
+
+    let matches = |signature: &Signature| match *signature {
+        Signature::Header(header, text) => headers
+            .iter()
+            .any(|(name, value)| name == header && value.to_ascii_lowercase().contains(text)),
+        Signature::Body(text) => body.contains(text),
+    };
+
+    TECHNOLOGIES
+        .iter()
+        .filter(|(_, signatures)| signatures.iter().any(matches))
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// Extracts the text of the first `<title>` element.
+fn title(body: &str) -> Option<String> {
+    let lower = body.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+
+    let title = body[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_favicons_like_shodan() {
+        // `mmh3.hash(base64.encodebytes(icon))`, which wraps lines at 76 characters.
+        assert_eq!(favicon_mmh3(&[]), 0);
+        assert_eq!(favicon_mmh3(&(0..57).collect::<Vec<u8>>()), 459585070);
+        assert_eq!(favicon_mmh3(&(0..=255).collect::<Vec<u8>>()), -757223386);
+    }
+}
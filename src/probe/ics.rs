@@ -0,0 +1,284 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, ServiceReport};
+
+// `--ics-probe` identifies industrial control protocols with read-only requests that
+// engineering tools send when browsing a network; nothing is written to a device and no
+// program or output is touched:
+//
+// modbus/502     Read Device Identification (function 43/14): vendor, product, revision
+// s7comm/102     COTP connect, S7 setup, then read SZL 0x0011 and 0x001c: module,
+//                hardware, firmware, system name and serial number
+// enip/44818     List Identity: vendor, device type, product, revision, serial number
+//
+// Devices that answer without the identity are still reported as speaking the protocol.
+
+pub const MODBUS_PORT: u16 = 502;
+pub const S7_PORT: u16 = 102;
+pub const ENIP_PORT: u16 = 44818;
+
+/// The unit addressed on Modbus gateways; devices answer for themselves to any unit.
+const MODBUS_UNIT: u8 = 0;
+
+/// Modbus `Read Device Identification` of the basic objects: vendor, product code and
+/// revision.
+const MODBUS_READ_ID: [u8; 11] = [
+    0x00,
+    0x01,
+    0x00,
+    0x00,
+    0x00,
+    0x05,
+    MODBUS_UNIT, // MBAP: transaction 1, 5 bytes
+    0x2b,
+    0x0e,
+    0x01,
+    0x00, // function 43, MEI 14, basic objects, from object 0
+];
+
+/// COTP connection requests for rack 0, slot 2 and rack 0, slot 0, tried in turn; the
+/// last bytes are the destination TSAP.
+const S7_CONNECT: [[u8; 22]; 2] = [
+    [
+        0x03, 0x00, 0x00, 0x16, 0x11, 0xe0, 0x00, 0x00, 0x00, 0x14, 0x00, 0xc1, 0x02, 0x01, 0x00,
+        0xc2, 0x02, 0x01, 0x02, 0xc0, 0x01, 0x0a,
+    ],
+    [
+        0x03, 0x00, 0x00, 0x16, 0x11, 0xe0, 0x00, 0x00, 0x00, 0x05, 0x00, 0xc1, 0x02, 0x01, 0x00,
+        0xc2, 0x02, 0x02, 0x00, 0xc0, 0x01, 0x0a,
+    ],
+];
+
+/// S7 `Setup communication`.
+const S7_SETUP: [u8; 25] = [
+    0x03, 0x00, 0x00, 0x19, 0x02, 0xf0, 0x80, 0x32, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00,
+    0x00, 0xf0, 0x00, 0x00, 0x01, 0x00, 0x01, 0x01, 0xe0,
+];
+
+/// S7 `Read SZL` of module identification (0x0011) or component identification
+/// (0x001c), with the SZL ID in the two bytes before the last two.
+const fn s7_read_szl(szl: u8) -> [u8; 33] {
+    [
+        0x03, 0x00, 0x00, 0x21, 0x02, 0xf0, 0x80, 0x32, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08,
+        0x00, 0x08, 0x00, 0x01, 0x12, 0x04, 0x11, 0x44, 0x01, 0x00, 0xff, 0x09, 0x00, 0x04, 0x00,
+        szl, 0x00, 0x01,
+    ]
+}
+
+/// EtherNet/IP `List Identity`: an encapsulation header with no data.
+const ENIP_LIST_IDENTITY: [u8; 24] = {
+    let mut request = [0; 24];
+    request[0] = 0x63;
+    request
+};
+
+/// Details collected from an industrial device.
+#[derive(Debug, Default)]
+pub struct IcsInfo {
+    /// `modbus`, `s7comm` or `enip`.
+    pub protocol: &'static str,
+    /// Identity fields in the order the device reports them, e.g. `("vendor", "Schneider")`.
+    pub identity: Vec<(&'static str, String)>,
+}
+
+impl IcsInfo {
+    pub fn report(&self) -> ServiceReport {
+        self.identity.iter().fold(
+            ServiceReport::new(self.protocol),
+            |report, (name, value)| report.text(name, value.clone()),
+        )
+    }
+
+    fn new(protocol: &'static str) -> IcsInfo {
+        IcsInfo {
+            protocol,
+            identity: vec![],
+        }
+    }
+
+    fn add(&mut self, name: &'static str, value: String) {
+        let value = value.trim().to_string();
+        if !value.is_empty() {
+            self.identity.push((name, value));
+        }
+    }
+}
+
+/// Returns whether `port` is one an industrial protocol is probed on.
+pub fn applies_to(port: u16) -> bool {
+    [MODBUS_PORT, S7_PORT, ENIP_PORT].contains(&port)
+}
+
+/// Identifies the industrial protocol expected on the port of `target` and reads the
+/// device's identity where the device allows it.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+///
+/// # Returns
+///
+/// `None` if the port does not speak the protocol expected on it.
+pub fn probe(target: SocketAddr) -> Option<IcsInfo> {
+    match target.port() {
+        MODBUS_PORT => probe_modbus(target),
+        S7_PORT => probe_s7(target),
+        ENIP_PORT => probe_enip(target),
+        _ => None,
+    }
+}
+
+fn probe_modbus(target: SocketAddr) -> Option<IcsInfo> {
+    let mut stream = probe::connect(target).ok()?;
+    stream.write_all(&MODBUS_READ_ID).ok()?;
+
+    let mut header = [0; 7];
+    stream.read_exact(&mut header).ok()?;
+    // The transaction and protocol IDs must echo ours.
+    if header[..4] != MODBUS_READ_ID[..4] {
+        return None;
+    }
+    let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let mut pdu = vec![0; length.saturating_sub(1)];
+    stream.read_exact(&mut pdu).ok()?;
+
+    let mut info = IcsInfo::new("modbus");
+    // An exception (function 0x80 + 43) still shows Modbus is spoken.
+    if pdu.first() != Some(&0x2b) || pdu.len() < 7 {
+        return (pdu.first() == Some(&0xab)).then_some(info);
+    }
+
+    let count = pdu[6] as usize;
+    let mut objects = &pdu[7..];
+    for _ in 0..count {
+        let [id, len, rest @ ..] = objects else {
+            break;
+        };
+        let value = String::from_utf8_lossy(rest.get(..*len as usize).unwrap_or(rest));
+        let name = match id {
+            0 => "vendor",
+            1 => "product_code",
+            2 => "revision",
+            _ => "identity",
+        };
+        info.add(name, value.into_owned());
+        objects = rest.get(*len as usize..).unwrap_or_default();
+    }
+    Some(info)
+}
+
+fn probe_s7(target: SocketAddr) -> Option<IcsInfo> {
+    for connect in &S7_CONNECT {
+        let mut stream = probe::connect(target).ok()?;
+        stream.write_all(connect).ok()?;
+        // A COTP connection confirm.
+        match read_tpkt(&mut stream) {
+            Some(reply) if reply.get(5) == Some(&0xd0) => {}
+            Some(_) => continue,
+            None => return None,
+        }
+
+        stream.write_all(&S7_SETUP).ok()?;
+        let setup = read_tpkt(&mut stream)?;
+        if setup.get(7) != Some(&0x32) {
+            return None;
+        }
+        let mut found = IcsInfo::new("s7comm");
+
+        stream.write_all(&s7_read_szl(0x11)).ok()?;
+        if let Some(module) = read_tpkt(&mut stream).filter(|r| r.get(7) == Some(&0x32)) {
+            found.add("module", c_string(&module, 43));
+            found.add("hardware", c_string(&module, 71));
+            if let Some(version) = module.get(122..125) {
+                let firmware = format!("{}.{}.{}", version[0], version[1], version[2]);
+                found.add("firmware", firmware);
+            }
+        }
+
+        stream.write_all(&s7_read_szl(0x1c)).ok()?;
+        if let Some(component) = read_tpkt(&mut stream).filter(|r| r.get(7) == Some(&0x32)) {
+            found.add("system_name", c_string(&component, 39));
+            found.add("module_type", c_string(&component, 73));
+            found.add("plant", c_string(&component, 141));
+            found.add("serial", c_string(&component, 175));
+        }
+
+        return Some(found);
+    }
+    None
+}
+
+fn probe_enip(target: SocketAddr) -> Option<IcsInfo> {
+    let mut stream = probe::connect(target).ok()?;
+    stream.write_all(&ENIP_LIST_IDENTITY).ok()?;
+
+    let mut header = [0; 24];
+    stream.read_exact(&mut header).ok()?;
+    if header[..2] != [0x63, 0x00] {
+        return None;
+    }
+    let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let mut data = vec![0; length.min(1024)];
+    stream.read_exact(&mut data).ok()?;
+
+    let mut info = IcsInfo::new("enip");
+    // Item count, type and length, encapsulation version and socket address come first.
+    let Some(item) = data.get(24..) else {
+        return Some(info);
+    };
+    let le16 = |at: usize| u16::from_le_bytes([item[at], item[at + 1]]);
+    if item.len() < 15 {
+        return Some(info);
+    }
+
+    info.add("vendor_id", le16(0).to_string());
+    info.add("device_type", device_type(le16(2)));
+    info.add("product_code", le16(4).to_string());
+    info.add("revision", format!("{}.{}", item[6], item[7]));
+    let serial = u32::from_le_bytes([item[10], item[11], item[12], item[13]]);
+    info.add("serial", format!("{:08x}", serial));
+    let name_len = item[14] as usize;
+    if let Some(name) = item.get(15..15 + name_len) {
+        info.add("product", String::from_utf8_lossy(name).into_owned());
+    }
+    Some(info)
+}
+
+/// Returns the CIP name of a device type, or its number if it is not a common one.
+fn device_type(code: u16) -> String {
+    let name = match code {
+        0x00 => "Generic Device",
+        0x02 => "AC Drive",
+        0x07 => "General Purpose Discrete I/O",
+        0x0c => "Communications Adapter",
+        0x0e => "Programmable Logic Controller",
+        0x18 => "Human-Machine Interface",
+        0x2b => "Generic Device (keyable)",
+        _ => return code.to_string(),
+    };
+    name.to_string()
+}
+
+/// Reads one TPKT packet, header included.
+fn read_tpkt(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).ok()?;
+    if header[0] != 0x03 {
+        return None;
+    }
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+
+    let mut packet = header.to_vec();
+    packet.resize(length.max(4), 0);
+    stream.read_exact(&mut packet[4..]).ok()?;
+    Some(packet)
+}
+
+/// Reads the string at `offset`, up to a NUL or the longest SZL text field.
+fn c_string(packet: &[u8], offset: usize) -> String {
+    let field = packet.get(offset..).unwrap_or_default();
+    let field = &field[..field.len().min(32)];
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
@@ -0,0 +1,154 @@
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use ip_sniffer::scan::PortState;
+
+use crate::probe::{self, ServiceReport};
+
+pub const PORT: u16 = 623;
+
+/// How long to wait for a BMC to answer.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// IPMI 1.5 `Get Channel Authentication Capabilities` for the current channel at
+/// administrator level, asking for IPMI 2.0 capabilities too. It needs no session.
+const GET_AUTH_CAPABILITIES: [u8; 23] = [
+    0x06, 0x00, 0xff, 0x07, // RMCP, class IPMI
+    0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0x09, // no authentication, no session, 9 bytes
+    0x20, 0x18, 0xc8, 0x81, 0x00, 0x38, 0x8e, 0x04, 0xb5,
+];
+
+/// IPMI 2.0 RMCP+ `Open Session Request` proposing cipher suite 0: no authentication,
+/// integrity or confidentiality.
+const OPEN_SESSION_CIPHER_ZERO: [u8; 48] = [
+    0x06, 0x00, 0xff, 0x07, // RMCP, class IPMI
+    0x06, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0x20, 0x00, // RMCP+, open session, 32 bytes
+    0x00, 0x00, 0x00, 0x00, // message tag, highest privilege
+    0xa0, 0xa1, 0xa2, 0xa3, // our session ID
+    0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, // authentication: none
+    0x01, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, // integrity: none
+    0x02, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, // confidentiality: none
+];
+
+/// Details collected from a BMC.
+#[derive(Debug, Default)]
+pub struct IpmiInfo {
+    /// IPMI versions the BMC supports, e.g. `["1.5", "2.0"]`.
+    pub versions: Vec<String>,
+    /// Authentication types offered for IPMI 1.5 sessions, e.g. `["MD5", "password"]`.
+    pub auth_types: Vec<String>,
+    /// Whether the BMC opened an RMCP+ session with cipher suite 0.
+    pub cipher_zero: bool,
+    pub weak: Vec<String>,
+}
+
+impl IpmiInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("ipmi")
+            .udp()
+            .list("versions", &self.versions)
+            .list("auth_types", &self.auth_types)
+            .text("cipher_zero", self.cipher_zero.to_string())
+            .list("weak", &self.weak)
+    }
+}
+
+/// Probes the BMC on udp/623 of `addr` for its IPMI versions and authentication, and
+/// whether it accepts cipher suite 0.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+///
+/// # Returns
+///
+/// The BMC's details, or the state of the port if no BMC answered: `OpenFiltered` if
+/// nothing came back, `Open` if something other than IPMI did.
+///
+/// # Description
+///
+/// Cipher suite 0 lets anyone who knows a user name log in with any password. It is
+/// only tried on BMCs that support IPMI 2.0, by asking to open a session with it; the
+/// session is never activated, as that needs a user name.
+pub fn probe(addr: IpAddr) -> Result<IpmiInfo, PortState> {
+    let answer = query(addr, &GET_AUTH_CAPABILITIES)?;
+    // The answer's IPMI message starts after the 4-byte RMCP and 10-byte session headers.
+    let message = answer.get(14..).ok_or(PortState::Open)?;
+    if message.len() < 11 || message[5] != 0x38 || message[6] != 0 {
+        return Err(PortState::Open);
+    }
+    let (auth_support, auth_status, extended) = (message[8], message[9], message[10]);
+
+    let mut info = IpmiInfo::default();
+    let ipmi2 = auth_support & 0x80 != 0 && extended & 0x02 != 0;
+    if auth_support & 0x80 == 0 || extended & 0x01 != 0 {
+        info.versions.push("1.5".to_string());
+    }
+    if ipmi2 {
+        info.versions.push("2.0".to_string());
+    }
+    for (bit, name) in [
+        (0, "none"),
+        (1, "MD2"),
+        (2, "MD5"),
+        (4, "password"),
+        (5, "OEM"),
+    ] {
+        if auth_support & (1 << bit) != 0 {
+            info.auth_types.push(name.to_string());
+        }
+    }
+
+    if ipmi2 {
+        // The status code follows the 16 bytes of RMCP and RMCP+ session headers and the
+        // message tag, in an answer with the open session response payload type.
+        let answer = query(addr, &OPEN_SESSION_CIPHER_ZERO).unwrap_or_default();
+        info.cipher_zero = answer.len() > 17 && answer[5] & 0x3f == 0x11 && answer[17] == 0;
+    }
+
+    if info.cipher_zero {
+        info.weak
+            .push("cipher suite 0: any password is accepted".to_string());
+    }
+    if auth_status & 0x01 != 0 {
+        info.weak.push("anonymous login enabled".to_string());
+    }
+    if auth_status & 0x02 != 0 {
+        info.weak.push("null user names enabled".to_string());
+    }
+    if auth_support & 0x01 != 0 {
+        info.weak
+            .push("IPMI 1.5 sessions without authentication".to_string());
+    }
+    if auth_support & 0x10 != 0 {
+        info.weak
+            .push("IPMI 1.5 plaintext password authentication".to_string());
+    }
+
+    Ok(info)
+}
+
+/// Sends `packet` to udp/623 and returns the answer, or the state of the port if none
+/// came back.
+fn query(addr: IpAddr, packet: &[u8]) -> Result<Vec<u8>, PortState> {
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).map_err(|_| PortState::Unknown)?;
+    socket
+        .connect(SocketAddr::new(addr, PORT))
+        .map_err(|_| PortState::Unknown)?;
+    socket
+        .set_read_timeout(Some(QUERY_TIMEOUT))
+        .map_err(|_| PortState::Unknown)?;
+    socket.send(packet).map_err(|err| probe::udp_state(&err))?;
+
+    let mut buf = vec![0; 1024];
+    let n = socket
+        .recv(&mut buf)
+        .map_err(|err| probe::udp_state(&err))?;
+    buf.truncate(n);
+    Ok(buf)
+}
@@ -0,0 +1,561 @@
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::hash;
+use crate::probe::{self, ServiceReport};
+
+// JARM (https://github.com/salesforce/jarm) sends ten TLS ClientHellos that differ in
+// version, cipher order, GREASE, ALPN and extension order, and records the cipher,
+// version and extensions each ServerHello picks. No handshake is completed, so no TLS
+// implementation is needed. The lists and orders below must match the reference
+// implementation byte for byte for fingerprints to be comparable.
+
+/// Cipher suites offered, in the reference order. A cipher is identified in the
+/// fingerprint by its 1-based position in this list.
+const CIPHERS: &[u16] = &[
+    0x0016, 0x0033, 0x0067, 0xc09e, 0xc0a2, 0x009e, 0x0039, 0x006b, 0xc09f, 0xc0a3, 0x009f, 0x0045,
+    0x00be, 0x0088, 0x00c4, 0x009a, 0xc008, 0xc009, 0xc023, 0xc0ac, 0xc0ae, 0xc02b, 0xc00a, 0xc024,
+    0xc0ad, 0xc0af, 0xc02c, 0xc072, 0xc073, 0xcca9, 0x1302, 0x1301, 0xcc14, 0xc007, 0xc012, 0xc013,
+    0xc027, 0xc02f, 0xc014, 0xc028, 0xc030, 0xc060, 0xc061, 0xc076, 0xc077, 0xcca8, 0x1305, 0x1304,
+    0x1303, 0xcc13, 0xc011, 0x000a, 0x002f, 0x003c, 0xc09c, 0xc0a0, 0x009c, 0x0035, 0x003d, 0xc09d,
+    0xc0a1, 0x009d, 0x0041, 0x00ba, 0x0084, 0x00c0, 0x0007, 0x0004, 0x0005,
+];
+
+/// ALPN protocols offered, weakest first.
+const ALPNS: &[&[u8]] = &[
+    b"http/0.9",
+    b"http/1.0",
+    b"http/1.1",
+    b"spdy/1",
+    b"spdy/2",
+    b"spdy/3",
+    b"h2",
+    b"h2c",
+    b"hq",
+];
+
+/// ALPN protocols offered by the "rare ALPN" hellos: `ALPNS` without h2 and http/1.1.
+const RARE_ALPNS: &[&[u8]] = &[
+    b"http/0.9",
+    b"http/1.0",
+    b"spdy/1",
+    b"spdy/2",
+    b"spdy/3",
+    b"h2c",
+    b"hq",
+];
+
+/// GREASE values (RFC 8701).
+const GREASE: [u16; 16] = [
+    0x0a0a, 0x1a1a, 0x2a2a, 0x3a3a, 0x4a4a, 0x5a5a, 0x6a6a, 0x7a7a, 0x8a8a, 0x9a9a, 0xaaaa, 0xbaba,
+    0xcaca, 0xdada, 0xeaea, 0xfafa,
+];
+
+/// Maximum number of ServerHello bytes read, as in the reference implementation.
+const MAX_RESPONSE: u64 = 1484;
+
+/// Fingerprint of a port that answered none of the hellos.
+const EMPTY: &str = "00000000000000000000000000000000000000000000000000000000000000";
+
+/// How a list of ciphers, ALPNs or versions is reordered.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Order {
+    Forward,
+    Reverse,
+    TopHalf,
+    BottomHalf,
+    MiddleOut,
+}
+
+/// Which `supported_versions` extension a hello carries.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Versions {
+    /// None.
+    Omitted,
+    /// TLS 1.0 to 1.2.
+    UpTo12,
+    /// TLS 1.0 to 1.3.
+    UpTo13,
+}
+
+/// One of the ten ClientHellos.
+struct Hello {
+    /// The record and ClientHello version, e.g. `[3, 3]` for TLS 1.2.
+    version: [u8; 2],
+    /// Whether the TLS 1.3 cipher suites are offered.
+    tls13_ciphers: bool,
+    cipher_order: Order,
+    grease: bool,
+    rare_alpn: bool,
+    versions: Versions,
+    /// Order of the ALPN protocols and supported versions.
+    extension_order: Order,
+}
+
+const fn hello(
+    version: [u8; 2],
+    tls13_ciphers: bool,
+    cipher_order: Order,
+    grease: bool,
+    rare_alpn: bool,
+    versions: Versions,
+    extension_order: Order,
+) -> Hello {
+    Hello {
+        version,
+        tls13_ciphers,
+        cipher_order,
+        grease,
+        rare_alpn,
+        versions,
+        extension_order,
+    }
+}
+
+/// The hellos, in the order their answers make up the fingerprint.
+const HELLOS: [Hello; 10] = {
+    use Order::*;
+    use Versions::*;
+    const TLS11: [u8; 2] = [3, 2];
+    const TLS12: [u8; 2] = [3, 3];
+    // TLS 1.3 hellos use a TLS 1.0 record version; see `client_hello`.
+    const TLS13: [u8; 2] = [3, 4];
+
+    [
+        hello(TLS12, true, Forward, false, false, UpTo12, Reverse),
+        hello(TLS12, true, Reverse, false, false, UpTo12, Forward),
+        hello(TLS12, true, TopHalf, false, false, Omitted, Forward),
+        hello(TLS12, true, BottomHalf, false, true, Omitted, Forward),
+        hello(TLS12, true, MiddleOut, true, true, Omitted, Reverse),
+        hello(TLS11, true, Forward, false, false, Omitted, Forward),
+        hello(TLS13, true, Forward, false, false, UpTo13, Reverse),
+        hello(TLS13, true, Reverse, false, false, UpTo13, Forward),
+        hello(TLS13, false, Forward, false, false, UpTo13, Forward),
+        hello(TLS13, true, MiddleOut, true, false, UpTo13, Reverse),
+    ]
+};
+
+/// A JARM fingerprint of a TLS service.
+#[derive(Debug)]
+pub struct JarmInfo {
+    /// The 62-character fingerprint.
+    pub fingerprint: String,
+}
+
+impl JarmInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("tls").text("jarm", &self.fingerprint)
+    }
+}
+
+/// Computes the JARM fingerprint of a TLS service.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `server_name` - The name sent with SNI; the IP address is sent if `None`.
+///
+/// # Returns
+///
+/// `None` if the port answered none of the hellos with a ServerHello.
+pub fn probe(target: SocketAddr, server_name: Option<&str>) -> Option<JarmInfo> {
+    let host = server_name.map_or_else(|| target.ip().to_string(), str::to_string);
+    let answers: Vec<String> = HELLOS
+        .iter()
+        .map(|hello| {
+            let response = send(target, &client_hello(hello, &host)).unwrap_or_default();
+            read_server_hello(&response)
+        })
+        .collect();
+
+    let fingerprint = fingerprint(&answers);
+    (fingerprint != EMPTY).then_some(JarmInfo { fingerprint })
+}
+
+/// Sends a hello and reads the start of the answer.
+fn send(target: SocketAddr, packet: &[u8]) -> Option<Vec<u8>> {
+    let mut stream = probe::connect(target).ok()?;
+    stream.write_all(packet).ok()?;
+
+    let mut response = vec![];
+    let _ = stream.take(MAX_RESPONSE).read_to_end(&mut response);
+    Some(response)
+}
+
+/// Builds the TLS record carrying a ClientHello.
+fn client_hello(hello: &Hello, host: &str) -> Vec<u8> {
+    let (record_version, hello_version) = match hello.version {
+        [3, 4] => ([3, 1], [3, 3]),
+        version => (version, version),
+    };
+
+    let mut body = hello_version.to_vec();
+    body.extend_from_slice(&random_bytes());
+    body.push(32);
+    body.extend_from_slice(&random_bytes());
+
+    let mut ciphers: Vec<u16> = CIPHERS
+        .iter()
+        .copied()
+        .filter(|cipher| hello.tls13_ciphers || cipher >> 8 != 0x13)
+        .collect();
+    ciphers = reorder(&ciphers, hello.cipher_order);
+    if hello.grease {
+        ciphers.insert(0, u16::from_be_bytes(grease()));
+    }
+    put_u16(&mut body, ciphers.len() * 2);
+    body.extend(ciphers.iter().flat_map(|cipher| cipher.to_be_bytes()));
+
+    // One compression method: null.
+    body.extend_from_slice(&[1, 0]);
+
+    let extensions = extensions(hello, host);
+    put_u16(&mut body, extensions.len());
+    body.extend(extensions);
+
+    let mut handshake = vec![1, 0];
+    put_u16(&mut handshake, body.len());
+    handshake.extend(body);
+
+    let mut record = vec![0x16, record_version[0], record_version[1]];
+    put_u16(&mut record, handshake.len());
+    record.extend(handshake);
+    record
+}
+
+/// Builds the extensions of a ClientHello.
+fn extensions(hello: &Hello, host: &str) -> Vec<u8> {
+    let mut out = vec![];
+
+    if hello.grease {
+        out.extend_from_slice(&grease());
+        out.extend_from_slice(&[0, 0]);
+    }
+
+    // server_name
+    out.extend_from_slice(&[0, 0]);
+    put_u16(&mut out, host.len() + 5);
+    put_u16(&mut out, host.len() + 3);
+    out.push(0);
+    put_u16(&mut out, host.len());
+    out.extend_from_slice(host.as_bytes());
+
+    // extended_master_secret, max_fragment_length, renegotiation_info,
+    // supported_groups, ec_point_formats and session_ticket
+    out.extend_from_slice(&[0x00, 0x17, 0x00, 0x00]);
+    out.extend_from_slice(&[0x00, 0x01, 0x00, 0x01, 0x01]);
+    out.extend_from_slice(&[0xff, 0x01, 0x00, 0x01, 0x00]);
+    out.extend_from_slice(&[
+        0x00, 0x0a, 0x00, 0x0a, 0x00, 0x08, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18, 0x00, 0x19,
+    ]);
+    out.extend_from_slice(&[0x00, 0x0b, 0x00, 0x02, 0x01, 0x00]);
+    out.extend_from_slice(&[0x00, 0x23, 0x00, 0x00]);
+
+    // application_layer_protocol_negotiation
+    let alpns = if hello.rare_alpn { RARE_ALPNS } else { ALPNS };
+    let alpns: Vec<u8> = reorder(alpns, hello.extension_order)
+        .iter()
+        .flat_map(|alpn| [&[alpn.len() as u8][..], alpn].concat())
+        .collect();
+    out.extend_from_slice(&[0x00, 0x10]);
+    put_u16(&mut out, alpns.len() + 2);
+    put_u16(&mut out, alpns.len());
+    out.extend(alpns);
+
+    // signature_algorithms
+    out.extend_from_slice(&[
+        0x00, 0x0d, 0x00, 0x14, 0x00, 0x12, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08,
+        0x05, 0x05, 0x01, 0x08, 0x06, 0x06, 0x01, 0x02, 0x01,
+    ]);
+
+    // key_share: an X25519 key, after a GREASE share if GREASE is on
+    let mut shares = vec![];
+    if hello.grease {
+        shares.extend_from_slice(&grease());
+        shares.extend_from_slice(&[0x00, 0x01, 0x00]);
+    }
+    shares.extend_from_slice(&[0x00, 0x1d, 0x00, 0x20]);
+    shares.extend_from_slice(&random_bytes());
+    out.extend_from_slice(&[0x00, 0x33]);
+    put_u16(&mut out, shares.len() + 2);
+    put_u16(&mut out, shares.len());
+    out.extend(shares);
+
+    // psk_key_exchange_modes
+    out.extend_from_slice(&[0x00, 0x2d, 0x00, 0x02, 0x01, 0x01]);
+
+    // supported_versions
+    let versions: &[[u8; 2]] = match hello.versions {
+        Versions::Omitted => return out,
+        Versions::UpTo12 => &[[3, 1], [3, 2], [3, 3]],
+        Versions::UpTo13 => &[[3, 1], [3, 2], [3, 3], [3, 4]],
+    };
+    let mut list = vec![];
+    if hello.grease {
+        list.extend_from_slice(&grease());
+    }
+    list.extend(reorder(versions, hello.extension_order).iter().flatten());
+    out.extend_from_slice(&[0x00, 0x2b]);
+    put_u16(&mut out, list.len() + 1);
+    out.push(list.len() as u8);
+    out.extend(list);
+
+    out
+}
+
+/// Reorders a list the way the reference implementation does.
+fn reorder<T: Copy>(items: &[T], order: Order) -> Vec<T> {
+    let len = items.len();
+    let middle = len / 2;
+
+    match order {
+        Order::Forward => items.to_vec(),
+        Order::Reverse => items.iter().rev().copied().collect(),
+        Order::BottomHalf => items[middle + len % 2..].to_vec(),
+        Order::TopHalf => {
+            let mut out = vec![];
+            if len % 2 == 1 {
+                out.push(items[middle]);
+            }
+            out.extend(reorder(&reorder(items, Order::Reverse), Order::BottomHalf));
+            out
+        }
+        Order::MiddleOut if len % 2 == 1 => {
+            let mut out = vec![items[middle]];
+            for i in 1..=middle {
+                out.push(items[middle + i]);
+                out.push(items[middle - i]);
+            }
+            out
+        }
+        Order::MiddleOut => {
+            let mut out = vec![];
+            for i in 1..=middle {
+                out.push(items[middle - 1 + i]);
+                out.push(items[middle - i]);
+            }
+            out
+        }
+    }
+}
+
+/// Reads the cipher, version and extensions a ServerHello picked, as
+/// `CIPHER|VERSION|ALPN|EXTENSIONS`; `|||` if the answer is not a ServerHello.
+fn read_server_hello(data: &[u8]) -> String {
+    let empty = "|||".to_string();
+
+    // A handshake record starting with a ServerHello.
+    if data.len() < 44 || data[0] != 0x16 || data[5] != 2 {
+        return empty;
+    }
+
+    let length = u16::from_be_bytes([data[3], data[4]]) as usize;
+    let session_id = data[43] as usize;
+    let (Some(cipher), Some(version)) =
+        (data.get(session_id + 44..session_id + 46), data.get(9..11))
+    else {
+        return empty;
+    };
+
+    match read_extensions(data, session_id, length) {
+        Some(extensions) => format!("{}|{}|{}", hex(cipher), hex(version), extensions),
+        None => empty,
+    }
+}
+
+/// Reads the ALPN and extension types of a ServerHello, as `ALPN|TYPE-TYPE-...`.
+///
+/// # Returns
+///
+/// `|` if the hello has no extensions or looks malformed, and `None` if the
+/// extensions are cut short.
+fn read_extensions(data: &[u8], session_id: usize, length: usize) -> Option<String> {
+    let at = |i: usize| data.get(i).copied();
+
+    match at(session_id + 47) {
+        None | Some(11) => return Some("|".to_string()),
+        _ => {}
+    }
+    if data.get(session_id + 50..session_id + 53) == Some(&[0x0e, 0xac, 0x0b])
+        || data.get(82..85) == Some(&[0x0f, 0xf0, 0x0b])
+        || session_id + 42 >= length
+    {
+        return Some("|".to_string());
+    }
+
+    let total = u16::from_be_bytes([at(session_id + 47)?, at(session_id + 48)?]) as usize;
+    let mut i = session_id + 49;
+    let end = total + i - 1;
+    let mut extensions = vec![];
+
+    while i < end {
+        let kind = data.get(i..i + 2)?;
+        let len = u16::from_be_bytes([at(i + 2)?, at(i + 3)?]) as usize;
+        let value = data.get(i + 4..(i + 4 + len).min(data.len()))?;
+        extensions.push((kind, value));
+        i += len + 4;
+    }
+
+    let alpn = match extensions.iter().find(|(kind, _)| *kind == [0x00, 0x10]) {
+        Some((_, value)) => std::str::from_utf8(value.get(3..).unwrap_or_default()).ok()?,
+        None => "",
+    };
+    let kinds: Vec<String> = extensions.iter().map(|(kind, _)| hex(kind)).collect();
+
+    Some(format!("{}|{}", alpn, kinds.join("-")))
+}
+
+/// Builds the fingerprint from the answers to the ten hellos: the position of each
+/// picked cipher and a letter for each version, followed by the first 32 hex digits of
+/// the SHA-256 of the ALPNs and extensions.
+fn fingerprint(answers: &[String]) -> String {
+    if answers.iter().all(|answer| answer == "|||") {
+        return EMPTY.to_string();
+    }
+
+    let mut fuzzy = String::new();
+    let mut rest = String::new();
+
+    for answer in answers {
+        let parts: Vec<&str> = answer.split('|').collect();
+        let [cipher, version, alpn, extensions] = parts[..] else {
+            continue;
+        };
+
+        fuzzy += &match cipher {
+            "" => "00".to_string(),
+            cipher => {
+                let position = CIPHERS.iter().position(|c| format!("{:04x}", c) == cipher);
+                format!("{:02x}", position.unwrap_or(CIPHERS.len()) + 1)
+            }
+        };
+        fuzzy += match version.get(3..4).and_then(|v| v.parse::<usize>().ok()) {
+            Some(minor) => &"abcdef"[minor..minor + 1],
+            None => "0",
+        };
+        rest += alpn;
+        rest += extensions;
+    }
+
+    let digest = hex(&hash::sha256(rest.as_bytes()));
+    fuzzy + &digest[..32]
+}
+
+/// Formats bytes as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn put_u16(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u16).to_be_bytes());
+}
+
+/// Returns 32 bytes to use as a random, session ID or key share; they only need to
+/// differ between hellos.
+fn random_bytes() -> [u8; 32] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed) as u128;
+    hash::sha256(&(nanos ^ count << 64).to_be_bytes())
+}
+
+/// Returns a GREASE value.
+fn grease() -> [u8; 2] {
+    GREASE[random_bytes()[0] as usize % GREASE.len()].to_be_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ServerHello picking ECDHE-RSA-AES128-GCM-SHA256 and h2, with a 32-byte session ID.
+    fn server_hello() -> Vec<u8> {
+        let extensions = [
+            &[0xff, 0x01, 0x00, 0x01, 0x00][..],
+            &[0x00, 0x10, 0x00, 0x05, 0x00, 0x03, 0x02, b'h', b'2'],
+            &[0x00, 0x0b, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02],
+        ]
+        .concat();
+
+        let mut body = vec![3, 3];
+        body.extend([0x11; 32]);
+        body.push(32);
+        body.extend([0x22; 32]);
+        body.extend([0xc0, 0x2f, 0]);
+        put_u16(&mut body, extensions.len());
+        body.extend(extensions);
+
+        let mut record = vec![0x16, 3, 3];
+        put_u16(&mut record, body.len() + 4);
+        record.extend([2, 0]);
+        put_u16(&mut record, body.len());
+        record.extend(body);
+        record
+    }
+
+    #[test]
+    fn reorders_like_the_reference() {
+        let odd = [1, 2, 3, 4, 5];
+        assert_eq!(reorder(&odd, Order::Forward), [1, 2, 3, 4, 5]);
+        assert_eq!(reorder(&odd, Order::Reverse), [5, 4, 3, 2, 1]);
+        assert_eq!(reorder(&odd, Order::TopHalf), [3, 2, 1]);
+        assert_eq!(reorder(&odd, Order::BottomHalf), [4, 5]);
+        assert_eq!(reorder(&odd, Order::MiddleOut), [3, 4, 2, 5, 1]);
+
+        let even = [1, 2, 3, 4];
+        assert_eq!(reorder(&even, Order::TopHalf), [2, 1]);
+        assert_eq!(reorder(&even, Order::BottomHalf), [3, 4]);
+        assert_eq!(reorder(&even, Order::MiddleOut), [3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn reads_server_hellos() {
+        let hello = server_hello();
+        assert_eq!(read_server_hello(&hello), "c02f|0303|h2|ff01-0010-000b");
+        // Cut short in the middle of the extensions.
+        assert_eq!(read_server_hello(&hello[..90]), "|||");
+        // An alert.
+        assert_eq!(read_server_hello(&[0x15, 3, 3, 0, 2, 2, 40]), "|||");
+        assert_eq!(read_server_hello(&[]), "|||");
+    }
+
+    #[test]
+    fn fingerprints_like_the_reference() {
+        // Computed with `jarm_hash` of the reference implementation.
+        let answers = [
+            "c02f|0303|h2|ff01-0010-000b",
+            "|||",
+            "009c|0303||ff01",
+            "|||",
+            "|||",
+            "c013|0302||ff01-000b",
+            "1301|0303||002b-0033",
+            "|||",
+            "|||",
+            "1302|0303||002b-0033",
+        ]
+        .map(str::to_string);
+        assert_eq!(
+            fingerprint(&answers),
+            "26d00039d00000024c20d0000001fd7a3a207943b33cfcc1327a83253eb817"
+        );
+        assert_eq!(fingerprint(&vec!["|||".to_string(); 10]), EMPTY);
+    }
+
+    #[test]
+    fn builds_client_hellos() {
+        for hello in &HELLOS {
+            let packet = client_hello(hello, "example.com");
+            let length = u16::from_be_bytes([packet[3], packet[4]]) as usize;
+            assert_eq!(packet.len(), length + 5);
+            assert_eq!(packet[5], 1);
+            assert!(packet.windows(11).any(|window| window == b"example.com"));
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use ip_sniffer::scan::PortState;
+
+pub mod amplification;
+pub mod dns;
+pub mod http;
+pub mod ics;
+pub mod ipmi;
+pub mod jarm;
+pub mod mongodb;
+pub mod mysql;
+pub mod postgres;
+pub mod rdp;
+pub mod redis;
+pub mod sip;
+pub mod smb;
+pub mod smtp;
+pub mod snmp;
+pub mod ssh;
+pub mod starttls;
+pub mod upnp;
+pub mod vnc;
+
+/// Timeout for connecting to, reading from and writing to a service during a probe.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Maximum number of ports probed at the same time.
+const PROBE_CONCURRENCY: usize = 32;
+
+/// Identifying strings sent by application-layer probes.
+#[derive(Clone, Debug)]
+pub struct Identity {
+    /// The `User-Agent` header of HTTP requests.
+    pub user_agent: String,
+    /// The name sent with SMTP `EHLO`.
+    pub ehlo: String,
+    /// The SSH identification string, e.g. `SSH-2.0-ip-sniffer`.
+    pub ssh_banner: String,
+}
+
+impl Default for Identity {
+    fn default() -> Identity {
+        Identity {
+            user_agent: concat!("ip-sniffer/", env!("CARGO_PKG_VERSION")).to_string(),
+            ehlo: "ip-sniffer".to_string(),
+            ssh_banner: "SSH-2.0-ip-sniffer".to_string(),
+        }
+    }
+}
+
+/// A value collected by a probe.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Text(String),
+    List(Vec<String>),
+    /// Named values, e.g. fingerprints keyed by key type.
+    Map(Vec<(String, String)>),
+}
+
+/// What a probe found on a port, in a form every output format can render.
+#[derive(Clone, Debug)]
+pub struct ServiceReport {
+    /// Short service name, e.g. `ssh`.
+    pub service: &'static str,
+    /// `tcp` or `udp`.
+    pub transport: &'static str,
+    /// Named details, in the order they should be shown. Empty values are omitted.
+    pub fields: Vec<(&'static str, Value)>,
+}
+
+impl ServiceReport {
+    pub fn new(service: &'static str) -> ServiceReport {
+        ServiceReport {
+            service,
+            transport: "tcp",
+            fields: vec![],
+        }
+    }
+
+    /// Marks the service as running over UDP.
+    pub fn udp(mut self) -> ServiceReport {
+        self.transport = "udp";
+        self
+    }
+
+    /// Adds a text field, skipping it if `value` is empty.
+    pub fn text(mut self, name: &'static str, value: impl Into<String>) -> ServiceReport {
+        let value = value.into();
+        if !value.is_empty() {
+            self.fields.push((name, Value::Text(value)));
+        }
+        self
+    }
+
+    /// Adds a text field if `value` is `Some`.
+    pub fn maybe(self, name: &'static str, value: Option<&String>) -> ServiceReport {
+        match value {
+            Some(value) => self.text(name, value.clone()),
+            None => self,
+        }
+    }
+
+    /// Adds a list field, skipping it if `values` is empty.
+    pub fn list(mut self, name: &'static str, values: &[String]) -> ServiceReport {
+        if !values.is_empty() {
+            self.fields.push((name, Value::List(values.to_vec())));
+        }
+        self
+    }
+
+    /// Adds a map field, skipping it if `values` is empty.
+    pub fn map(mut self, name: &'static str, values: &[(String, String)]) -> ServiceReport {
+        if !values.is_empty() {
+            self.fields.push((name, Value::Map(values.to_vec())));
+        }
+        self
+    }
+
+    /// Reports a UDP port whose probe got no usable answer, with the state it was left in.
+    pub fn unanswered(service: &'static str, state: PortState) -> ServiceReport {
+        ServiceReport::new(service)
+            .udp()
+            .text("state", state.as_str())
+    }
+}
+
+/// Returns the state of a UDP port from the error that ended the wait for its answer.
+///
+/// # Description
+///
+/// UDP has no handshake, so a port that sends nothing back may run a service that
+/// ignored the probe or be filtered. Only an ICMP port unreachable, which a connected
+/// socket reports as a refused connection, shows that it is closed.
+pub fn udp_state(err: &io::Error) -> PortState {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => PortState::OpenFiltered,
+        io::ErrorKind::ConnectionRefused => PortState::Closed,
+        _ => PortState::Unknown,
+    }
+}
+
+/// Connects to `target` with `PROBE_TIMEOUT` applied to the connect, reads and writes.
+pub fn connect(target: SocketAddr) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&target, PROBE_TIMEOUT)?;
+    stream.set_read_timeout(Some(PROBE_TIMEOUT))?;
+    stream.set_write_timeout(Some(PROBE_TIMEOUT))?;
+    Ok(stream)
+}
+
+/// Runs `probe` against each of `ports` on `addr`, up to `PROBE_CONCURRENCY` at a time.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+/// * `ports` - The ports to probe.
+/// * `probe` - Returns `Some` with the collected details if the port runs the
+///   service the probe understands.
+///
+/// # Returns
+///
+/// The details collected for each port where `probe` returned `Some`, in port order.
+pub fn run_on_ports<T, F>(addr: IpAddr, ports: &[u16], probe: F) -> Vec<(u16, T)>
+where
+    T: Send,
+    F: Fn(SocketAddr) -> Option<T> + Sync,
+{
+    let mut ports = ports.to_vec();
+    ports.sort();
+    let probe = &probe;
+
+    ports
+        .chunks(PROBE_CONCURRENCY)
+        .flat_map(|chunk| {
+            thread::scope(|s| {
+                let handles: Vec<_> = chunk
+                    .iter()
+                    .map(|&port| (port, s.spawn(move || probe(SocketAddr::new(addr, port)))))
+                    .collect();
+
+                handles
+                    .into_iter()
+                    .filter_map(|(port, h)| Some((port, h.join().ok()??)))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect()
+}
+
+/// Runs `probe` on each of `ports` that `applies_to` accepts and renders the results.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+/// * `ports` - The open ports of the host.
+/// * `applies_to` - Returns whether the probe should run on a port.
+/// * `probe` - The probe to run.
+/// * `report` - Renders the details collected by the probe.
+///
+/// # Returns
+///
+/// The report for each port the service was found on, in port order.
+pub fn collect<T: Send>(
+    addr: IpAddr,
+    ports: &[u16],
+    applies_to: impl Fn(u16) -> bool,
+    probe: impl Fn(SocketAddr) -> Option<T> + Sync,
+    report: fn(&T) -> ServiceReport,
+) -> Vec<(u16, ServiceReport)> {
+    let ports: Vec<u16> = ports.iter().copied().filter(|&p| applies_to(p)).collect();
+
+    run_on_ports(addr, &ports, probe)
+        .into_iter()
+        .map(|(port, info)| (port, report(&info)))
+        .collect()
+}
+
+/// Runs the probes enabled by `--service-detect` on the open ports of `addr`.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+/// * `ports` - The open ports of the host.
+/// * `identity` - Identifying strings sent by the HTTP and SMTP probes.
+/// * `vhost` - The host name the target was given as, sent as the HTTP `Host`.
+///
+/// # Returns
+///
+/// The report for each port a service was identified on, in port order.
+///
+/// # Description
+///
+/// Each probe only runs on the ports its service usually listens on. HTTP and SMTP
+/// probes record what the server announces about itself. Database probes
+/// record the server version and whether the server accepts commands or logins
+/// without credentials.
+pub fn detect_services(
+    addr: IpAddr,
+    ports: &[u16],
+    identity: &Identity,
+    vhost: Option<&str>,
+) -> Vec<(u16, ServiceReport)> {
+    let mut services = vec![];
+
+    services.extend(collect(
+        addr,
+        ports,
+        |port| http::PORTS.contains(&port),
+        |target| http::probe(target, identity, vhost),
+        http::HttpInfo::report,
+    ));
+    services.extend(collect(
+        addr,
+        ports,
+        |port| smtp::PORTS.contains(&port),
+        |target| smtp::probe(target, identity),
+        smtp::SmtpInfo::report,
+    ));
+    services.extend(collect(
+        addr,
+        ports,
+        |port| mysql::PORTS.contains(&port),
+        mysql::probe,
+        mysql::MysqlInfo::report,
+    ));
+    services.extend(collect(
+        addr,
+        ports,
+        |port| postgres::PORTS.contains(&port),
+        postgres::probe,
+        postgres::PostgresInfo::report,
+    ));
+    services.extend(collect(
+        addr,
+        ports,
+        |port| redis::PORTS.contains(&port),
+        redis::probe,
+        redis::RedisInfo::report,
+    ));
+    services.extend(collect(
+        addr,
+        ports,
+        |port| mongodb::PORTS.contains(&port),
+        mongodb::probe,
+        mongodb::MongoInfo::report,
+    ));
+
+    services.sort_by_key(|(port, _)| *port);
+    services
+}
@@ -0,0 +1,195 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, ServiceReport};
+
+/// Ports on which MongoDB is probed.
+pub const PORTS: &[u16] = &[27017];
+
+const OP_MSG: i32 = 2013;
+
+/// Error code returned when a command needs authentication.
+const UNAUTHORIZED: i32 = 13;
+
+/// Details collected from a MongoDB server.
+#[derive(Debug, Default)]
+pub struct MongoInfo {
+    /// The server version reported by `buildInfo`.
+    pub version: Option<String>,
+    /// Names of the databases, when they can be listed without authentication.
+    pub databases: Vec<String>,
+    /// Whether `listDatabases` succeeded without authentication.
+    pub unauthenticated: bool,
+    pub weak: Vec<String>,
+}
+
+impl MongoInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("mongodb")
+            .maybe("version", self.version.as_ref())
+            .list("databases", &self.databases)
+            .text("unauthenticated", self.unauthenticated.to_string())
+            .list("weak", &self.weak)
+    }
+}
+
+/// A top-level BSON value we care about.
+#[derive(Debug)]
+enum Bson {
+    Number(f64),
+    Text(String),
+    /// An embedded document, or the documents of an array.
+    Documents(Vec<Vec<(String, Bson)>>),
+    Other,
+}
+
+/// Probes a MongoDB server with `buildInfo` and `listDatabases`.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+///
+/// # Returns
+///
+/// `None` if the port does not speak the MongoDB wire protocol.
+pub fn probe(target: SocketAddr) -> Option<MongoInfo> {
+    let mut stream = probe::connect(target).ok()?;
+    let mut info = MongoInfo::default();
+
+    // buildInfo is allowed before authentication on every version.
+    let build_info = command(&mut stream, 1, "buildInfo").ok()?;
+    info.version = build_info.iter().find_map(|(name, value)| match value {
+        Bson::Text(version) if name == "version" => Some(version.clone()),
+        _ => None,
+    });
+
+    let databases = command(&mut stream, 2, "listDatabases").ok()?;
+    let ok = databases
+        .iter()
+        .any(|(name, value)| name == "ok" && matches!(value, Bson::Number(ok) if *ok == 1.0));
+    let code = databases.iter().find_map(|(name, value)| match value {
+        Bson::Number(code) if name == "code" => Some(*code as i32),
+        _ => None,
+    });
+
+    if ok {
+        info.unauthenticated = true;
+        info.weak.push("no authentication".to_string());
+
+        for (name, value) in &databases {
+            if let (true, Bson::Documents(entries)) = (name == "databases", value) {
+                info.databases = entries
+                    .iter()
+                    .flat_map(|entry| entry.iter())
+                    .filter_map(|(name, value)| match value {
+                        Bson::Text(db) if name == "name" => Some(db.clone()),
+                        _ => None,
+                    })
+                    .collect();
+            }
+        }
+    } else if code != Some(UNAUTHORIZED) && info.version.is_none() {
+        return None;
+    }
+
+    Some(info)
+}
+
+/// Runs `{<name>: 1, $db: "admin"}` with OP_MSG and returns the reply document.
+fn command(stream: &mut TcpStream, request_id: i32, name: &str) -> io::Result<Vec<(String, Bson)>> {
+    let mut document = vec![];
+    document.push(0x10); // int32
+    document.extend_from_slice(name.as_bytes());
+    document.push(0);
+    document.extend_from_slice(&1i32.to_le_bytes());
+    document.push(0x02); // string
+    document.extend_from_slice(b"$db\0");
+    document.extend_from_slice(&6i32.to_le_bytes());
+    document.extend_from_slice(b"admin\0");
+    document.push(0);
+    let mut document_length = ((document.len() + 4) as i32).to_le_bytes().to_vec();
+    document_length.append(&mut document);
+
+    let mut body = 0u32.to_le_bytes().to_vec(); // flags
+    body.push(0); // section kind 0: body
+    body.extend_from_slice(&document_length);
+
+    let mut message = ((body.len() + 16) as i32).to_le_bytes().to_vec();
+    message.extend_from_slice(&request_id.to_le_bytes());
+    message.extend_from_slice(&0i32.to_le_bytes()); // responseTo
+    message.extend_from_slice(&OP_MSG.to_le_bytes());
+    message.extend_from_slice(&body);
+    stream.write_all(&message)?;
+
+    let mut header = [0; 16];
+    stream.read_exact(&mut header)?;
+    let length = i32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let opcode = i32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+    if opcode != OP_MSG || !(21..=16 * 1024 * 1024).contains(&length) {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    let mut reply = vec![0; length as usize - 16];
+    stream.read_exact(&mut reply)?;
+
+    // Skip the flags and section kind.
+    parse_document(reply.get(5..).unwrap_or_default()).ok_or(io::ErrorKind::InvalidData.into())
+}
+
+/// Parses the top-level elements of a BSON document.
+fn parse_document(buf: &[u8]) -> Option<Vec<(String, Bson)>> {
+    let length = i32::from_le_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+    let mut rest = buf.get(4..length.checked_sub(1)?)?;
+    let mut elements = vec![];
+
+    while let Some((&kind, tail)) = rest.split_first() {
+        let end = tail.iter().position(|&b| b == 0)?;
+        let name = String::from_utf8_lossy(&tail[..end]).into_owned();
+        let data = &tail[end + 1..];
+
+        let int32 = || Some(i32::from_le_bytes(data.get(..4)?.try_into().ok()?));
+        let (value, size) = match kind {
+            0x01 => (
+                Bson::Number(f64::from_le_bytes(data.get(..8)?.try_into().ok()?)),
+                8,
+            ),
+            0x02 => {
+                let size = int32()? as usize;
+                let text = data.get(4..4 + size.checked_sub(1)?)?;
+                (
+                    Bson::Text(String::from_utf8_lossy(text).into_owned()),
+                    4 + size,
+                )
+            }
+            0x03 => {
+                let size = int32()? as usize;
+                let document = parse_document(data.get(..size)?)?;
+                (Bson::Documents(vec![document]), size)
+            }
+            // Arrays are documents keyed by index; only arrays of documents are kept.
+            0x04 => {
+                let size = int32()? as usize;
+                let documents = parse_document(data.get(..size)?)?
+                    .into_iter()
+                    .filter_map(|(_, value)| match value {
+                        Bson::Documents(mut documents) => documents.pop(),
+                        _ => None,
+                    })
+                    .collect();
+                (Bson::Documents(documents), size)
+            }
+            0x05 => (Bson::Other, 5 + int32()? as usize),
+            0x07 => (Bson::Other, 12),
+            0x08 => (Bson::Other, 1),
+            0x09 | 0x11 | 0x12 => (Bson::Other, 8),
+            0x0a => (Bson::Other, 0),
+            0x10 => (Bson::Number(int32()? as f64), 4),
+            _ => return None,
+        };
+
+        elements.push((name, value));
+        rest = data.get(size..)?;
+    }
+
+    Some(elements)
+}
@@ -0,0 +1,138 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, ServiceReport};
+
+/// Ports on which MySQL is probed.
+pub const PORTS: &[u16] = &[3306];
+
+const CLIENT_LONG_PASSWORD: u32 = 0x0000_0001;
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+
+const PACKET_OK: u8 = 0x00;
+const PACKET_AUTH_SWITCH: u8 = 0xfe;
+const PACKET_ERR: u8 = 0xff;
+
+/// Details collected from a MySQL or MariaDB server.
+#[derive(Debug, Default)]
+pub struct MysqlInfo {
+    /// The server version from the greeting, e.g. `8.0.36`.
+    pub version: Option<String>,
+    /// The default authentication plugin, e.g. `caching_sha2_password`.
+    pub auth_plugin: Option<String>,
+    /// Why the server refused the connection, if it did before the greeting.
+    pub error: Option<String>,
+    /// Whether `root` could log in with an empty password.
+    pub unauthenticated: bool,
+    pub weak: Vec<String>,
+}
+
+impl MysqlInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("mysql")
+            .maybe("version", self.version.as_ref())
+            .maybe("auth_plugin", self.auth_plugin.as_ref())
+            .maybe("error", self.error.as_ref())
+            .text("unauthenticated", self.unauthenticated.to_string())
+            .list("weak", &self.weak)
+    }
+}
+
+/// Probes a MySQL server for its version and whether `root` has an empty password.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+///
+/// # Returns
+///
+/// `None` if the port does not speak the MySQL protocol.
+pub fn probe(target: SocketAddr) -> Option<MysqlInfo> {
+    let mut stream = probe::connect(target).ok()?;
+    let (_, greeting) = read_packet(&mut stream).ok()?;
+    let mut info = MysqlInfo::default();
+
+    // Servers refusing the client host send an error packet instead of the greeting.
+    if greeting.first() == Some(&PACKET_ERR) {
+        info.error = Some(String::from_utf8_lossy(greeting.get(3..)?).into_owned());
+        return Some(info);
+    }
+    if greeting.first() != Some(&10) {
+        return None;
+    }
+
+    let (version, rest) = cstring(&greeting[1..])?;
+    info.version = Some(version);
+    // connection id, auth data part 1, filler, capabilities, charset, status,
+    // capabilities, auth data length, reserved, auth data part 2
+    let auth_length = *rest.get(20)? as usize;
+    let plugin = rest.get(31 + auth_length.saturating_sub(8).max(13)..);
+    info.auth_plugin = plugin.and_then(cstring).map(|(plugin, _)| plugin);
+
+    info.unauthenticated = login_without_password(&mut stream).unwrap_or(false);
+    if info.unauthenticated {
+        info.weak.push("root has an empty password".to_string());
+    }
+
+    Some(info)
+}
+
+/// Logs in as `root` with an empty password.
+///
+/// # Returns
+///
+/// Whether the server accepted the login.
+fn login_without_password(stream: &mut TcpStream) -> io::Result<bool> {
+    let capabilities =
+        CLIENT_LONG_PASSWORD | CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH;
+
+    let mut response = capabilities.to_le_bytes().to_vec();
+    response.extend_from_slice(&(1u32 << 24).to_le_bytes()); // max packet size
+    response.push(0x21); // utf8_general_ci
+    response.extend_from_slice(&[0; 23]);
+    response.extend_from_slice(b"root\0");
+    response.push(0); // empty auth response
+    response.extend_from_slice(b"mysql_native_password\0");
+    write_packet(stream, 1, &response)?;
+
+    let (sequence, reply) = read_packet(stream)?;
+
+    // An empty password is the same for every plugin, so answer a switch with nothing.
+    let reply = if reply.first() == Some(&PACKET_AUTH_SWITCH) {
+        write_packet(stream, sequence.wrapping_add(1), &[])?;
+        read_packet(stream)?.1
+    } else {
+        reply
+    };
+
+    Ok(reply.first() == Some(&PACKET_OK))
+}
+
+fn write_packet(stream: &mut TcpStream, sequence: u8, payload: &[u8]) -> io::Result<()> {
+    let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+    packet.push(sequence);
+    packet.extend_from_slice(payload);
+    stream.write_all(&packet)
+}
+
+/// Reads one packet and returns its sequence number and payload.
+fn read_packet(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 4];
+    stream.read_exact(&mut header)?;
+
+    let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    let mut payload = vec![0; length];
+    stream.read_exact(&mut payload)?;
+    Ok((header[3], payload))
+}
+
+/// Splits a NUL-terminated string off the front of `buf`.
+fn cstring(buf: &[u8]) -> Option<(String, &[u8])> {
+    let end = buf.iter().position(|&b| b == 0)?;
+    Some((
+        String::from_utf8_lossy(&buf[..end]).into_owned(),
+        &buf[end + 1..],
+    ))
+}
@@ -0,0 +1,161 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, ServiceReport};
+
+/// Ports on which PostgreSQL is probed.
+pub const PORTS: &[u16] = &[5432];
+
+const PROTOCOL_VERSION: u32 = 196608; // 3.0
+const SSL_REQUEST_CODE: u32 = 80877103;
+
+/// Details collected from a PostgreSQL server.
+#[derive(Debug, Default)]
+pub struct PostgresInfo {
+    /// The server version, only known when a login succeeds.
+    pub version: Option<String>,
+    /// Whether the server accepts TLS.
+    pub ssl: bool,
+    /// The authentication method requested for the `postgres` user, e.g. `SCRAM-SHA-256`.
+    pub auth_method: Option<String>,
+    /// Why the server refused the login, if it did before asking for a password.
+    pub error: Option<String>,
+    /// Whether `postgres` could log in without a password.
+    pub unauthenticated: bool,
+    pub weak: Vec<String>,
+}
+
+impl PostgresInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("postgresql")
+            .maybe("version", self.version.as_ref())
+            .text("ssl", self.ssl.to_string())
+            .maybe("auth_method", self.auth_method.as_ref())
+            .maybe("error", self.error.as_ref())
+            .text("unauthenticated", self.unauthenticated.to_string())
+            .list("weak", &self.weak)
+    }
+}
+
+/// Probes a PostgreSQL server for TLS support and how it authenticates `postgres`.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+///
+/// # Returns
+///
+/// `None` if the port does not speak the PostgreSQL protocol.
+pub fn probe(target: SocketAddr) -> Option<PostgresInfo> {
+    let mut info = PostgresInfo {
+        ssl: ssl_supported(target).ok()?,
+        ..PostgresInfo::default()
+    };
+
+    let mut stream = probe::connect(target).ok()?;
+    let mut startup = PROTOCOL_VERSION.to_be_bytes().to_vec();
+    startup.extend_from_slice(b"user\0postgres\0database\0postgres\0application_name\0");
+    startup.extend_from_slice(b"ip-sniffer\0\0");
+    let mut message = ((startup.len() + 4) as u32).to_be_bytes().to_vec();
+    message.extend_from_slice(&startup);
+    stream.write_all(&message).ok()?;
+
+    loop {
+        let (kind, body) = read_message(&mut stream).ok()?;
+
+        match kind {
+            b'R' => {
+                let code = u32::from_be_bytes(body.get(..4)?.try_into().ok()?);
+                if code != 0 {
+                    info.auth_method = Some(auth_method(code, &body[4..]));
+                    break;
+                }
+                info.auth_method = Some("trust".to_string());
+                info.unauthenticated = true;
+            }
+            b'S' => {
+                let mut parts = body.split(|&b| b == 0);
+                if parts.next() == Some(b"server_version") {
+                    let version = parts.next().unwrap_or_default();
+                    info.version = Some(String::from_utf8_lossy(version).into_owned());
+                }
+            }
+            b'E' => {
+                info.error = error_message(&body);
+                break;
+            }
+            b'Z' => break,
+            _ => {}
+        }
+    }
+
+    if info.unauthenticated {
+        info.weak
+            .push("trust authentication for postgres".to_string());
+    }
+    if info.auth_method.as_deref() == Some("cleartext password") {
+        info.weak
+            .push("cleartext password authentication".to_string());
+    }
+
+    Some(info)
+}
+
+/// Sends an SSLRequest and returns whether the server agreed to TLS.
+fn ssl_supported(target: SocketAddr) -> io::Result<bool> {
+    let mut stream = probe::connect(target)?;
+    let mut request = 8u32.to_be_bytes().to_vec();
+    request.extend_from_slice(&SSL_REQUEST_CODE.to_be_bytes());
+    stream.write_all(&request)?;
+
+    let mut answer = [0; 1];
+    stream.read_exact(&mut answer)?;
+
+    match answer[0] {
+        b'S' => Ok(true),
+        b'N' => Ok(false),
+        _ => Err(io::ErrorKind::InvalidData.into()),
+    }
+}
+
+/// Names the authentication method of an AuthenticationRequest message.
+fn auth_method(code: u32, body: &[u8]) -> String {
+    match code {
+        2 => "Kerberos V5".to_string(),
+        3 => "cleartext password".to_string(),
+        5 => "MD5".to_string(),
+        7 => "GSSAPI".to_string(),
+        9 => "SSPI".to_string(),
+        10 => {
+            let mechanisms: Vec<String> = body
+                .split(|&b| b == 0)
+                .filter(|m| !m.is_empty())
+                .map(|m| String::from_utf8_lossy(m).into_owned())
+                .collect();
+            mechanisms.join(", ")
+        }
+        _ => format!("unknown ({})", code),
+    }
+}
+
+/// Returns the `M` (message) field of an ErrorResponse.
+fn error_message(body: &[u8]) -> Option<String> {
+    body.split(|&b| b == 0)
+        .find_map(|field| field.strip_prefix(b"M"))
+        .map(|message| String::from_utf8_lossy(message).into_owned())
+}
+
+/// Reads one message and returns its type byte and body.
+fn read_message(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 5];
+    stream.read_exact(&mut header)?;
+
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if !(4..=65536).contains(&length) {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    let mut body = vec![0; length - 4];
+    stream.read_exact(&mut body)?;
+    Ok((header[0], body))
+}
@@ -0,0 +1,146 @@
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+
+use crate::probe::{self, ServiceReport};
+
+/// Ports on which RDP is probed.
+pub const PORTS: &[u16] = &[3389];
+
+const PROTOCOL_RDP: u32 = 0x00;
+const PROTOCOL_SSL: u32 = 0x01;
+const PROTOCOL_HYBRID: u32 = 0x02;
+const PROTOCOL_HYBRID_EX: u32 = 0x08;
+
+const TYPE_RDP_NEG_RSP: u8 = 0x02;
+const TYPE_RDP_NEG_FAILURE: u8 = 0x03;
+
+const HYBRID_REQUIRED_BY_SERVER: u32 = 0x05;
+
+/// The outcome of one X.224 connection request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Negotiation {
+    /// The server picked this protocol.
+    Selected(u32),
+    /// The server refused with this failure code.
+    Failed(u32),
+    /// The server answered without a negotiation response (very old servers).
+    Legacy,
+}
+
+/// Details collected from an RDP server.
+#[derive(Debug, Default)]
+pub struct RdpInfo {
+    /// Security protocols the server accepts, e.g. `["TLS", "CredSSP (NLA)"]`.
+    pub protocols: Vec<String>,
+    /// Whether Network Level Authentication is required before a session is created.
+    pub nla_required: bool,
+    pub weak: Vec<String>,
+}
+
+impl RdpInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("rdp")
+            .list("protocols", &self.protocols)
+            .text("nla_required", self.nla_required.to_string())
+            .list("weak", &self.weak)
+    }
+}
+
+/// Probes an RDP server for the security protocols it accepts.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+///
+/// # Returns
+///
+/// `None` if the port does not speak RDP.
+///
+/// # Description
+///
+/// Three X.224 connection requests are sent, offering standard RDP security only, TLS
+/// only, and TLS with CredSSP. The server's choice or refusal for each tells whether
+/// legacy RDP security is still allowed and whether NLA is enforced.
+pub fn probe(target: SocketAddr) -> Option<RdpInfo> {
+    let rdp = negotiate(target, PROTOCOL_RDP).ok()?;
+    let tls = negotiate(target, PROTOCOL_SSL).ok();
+    let hybrid = negotiate(target, PROTOCOL_SSL | PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX).ok();
+
+    let mut info = RdpInfo::default();
+
+    if matches!(
+        rdp,
+        Negotiation::Selected(PROTOCOL_RDP) | Negotiation::Legacy
+    ) {
+        info.protocols.push("Standard RDP".to_string());
+    }
+    if tls == Some(Negotiation::Selected(PROTOCOL_SSL)) {
+        info.protocols.push("TLS".to_string());
+    }
+    if let Some(Negotiation::Selected(selected)) = hybrid {
+        if selected & (PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX) != 0 {
+            info.protocols.push("CredSSP (NLA)".to_string());
+        }
+    }
+
+    info.nla_required = [Some(rdp), tls]
+        .iter()
+        .all(|n| *n == Some(Negotiation::Failed(HYBRID_REQUIRED_BY_SERVER)));
+
+    if info.protocols.iter().any(|p| p == "Standard RDP") {
+        info.weak.push("standard RDP security allowed".to_string());
+    }
+    if !info.nla_required {
+        info.weak.push("NLA not required".to_string());
+    }
+
+    Some(info)
+}
+
+/// Sends an X.224 Connection Request offering `protocols` and reads the server's answer.
+fn negotiate(target: SocketAddr, protocols: u32) -> io::Result<Negotiation> {
+    let mut stream = probe::connect(target)?;
+
+    let cookie = b"Cookie: mstshash=ipsniffer\r\n";
+    let mut x224 = vec![0, 0xe0, 0, 0, 0, 0, 0]; // length indicator, CR, dst/src refs, class
+    x224.extend_from_slice(cookie);
+    x224.extend_from_slice(&[0x01, 0x00, 0x08, 0x00]); // RDP_NEG_REQ, flags, length
+    x224.extend_from_slice(&protocols.to_le_bytes());
+    x224[0] = (x224.len() - 1) as u8;
+
+    let mut tpkt = vec![0x03, 0x00];
+    tpkt.extend_from_slice(&((x224.len() + 4) as u16).to_be_bytes());
+    tpkt.extend_from_slice(&x224);
+    stream.write_all(&tpkt)?;
+
+    let mut header = [0; 4];
+    stream.read_exact(&mut header)?;
+    if header[0] != 0x03 {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    let mut body = vec![0; length.saturating_sub(4)];
+    stream.read_exact(&mut body)?;
+
+    // X.224 Connection Confirm
+    if body.get(1).map(|code| code & 0xf0) != Some(0xd0) {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    let Some(negotiation) = body.get(7..15) else {
+        return Ok(Negotiation::Legacy);
+    };
+    let value = u32::from_le_bytes([
+        negotiation[4],
+        negotiation[5],
+        negotiation[6],
+        negotiation[7],
+    ]);
+
+    match negotiation[0] {
+        TYPE_RDP_NEG_RSP => Ok(Negotiation::Selected(value)),
+        TYPE_RDP_NEG_FAILURE => Ok(Negotiation::Failed(value)),
+        _ => Err(io::ErrorKind::InvalidData.into()),
+    }
+}
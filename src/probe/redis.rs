@@ -0,0 +1,82 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, ServiceReport};
+
+/// Ports on which Redis is probed.
+pub const PORTS: &[u16] = &[6379];
+
+/// Details collected from a Redis server.
+#[derive(Debug, Default)]
+pub struct RedisInfo {
+    /// The server version, only known when commands are accepted without a password.
+    pub version: Option<String>,
+    /// The error returned to PING, e.g. `NOAUTH Authentication required.`
+    pub error: Option<String>,
+    /// Whether the server answered PING without authentication.
+    pub unauthenticated: bool,
+    pub weak: Vec<String>,
+}
+
+impl RedisInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("redis")
+            .maybe("version", self.version.as_ref())
+            .maybe("error", self.error.as_ref())
+            .text("unauthenticated", self.unauthenticated.to_string())
+            .list("weak", &self.weak)
+    }
+}
+
+/// Probes a Redis server by sending PING and, if that is allowed, `INFO server`.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+///
+/// # Returns
+///
+/// `None` if the port does not speak RESP.
+pub fn probe(target: SocketAddr) -> Option<RedisInfo> {
+    let mut reader = BufReader::new(probe::connect(target).ok()?);
+    let mut info = RedisInfo::default();
+
+    let pong = command(&mut reader, &["PING"])?;
+    if let Some(error) = pong.strip_prefix('-') {
+        info.error = Some(error.to_string());
+        return Some(info);
+    }
+    if pong != "+PONG" {
+        return None;
+    }
+
+    info.unauthenticated = true;
+    info.weak.push("no authentication".to_string());
+
+    if let Some(length) = command(&mut reader, &["INFO", "server"])
+        .and_then(|reply| reply.strip_prefix('$')?.parse::<usize>().ok())
+    {
+        let mut body = vec![0; length + 2];
+        if reader.read_exact(&mut body).is_ok() {
+            info.version = String::from_utf8_lossy(&body)
+                .lines()
+                .find_map(|line| line.strip_prefix("redis_version:"))
+                .map(str::to_string);
+        }
+    }
+
+    Some(info)
+}
+
+/// Sends `args` as a RESP array and returns the first line of the reply.
+fn command(reader: &mut BufReader<TcpStream>, args: &[&str]) -> Option<String> {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    reader.get_mut().write_all(request.as_bytes()).ok()?;
+
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    Some(line.trim_end().to_string())
+}
@@ -0,0 +1,181 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ip_sniffer::scan::PortState;
+
+use crate::probe::{self, Identity, ServiceReport};
+
+pub const PORT: u16 = 5060;
+
+/// How long to wait for an answer over UDP.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Maximum number of response bytes read over TCP.
+const MAX_RESPONSE: u64 = 16 * 1024;
+
+/// Details collected from a SIP server.
+#[derive(Debug, Default)]
+pub struct SipInfo {
+    /// The status line, e.g. `SIP/2.0 200 OK`.
+    pub status: String,
+    /// The `Server` or `User-Agent` header, e.g. `Asterisk PBX 18.2.0`.
+    pub agent: Option<String>,
+    /// Methods from the `Allow` headers, e.g. `["INVITE", "OPTIONS", "BYE"]`.
+    pub methods: Vec<String>,
+    /// Extensions from the `Supported` headers, e.g. `["replaces", "timer"]`.
+    pub extensions: Vec<String>,
+}
+
+impl SipInfo {
+    pub fn report(&self, transport_udp: bool) -> ServiceReport {
+        let report = ServiceReport::new("sip")
+            .text("status", &self.status)
+            .maybe("agent", self.agent.as_ref())
+            .list("methods", &self.methods)
+            .list("extensions", &self.extensions);
+
+        if transport_udp {
+            report.udp()
+        } else {
+            report
+        }
+    }
+}
+
+/// Sends SIP `OPTIONS` to port 5060 of `addr`, over UDP and, if it was found open, TCP.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+/// * `tcp` - Whether tcp/5060 was found open.
+/// * `identity` - Supplies the `User-Agent` header.
+///
+/// # Returns
+///
+/// The details collected over UDP, or the state of udp/5060 if it did not answer with
+/// SIP, and those collected over TCP if it did.
+///
+/// # Description
+///
+/// `OPTIONS` asks a server what it supports without placing a call or registering.
+/// SIP over TLS on 5061 is not probed, as there is no TLS client to send it with.
+pub fn probe(
+    addr: IpAddr,
+    tcp: bool,
+    identity: &Identity,
+) -> (Result<SipInfo, PortState>, Option<SipInfo>) {
+    let target = SocketAddr::new(addr, PORT);
+    let udp = options_udp(target, identity);
+    let tcp = tcp.then(|| options_tcp(target, identity)).flatten();
+    (udp, tcp)
+}
+
+fn options_udp(target: SocketAddr, identity: &Identity) -> Result<SipInfo, PortState> {
+    let local = if target.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).map_err(|_| PortState::Unknown)?;
+    socket.connect(target).map_err(|_| PortState::Unknown)?;
+    socket
+        .set_read_timeout(Some(QUERY_TIMEOUT))
+        .map_err(|_| PortState::Unknown)?;
+    let local = socket.local_addr().map_err(|_| PortState::Unknown)?;
+    let request = options(target, local, "UDP", identity);
+    socket
+        .send(request.as_bytes())
+        .map_err(|err| probe::udp_state(&err))?;
+
+    let mut buf = vec![0; 8192];
+    let n = socket
+        .recv(&mut buf)
+        .map_err(|err| probe::udp_state(&err))?;
+    parse(&String::from_utf8_lossy(&buf[..n])).ok_or(PortState::Open)
+}
+
+fn options_tcp(target: SocketAddr, identity: &Identity) -> Option<SipInfo> {
+    let mut stream = probe::connect(target).ok()?;
+    let request = options(target, stream.local_addr().ok()?, "TCP", identity);
+    stream.write_all(request.as_bytes()).ok()?;
+
+    // Read up to the end of the headers; an OPTIONS answer rarely has a body.
+    let mut response = vec![];
+    let mut byte = [0; 1];
+    let mut reader = stream.take(MAX_RESPONSE);
+    while !response.ends_with(b"\r\n\r\n") && reader.read(&mut byte).ok()? == 1 {
+        response.push(byte[0]);
+    }
+    parse(&String::from_utf8_lossy(&response))
+}
+
+/// Builds an `OPTIONS` request to `target` sent from `local` over `transport`.
+fn options(target: SocketAddr, local: SocketAddr, transport: &str, identity: &Identity) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    let id = format!("{:08x}{:08x}", process::id(), nanos);
+    let uri = match target {
+        SocketAddr::V4(_) => format!("sip:{}", target.ip()),
+        SocketAddr::V6(_) => format!("sip:[{}]", target.ip()),
+    };
+
+    format!(
+        "OPTIONS {uri} SIP/2.0\r\n\
+         Via: SIP/2.0/{transport} {local};branch=z9hG4bK{id};rport\r\n\
+         Max-Forwards: 70\r\n\
+         From: <sip:ip-sniffer@{local}>;tag={id}\r\n\
+         To: <{uri}>\r\n\
+         Call-ID: {id}@ip-sniffer\r\n\
+         CSeq: 1 OPTIONS\r\n\
+         Contact: <sip:ip-sniffer@{local}>\r\n\
+         User-Agent: {agent}\r\n\
+         Accept: application/sdp\r\n\
+         Content-Length: 0\r\n\r\n",
+        agent = identity.user_agent
+    )
+}
+
+/// Parses the status line and headers of a SIP response.
+///
+/// # Returns
+///
+/// `None` if it is not a SIP response.
+fn parse(response: &str) -> Option<SipInfo> {
+    let mut lines = response.lines();
+    let status = lines.next()?.trim();
+    if !status.starts_with("SIP/2.0 ") {
+        return None;
+    }
+
+    let mut info = SipInfo {
+        status: status.to_string(),
+        ..SipInfo::default()
+    };
+    let list = |value: &str| -> Vec<String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    for line in lines.take_while(|line| !line.trim().is_empty()) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        // Compact forms: `k` for Supported.
+        match name.trim().to_ascii_lowercase().as_str() {
+            "server" => info.agent = Some(value.to_string()),
+            "user-agent" if info.agent.is_none() => info.agent = Some(value.to_string()),
+            "allow" => info.methods.extend(list(value)),
+            "supported" | "k" => info.extensions.extend(list(value)),
+            _ => {}
+        }
+    }
+    Some(info)
+}
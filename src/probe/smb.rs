@@ -0,0 +1,384 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, ServiceReport};
+
+/// Ports on which SMB is probed: direct-hosted SMB and SMB over NetBIOS.
+pub const PORTS: &[u16] = &[139, 445];
+
+const NETBIOS_SESSION_PORT: u16 = 139;
+
+const SMB2_NEGOTIATE: u16 = 0;
+const SMB2_SESSION_SETUP: u16 = 1;
+
+const STATUS_SUCCESS: u32 = 0;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+
+/// SMB2/3 dialects, tried one at a time to find every dialect the server accepts.
+const SMB2_DIALECTS: &[(u16, &str)] = &[
+    (0x0202, "2.0.2"),
+    (0x0210, "2.1"),
+    (0x0300, "3.0"),
+    (0x0302, "3.0.2"),
+    (0x0311, "3.1.1"),
+];
+
+/// Flags of our NTLMSSP NEGOTIATE message: unicode, request target, NTLM, always sign,
+/// extended session security, target info, version, 128-bit, key exchange and 56-bit.
+const NTLMSSP_NEGOTIATE_FLAGS: u32 = 0xe288_8205;
+
+/// Details collected from an SMB server.
+#[derive(Debug, Default)]
+pub struct SmbInfo {
+    /// Every dialect the server accepted, e.g. `["SMB1", "2.1", "3.1.1"]`.
+    pub dialects: Vec<String>,
+    /// `required`, `enabled` or `disabled`.
+    pub signing: String,
+    /// NetBIOS computer name, from the NTLM challenge.
+    pub hostname: Option<String>,
+    /// NetBIOS domain name, from the NTLM challenge.
+    pub domain: Option<String>,
+    /// DNS computer name, from the NTLM challenge.
+    pub dns_hostname: Option<String>,
+    /// DNS domain name, from the NTLM challenge.
+    pub dns_domain: Option<String>,
+    /// Windows version reported in the NTLM challenge, e.g. `10.0.20348`.
+    pub os_version: Option<String>,
+    /// Weak settings: SMB1 being enabled, or signing not being required.
+    pub weak: Vec<String>,
+}
+
+impl SmbInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("smb")
+            .list("dialects", &self.dialects)
+            .text("signing", &self.signing)
+            .maybe("hostname", self.hostname.as_ref())
+            .maybe("domain", self.domain.as_ref())
+            .maybe("dns_hostname", self.dns_hostname.as_ref())
+            .maybe("dns_domain", self.dns_domain.as_ref())
+            .maybe("os_version", self.os_version.as_ref())
+            .list("weak", &self.weak)
+    }
+}
+
+/// Probes an SMB server for its dialects, signing requirements and identity.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe; port 139 uses a NetBIOS session.
+///
+/// # Returns
+///
+/// `None` if the port does not speak SMB.
+///
+/// # Description
+///
+/// Each SMB2/3 dialect is negotiated on its own connection, and SMB1 is checked with an
+/// `NT LM 0.12` negotiation. The hostname and domain are read from the NTLM challenge
+/// the server sends in reply to an anonymous session setup, as `nmap`'s
+/// `smb-os-discovery` does; no credentials are sent.
+pub fn probe(target: SocketAddr) -> Option<SmbInfo> {
+    let mut info = SmbInfo::default();
+    let mut security_mode = None;
+
+    if negotiate_smb1(target).unwrap_or(false) {
+        info.dialects.push("SMB1".to_string());
+        info.weak.push("SMB1 enabled".to_string());
+    }
+
+    for &(dialect, name) in SMB2_DIALECTS {
+        if let Ok((_, mode)) = negotiate_smb2(target, &[dialect]) {
+            info.dialects.push(name.to_string());
+            security_mode = Some(mode);
+        }
+    }
+
+    if info.dialects.is_empty() {
+        return None;
+    }
+
+    info.signing = match security_mode {
+        Some(mode) if mode & 0x02 != 0 => "required",
+        Some(mode) if mode & 0x01 != 0 => "enabled",
+        Some(_) => "disabled",
+        // SMB1-only servers: signing is not checked
+        None => "unknown",
+    }
+    .to_string();
+    if info.signing != "required" {
+        info.weak.push("signing not required".to_string());
+    }
+
+    if let Some(challenge) = ntlm_challenge(target) {
+        read_challenge(&challenge, &mut info);
+    }
+
+    Some(info)
+}
+
+/// Connects to the server, starting a NetBIOS session first on port 139.
+fn open(target: SocketAddr) -> io::Result<TcpStream> {
+    let mut stream = probe::connect(target)?;
+
+    if target.port() == NETBIOS_SESSION_PORT {
+        let mut request = vec![0x81, 0, 0, 68];
+        request.extend_from_slice(&netbios_name("*SMBSERVER", 0x20));
+        request.extend_from_slice(&netbios_name("IPSNIFFER", 0x00));
+        stream.write_all(&request)?;
+
+        let mut response = [0; 4];
+        stream.read_exact(&mut response)?;
+        if response[0] != 0x82 {
+            return Err(io::ErrorKind::ConnectionRefused.into());
+        }
+    }
+
+    Ok(stream)
+}
+
+/// Encodes a NetBIOS name with first-level encoding (RFC 1001, section 14.1).
+fn netbios_name(name: &str, suffix: u8) -> Vec<u8> {
+    let mut padded = [b' '; 16];
+    padded[..name.len().min(15)].copy_from_slice(&name.as_bytes()[..name.len().min(15)]);
+    padded[15] = suffix;
+
+    let mut encoded = vec![32];
+    for b in padded {
+        encoded.push(b'A' + (b >> 4));
+        encoded.push(b'A' + (b & 0x0f));
+    }
+    encoded.push(0);
+    encoded
+}
+
+/// Sends a message framed with the 4-byte NetBIOS session header and reads the reply.
+fn exchange(stream: &mut TcpStream, message: &[u8]) -> io::Result<Vec<u8>> {
+    let mut framed = (message.len() as u32).to_be_bytes().to_vec();
+    framed.extend_from_slice(message);
+    stream.write_all(&framed)?;
+
+    let mut header = [0; 4];
+    stream.read_exact(&mut header)?;
+    let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+    let mut response = vec![0; length];
+    stream.read_exact(&mut response)?;
+    Ok(response)
+}
+
+/// Returns whether the server accepts an SMB1 `NT LM 0.12` negotiation.
+fn negotiate_smb1(target: SocketAddr) -> io::Result<bool> {
+    let mut stream = open(target)?;
+
+    let mut message = vec![0xff, b'S', b'M', b'B', 0x72];
+    message.extend_from_slice(&[0; 4]); // status
+    message.push(0x18); // flags: canonicalized paths, case insensitive
+    message.extend_from_slice(&0xc801u16.to_le_bytes()); // flags2
+    message.extend_from_slice(&[0; 12]); // PID high, signature, reserved
+    message.extend_from_slice(&[0, 0, 0xff, 0xfe, 0, 0, 0, 0]); // TID, PID, UID, MID
+    message.push(0); // word count
+    let dialect = b"\x02NT LM 0.12\x00";
+    message.extend_from_slice(&(dialect.len() as u16).to_le_bytes());
+    message.extend_from_slice(dialect);
+
+    let response = exchange(&mut stream, &message)?;
+
+    Ok(response.starts_with(b"\xffSMB")
+        && response.get(5..9) == Some(&[0; 4])
+        && response.get(33..35) == Some(&[0, 0]))
+}
+
+/// Builds an SMB2 header for `command`.
+fn smb2_header(command: u16, message_id: u64) -> Vec<u8> {
+    let mut header = vec![0xfe, b'S', b'M', b'B'];
+    header.extend_from_slice(&64u16.to_le_bytes()); // structure size
+    header.extend_from_slice(&[0; 2]); // credit charge
+    header.extend_from_slice(&[0; 4]); // status
+    header.extend_from_slice(&command.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // credits requested
+    header.extend_from_slice(&[0; 8]); // flags, next command
+    header.extend_from_slice(&message_id.to_le_bytes());
+    header.extend_from_slice(&[0; 4 + 4 + 8 + 16]); // process id, tree id, session id, signature
+    header
+}
+
+/// Negotiates the given SMB2/3 dialects on a new connection.
+///
+/// # Returns
+///
+/// The open connection and the server's security mode.
+fn negotiate_smb2(target: SocketAddr, dialects: &[u16]) -> io::Result<(TcpStream, u16)> {
+    let mut stream = open(target)?;
+    let with_contexts = dialects.contains(&0x0311);
+
+    let mut message = smb2_header(SMB2_NEGOTIATE, 0);
+    message.extend_from_slice(&36u16.to_le_bytes()); // structure size
+    message.extend_from_slice(&(dialects.len() as u16).to_le_bytes());
+    message.extend_from_slice(&1u16.to_le_bytes()); // security mode: signing enabled
+    message.extend_from_slice(&[0; 2]); // reserved
+    message.extend_from_slice(&[0; 4]); // capabilities
+    message.extend_from_slice(b"ip-sniffer-guid!"); // client GUID
+
+    let contexts_offset = (64 + 36 + 2 * dialects.len()).next_multiple_of(8);
+    if with_contexts {
+        message.extend_from_slice(&(contexts_offset as u32).to_le_bytes());
+        message.extend_from_slice(&1u16.to_le_bytes()); // context count
+        message.extend_from_slice(&[0; 2]);
+    } else {
+        message.extend_from_slice(&[0; 8]); // client start time
+    }
+
+    for dialect in dialects {
+        message.extend_from_slice(&dialect.to_le_bytes());
+    }
+
+    if with_contexts {
+        // SMB 3.1.1 requires a pre-authentication integrity context (SHA-512).
+        message.resize(contexts_offset, 0);
+        message.extend_from_slice(&1u16.to_le_bytes()); // context type
+        message.extend_from_slice(&38u16.to_le_bytes()); // data length
+        message.extend_from_slice(&[0; 4]);
+        message.extend_from_slice(&1u16.to_le_bytes()); // hash algorithm count
+        message.extend_from_slice(&32u16.to_le_bytes()); // salt length
+        message.extend_from_slice(&1u16.to_le_bytes()); // SHA-512
+        message.extend_from_slice(&[0x5a; 32]);
+    }
+
+    let response = exchange(&mut stream, &message)?;
+
+    if !response.starts_with(b"\xfeSMB") || status(&response) != Some(STATUS_SUCCESS) {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    let security_mode = u16_at(&response, 66).ok_or(io::ErrorKind::InvalidData)?;
+
+    Ok((stream, security_mode))
+}
+
+/// Starts an anonymous NTLM session setup and returns the server's NTLMSSP CHALLENGE.
+fn ntlm_challenge(target: SocketAddr) -> Option<Vec<u8>> {
+    let dialects: Vec<u16> = SMB2_DIALECTS
+        .iter()
+        .map(|&(dialect, _)| dialect)
+        .filter(|&dialect| dialect != 0x0311)
+        .collect();
+    let (mut stream, _) = negotiate_smb2(target, &dialects).ok()?;
+
+    let mut negotiate = b"NTLMSSP\x00".to_vec();
+    negotiate.extend_from_slice(&1u32.to_le_bytes());
+    negotiate.extend_from_slice(&NTLMSSP_NEGOTIATE_FLAGS.to_le_bytes());
+    negotiate.extend_from_slice(&[0; 16]); // domain and workstation fields
+    negotiate.extend_from_slice(&[10, 0, 0x61, 0x4a, 0, 0, 0, 15]); // version 10.0.19041
+    let token = spnego_init(&negotiate);
+
+    let mut message = smb2_header(SMB2_SESSION_SETUP, 1);
+    message.extend_from_slice(&25u16.to_le_bytes()); // structure size
+    message.push(0); // flags
+    message.push(1); // security mode: signing enabled
+    message.extend_from_slice(&[0; 8]); // capabilities, channel
+    message.extend_from_slice(&(64u16 + 24).to_le_bytes()); // security buffer offset
+    message.extend_from_slice(&(token.len() as u16).to_le_bytes());
+    message.extend_from_slice(&[0; 8]); // previous session id
+    message.extend_from_slice(&token);
+
+    let response = exchange(&mut stream, &message).ok()?;
+    if status(&response) != Some(STATUS_MORE_PROCESSING_REQUIRED) {
+        return None;
+    }
+
+    // The challenge is wrapped in an SPNEGO response; find it by its signature.
+    let start = response.windows(8).position(|w| w == b"NTLMSSP\x00")?;
+    Some(response[start..].to_vec())
+}
+
+/// Wraps an NTLMSSP message in a minimal SPNEGO `NegTokenInit`.
+fn spnego_init(ntlmssp: &[u8]) -> Vec<u8> {
+    const SPNEGO_OID: &[u8] = &[0x06, 0x06, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x02];
+    const NTLMSSP_MECH: &[u8] = &[
+        0xa0, 0x0e, 0x30, 0x0c, 0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02,
+        0x0a,
+    ];
+
+    let mech_token = der(0xa2, &der(0x04, ntlmssp));
+    let mut init = NTLMSSP_MECH.to_vec();
+    init.extend_from_slice(&mech_token);
+
+    let mut token = SPNEGO_OID.to_vec();
+    token.extend_from_slice(&der(0xa0, &der(0x30, &init)));
+    der(0x60, &token)
+}
+
+/// Encodes a DER tag-length-value.
+fn der(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match value.len() {
+        len @ 0..=0x7f => out.push(len as u8),
+        len @ 0x80..=0xff => out.extend_from_slice(&[0x81, len as u8]),
+        len => {
+            out.push(0x82);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+/// Reads the names and OS version out of an NTLMSSP CHALLENGE message.
+fn read_challenge(challenge: &[u8], info: &mut SmbInfo) {
+    if let (Some(&major), Some(&minor), Some(build)) =
+        (challenge.get(48), challenge.get(49), u16_at(challenge, 50))
+    {
+        if major > 0 {
+            info.os_version = Some(format!("{}.{}.{}", major, minor, build));
+        }
+    }
+
+    let (Some(length), Some(offset)) = (u16_at(challenge, 40), u32_at(challenge, 44)) else {
+        return;
+    };
+    let Some(mut pairs) = challenge.get(offset as usize..offset as usize + length as usize) else {
+        return;
+    };
+
+    while let (Some(id), Some(length)) = (u16_at(pairs, 0), u16_at(pairs, 2)) {
+        let Some(value) = pairs.get(4..4 + length as usize) else {
+            break;
+        };
+        let value = Some(utf16le(value));
+
+        match id {
+            0 => break,
+            1 => info.hostname = value,
+            2 => info.domain = value,
+            3 => info.dns_hostname = value,
+            4 => info.dns_domain = value,
+            _ => {}
+        }
+        pairs = &pairs[4 + length as usize..];
+    }
+}
+
+fn utf16le(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Returns the NT status of an SMB2 response.
+fn status(response: &[u8]) -> Option<u32> {
+    u32_at(response, 8)
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
@@ -0,0 +1,164 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, Identity, ServiceReport};
+
+/// Ports on which SMTP is probed.
+pub const PORTS: &[u16] = &[25, 587, 2525];
+
+/// Details collected from an SMTP server.
+#[derive(Debug, Default)]
+pub struct SmtpInfo {
+    /// The greeting, e.g. `mail.example.com ESMTP Postfix`.
+    pub banner: String,
+    /// Extensions announced in reply to EHLO, e.g. `["PIPELINING", "STARTTLS"]`.
+    pub extensions: Vec<String>,
+}
+
+impl SmtpInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("smtp")
+            .text("banner", &self.banner)
+            .list("extensions", &self.extensions)
+    }
+}
+
+/// Sender of the relay check, in a domain reserved for examples.
+const RELAY_FROM: &str = "ip-sniffer@example.com";
+
+/// Recipient of the relay check, in a domain reserved for examples, so mail to it could
+/// never be delivered even if it were sent.
+const RELAY_TO: &str = "relay-check@example.net";
+
+/// What an SMTP server answered when asked to relay.
+#[derive(Debug, Default)]
+pub struct RelayInfo {
+    /// Whether the server accepted a recipient in a domain it is not responsible for.
+    pub open_relay: bool,
+    /// The reply to `RCPT TO`, e.g. `554 5.7.1 Relay access denied`.
+    pub reply: String,
+    pub weak: Vec<String>,
+}
+
+impl RelayInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("smtp")
+            .text("open_relay", self.open_relay.to_string())
+            .text("relay_reply", &self.reply)
+            .list("weak", &self.weak)
+    }
+}
+
+/// Reads the greeting of an SMTP server and the extensions it announces.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `identity` - Supplies the name sent with EHLO.
+///
+/// # Returns
+///
+/// `None` if the port does not speak SMTP.
+pub fn probe(target: SocketAddr, identity: &Identity) -> Option<SmtpInfo> {
+    let mut reader = BufReader::new(probe::connect(target).ok()?);
+
+    let (code, greeting) = read_reply(&mut reader)?;
+    if code != 220 {
+        return None;
+    }
+
+    let mut info = SmtpInfo {
+        banner: greeting.join(" "),
+        ..SmtpInfo::default()
+    };
+
+    let ehlo = format!("EHLO {}\r\n", identity.ehlo);
+    reader.get_mut().write_all(ehlo.as_bytes()).ok()?;
+
+    if let Some((250, lines)) = read_reply(&mut reader) {
+        // The first line repeats the server's name.
+        info.extensions = lines.into_iter().skip(1).collect();
+    }
+
+    let _ = reader.get_mut().write_all(b"QUIT\r\n");
+    Some(info)
+}
+
+/// Asks an SMTP server to accept mail from and to domains it is not responsible for.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `identity` - Supplies the name sent with EHLO.
+///
+/// # Returns
+///
+/// `None` if the port does not speak SMTP or refuses the sender before a recipient can
+/// be tried.
+///
+/// # Description
+///
+/// The check stops after `RCPT TO`: the transaction is reset with `RSET` and the session
+/// ended with `QUIT`, so no message is ever sent. Both addresses are in domains reserved
+/// for examples. A server that accepts the recipient is reported as an open relay,
+/// though some only refuse relayed mail after `DATA`.
+pub fn relay_check(target: SocketAddr, identity: &Identity) -> Option<RelayInfo> {
+    let mut reader = BufReader::new(probe::connect(target).ok()?);
+    let (220, _) = read_reply(&mut reader)? else {
+        return None;
+    };
+
+    let mut command = |line: String| {
+        reader.get_mut().write_all(line.as_bytes()).ok()?;
+        read_reply(&mut reader)
+    };
+    let (code, _) = command(format!("EHLO {}\r\n", identity.ehlo))?;
+    if code != 250 {
+        let (250, _) = command(format!("HELO {}\r\n", identity.ehlo))? else {
+            return None;
+        };
+    }
+    let (250, _) = command(format!("MAIL FROM:<{}>\r\n", RELAY_FROM))? else {
+        return None;
+    };
+    let (code, lines) = command(format!("RCPT TO:<{}>\r\n", RELAY_TO))?;
+    let _ = command("RSET\r\n".to_string());
+    let _ = command("QUIT\r\n".to_string());
+
+    let mut info = RelayInfo {
+        open_relay: code == 250 || code == 251,
+        reply: format!("{} {}", code, lines.join(" "))
+            .trim_end()
+            .to_string(),
+        ..RelayInfo::default()
+    };
+    if info.open_relay {
+        info.weak
+            .push("accepts mail for other domains (open relay)".to_string());
+    }
+    Some(info)
+}
+
+/// Reads a possibly multi-line reply.
+///
+/// # Returns
+///
+/// The reply code and the text of every line.
+pub fn read_reply(reader: &mut BufReader<TcpStream>) -> Option<(u16, Vec<String>)> {
+    let mut lines = vec![];
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        let line = line.trim_end();
+        let code = line.get(..3)?.parse().ok()?;
+        lines.push(line.get(4..).unwrap_or_default().to_string());
+
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Some((code, lines));
+        }
+    }
+}
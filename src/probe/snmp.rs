@@ -0,0 +1,228 @@
+use std::fs;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use ip_sniffer::scan::PortState;
+
+use crate::probe::{self, ServiceReport};
+
+pub const PORT: u16 = 161;
+
+/// Community strings tried when no list is given.
+pub const DEFAULT_COMMUNITIES: &[&str] = &["public", "private"];
+
+/// How long to wait for answers after the last request was sent.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// BER encoding of the `sysDescr.0` OID, 1.3.6.1.2.1.1.1.0.
+const SYS_DESCR: &[u8] = &[0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, 0x00];
+
+const GET_REQUEST: u8 = 0xa0;
+const GET_RESPONSE: u8 = 0xa2;
+
+/// Details collected from an SNMP agent.
+#[derive(Debug, Default)]
+pub struct SnmpInfo {
+    /// Community strings the agent answered to.
+    pub communities: Vec<String>,
+    /// The agent's `sysDescr`, usually the device model and OS version.
+    pub sys_descr: String,
+    pub weak: Vec<String>,
+}
+
+impl SnmpInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("snmp")
+            .udp()
+            .list("communities", &self.communities)
+            .text("sys_descr", &self.sys_descr)
+            .list("weak", &self.weak)
+    }
+}
+
+/// Loads community strings from a file with one community per line.
+///
+/// # Errors
+///
+/// * "failed to read community file" if the file cannot be read.
+/// * "community file is empty" if it contains no communities.
+pub fn load_communities(path: &str) -> Result<Vec<String>, &'static str> {
+    let contents = fs::read_to_string(path).map_err(|_| "failed to read community file")?;
+    let communities: Vec<String> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    if communities.is_empty() {
+        return Err("community file is empty");
+    }
+    Ok(communities)
+}
+
+/// Tries each community string against the SNMP agent on udp/161 of `addr`.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to probe.
+/// * `communities` - The community strings to try.
+///
+/// # Returns
+///
+/// The agent's details, or the state of the port if no community string was accepted:
+/// `OpenFiltered` if nothing answered, `Open` if only malformed answers came back.
+///
+/// # Description
+///
+/// An SNMPv1 `GetRequest` for `sysDescr.0` is sent for every community at once from a
+/// single socket, each with its own request ID, and answers are collected until
+/// `RESPONSE_TIMEOUT` passes without a new one. Agents silently drop requests with a
+/// wrong community, so only accepted communities produce an answer.
+pub fn probe(addr: IpAddr, communities: &[String]) -> Result<SnmpInfo, PortState> {
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).map_err(|_| PortState::Unknown)?;
+    socket
+        .connect(SocketAddr::new(addr, PORT))
+        .map_err(|_| PortState::Unknown)?;
+
+    for (id, community) in communities.iter().enumerate() {
+        socket
+            .send(&get_request(id as u32, community))
+            .map_err(|err| probe::udp_state(&err))?;
+    }
+
+    let mut info = SnmpInfo::default();
+    let mut deadline = Instant::now() + RESPONSE_TIMEOUT;
+    let mut buf = [0; 4096];
+    let mut state = PortState::OpenFiltered;
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        socket
+            .set_read_timeout(Some(remaining.max(Duration::from_millis(1))))
+            .map_err(|_| PortState::Unknown)?;
+
+        let n = match socket.recv(&mut buf) {
+            Ok(n) => n,
+            Err(err) if state != PortState::Open => return Err(probe::udp_state(&err)),
+            Err(_) => break,
+        };
+        state = PortState::Open;
+        let Some((id, sys_descr)) = parse_response(&buf[..n]) else {
+            continue;
+        };
+        let Some(community) = communities.get(id as usize) else {
+            continue;
+        };
+
+        if !info.communities.contains(community) {
+            info.communities.push(community.clone());
+            info.sys_descr = sys_descr;
+            deadline = Instant::now() + RESPONSE_TIMEOUT;
+        }
+    }
+
+    if info.communities.is_empty() {
+        return Err(state);
+    }
+
+    for community in &info.communities {
+        if DEFAULT_COMMUNITIES.contains(&community.as_str()) {
+            info.weak.push(format!("default community {:?}", community));
+        }
+    }
+    if info.weak.is_empty() {
+        info.weak.push("guessable community string".to_string());
+    }
+
+    Ok(info)
+}
+
+/// Builds an SNMPv1 `GetRequest` for `sysDescr.0`.
+fn get_request(id: u32, community: &str) -> Vec<u8> {
+    let varbind = tlv(0x30, &[tlv(0x06, SYS_DESCR), tlv(0x05, &[])].concat());
+    let pdu = tlv(
+        GET_REQUEST,
+        &[
+            tlv(0x02, &id.to_be_bytes()),
+            tlv(0x02, &[0]), // error status
+            tlv(0x02, &[0]), // error index
+            tlv(0x30, &varbind),
+        ]
+        .concat(),
+    );
+
+    tlv(
+        0x30,
+        &[tlv(0x02, &[0]), tlv(0x04, community.as_bytes()), pdu].concat(),
+    )
+}
+
+/// Parses a `GetResponse` and returns its request ID and the `sysDescr` value.
+fn parse_response(packet: &[u8]) -> Option<(u32, String)> {
+    let (tag, message, _) = read_tlv(packet)?;
+    if tag != 0x30 {
+        return None;
+    }
+
+    let (_, _, rest) = read_tlv(message)?; // version
+    let (_, _, rest) = read_tlv(rest)?; // community
+    let (tag, pdu, _) = read_tlv(rest)?;
+    if tag != GET_RESPONSE {
+        return None;
+    }
+
+    let (_, id, rest) = read_tlv(pdu)?;
+    let (_, error_status, rest) = read_tlv(rest)?;
+    if error_status.iter().any(|&b| b != 0) {
+        return None;
+    }
+    let (_, _, rest) = read_tlv(rest)?; // error index
+    let (_, varbinds, _) = read_tlv(rest)?;
+    let (_, varbind, _) = read_tlv(varbinds)?;
+    let (_, _, rest) = read_tlv(varbind)?; // OID
+    let (tag, value, _) = read_tlv(rest)?;
+    if tag != 0x04 {
+        return None;
+    }
+
+    let id = id.iter().fold(0u32, |acc, &b| acc << 8 | b as u32);
+    Some((id, String::from_utf8_lossy(value).trim().to_string()))
+}
+
+/// Encodes a BER tag-length-value.
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    if value.len() < 0x80 {
+        out.push(value.len() as u8);
+    } else {
+        out.push(0x82);
+        out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+/// Reads a BER tag-length-value, returning the tag, the value and the remaining bytes.
+fn read_tlv(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *buf.first()?;
+    let first = *buf.get(1)? as usize;
+
+    let (length, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        let bytes = buf.get(2..2 + count)?;
+        (
+            bytes.iter().fold(0, |acc, &b| acc << 8 | b as usize),
+            2 + count,
+        )
+    };
+
+    let value = buf.get(header..header + length)?;
+    Some((tag, value, &buf[header + length..]))
+}
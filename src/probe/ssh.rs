@@ -0,0 +1,325 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use ip_sniffer::base64;
+
+use crate::hash;
+use crate::probe::{self, Identity, ServiceReport};
+
+const MSG_KEXINIT: u8 = 20;
+const MSG_KEX_ECDH_INIT: u8 = 30;
+const MSG_KEX_ECDH_REPLY: u8 = 31;
+
+/// Key exchange we offer when fetching host keys. X25519 accepts any 32 bytes as a
+/// public key, so the exchange can be started without implementing the curve.
+const FINGERPRINT_KEX: &str = "curve25519-sha256,curve25519-sha256@libssh.org";
+
+/// Host key algorithms whose keys are fetched, grouped by key type.
+const HOST_KEY_TYPES: &[&[&str]] = &[
+    &["ssh-ed25519"],
+    &[
+        "ecdsa-sha2-nistp256",
+        "ecdsa-sha2-nistp384",
+        "ecdsa-sha2-nistp521",
+    ],
+    &["rsa-sha2-512", "rsa-sha2-256", "ssh-rsa"],
+    &["ssh-dss"],
+];
+
+/// Algorithms considered weak, by exact name or by suffix (for `-cbc` ciphers).
+const WEAK_KEX: &[&str] = &[
+    "diffie-hellman-group1-sha1",
+    "diffie-hellman-group14-sha1",
+    "diffie-hellman-group-exchange-sha1",
+    "rsa1024-sha1",
+];
+const WEAK_HOST_KEYS: &[&str] = &["ssh-dss", "ssh-rsa"];
+const WEAK_CIPHERS: &[&str] = &["3des-cbc", "arcfour", "arcfour128", "arcfour256", "none"];
+const WEAK_MACS: &[&str] = &[
+    "hmac-md5",
+    "hmac-md5-96",
+    "hmac-md5-etm@openssh.com",
+    "hmac-md5-96-etm@openssh.com",
+    "hmac-sha1-96",
+    "hmac-sha1-96-etm@openssh.com",
+    "umac-64@openssh.com",
+    "umac-64-etm@openssh.com",
+    "none",
+];
+
+/// Details collected from an SSH server.
+#[derive(Debug, Default)]
+pub struct SshInfo {
+    /// The server's identification string, e.g. `SSH-2.0-OpenSSH_9.6`.
+    pub banner: String,
+    pub kex: Vec<String>,
+    pub host_key_algorithms: Vec<String>,
+    /// Server-to-client encryption algorithms.
+    pub ciphers: Vec<String>,
+    /// Server-to-client MAC algorithms.
+    pub macs: Vec<String>,
+    /// `(key type, SHA256:... fingerprint)` for every host key that could be fetched.
+    pub fingerprints: Vec<(String, String)>,
+    /// Weak algorithms or protocol versions offered by the server.
+    pub weak: Vec<String>,
+}
+
+impl SshInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("ssh")
+            .text("banner", &self.banner)
+            .list("kex", &self.kex)
+            .list("host_key_algorithms", &self.host_key_algorithms)
+            .list("ciphers", &self.ciphers)
+            .list("macs", &self.macs)
+            .map("fingerprints", &self.fingerprints)
+            .list("weak", &self.weak)
+    }
+}
+
+/// Probes an SSH server for its banner, algorithms and host key fingerprints.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `identity` - Supplies the identification string we send.
+///
+/// # Returns
+///
+/// `None` if the port does not speak SSH.
+///
+/// # Description
+///
+/// The algorithms come from the server's `SSH_MSG_KEXINIT`. Host keys are fetched the
+/// way `ssh-keyscan` does: a curve25519 key exchange is started once per key type, and
+/// the key is read from the server's reply before the connection is dropped. Servers
+/// that do not offer curve25519 only report their algorithms.
+pub fn probe(target: SocketAddr, identity: &Identity) -> Option<SshInfo> {
+    let client_banner = &identity.ssh_banner;
+    let (banner, kexinit, _) = handshake(target, client_banner, None).ok()?;
+    let lists = parse_kexinit(&kexinit)?;
+
+    let mut info = SshInfo {
+        banner,
+        kex: lists[0].clone(),
+        host_key_algorithms: lists[1].clone(),
+        ciphers: lists[3].clone(),
+        macs: lists[5].clone(),
+        ..SshInfo::default()
+    };
+
+    if !info.banner.starts_with("SSH-2.0-") && !info.banner.starts_with("SSH-1.99-") {
+        info.weak.push("protocol 1".to_string());
+    }
+    flag_weak(&mut info.weak, &info.kex, WEAK_KEX);
+    flag_weak(&mut info.weak, &info.host_key_algorithms, WEAK_HOST_KEYS);
+    flag_weak(&mut info.weak, &info.ciphers, WEAK_CIPHERS);
+    flag_weak(&mut info.weak, &info.macs, WEAK_MACS);
+    for cipher in info.ciphers.iter().filter(|c| c.ends_with("-cbc")) {
+        if !info.weak.contains(cipher) {
+            info.weak.push(cipher.clone());
+        }
+    }
+
+    let supports_curve25519 = FINGERPRINT_KEX
+        .split(',')
+        .any(|k| info.kex.iter().any(|s| s == k));
+
+    if supports_curve25519 {
+        for key_type in HOST_KEY_TYPES {
+            let offered: Vec<&str> = key_type
+                .iter()
+                .copied()
+                .filter(|alg| info.host_key_algorithms.iter().any(|s| s == alg))
+                .collect();
+
+            if offered.is_empty() {
+                continue;
+            }
+            if let Some(fingerprint) = fetch_host_key(target, client_banner, &offered.join(",")) {
+                info.fingerprints.push(fingerprint);
+            }
+        }
+    }
+
+    Some(info)
+}
+
+/// Adds every algorithm of `offered` that appears in `weak` to `out`.
+fn flag_weak(out: &mut Vec<String>, offered: &[String], weak: &[&str]) {
+    out.extend(
+        offered
+            .iter()
+            .filter(|alg| weak.contains(&alg.as_str()))
+            .cloned(),
+    );
+}
+
+/// Exchanges identification strings and reads the server's `SSH_MSG_KEXINIT`.
+///
+/// If `host_key_algorithms` is given, our own `SSH_MSG_KEXINIT` is sent with it so the
+/// caller can continue the key exchange on the returned stream.
+fn handshake(
+    target: SocketAddr,
+    client_banner: &str,
+    host_key_algorithms: Option<&str>,
+) -> io::Result<(String, Vec<u8>, BufReader<TcpStream>)> {
+    let stream = probe::connect(target)?;
+    let mut reader = BufReader::new(stream);
+
+    // Servers may send other lines before their identification string.
+    let mut banner = String::new();
+    for _ in 0..20 {
+        banner.clear();
+        if reader.read_line(&mut banner)? == 0 || banner.starts_with("SSH-") {
+            break;
+        }
+    }
+    if !banner.starts_with("SSH-") {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    reader
+        .get_mut()
+        .write_all(format!("{}\r\n", client_banner).as_bytes())?;
+
+    if let Some(host_key_algorithms) = host_key_algorithms {
+        let kexinit = build_kexinit(host_key_algorithms);
+        write_packet(reader.get_mut(), &kexinit)?;
+    }
+
+    let kexinit = read_packet(&mut reader)?;
+    if kexinit.first() != Some(&MSG_KEXINIT) {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    Ok((banner.trim_end().to_string(), kexinit, reader))
+}
+
+/// Starts a key exchange restricted to `host_key_algorithms` and returns the type and
+/// fingerprint of the host key the server presents.
+fn fetch_host_key(
+    target: SocketAddr,
+    client_banner: &str,
+    host_key_algorithms: &str,
+) -> Option<(String, String)> {
+    let (_, _, mut reader) = handshake(target, client_banner, Some(host_key_algorithms)).ok()?;
+
+    let mut init = vec![MSG_KEX_ECDH_INIT];
+    put_string(&mut init, &ephemeral_key());
+    write_packet(reader.get_mut(), &init).ok()?;
+
+    let reply = read_packet(&mut reader).ok()?;
+    if reply.first() != Some(&MSG_KEX_ECDH_REPLY) {
+        return None;
+    }
+
+    let (host_key, _) = get_string(&reply[1..])?;
+    let (key_type, _) = get_string(host_key)?;
+    let digest = hash::sha256(host_key);
+    let fingerprint = format!("SHA256:{}", base64::encode(&digest).trim_end_matches('='));
+
+    Some((String::from_utf8_lossy(key_type).into_owned(), fingerprint))
+}
+
+/// Returns 32 bytes to use as our X25519 public key. The exchange is never completed,
+/// so the value only needs to differ between connections.
+fn ephemeral_key() -> [u8; 32] {
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    hash::sha256(&seed.to_be_bytes())
+}
+
+/// Builds our `SSH_MSG_KEXINIT` payload.
+fn build_kexinit(host_key_algorithms: &str) -> Vec<u8> {
+    let mut payload = vec![MSG_KEXINIT];
+    payload.extend_from_slice(&ephemeral_key()[..16]);
+
+    let ciphers = "aes128-ctr,aes256-ctr,chacha20-poly1305@openssh.com,aes128-gcm@openssh.com";
+    let macs = "hmac-sha2-256,hmac-sha2-512,hmac-sha1";
+    let lists = [
+        FINGERPRINT_KEX,
+        host_key_algorithms,
+        ciphers,
+        ciphers,
+        macs,
+        macs,
+        "none",
+        "none",
+        "",
+        "",
+    ];
+    for list in lists {
+        put_string(&mut payload, list.as_bytes());
+    }
+
+    payload.push(0); // first_kex_packet_follows
+    payload.extend_from_slice(&[0; 4]); // reserved
+    payload
+}
+
+/// Parses the ten name-lists of an `SSH_MSG_KEXINIT` payload.
+fn parse_kexinit(payload: &[u8]) -> Option<Vec<Vec<String>>> {
+    let mut rest = payload.get(17..)?;
+    let mut lists = vec![];
+
+    for _ in 0..10 {
+        let (list, next) = get_string(rest)?;
+        let list = String::from_utf8_lossy(list);
+        lists.push(
+            list.split(',')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+        );
+        rest = next;
+    }
+
+    Some(lists)
+}
+
+/// Writes an unencrypted SSH binary packet.
+fn write_packet(stream: &mut TcpStream, payload: &[u8]) -> io::Result<()> {
+    let mut padding = 8 - (5 + payload.len()) % 8;
+    if padding < 4 {
+        padding += 8;
+    }
+
+    let mut packet = Vec::with_capacity(5 + payload.len() + padding);
+    packet.extend_from_slice(&((1 + payload.len() + padding) as u32).to_be_bytes());
+    packet.push(padding as u8);
+    packet.extend_from_slice(payload);
+    packet.extend(std::iter::repeat_n(0, padding));
+    stream.write_all(&packet)
+}
+
+/// Reads an unencrypted SSH binary packet and returns its payload.
+fn read_packet(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let padding = header[4] as usize;
+    if !(1 + padding..=256 * 1024).contains(&length) {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    let mut body = vec![0; length - 1];
+    reader.read_exact(&mut body)?;
+    body.truncate(length - 1 - padding);
+    Ok(body)
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &[u8]) {
+    buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    buf.extend_from_slice(s);
+}
+
+/// Reads an SSH `string` from the front of `buf`, returning it and the remaining bytes.
+fn get_string(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let length = u32::from_be_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+    let s = buf.get(4..4 + length)?;
+    Some((s, &buf[4 + length..]))
+}
@@ -0,0 +1,372 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::probe::{self, smtp, Identity, ServiceReport};
+
+/// Protocols that can upgrade a plaintext connection to TLS.
+#[derive(Clone, Copy, Debug)]
+pub enum Protocol {
+    Smtp,
+    Imap,
+    Pop3,
+    Ldap,
+    Ftp,
+}
+
+/// Ports on which STARTTLS is probed, with the protocol spoken on each.
+pub const PORTS: &[(u16, Protocol)] = &[
+    (21, Protocol::Ftp),
+    (25, Protocol::Smtp),
+    (110, Protocol::Pop3),
+    (143, Protocol::Imap),
+    (389, Protocol::Ldap),
+    (587, Protocol::Smtp),
+    (2525, Protocol::Smtp),
+];
+
+/// The OID of the LDAP StartTLS extended operation.
+const LDAP_STARTTLS_OID: &[u8] = b"1.3.6.1.4.1.1466.20037";
+
+/// How a plaintext service handles TLS and authentication.
+#[derive(Debug)]
+pub struct StartTlsInfo {
+    pub protocol: Protocol,
+    /// Whether the server offers to upgrade the connection to TLS.
+    pub starttls: bool,
+    /// Whether the server accepts passwords before the connection is upgraded.
+    pub plaintext_auth: bool,
+    /// Authentication mechanisms announced before the upgrade, e.g. `["PLAIN", "LOGIN"]`.
+    pub mechanisms: Vec<String>,
+    pub weak: Vec<String>,
+}
+
+impl StartTlsInfo {
+    pub fn report(&self) -> ServiceReport {
+        let service = match self.protocol {
+            Protocol::Smtp => "smtp",
+            Protocol::Imap => "imap",
+            Protocol::Pop3 => "pop3",
+            Protocol::Ldap => "ldap",
+            Protocol::Ftp => "ftp",
+        };
+        let opportunistic = match (self.starttls, self.plaintext_auth) {
+            (false, _) => "not offered; plaintext only",
+            (true, true) => "offered; authentication allowed without it",
+            (true, false) => "offered; authentication requires it",
+        };
+
+        ServiceReport::new(service)
+            .text("opportunistic_tls", opportunistic)
+            .text("starttls", self.starttls.to_string())
+            .text("plaintext_auth", self.plaintext_auth.to_string())
+            .list("auth_mechanisms", &self.mechanisms)
+            .list("weak", &self.weak)
+    }
+}
+
+/// Returns whether `port` is one STARTTLS is probed on.
+pub fn applies_to(port: u16) -> bool {
+    PORTS.iter().any(|(p, _)| *p == port)
+}
+
+/// Checks whether a mail, LDAP or FTP server offers STARTTLS and whether it accepts
+/// passwords in plaintext.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+/// * `identity` - Supplies the name sent with SMTP `EHLO` and FTP `USER`.
+///
+/// # Returns
+///
+/// `None` if the port does not speak the protocol expected on it.
+///
+/// # Description
+///
+/// SMTP, IMAP and POP3 announce STARTTLS and their login mechanisms as capabilities,
+/// and FTP as features; plaintext authentication is allowed if `PLAIN`, `LOGIN` or a
+/// clear-text login command is announced before the upgrade. An FTP server is also sent
+/// `USER` without a password, and an LDAP server a simple bind with a made-up name,
+/// since they only say whether they need TLS when a login is attempted. The upgrade
+/// itself is never made.
+pub fn probe(target: SocketAddr, identity: &Identity) -> Option<StartTlsInfo> {
+    let (_, protocol) = PORTS.iter().find(|(port, _)| *port == target.port())?;
+    let reader = BufReader::new(probe::connect(target).ok()?);
+
+    let mut info = match protocol {
+        Protocol::Smtp => probe_smtp(reader, identity),
+        Protocol::Imap => probe_imap(reader),
+        Protocol::Pop3 => probe_pop3(reader),
+        Protocol::Ldap => probe_ldap(reader),
+        Protocol::Ftp => probe_ftp(reader, identity),
+    }?;
+
+    if info.plaintext_auth {
+        let weak = if info.starttls {
+            "passwords accepted before STARTTLS"
+        } else {
+            "passwords accepted without TLS"
+        };
+        info.weak.push(weak.to_string());
+    }
+    Some(info)
+}
+
+fn info(protocol: Protocol, starttls: bool, plaintext_auth: bool) -> StartTlsInfo {
+    StartTlsInfo {
+        protocol,
+        starttls,
+        plaintext_auth,
+        mechanisms: vec![],
+        weak: vec![],
+    }
+}
+
+/// Returns whether a SASL list has a mechanism that sends the password as it is.
+fn plaintext_mechanisms(mechanisms: &[String]) -> bool {
+    mechanisms
+        .iter()
+        .any(|m| m.eq_ignore_ascii_case("PLAIN") || m.eq_ignore_ascii_case("LOGIN"))
+}
+
+fn probe_smtp(mut reader: BufReader<TcpStream>, identity: &Identity) -> Option<StartTlsInfo> {
+    let (220, _) = smtp::read_reply(&mut reader)? else {
+        return None;
+    };
+    let ehlo = format!("EHLO {}\r\n", identity.ehlo);
+    reader.get_mut().write_all(ehlo.as_bytes()).ok()?;
+    let (250, lines) = smtp::read_reply(&mut reader)? else {
+        return None;
+    };
+    let _ = reader.get_mut().write_all(b"QUIT\r\n");
+
+    let mut info = info(Protocol::Smtp, false, false);
+    for line in lines.iter().skip(1) {
+        let mut words = line.split_whitespace();
+        match words.next().map(str::to_ascii_uppercase).as_deref() {
+            Some("STARTTLS") => info.starttls = true,
+            Some("AUTH") => info.mechanisms.extend(words.map(str::to_string)),
+            _ => {}
+        }
+    }
+    info.plaintext_auth = plaintext_mechanisms(&info.mechanisms);
+    Some(info)
+}
+
+fn probe_imap(mut reader: BufReader<TcpStream>) -> Option<StartTlsInfo> {
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting).ok()?;
+    if !greeting.starts_with("* OK") {
+        return None;
+    }
+    reader.get_mut().write_all(b"a1 CAPABILITY\r\n").ok()?;
+
+    let mut capabilities = vec![];
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        if let Some(list) = line.trim_end().strip_prefix("* CAPABILITY ") {
+            capabilities.extend(list.split_whitespace().map(str::to_ascii_uppercase));
+        } else if line.starts_with("a1 ") {
+            break;
+        }
+    }
+    let _ = reader.get_mut().write_all(b"a2 LOGOUT\r\n");
+
+    let mut info = info(Protocol::Imap, false, false);
+    info.starttls = capabilities.iter().any(|c| c == "STARTTLS");
+    info.mechanisms = capabilities
+        .iter()
+        .filter_map(|c| c.strip_prefix("AUTH="))
+        .map(str::to_string)
+        .collect();
+    // LOGIN sends the password as it is unless the server disables it.
+    info.plaintext_auth = !capabilities.iter().any(|c| c == "LOGINDISABLED");
+    Some(info)
+}
+
+fn probe_pop3(mut reader: BufReader<TcpStream>) -> Option<StartTlsInfo> {
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting).ok()?;
+    if !greeting.starts_with("+OK") {
+        return None;
+    }
+    reader.get_mut().write_all(b"CAPA\r\n").ok()?;
+
+    let mut status = String::new();
+    reader.read_line(&mut status).ok()?;
+    if !status.starts_with("+OK") {
+        // Servers from before CAPA know USER and PASS and no STLS.
+        let _ = reader.get_mut().write_all(b"QUIT\r\n");
+        return Some(info(Protocol::Pop3, false, true));
+    }
+
+    let mut info = info(Protocol::Pop3, false, false);
+    let mut user = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line == "." {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        match words.next().map(str::to_ascii_uppercase).as_deref() {
+            Some("STLS") => info.starttls = true,
+            Some("USER") => user = true,
+            Some("SASL") => info.mechanisms.extend(words.map(str::to_string)),
+            _ => {}
+        }
+    }
+    let _ = reader.get_mut().write_all(b"QUIT\r\n");
+
+    info.plaintext_auth = user || plaintext_mechanisms(&info.mechanisms);
+    Some(info)
+}
+
+fn probe_ftp(mut reader: BufReader<TcpStream>, identity: &Identity) -> Option<StartTlsInfo> {
+    let (220, _) = read_ftp_reply(&mut reader)? else {
+        return None;
+    };
+    reader.get_mut().write_all(b"FEAT\r\n").ok()?;
+    let (code, features) = read_ftp_reply(&mut reader)?;
+
+    let mut info = info(Protocol::Ftp, false, false);
+    if code == 211 {
+        info.starttls = features.iter().any(|feature| {
+            let feature = feature.trim().to_ascii_uppercase();
+            feature.starts_with("AUTH") && feature.contains("TLS")
+        });
+    }
+
+    // 331 asks for the password; servers that need TLS first refuse with 530 or 534.
+    let user = format!("USER {}\r\n", identity.ehlo);
+    reader.get_mut().write_all(user.as_bytes()).ok()?;
+    let (code, _) = read_ftp_reply(&mut reader)?;
+    info.plaintext_auth = code == 331 || code == 230;
+
+    let _ = reader.get_mut().write_all(b"QUIT\r\n");
+    Some(info)
+}
+
+/// Reads an FTP reply, whose inner lines, unlike those of SMTP, need not start with the
+/// code.
+///
+/// # Returns
+///
+/// The reply code and the text of every line.
+fn read_ftp_reply(reader: &mut BufReader<TcpStream>) -> Option<(u16, Vec<String>)> {
+    let mut lines = vec![];
+    let mut code = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+
+        let first = code.is_none();
+        let numbered = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+        if first {
+            code = Some(numbered?);
+        }
+        if numbered == code && line.as_bytes().get(3) != Some(&b'-') {
+            lines.push(line.get(4..).unwrap_or_default().to_string());
+            return Some((code?, lines));
+        }
+        let text = if first || numbered == code {
+            line.get(4..).unwrap_or_default()
+        } else {
+            line
+        };
+        lines.push(text.to_string());
+    }
+}
+
+fn probe_ldap(mut reader: BufReader<TcpStream>) -> Option<StartTlsInfo> {
+    // A simple bind of a name that does not exist: invalidCredentials (49) means the
+    // password was read, confidentialityRequired (13) that TLS must come first.
+    let name = b"cn=ip-sniffer";
+    let password = b"ip-sniffer";
+    let mut bind = vec![0x02, 0x01, 0x03, 0x04, name.len() as u8];
+    bind.extend_from_slice(name);
+    bind.extend_from_slice(&[0x80, password.len() as u8]);
+    bind.extend_from_slice(password);
+    reader
+        .get_mut()
+        .write_all(&ldap_message(1, 0x60, &bind))
+        .ok()?;
+    let bound = ldap_result(&mut reader, 0x61)?;
+
+    let mut starttls = vec![0x80, LDAP_STARTTLS_OID.len() as u8];
+    starttls.extend_from_slice(LDAP_STARTTLS_OID);
+    reader
+        .get_mut()
+        .write_all(&ldap_message(2, 0x77, &starttls))
+        .ok()?;
+    let upgraded = ldap_result(&mut reader, 0x78);
+
+    Some(info(
+        Protocol::Ldap,
+        upgraded == Some(0),
+        bound == 0 || bound == 49,
+    ))
+}
+
+/// Wraps an LDAP operation in a message with ID `id`; every length must be short.
+fn ldap_message(id: u8, op: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = vec![0x30, (body.len() + 5) as u8, 0x02, 0x01, id, op];
+    message.push(body.len() as u8);
+    message.extend_from_slice(body);
+    message
+}
+
+/// Reads an LDAP response and returns its result code.
+///
+/// # Returns
+///
+/// `None` if the response is not a message with operation `op`.
+fn ldap_result(reader: &mut impl Read, op: u8) -> Option<u8> {
+    let mut tag = [0; 1];
+    reader.read_exact(&mut tag).ok()?;
+    if tag[0] != 0x30 {
+        return None;
+    }
+    let length = ber_length(reader)?;
+    let mut message = vec![0; length.min(64 * 1024)];
+    reader.read_exact(&mut message).ok()?;
+
+    // messageID, then the operation, then its resultCode enumeration.
+    let mut rest = message.get(2 + *message.get(1)? as usize..)?;
+    if *rest.first()? != op {
+        return None;
+    }
+    rest = &rest[1..];
+    ber_length(&mut rest)?;
+    match rest {
+        [0x0a, 0x01, code, ..] => Some(*code),
+        _ => None,
+    }
+}
+
+/// Reads a BER length, short or long form.
+fn ber_length(reader: &mut impl Read) -> Option<usize> {
+    let mut first = [0; 1];
+    reader.read_exact(&mut first).ok()?;
+    if first[0] < 0x80 {
+        return Some(first[0] as usize);
+    }
+
+    let count = (first[0] & 0x7f) as usize;
+    if count == 0 || count > 4 {
+        return None;
+    }
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes[4 - count..]).ok()?;
+    Some(u32::from_be_bytes(bytes) as usize)
+}
@@ -0,0 +1,286 @@
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::probe::{self, http, Identity, ServiceReport};
+
+pub const PORT: u16 = 1900;
+
+/// How long to wait for the gateway to answer the search.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most port mappings read from a gateway; some never report the end of the table.
+const MAX_MAPPINGS: usize = 256;
+
+const SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+                      HOST: 239.255.255.250:1900\r\n\
+                      MAN: \"ssdp:discover\"\r\n\
+                      MX: 1\r\n\
+                      ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n";
+
+/// Services of an Internet Gateway Device that manage port mappings.
+const WAN_SERVICES: &[&str] = &["WANIPConnection", "WANPPPConnection"];
+
+/// A port mapping of a gateway.
+#[derive(Debug)]
+pub struct Mapping {
+    /// `TCP` or `UDP`.
+    pub protocol: String,
+    pub external_port: u16,
+    /// The LAN address and port the mapping forwards to, e.g. `192.168.1.20:51413`.
+    pub internal: String,
+    /// What the program that added the mapping called it.
+    pub description: String,
+    pub enabled: bool,
+}
+
+impl Mapping {
+    fn describe(&self) -> String {
+        let mut text = format!(
+            "{} {} -> {}",
+            self.protocol.to_ascii_lowercase(),
+            self.external_port,
+            self.internal
+        );
+        if !self.description.is_empty() {
+            text.push_str(&format!(" ({})", self.description));
+        }
+        if !self.enabled {
+            text.push_str(" disabled");
+        }
+        text
+    }
+}
+
+/// Details collected from a UPnP Internet Gateway Device.
+#[derive(Debug, Default)]
+pub struct UpnpInfo {
+    /// The `SERVER` header of the search answer, e.g. `Linux/3.4 UPnP/1.0 miniupnpd/2.1`.
+    pub server: String,
+    /// URL of the device description.
+    pub location: String,
+    /// The gateway's address on the internet, as it reports it.
+    pub external_ip: String,
+    pub mappings: Vec<Mapping>,
+    /// Mappings whose external port accepted a connection on the external address.
+    pub open: Vec<String>,
+}
+
+impl UpnpInfo {
+    pub fn report(&self) -> ServiceReport {
+        let mappings: Vec<String> = self.mappings.iter().map(Mapping::describe).collect();
+        ServiceReport::new("upnp")
+            .udp()
+            .text("server", &self.server)
+            .text("location", &self.location)
+            .text("external_ip", &self.external_ip)
+            .list("mappings", &mappings)
+            .list("open_mappings", &self.open)
+    }
+}
+
+/// Reads the port mappings of the UPnP gateway at `addr` and checks which are open.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address of the gateway.
+/// * `identity` - Supplies the `User-Agent` header of the SOAP requests.
+///
+/// # Returns
+///
+/// `None` if `addr` does not answer an SSDP search for an Internet Gateway Device or
+/// has no port mapping service.
+///
+/// # Description
+///
+/// The mappings are read with `GetGenericPortMappingEntry`, which needs no credentials
+/// on most gateways; any program on the LAN may have added them. Each enabled TCP
+/// mapping is then connected to on the external address the gateway reports, to find
+/// the holes that are open to the internet. Those connections leave the LAN and come
+/// back through the gateway, so a gateway without NAT loopback shows none as open.
+pub fn probe(addr: IpAddr, identity: &Identity) -> Option<UpnpInfo> {
+    let (server, location) = search(addr)?;
+
+    let (description_addr, description_path) = split_url(&location, addr)?;
+    let description = http::get(description_addr, &description_path, identity, None)?;
+    let description = String::from_utf8_lossy(&description.body).into_owned();
+
+    let (service_type, control_url) = description
+        .split("<service>")
+        .skip(1)
+        .filter_map(|service| Some((tag(service, "serviceType")?, tag(service, "controlURL")?)))
+        .find(|(service_type, _)| WAN_SERVICES.iter().any(|s| service_type.contains(s)))?;
+    let (control_addr, control_path) = split_url(control_url, addr).unwrap_or_else(|| {
+        let path = format!("/{}", control_url.trim_start_matches('/'));
+        (description_addr, path)
+    });
+
+    let soap = |action: &str, arguments: &str| {
+        soap_call(
+            control_addr,
+            &control_path,
+            service_type,
+            action,
+            arguments,
+            identity,
+        )
+    };
+
+    let mut info = UpnpInfo {
+        server,
+        location,
+        ..UpnpInfo::default()
+    };
+    if let Some(answer) = soap("GetExternalIPAddress", "") {
+        info.external_ip = tag(&answer, "NewExternalIPAddress")
+            .unwrap_or_default()
+            .to_string();
+    }
+
+    for index in 0..MAX_MAPPINGS {
+        let arguments = format!("<NewPortMappingIndex>{}</NewPortMappingIndex>", index);
+        // Gateways answer with a SOAP fault past the last mapping.
+        let Some(answer) = soap("GetGenericPortMappingEntry", &arguments) else {
+            break;
+        };
+        let field = |name| tag(&answer, name).unwrap_or_default().to_string();
+
+        info.mappings.push(Mapping {
+            protocol: field("NewProtocol"),
+            external_port: field("NewExternalPort").parse().unwrap_or(0),
+            internal: format!(
+                "{}:{}",
+                field("NewInternalClient"),
+                field("NewInternalPort")
+            ),
+            description: field("NewPortMappingDescription"),
+            enabled: field("NewEnabled") != "0",
+        });
+    }
+
+    if let Ok(external) = IpAddr::from_str(&info.external_ip) {
+        let ports: Vec<u16> = info
+            .mappings
+            .iter()
+            .filter(|m| m.enabled && m.protocol.eq_ignore_ascii_case("TCP"))
+            .map(|m| m.external_port)
+            .collect();
+        let open = probe::run_on_ports(external, &ports, |target| {
+            probe::connect(target).ok().map(|_| ())
+        });
+
+        info.open = info
+            .mappings
+            .iter()
+            .filter(|m| m.protocol.eq_ignore_ascii_case("TCP"))
+            .filter(|m| open.iter().any(|(port, _)| *port == m.external_port))
+            .map(Mapping::describe)
+            .collect();
+    }
+
+    Some(info)
+}
+
+/// Sends an SSDP search for an Internet Gateway Device to `addr`.
+///
+/// # Returns
+///
+/// The `SERVER` and `LOCATION` headers of the answer.
+fn search(addr: IpAddr) -> Option<(String, String)> {
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).ok()?;
+    socket.connect(SocketAddr::new(addr, PORT)).ok()?;
+    socket.send(SEARCH.as_bytes()).ok()?;
+
+    let deadline = Instant::now() + SEARCH_TIMEOUT;
+    let mut buf = [0; 2048];
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        socket
+            .set_read_timeout(Some(remaining.max(Duration::from_millis(1))))
+            .ok()?;
+        let n = socket.recv(&mut buf).ok()?;
+        let answer = String::from_utf8_lossy(&buf[..n]);
+
+        let header = |name: &str| {
+            answer.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.trim()
+                    .eq_ignore_ascii_case(name)
+                    .then(|| value.trim().to_string())
+            })
+        };
+        if let Some(location) = header("LOCATION") {
+            return Some((header("SERVER").unwrap_or_default(), location));
+        }
+    }
+    None
+}
+
+/// Calls a SOAP action of a gateway service.
+///
+/// # Returns
+///
+/// The body of the answer, or `None` if the call failed or returned a fault.
+fn soap_call(
+    target: SocketAddr,
+    path: &str,
+    service_type: &str,
+    action: &str,
+    arguments: &str,
+    identity: &Identity,
+) -> Option<String> {
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{0} xmlns:u=\"{1}\">{2}</u:{0}></s:Body>\
+         </s:Envelope>",
+        action, service_type, arguments
+    );
+    let soap_action = format!("\"{}#{}\"", service_type, action);
+    let headers = [
+        ("Content-Type", "text/xml; charset=\"utf-8\""),
+        ("SOAPAction", soap_action.as_str()),
+    ];
+
+    let response = http::post(target, path, &headers, &body, identity)?;
+    if response.status.split_whitespace().nth(1) != Some("200") {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&response.body).into_owned())
+}
+
+/// Splits an `http://HOST:PORT/PATH` URL into the address to connect to and the path.
+/// A host name is replaced by `addr`, the address the gateway answered from.
+fn split_url(url: &str, addr: IpAddr) -> Option<(SocketAddr, String)> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], rest[i..].to_string()),
+        None => (rest, "/".to_string()),
+    };
+
+    let target = SocketAddr::from_str(authority).unwrap_or_else(|_| {
+        let port = authority
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+            .unwrap_or(80);
+        SocketAddr::new(addr, port)
+    });
+    Some((target, path))
+}
+
+/// Returns the text of the first `<name>` element, ignoring any namespace prefix on
+/// the element.
+fn tag<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let (start, open) = [format!("<{}>", name), format!(":{}>", name)]
+        .iter()
+        .filter_map(|open| Some((xml.find(open.as_str())?, open.len())))
+        .min()?;
+    let text = &xml[start + open..];
+    Some(text[..text.find("</")?].trim())
+}
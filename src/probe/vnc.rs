@@ -0,0 +1,124 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+
+use crate::probe::{self, ServiceReport};
+
+/// Ports on which VNC is probed: display `:0` to `:99`.
+pub const PORTS: RangeInclusive<u16> = 5900..=5999;
+
+/// Details collected from a VNC server.
+#[derive(Debug, Default)]
+pub struct VncInfo {
+    /// The RFB protocol version announced by the server, e.g. `RFB 003.008`.
+    pub version: String,
+    /// Security types offered by the server, e.g. `["VNC Authentication"]`.
+    pub auth_types: Vec<String>,
+    /// Why the server refused the connection, if it did.
+    pub error: Option<String>,
+    pub weak: Vec<String>,
+}
+
+impl VncInfo {
+    pub fn report(&self) -> ServiceReport {
+        ServiceReport::new("vnc")
+            .text("version", &self.version)
+            .list("auth_types", &self.auth_types)
+            .maybe("error", self.error.as_ref())
+            .list("weak", &self.weak)
+    }
+}
+
+/// Returns whether `port` is one VNC is probed on.
+pub fn applies_to(port: u16) -> bool {
+    PORTS.contains(&port)
+}
+
+/// Probes a VNC server for its protocol version and authentication types.
+///
+/// # Arguments
+///
+/// * `target` - The address and port to probe.
+///
+/// # Returns
+///
+/// `None` if the port does not speak RFB.
+pub fn probe(target: SocketAddr) -> Option<VncInfo> {
+    let mut reader = BufReader::new(probe::connect(target).ok()?);
+
+    let mut version = String::new();
+    reader.read_line(&mut version).ok()?;
+    let version = version.trim_end().to_string();
+    let (major, minor) = version.strip_prefix("RFB ")?.split_once('.')?;
+    let (major, minor): (u32, u32) = (major.parse().ok()?, minor.parse().ok()?);
+
+    let mut info = VncInfo {
+        version,
+        ..VncInfo::default()
+    };
+
+    // Answer with the highest version we both speak; 3.3 servers pick the type themselves.
+    let legacy = (major, minor) < (3, 7);
+    let reply = if legacy {
+        "RFB 003.003\n"
+    } else {
+        "RFB 003.008\n"
+    };
+    reader.get_mut().write_all(reply.as_bytes()).ok()?;
+
+    let types: Vec<u32> = if legacy {
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).ok()?;
+        vec![u32::from_be_bytes(buf)]
+    } else {
+        let mut count = [0; 1];
+        reader.read_exact(&mut count).ok()?;
+        let mut buf = vec![0; count[0] as usize];
+        reader.read_exact(&mut buf).ok()?;
+        buf.into_iter().map(u32::from).collect()
+    };
+
+    if types == [0] || types.is_empty() {
+        // The server refused the connection and sends a reason string.
+        let mut length = [0; 4];
+        if reader.read_exact(&mut length).is_ok() {
+            let mut reason = vec![0; u32::from_be_bytes(length).min(1024) as usize];
+            if reader.read_exact(&mut reason).is_ok() {
+                info.error = Some(String::from_utf8_lossy(&reason).into_owned());
+            }
+        }
+        return Some(info);
+    }
+
+    info.auth_types = types.iter().map(|&t| auth_type_name(t)).collect();
+
+    if types.contains(&1) {
+        info.weak.push("no authentication required".to_string());
+    }
+    if types.contains(&2) {
+        info.weak
+            .push("VNC Authentication (DES, 8-character passwords)".to_string());
+    }
+
+    Some(info)
+}
+
+/// Returns the IANA name of an RFB security type.
+fn auth_type_name(auth_type: u32) -> String {
+    match auth_type {
+        1 => "None",
+        2 => "VNC Authentication",
+        5 => "RA2",
+        6 => "RA2ne",
+        16 => "Tight",
+        17 => "Ultra",
+        18 => "TLS",
+        19 => "VeNCrypt",
+        20 => "SASL",
+        21 => "MD5 hash",
+        22 => "xvp",
+        30 => "Apple Remote Desktop",
+        _ => return format!("type {}", auth_type),
+    }
+    .to_string()
+}
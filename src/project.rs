@@ -0,0 +1,560 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ip_sniffer::events::Bus;
+use ip_sniffer::scan::{self, Connector, PortOrder};
+
+use crate::i18n::Lang;
+use crate::output;
+use crate::query::{self, Query, State};
+use crate::watch::Timeline;
+
+// Layout of a project directory:
+// <DIR>/targets.txt        one IP address per line
+// <DIR>/settings.txt       `key = value` scan settings
+// <DIR>/annotations.txt    `IPADDR text` notes copied into every run
+// <DIR>/results/<TS>.jsonl one file per run, named after its UNIX timestamp
+
+const TARGETS: &str = "targets.txt";
+const SETTINGS: &str = "settings.txt";
+const ANNOTATIONS: &str = "annotations.txt";
+const RESULTS: &str = "results";
+
+const DEFAULT_SETTINGS: &str = "# Scan settings used by `ip-sniffer project run`
+threads = 4
+verify = false
+";
+
+/// Settings stored in a project's `settings.txt`.
+struct Settings {
+    threads: u16,
+    verify: bool,
+}
+
+impl Settings {
+    /// Parses the contents of a `settings.txt` file.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid line in settings.txt" if a line is not a `key = value` pair.
+    /// * "failed to parse thread number" if `threads` is not a valid number.
+    /// * "verify must be true or false" if `verify` is not a boolean.
+    /// * "unknown setting in settings.txt" if the key is not recognized.
+    fn parse(contents: &str) -> Result<Settings, &'static str> {
+        let mut settings = Settings {
+            threads: 4,
+            verify: false,
+        };
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err("invalid line in settings.txt"),
+            };
+
+            match key {
+                "threads" => {
+                    settings.threads = value.parse().map_err(|_| "failed to parse thread number")?
+                }
+                "verify" => {
+                    settings.verify = value.parse().map_err(|_| "verify must be true or false")?
+                }
+                _ => return Err("unknown setting in settings.txt"),
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Runs a `project` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `project` on the command line.
+/// * `lang` - The language messages are printed in.
+///
+/// # Errors
+///
+/// * "missing project subcommand" if no subcommand or directory is given.
+/// * "unknown project subcommand" if the subcommand is not recognized.
+/// * Any error returned by the subcommand itself.
+///
+/// # Usage
+///
+/// * `init <DIR>` - Create a new project directory.
+/// * `add-target <DIR> <IPADDR>` - Add a target to the project.
+/// * `annotate <DIR> <IPADDR> <TEXT>` - Attach a note to a target, kept with every run.
+/// * `run <DIR> [--note <TEXT>]` - Scan every target and store the results, with an
+///   optional note on the run.
+/// * `report <DIR>` - Show the latest results and the changes since the previous run.
+/// * `query <DIR> <QUERY>` - Show the results of every run that match a query, e.g.
+///   `host=10.0.0.5 AND state=open SINCE 30d`.
+/// * `query <DIR> <QUERY> --transitions` - Show when each matching port was first and
+///   last seen open, and when it closed.
+/// * `prune <DIR> [--older-than <AGE>] [--keep-latest <N>] [--dry-run]` - Delete old
+///   runs, always keeping the latest N.
+pub fn run(args: &[String], lang: Lang) -> Result<(), &'static str> {
+    let (command, dir) = match args {
+        [command, dir, ..] => (command.as_str(), Path::new(dir)),
+        _ => return Err("missing project subcommand"),
+    };
+
+    match (command, &args[2..]) {
+        ("init", []) => init(dir, lang),
+        ("add-target", [target]) => add_target(dir, target, lang),
+        ("annotate", [target, text]) => annotate(dir, target, text, lang),
+        ("run", []) => run_scans(dir, None, lang),
+        ("run", [flag, note]) if flag == "--note" => run_scans(dir, Some(note), lang),
+        ("report", []) => report(dir, lang),
+        ("query", [query]) => search(dir, &Query::from_str(query)?, lang),
+        ("query", [query, flag]) if flag == "--transitions" => {
+            transitions(dir, &Query::from_str(query)?, lang)
+        }
+        ("prune", options) => prune(dir, options, lang),
+        ("init" | "add-target" | "annotate" | "run" | "report" | "query", _) => {
+            Err("wrong number of arguments")
+        }
+        _ => Err("unknown project subcommand"),
+    }
+}
+
+/// Creates the project directory with an empty target list and default settings.
+fn init(dir: &Path, lang: Lang) -> Result<(), &'static str> {
+    if dir.join(SETTINGS).exists() {
+        return Err("project already initialized");
+    }
+
+    fs::create_dir_all(dir.join(RESULTS)).map_err(|_| "failed to create project directory")?;
+    fs::write(dir.join(TARGETS), "").map_err(|_| "failed to write targets.txt")?;
+    fs::write(dir.join(SETTINGS), DEFAULT_SETTINGS).map_err(|_| "failed to write settings.txt")?;
+
+    println!(
+        "{}",
+        lang.format("initialized project in {}", &[&dir.display()])
+    );
+    Ok(())
+}
+
+/// Appends `target` to the project's target list unless it is already there.
+fn add_target(dir: &Path, target: &str, lang: Lang) -> Result<(), &'static str> {
+    let addr = IpAddr::from_str(target).map_err(|_| "not a valid IPADDR; must be IPv4 or IPv6")?;
+
+    if load_targets(dir)?.contains(&addr) {
+        println!("{}", lang.format("{} is already a target", &[&addr]));
+        return Ok(());
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join(TARGETS))
+        .map_err(|_| "failed to open targets.txt")?;
+    writeln!(file, "{}", addr).map_err(|_| "failed to write targets.txt")?;
+
+    println!("{}", lang.format("added {}", &[&addr]));
+    Ok(())
+}
+
+/// Appends a note on `target` to the project's annotations.
+fn annotate(dir: &Path, target: &str, text: &str, lang: Lang) -> Result<(), &'static str> {
+    let addr = IpAddr::from_str(target).map_err(|_| "not a valid IPADDR; must be IPv4 or IPv6")?;
+    let text = text.trim();
+
+    if text.is_empty() || text.contains('\n') {
+        return Err("annotation must be a single non-empty line");
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(ANNOTATIONS))
+        .map_err(|_| "failed to open annotations.txt")?;
+    writeln!(file, "{} {}", addr, text).map_err(|_| "failed to write annotations.txt")?;
+
+    println!("{}", lang.format("annotated {}", &[&addr]));
+    Ok(())
+}
+
+/// Scans every target of the project and stores the results as a new run.
+fn run_scans(dir: &Path, note: Option<&str>, lang: Lang) -> Result<(), &'static str> {
+    let settings = load_settings(dir)?;
+    let targets = load_targets(dir)?;
+    let annotations = load_annotations(dir)?;
+
+    if targets.is_empty() {
+        return Err("project has no targets");
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| "system clock is before 1970")?
+        .as_secs();
+    let path = dir.join(RESULTS).join(format!("{}.jsonl", timestamp));
+    let mut file = fs::File::create(&path).map_err(|_| "failed to create results file")?;
+
+    if let Some(note) = note {
+        writeln!(file, "{}", output::jsonl_note_record(None, note))
+            .map_err(|_| "failed to write results file")?;
+    }
+
+    for addr in targets {
+        for (_, text) in annotations.iter().filter(|(a, _)| *a == addr) {
+            writeln!(file, "{}", output::jsonl_note_record(Some(addr), text))
+                .map_err(|_| "failed to write results file")?;
+        }
+
+        let mut bus = Bus::new();
+        bus.subscribe(crate::health_warnings(lang, false));
+        let host_scan = scan::scan_host_with(
+            addr,
+            settings.threads,
+            Connector::default(),
+            PortOrder::Sequential,
+            None,
+            &mut bus,
+        );
+        let mut open = host_scan.open;
+
+        if host_scan.local_errors > 0 {
+            let message =
+                "warning: {} port(s) of {} could not be probed because of local network errors";
+            eprintln!(
+                "{}",
+                lang.format(message, &[&host_scan.local_errors, &addr])
+            );
+        }
+
+        if settings.verify {
+            open = scan::verify(addr, &open, &Connector::default()).0;
+        }
+        open.sort_by_key(|p| p.port);
+
+        for p in &open {
+            writeln!(file, "{}", output::jsonl_record(addr, p.port))
+                .map_err(|_| "failed to write results file")?;
+        }
+        println!(
+            "{}",
+            lang.format("{}: {} open port(s)", &[&addr, &open.len()])
+        );
+    }
+
+    println!(
+        "{}",
+        lang.format("results stored in {}", &[&path.display()])
+    );
+    Ok(())
+}
+
+/// Prints the open ports of the latest run, and what changed since the run before it.
+fn report(dir: &Path, lang: Lang) -> Result<(), &'static str> {
+    let runs = load_runs(dir)?;
+
+    let (latest_path, latest) = match runs.last() {
+        Some(run) => run,
+        None => return Err("project has no results; use `project run` first"),
+    };
+    let previous = runs.len().checked_sub(2).map(|i| &runs[i].1);
+
+    let message = "{} run(s); latest is {}";
+    println!(
+        "{}",
+        lang.format(message, &[&runs.len(), &latest_path.display()])
+    );
+
+    let contents = fs::read_to_string(latest_path).map_err(|_| "failed to read results file")?;
+    let notes: Vec<(Option<IpAddr>, String)> = contents
+        .lines()
+        .filter_map(output::parse_jsonl_note_record)
+        .collect();
+
+    for (_, note) in notes.iter().filter(|(addr, _)| addr.is_none()) {
+        println!("{}", lang.format("note: {}", &[note]));
+    }
+
+    let empty = BTreeSet::new();
+    let mut hosts: BTreeSet<&IpAddr> = latest.keys().collect();
+    hosts.extend(previous.iter().flat_map(|run| run.keys()));
+
+    for addr in hosts {
+        let now = latest.get(addr).unwrap_or(&empty);
+        let ports: Vec<String> = now.iter().map(u16::to_string).collect();
+        println!("\n{}: {}", addr, ports.join(", "));
+
+        for (_, note) in notes.iter().filter(|(a, _)| a.as_ref() == Some(addr)) {
+            println!("{}", lang.format("  note: {}", &[note]));
+        }
+
+        if let Some(previous) = previous {
+            let before = previous.get(addr).unwrap_or(&empty);
+
+            for port in now.difference(before) {
+                let message = "  + {} opened since the previous run";
+                println!("{}", lang.format(message, &[port]));
+            }
+            for port in before.difference(now) {
+                let message = "  - {} closed since the previous run";
+                println!("{}", lang.format(message, &[port]));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the results of every run that match `query`, oldest first, as
+/// `TIMESTAMP HOST:PORT STATE` lines.
+fn search(dir: &Path, query: &Query, lang: Lang) -> Result<(), &'static str> {
+    let runs = load_runs(dir)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| "system clock is before 1970")?
+        .as_secs();
+    let oldest = query.since.map_or(0, |age| now.saturating_sub(age));
+
+    let empty = BTreeSet::new();
+    let mut matches = 0;
+    for (i, (path, run)) in runs.iter().enumerate() {
+        let timestamp: u64 = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok())
+            .unwrap_or(0);
+        if timestamp < oldest {
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|i| &runs[i].1);
+
+        let mut hosts: BTreeSet<&IpAddr> = run.keys().collect();
+        hosts.extend(previous.iter().flat_map(|run| run.keys()));
+
+        for &addr in hosts {
+            let now = run.get(&addr).unwrap_or(&empty);
+            let before = previous.and_then(|run| run.get(&addr)).unwrap_or(&empty);
+
+            let open = now.iter().map(|&port| (port, State::Open));
+            let closed = before.difference(now).map(|&port| (port, State::Closed));
+            let mut ports: Vec<(u16, State)> = open.chain(closed).collect();
+            ports.sort_by_key(|(port, _)| *port);
+
+            for (port, state) in ports {
+                if query.matches(addr, port, state) {
+                    let target = SocketAddr::new(addr, port);
+                    println!("{} {} {}", timestamp, target, state.as_str());
+                    matches += 1;
+                }
+            }
+        }
+    }
+
+    let message = "{} result(s) in {} run(s)";
+    println!("{}", lang.format(message, &[&matches, &runs.len()]));
+    Ok(())
+}
+
+/// Prints when each port matching `query` opened and closed across every run, as
+/// `HOST:PORT STATE first_seen=TIMESTAMP last_seen=TIMESTAMP closed_at=TIMESTAMP` lines.
+///
+/// # Description
+///
+/// A port's state is its state in the latest run, and only ports that were open or
+/// closed since the age of `SINCE` are shown. A port that reopened is shown from the
+/// run it last opened in; `closed_at` is `-` while it is open.
+fn transitions(dir: &Path, query: &Query, lang: Lang) -> Result<(), &'static str> {
+    let runs = run_paths(dir)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| "system clock is before 1970")?
+        .as_secs();
+    let oldest = query.since.map_or(0, |age| now.saturating_sub(age));
+
+    let mut timelines: BTreeMap<(IpAddr, u16), Timeline> = BTreeMap::new();
+    for ((timestamp, _), (_, run)) in runs.iter().zip(load_runs(dir)?) {
+        let open: BTreeSet<(IpAddr, u16)> = run
+            .iter()
+            .flat_map(|(&addr, ports)| ports.iter().map(move |&port| (addr, port)))
+            .collect();
+        Timeline::track(&mut timelines, &open, *timestamp);
+    }
+
+    let mut matches = 0;
+    for (&(addr, port), timeline) in &timelines {
+        let state = match timeline.closed_at {
+            Some(_) => State::Closed,
+            None => State::Open,
+        };
+        let recent = timeline.closed_at.unwrap_or(timeline.last_seen) >= oldest;
+        if !recent || !query.matches(addr, port, state) {
+            continue;
+        }
+
+        let closed_at = timeline
+            .closed_at
+            .map_or("-".to_string(), |time| time.to_string());
+        println!(
+            "{} {} first_seen={} last_seen={} closed_at={}",
+            SocketAddr::new(addr, port),
+            state.as_str(),
+            timeline.first_seen,
+            timeline.last_seen,
+            closed_at
+        );
+        matches += 1;
+    }
+
+    let message = "{} port(s) in {} run(s)";
+    println!("{}", lang.format(message, &[&matches, &runs.len()]));
+    Ok(())
+}
+
+/// Deletes the stored runs older than `--older-than`, or beyond the latest
+/// `--keep-latest`, or both; the latest runs are kept even if they are older.
+///
+/// # Errors
+///
+/// * "prune needs --older-than or --keep-latest" if neither is given.
+/// * "invalid age, e.g. 90d" if `--older-than` is not an age.
+/// * "--keep-latest must be a number" if it is not.
+/// * "unknown prune option" for any other option.
+/// * "failed to delete results file" if a run cannot be deleted.
+fn prune(dir: &Path, options: &[String], lang: Lang) -> Result<(), &'static str> {
+    let mut older_than = None;
+    let mut keep_latest = None;
+    let mut dry_run = false;
+    let mut rest = options.iter();
+
+    while let Some(option) = rest.next() {
+        match option.as_str() {
+            "--older-than" => {
+                let age = rest.next().and_then(|age| query::parse_age(age));
+                older_than = Some(age.ok_or("invalid age, e.g. 90d")?);
+            }
+            "--keep-latest" => {
+                let count = rest.next().and_then(|count| count.parse().ok());
+                keep_latest = Some(count.ok_or("--keep-latest must be a number")?);
+            }
+            "--dry-run" => dry_run = true,
+            _ => return Err("unknown prune option"),
+        }
+    }
+    if older_than.is_none() && keep_latest.is_none() {
+        return Err("prune needs --older-than or --keep-latest");
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| "system clock is before 1970")?
+        .as_secs();
+    let oldest = older_than.map(|age| now.saturating_sub(age));
+
+    let runs = run_paths(dir)?;
+    let kept = keep_latest.unwrap_or(0).min(runs.len());
+    let candidates = &runs[..runs.len() - kept];
+
+    let mut pruned = 0;
+    for (timestamp, path) in candidates {
+        if oldest.is_some_and(|oldest| *timestamp >= oldest) {
+            continue;
+        }
+
+        if dry_run {
+            println!("{}", lang.format("would delete {}", &[&path.display()]));
+        } else {
+            fs::remove_file(path).map_err(|_| "failed to delete results file")?;
+            println!("{}", lang.format("deleted {}", &[&path.display()]));
+        }
+        pruned += 1;
+    }
+
+    let message = if dry_run {
+        "{} of {} run(s) would be deleted"
+    } else {
+        "{} of {} run(s) deleted"
+    };
+    println!("{}", lang.format(message, &[&pruned, &runs.len()]));
+    Ok(())
+}
+
+fn load_settings(dir: &Path) -> Result<Settings, &'static str> {
+    let contents =
+        fs::read_to_string(dir.join(SETTINGS)).map_err(|_| "not a project; use `project init`")?;
+    Settings::parse(&contents)
+}
+
+fn load_targets(dir: &Path) -> Result<Vec<IpAddr>, &'static str> {
+    let contents =
+        fs::read_to_string(dir.join(TARGETS)).map_err(|_| "not a project; use `project init`")?;
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| IpAddr::from_str(line).map_err(|_| "invalid IPADDR in targets.txt"))
+        .collect()
+}
+
+/// Loads the project's annotations, or none if it has no annotations file.
+fn load_annotations(dir: &Path) -> Result<Vec<(IpAddr, String)>, &'static str> {
+    let contents = match fs::read_to_string(dir.join(ANNOTATIONS)) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(vec![]),
+    };
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (addr, text) = line
+                .split_once(' ')
+                .ok_or("invalid line in annotations.txt")?;
+            let addr = IpAddr::from_str(addr).map_err(|_| "invalid IPADDR in annotations.txt")?;
+            Ok((addr, text.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Lists the results file of every stored run with its timestamp, oldest first.
+fn run_paths(dir: &Path) -> Result<Vec<(u64, PathBuf)>, &'static str> {
+    let entries =
+        fs::read_dir(dir.join(RESULTS)).map_err(|_| "not a project; use `project init`")?;
+
+    let mut paths: Vec<(u64, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let timestamp = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((timestamp, path))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Open ports per host for one run.
+type Run = BTreeMap<IpAddr, BTreeSet<u16>>;
+
+/// Loads every stored run, oldest first.
+fn load_runs(dir: &Path) -> Result<Vec<(PathBuf, Run)>, &'static str> {
+    run_paths(dir)?
+        .into_iter()
+        .map(|(_, path)| {
+            let contents = fs::read_to_string(&path).map_err(|_| "failed to read results file")?;
+            let mut run = Run::new();
+
+            for (addr, port) in contents.lines().filter_map(output::parse_jsonl_record) {
+                run.entry(addr).or_default().insert(port);
+            }
+            Ok((path, run))
+        })
+        .collect()
+}
@@ -0,0 +1,245 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::str::FromStr;
+use std::time::Duration;
+
+use ip_sniffer::idna;
+
+use crate::probe::dns;
+use crate::target::{self, UrlTarget};
+
+// Targets given with `--targets` come from a source: a file with one target per line,
+// `-` for the same on standard input, or `axfr:ZONE@SERVER` for the addresses of a DNS
+// zone. Target files may hold comments:
+//
+// # web tier
+// 203.0.113.5
+// www.example.com
+
+/// How long to wait for the DNS server of a zone transfer to accept the connection.
+const AXFR_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for each message of a zone transfer.
+const AXFR_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+const TYPE_AAAA: u16 = 28;
+const TYPE_SOA: u16 = 6;
+const TYPE_AXFR: u16 = 252;
+
+/// A source of targets to scan.
+///
+/// # Usage
+///
+/// * `provider.describe()` - Name the source in messages, as it was given, e.g. `hosts.txt`.
+/// * `provider.targets()` - Read the targets, as they would be given as IPADDR.
+///
+/// Every target a provider returns is scanned on its own, as if it were given on the
+/// command line, so new sources only need to list targets.
+pub trait TargetProvider {
+    fn describe(&self) -> String;
+
+    /// Returns the targets of the source.
+    ///
+    /// # Errors
+    ///
+    /// A description of why the source could not be read.
+    fn targets(&self) -> Result<Vec<Target>, &'static str>;
+}
+
+/// A target of a source.
+#[derive(Debug)]
+pub struct Target {
+    /// An IP address, hostname or URL.
+    pub name: String,
+    /// What the target belongs to, e.g. the ID of a cloud instance, if the source knows.
+    pub resource: Option<String>,
+}
+
+impl Target {
+    pub fn new(name: &str) -> Target {
+        Target {
+            name: name.to_string(),
+            resource: None,
+        }
+    }
+}
+
+/// Targets given on the command line.
+pub struct CommandLine(pub Vec<String>);
+
+/// Targets listed in a file, one per line.
+pub struct TargetFile(pub String);
+
+/// Targets listed on standard input, one per line.
+pub struct Stdin;
+
+/// The addresses of a DNS zone, read with a zone transfer (AXFR).
+///
+/// # Description
+///
+/// Zone transfers are only answered for servers and zones that allow them, and should
+/// only be requested from servers one is permitted to query this way; servers refusing
+/// the transfer make `targets` fail.
+pub struct ZoneTransfer {
+    pub zone: String,
+    /// The DNS server, as an IP address or hostname, with an optional port.
+    pub server: String,
+}
+
+/// Returns the provider of a `--targets` source.
+///
+/// # Errors
+///
+/// * "not a valid zone transfer source; must be axfr:ZONE@SERVER" if an `axfr:` source
+///   lacks the zone or server.
+pub fn from_source(source: &str) -> Result<Box<dyn TargetProvider>, &'static str> {
+    if source == "-" {
+        return Ok(Box::new(Stdin));
+    }
+    if let Some(rest) = source.strip_prefix("axfr:") {
+        return match rest.split_once('@') {
+            Some((zone, server)) if !zone.is_empty() && !server.is_empty() => {
+                Ok(Box::new(ZoneTransfer {
+                    zone: zone.trim_end_matches('.').to_string(),
+                    server: server.to_string(),
+                }))
+            }
+            _ => Err("not a valid zone transfer source; must be axfr:ZONE@SERVER"),
+        };
+    }
+    Ok(Box::new(TargetFile(source.to_string())))
+}
+
+impl TargetProvider for CommandLine {
+    fn describe(&self) -> String {
+        self.0.join(" ")
+    }
+
+    fn targets(&self) -> Result<Vec<Target>, &'static str> {
+        Ok(self.0.iter().map(|name| Target::new(name)).collect())
+    }
+}
+
+impl TargetProvider for TargetFile {
+    fn describe(&self) -> String {
+        self.0.clone()
+    }
+
+    fn targets(&self) -> Result<Vec<Target>, &'static str> {
+        let contents = fs::read_to_string(&self.0).map_err(|_| "failed to read targets file")?;
+        parse_list(&contents)
+    }
+}
+
+impl TargetProvider for Stdin {
+    fn describe(&self) -> String {
+        "-".to_string()
+    }
+
+    fn targets(&self) -> Result<Vec<Target>, &'static str> {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(|_| "failed to read targets from standard input")?;
+        parse_list(&contents)
+    }
+}
+
+impl TargetProvider for ZoneTransfer {
+    fn describe(&self) -> String {
+        format!("axfr:{}@{}", self.zone, self.server)
+    }
+
+    /// Returns every distinct address of the zone's A and AAAA records, in the order the
+    /// server sent them.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to resolve hostname" if the server's name does not resolve.
+    /// * "failed to connect to the DNS server" if the server cannot be reached.
+    /// * "zone transfer refused" if the server answers with an error.
+    /// * "zone transfer failed" if the transfer ends early or cannot be parsed.
+    fn targets(&self) -> Result<Vec<Target>, &'static str> {
+        let server = server_addr(&self.server)?;
+        let mut stream = TcpStream::connect_timeout(&server, AXFR_CONNECT_TIMEOUT)
+            .map_err(|_| "failed to connect to the DNS server")?;
+        stream.set_read_timeout(Some(AXFR_READ_TIMEOUT)).ok();
+
+        let query = dns::build_query(0x2001, &self.zone, TYPE_AXFR, dns::CLASS_IN, false);
+        let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&query);
+        stream
+            .write_all(&framed)
+            .map_err(|_| "failed to connect to the DNS server")?;
+
+        // The zone starts and ends with its SOA record, possibly over many messages.
+        let mut addrs: Vec<IpAddr> = vec![];
+        let mut soas = 0;
+        while soas < 2 {
+            let message = dns::read_tcp_message(&mut stream).ok_or("zone transfer failed")?;
+            if message[3] & 0x0f != 0 {
+                return Err("zone transfer refused");
+            }
+
+            for record in dns::answers(&message).ok_or("zone transfer failed")? {
+                let addr = match (record.rtype, record.data.len()) {
+                    (dns::TYPE_A, 4) => <[u8; 4]>::try_from(record.data).ok().map(IpAddr::from),
+                    (TYPE_AAAA, 16) => <[u8; 16]>::try_from(record.data).ok().map(IpAddr::from),
+                    (TYPE_SOA, _) => {
+                        soas += 1;
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(addr) = addr.filter(|addr| !addrs.contains(addr)) {
+                    addrs.push(addr);
+                }
+            }
+        }
+
+        Ok(addrs
+            .iter()
+            .map(|addr| Target::new(&addr.to_string()))
+            .collect())
+    }
+}
+
+/// Parses a list of targets, one per line, skipping blank lines and `#` comments.
+///
+/// # Errors
+///
+/// * "invalid target; must be an IP address, hostname or URL" if a line is none of them.
+fn parse_list(contents: &str) -> Result<Vec<Target>, &'static str> {
+    let mut targets = vec![];
+
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let hostname = idna::to_ascii(line).is_ok_and(|name| {
+            name.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+        });
+        let url = line.contains("://") && UrlTarget::from_str(line).is_ok();
+        if IpAddr::from_str(line).is_err() && !hostname && !url {
+            return Err("invalid target; must be an IP address, hostname or URL");
+        }
+        targets.push(Target::new(line));
+    }
+    Ok(targets)
+}
+
+/// Returns the address of a DNS server given as `HOST` or `HOST:PORT`.
+fn server_addr(server: &str) -> Result<SocketAddr, &'static str> {
+    if let Ok(addr) = IpAddr::from_str(server) {
+        return Ok(SocketAddr::new(addr, dns::PORT));
+    }
+    if let Ok(addr) = target::parse_host_port(server) {
+        return Ok(addr);
+    }
+    Ok(SocketAddr::new(target::resolve(server)?[0], dns::PORT))
+}
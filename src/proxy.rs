@@ -0,0 +1,160 @@
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::base64;
+
+/// How long the proxy may take to answer a CONNECT request when no timeout is given.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum size of the proxy's response headers.
+const MAX_RESPONSE: usize = 8192;
+
+/// An HTTP proxy that tunnels connections with the CONNECT method.
+#[derive(Clone, Debug)]
+pub struct Proxy {
+    /// The address of the proxy itself.
+    pub addr: SocketAddr,
+    /// The `Proxy-Authorization` header value, if credentials were given.
+    authorization: Option<String>,
+}
+
+impl FromStr for Proxy {
+    type Err = &'static str;
+
+    /// Parses `http://[USER:PASSWORD@]HOST[:PORT]`; the port defaults to 80.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("http://")
+            .ok_or("proxy must be an http:// URL")?;
+        let rest = rest.trim_end_matches('/');
+
+        let (credentials, host) = match rest.rsplit_once('@') {
+            Some((credentials, host)) => (Some(credentials), host),
+            None => (None, rest),
+        };
+        if let Some(credentials) = credentials {
+            if !credentials.contains(':') {
+                return Err("proxy credentials must be USER:PASSWORD");
+            }
+        }
+
+        let has_port = match host.rsplit_once(':') {
+            Some((name, port)) => !name.ends_with(':') && port.parse::<u16>().is_ok(),
+            None => false,
+        };
+        let host = if has_port {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+
+        let addr = host
+            .to_socket_addrs()
+            .map_err(|_| "failed to resolve proxy address")?
+            .next()
+            .ok_or("failed to resolve proxy address")?;
+
+        Ok(Proxy {
+            addr,
+            authorization: credentials.map(|c| format!("Basic {}", base64::encode(c.as_bytes()))),
+        })
+    }
+}
+
+impl Proxy {
+    /// Checks that the proxy can be reached and accepts our credentials, so a scan does
+    /// not start only to have every probe fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The host to be scanned; a tunnel to its port 80 is requested.
+    ///
+    /// # Errors
+    ///
+    /// * "proxy cannot be reached" if connecting to the proxy fails.
+    /// * "proxy authentication failed" if the proxy answers 407.
+    pub fn check(&self, target: IpAddr) -> Result<(), &'static str> {
+        match self.connect(SocketAddr::new(target, 80), None) {
+            Err(err) if err.kind() == io::ErrorKind::NetworkDown => Err("proxy cannot be reached"),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                Err("proxy authentication failed")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Opens a tunnel to `target` through the proxy.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The address and port to connect to.
+    /// * `timeout` - Timeout for reaching the proxy and for its answer.
+    ///
+    /// # Errors
+    ///
+    /// The proxy's answer is mapped to the error a direct connection would have given:
+    ///
+    /// * `ConnectionRefused` for 502 and 503, which proxies send when the target refused.
+    /// * `TimedOut` for 504, or if the proxy does not answer in time.
+    /// * `PermissionDenied` for 407, when the credentials are missing or wrong.
+    /// * `NetworkDown` if the proxy itself cannot be reached, so the failure is
+    ///   treated as a local error rather than a closed port.
+    /// * `Other` for any other status, e.g. 403 when the proxy does not allow the port.
+    pub fn connect(&self, target: SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+        let timeout = timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let mut stream = TcpStream::connect_timeout(&self.addr, timeout)
+            .map_err(|err| io::Error::new(io::ErrorKind::NetworkDown, format!("proxy: {}", err)))?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+        if let Some(authorization) = &self.authorization {
+            request += &format!("Proxy-Authorization: {}\r\n", authorization);
+        }
+        request += "\r\n";
+        stream.write_all(request.as_bytes())?;
+
+        let status = read_status(&mut stream).map_err(|err| match err.kind() {
+            io::ErrorKind::WouldBlock => io::ErrorKind::TimedOut.into(),
+            _ => err,
+        })?;
+
+        // The tunnel uses whatever timeouts the caller sets next.
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(None)?;
+
+        match status {
+            200..=299 => Ok(stream),
+            502 | 503 => Err(io::ErrorKind::ConnectionRefused.into()),
+            504 => Err(io::ErrorKind::TimedOut.into()),
+            407 => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "proxy authentication required",
+            )),
+            status => Err(io::Error::other(format!("proxy answered {}", status))),
+        }
+    }
+}
+
+/// Reads the proxy's response headers and returns the status code.
+///
+/// Bytes are read one at a time so nothing after the headers is consumed.
+fn read_status(stream: &mut TcpStream) -> io::Result<u16> {
+    let mut head = vec![];
+    let mut byte = [0; 1];
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE || stream.read(&mut byte)? == 0 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        head.push(byte[0]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    head.split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or(io::ErrorKind::InvalidData.into())
+}
@@ -0,0 +1,164 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::policy::Cidr;
+
+// Queries select results from the runs of a project. A query is conditions joined by
+// AND, optionally followed by SINCE and an age:
+//
+// host=10.0.0.5 AND state=open SINCE 30d
+// host=10.0.0.0/24 AND port=8000-8999
+// port!=22 AND state=closed SINCE 12h
+//
+// host      an IP address or network in CIDR notation
+// port      a port or inclusive range
+// state     open, or closed for ports open in the run before and not in this one
+//
+// `=` and `!=` are the only operators; ages are in s, m, h, d or w.
+
+/// A state of a port in the results of a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Open,
+    /// Open in the previous run and not in this one.
+    Closed,
+}
+
+impl State {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            State::Open => "open",
+            State::Closed => "closed",
+        }
+    }
+}
+
+/// What a condition compares.
+#[derive(Debug)]
+enum Test {
+    Host(Cidr),
+    Port(u16, u16),
+    State(State),
+}
+
+/// A parsed query.
+///
+/// # Usage
+///
+/// * `Query::from_str(text)` - Parse a query.
+/// * `query.since` - The age results must be younger than, in seconds.
+/// * `query.matches(addr, port, state)` - Whether a result satisfies every condition.
+#[derive(Debug, Default)]
+pub struct Query {
+    /// Conditions and whether each is negated.
+    tests: Vec<(Test, bool)>,
+    pub since: Option<u64>,
+}
+
+impl FromStr for Query {
+    type Err = &'static str;
+
+    /// Parses a query such as `host=10.0.0.5 AND state=open SINCE 30d`.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid query; must be FIELD=VALUE conditions joined by AND, then SINCE AGE" if
+    ///   it is not one.
+    /// * "unknown query field; must be host, port or state" for any other field.
+    /// * "invalid host in query; must be an IP address or CIDR network" if a host is not.
+    /// * "invalid port in query; must be a port or range" if a port is not.
+    /// * "invalid state in query; must be open or closed" if a state is neither.
+    /// * "invalid age in query, e.g. 30d" if the age of SINCE is not one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "invalid query; must be FIELD=VALUE conditions joined by AND, then SINCE AGE";
+        let mut query = Query::default();
+        let mut words = s.split_whitespace().peekable();
+
+        while let Some(word) = words.next() {
+            if word.eq_ignore_ascii_case("SINCE") {
+                let age = words.next().ok_or(invalid)?;
+                query.since = Some(parse_age(age).ok_or("invalid age in query, e.g. 30d")?);
+                if words.next().is_some() {
+                    return Err(invalid);
+                }
+                break;
+            }
+
+            let (field, negated, value) = match word.split_once("!=") {
+                Some((field, value)) => (field, true, value),
+                None => {
+                    let (field, value) = word.split_once('=').ok_or(invalid)?;
+                    (field, false, value)
+                }
+            };
+            query.tests.push((parse_test(field, value)?, negated));
+
+            match words.peek() {
+                Some(next) if next.eq_ignore_ascii_case("AND") => {
+                    words.next();
+                    if words.peek().is_none() {
+                        return Err(invalid);
+                    }
+                }
+                Some(next) if next.eq_ignore_ascii_case("SINCE") => {}
+                Some(_) => return Err(invalid),
+                None => {}
+            }
+        }
+        Ok(query)
+    }
+}
+
+impl Query {
+    /// Returns whether a port of a run satisfies every condition of the query.
+    pub fn matches(&self, addr: IpAddr, port: u16, state: State) -> bool {
+        self.tests.iter().all(|(test, negated)| {
+            let matched = match *test {
+                Test::Host(network) => network.contains(addr),
+                Test::Port(first, last) => (first..=last).contains(&port),
+                Test::State(wanted) => state == wanted,
+            };
+            matched != *negated
+        })
+    }
+}
+
+/// Parses the value of a condition on `field`.
+fn parse_test(field: &str, value: &str) -> Result<Test, &'static str> {
+    match field.to_ascii_lowercase().as_str() {
+        "host" => Cidr::from_str(value)
+            .map(Test::Host)
+            .map_err(|_| "invalid host in query; must be an IP address or CIDR network"),
+        "port" => {
+            let invalid = "invalid port in query; must be a port or range";
+            let (first, last) = value.split_once('-').unwrap_or((value, value));
+            let first: u16 = first.parse().map_err(|_| invalid)?;
+            let last: u16 = last.parse().map_err(|_| invalid)?;
+            if first == 0 || first > last {
+                return Err(invalid);
+            }
+            Ok(Test::Port(first, last))
+        }
+        "state" => match value.to_ascii_lowercase().as_str() {
+            "open" => Ok(Test::State(State::Open)),
+            "closed" => Ok(Test::State(State::Closed)),
+            _ => Err("invalid state in query; must be open or closed"),
+        },
+        _ => Err("unknown query field; must be host, port or state"),
+    }
+}
+
+/// Parses an age such as `30d` into seconds.
+pub fn parse_age(text: &str) -> Option<u64> {
+    let i = text.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = text.split_at(i);
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(secs)
+}
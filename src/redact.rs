@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::process;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::hash;
+use crate::i18n::Lang;
+
+// `redact` replaces the IP addresses and host names in a report, text or JSON Lines, with
+// opaque tokens so it can be shared without showing the layout of the network:
+//
+// 10.0.0.5                ip-3f9a0c21
+// 10.0.0.5:443            ip-3f9a0c21:443
+// db.example.com          host-8b1e44d0
+//
+// A token is a salted hash of what it replaces, so the same host gets the same token in
+// every report redacted with the same key file. The key file holds the salt and every
+// token with what it replaced, one per line, and lets the operator reveal a redacted
+// report again:
+//
+// salt 5c0d...e1
+// ip-3f9a0c21 10.0.0.5
+// host-8b1e44d0 db.example.com
+//
+// Anything that looks like a host name is redacted, so some file names are as well.
+
+/// Hex digits of the hash kept in a token.
+const TOKEN_DIGITS: usize = 8;
+
+/// The key of a redaction: its salt and the tokens handed out with it.
+struct Key {
+    salt: String,
+    tokens: BTreeMap<String, String>,
+}
+
+impl Key {
+    /// Reads a key file.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid key file; must start with a salt line" if it is not one.
+    fn load(path: &str) -> Result<Option<Key>, &'static str> {
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Ok(None);
+        };
+        let invalid = "invalid key file; must start with a salt line";
+        let mut lines = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let salt = match lines.next().and_then(|line| line.split_once(' ')) {
+            Some(("salt", salt)) => salt.trim().to_string(),
+            _ => return Err(invalid),
+        };
+        let tokens = lines
+            .filter_map(|line| line.split_once(' '))
+            .map(|(token, value)| (token.to_string(), value.trim().to_string()))
+            .collect();
+        Ok(Some(Key { salt, tokens }))
+    }
+
+    /// Creates a key with a new random salt.
+    fn new() -> Key {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let mut seed = nanos.to_be_bytes().to_vec();
+        seed.extend(process::id().to_be_bytes());
+
+        Key {
+            salt: hex(&hash::sha256(&seed)),
+            tokens: BTreeMap::new(),
+        }
+    }
+
+    fn save(&self, path: &str) -> Result<(), &'static str> {
+        let mut text = String::from(
+            "# ip-sniffer redaction key; keep it private, it reveals redacted reports\n",
+        );
+        text.push_str(&format!("salt {}\n", self.salt));
+        for (token, value) in &self.tokens {
+            text.push_str(&format!("{} {}\n", token, value));
+        }
+        fs::write(path, text).map_err(|_| "failed to write key file")
+    }
+
+    /// Returns the token of an address or host name, recording it in the key.
+    fn token(&mut self, kind: &str, value: &str) -> String {
+        let digest = hash::sha256(format!("{}{}", self.salt, value).as_bytes());
+        let token = format!("{}-{}", kind, &hex(&digest)[..TOKEN_DIGITS]);
+        self.tokens.insert(token.clone(), value.to_string());
+        token
+    }
+}
+
+/// Runs the `redact` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `redact` on the command line.
+/// * `lang` - The language the summary is printed in.
+///
+/// # Returns
+///
+/// The exit code, 0.
+///
+/// # Errors
+///
+/// * "missing report file" if no report, or more than one, is given.
+/// * "missing --key FILE" if no key file is given.
+/// * "missing output file" if `-o` has no file name.
+/// * "failed to read report file" if the report cannot be read.
+/// * "key file not found" if `--reveal` is given a key file that does not exist.
+/// * "failed to write key file" if the key file cannot be written.
+/// * "failed to write redacted report" if the output cannot be written.
+/// * Any error returned by `Key::load`.
+///
+/// # Usage
+///
+/// * `redact <FILE> --key <FILE>` - Redact a report to stdout, creating the key file if
+///   it does not exist.
+/// * `redact <FILE> --key <FILE> -o <FILE>` - Redact a report into a file.
+/// * `redact --reveal <FILE> --key <FILE>` - Put back what a report's tokens replaced.
+pub fn run(args: &[String], lang: Lang) -> Result<i32, &'static str> {
+    let mut input = None;
+    let mut key_path = None;
+    let mut out = None;
+    let mut reveal = false;
+    let mut rest = args.iter();
+
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--key" => key_path = Some(rest.next().ok_or("missing --key FILE")?),
+            "-o" | "--output" => out = Some(rest.next().ok_or("missing output file")?),
+            "--reveal" => reveal = true,
+            path if input.is_none() => input = Some(path),
+            _ => return Err("missing report file"),
+        }
+    }
+    let input = input.ok_or("missing report file")?;
+    let key_path = key_path.ok_or("missing --key FILE")?;
+    let report = fs::read_to_string(input).map_err(|_| "failed to read report file")?;
+
+    let (text, summary) = if reveal {
+        let key = Key::load(key_path)?.ok_or("key file not found")?;
+        let mut revealed = 0;
+        let text = rewrite(&report, |token| {
+            let value = key.tokens.get(token).cloned();
+            revealed += value.is_some() as usize;
+            value
+        });
+        (text, lang.format("revealed {} token(s)", &[&revealed]))
+    } else {
+        let mut key = Key::load(key_path)?.unwrap_or_else(Key::new);
+        let (mut addresses, mut names) = (0, 0);
+        let text = rewrite(&report, |word| {
+            if let Ok(addr) = IpAddr::from_str(word) {
+                addresses += 1;
+                Some(key.token("ip", &addr.to_string()))
+            } else if is_host_name(word) {
+                names += 1;
+                Some(key.token("host", &word.to_ascii_lowercase()))
+            } else {
+                None
+            }
+        });
+        key.save(key_path)?;
+        let message = "redacted {} address(es) and {} host name(s)";
+        let summary = lang.format(message, &[&addresses, &names]);
+        (text, summary)
+    };
+
+    match out {
+        Some(path) => fs::write(path, text).map_err(|_| "failed to write redacted report")?,
+        None => print!("{}", text),
+    }
+    eprintln!("{}", summary);
+    Ok(0)
+}
+
+/// Replaces every word of `text` that `replace` returns a replacement for. A word is a run
+/// of letters, digits, `.`, `-` and `:`; a `:PORT` at its end is kept as it is.
+fn rewrite(text: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':');
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let end = rest.find(|c| !is_word(c)).unwrap_or(rest.len());
+        // A word may end a sentence.
+        let word = rest[..end].trim_end_matches(['.', ':']);
+
+        if word.is_empty() {
+            let gap = rest[end..]
+                .chars()
+                .next()
+                .map_or(end, |c| end + c.len_utf8());
+            out.push_str(&rest[..gap]);
+            rest = &rest[gap..];
+            continue;
+        }
+
+        let replaced = replace(word).or_else(|| {
+            let (host, port) = word.rsplit_once(':')?;
+            port.parse::<u16>().ok()?;
+            Some(format!("{}:{}", replace(host)?, port))
+        });
+        out.push_str(replaced.as_deref().unwrap_or(word));
+        rest = &rest[word.len()..];
+    }
+    out
+}
+
+/// Returns whether `word` looks like a host name: two or more labels, the last of them
+/// letters only, e.g. `db.example.com` or `printer.local`.
+fn is_host_name(word: &str) -> bool {
+    let labels: Vec<&str> = word.split('.').collect();
+    let valid = |label: &str| {
+        (1..=63).contains(&label.len())
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+    let top = labels.last().copied().unwrap_or_default();
+
+    labels.len() >= 2
+        && labels.iter().all(|label| valid(label))
+        && top.len() >= 2
+        && top.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,342 @@
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::probe::ServiceReport;
+
+// Risk rule files map ports or port ranges, optionally limited to hosts reachable from the
+// internet or to internal ones, to a risk level. The first matching rule wins, and rules
+// from the file are tried before the built-in ones:
+//
+// # RDP is fine on the LAN but must never face the internet
+// 3389 internal: low
+// 3389 internet: critical
+// 8000-8099: info
+
+/// Built-in rules, as `(first port, last port, exposure, level)`.
+const BUILTIN: &[(u16, u16, Exposure, Level)] = &[
+    (23, 23, Exposure::Internet, Level::Critical),
+    (23, 23, Exposure::Any, Level::High),
+    (21, 21, Exposure::Any, Level::Medium),
+    (139, 139, Exposure::Internet, Level::High),
+    (445, 445, Exposure::Internet, Level::High),
+    (3389, 3389, Exposure::Internet, Level::High),
+    (3389, 3389, Exposure::Any, Level::Medium),
+    (5900, 5999, Exposure::Internet, Level::High),
+    (5900, 5999, Exposure::Any, Level::Medium),
+    (1433, 1433, Exposure::Internet, Level::High),
+    (3306, 3306, Exposure::Internet, Level::High),
+    (5432, 5432, Exposure::Internet, Level::High),
+    (6379, 6379, Exposure::Internet, Level::High),
+    (9200, 9200, Exposure::Internet, Level::High),
+    (11211, 11211, Exposure::Internet, Level::High),
+    (27017, 27017, Exposure::Internet, Level::High),
+    (1433, 1433, Exposure::Any, Level::Medium),
+    (3306, 3306, Exposure::Any, Level::Medium),
+    (5432, 5432, Exposure::Any, Level::Medium),
+    (6379, 6379, Exposure::Any, Level::Medium),
+    (9200, 9200, Exposure::Any, Level::Medium),
+    (11211, 11211, Exposure::Any, Level::Medium),
+    (27017, 27017, Exposure::Any, Level::Medium),
+    (443, 443, Exposure::Any, Level::Info),
+    (8443, 8443, Exposure::Any, Level::Info),
+];
+
+/// Level of an open port no rule matches.
+const DEFAULT_LEVEL: Level = Level::Low;
+
+/// How much an open port adds to the attack surface of a host, from least to most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Level {
+    /// Every level, from most to least severe, in the order summaries list them.
+    pub const ALL: [Level; 5] = [
+        Level::Critical,
+        Level::High,
+        Level::Medium,
+        Level::Low,
+        Level::Info,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Low => "low",
+            Level::Medium => "medium",
+            Level::High => "high",
+            Level::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.as_str() == s)
+            .ok_or("unknown risk level; must be info, low, medium, high or critical")
+    }
+}
+
+/// Which hosts a rule applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exposure {
+    Any,
+    /// Hosts with a globally routable address.
+    Internet,
+    /// Hosts with a private, loopback or otherwise non-routable address.
+    Internal,
+}
+
+/// A rule assigning a risk level to open ports.
+#[derive(Debug)]
+struct Rule {
+    first: u16,
+    last: u16,
+    exposure: Exposure,
+    level: Level,
+}
+
+/// The rules risk levels are assigned by.
+#[derive(Debug)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+/// The risk level assigned to an open port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub port: u16,
+    pub level: Level,
+}
+
+/// The number of findings at each level, for a host or a whole scan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Counts indexed like `Level::ALL`.
+    counts: [usize; 5],
+}
+
+impl Summary {
+    /// Counts `findings`.
+    pub fn of(findings: &[Finding]) -> Summary {
+        let mut summary = Summary::default();
+        for finding in findings {
+            summary.counts[Self::index(finding.level)] += 1;
+        }
+        summary
+    }
+
+    /// Adds the counts of `other`, e.g. to total the hosts of a scan.
+    pub fn add(&mut self, other: &Summary) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+    }
+
+    /// Returns the number of findings at `level`.
+    pub fn count(&self, level: Level) -> usize {
+        self.counts[Self::index(level)]
+    }
+
+    /// Returns the most severe level with a finding, or `None` if there are none.
+    pub fn highest(&self) -> Option<Level> {
+        Level::ALL.into_iter().find(|&level| self.count(level) > 0)
+    }
+
+    /// Returns the number of findings at each level, most severe first, leaving out
+    /// levels without findings.
+    pub fn counts(&self) -> Vec<(Level, usize)> {
+        Level::ALL
+            .into_iter()
+            .map(|level| (level, self.count(level)))
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+
+    fn index(level: Level) -> usize {
+        Level::ALL.iter().position(|&l| l == level).unwrap_or(0)
+    }
+}
+
+impl Rules {
+    /// Returns the built-in rules.
+    pub fn builtin() -> Rules {
+        Rules {
+            rules: BUILTIN
+                .iter()
+                .map(|&(first, last, exposure, level)| Rule {
+                    first,
+                    last,
+                    exposure,
+                    level,
+                })
+                .collect(),
+        }
+    }
+
+    /// Loads a rule file; its rules are tried before the built-in ones.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to read risk rules file" if the file cannot be read.
+    /// * Any error returned by `Rules::parse`.
+    pub fn load(path: &str) -> Result<Rules, &'static str> {
+        let contents = fs::read_to_string(path).map_err(|_| "failed to read risk rules file")?;
+        let mut rules = Rules::parse(&contents)?;
+        rules.rules.extend(Rules::builtin().rules);
+        Ok(rules)
+    }
+
+    /// Parses the contents of a rule file.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid port in risk rules" if a port or port range cannot be parsed.
+    /// * "unknown risk level; must be info, low, medium, high or critical" if a level is
+    ///   not one of those.
+    /// * "invalid line in risk rules" for anything else that is not understood.
+    pub fn parse(contents: &str) -> Result<Rules, &'static str> {
+        let mut rules = vec![];
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, level) = line.split_once(':').ok_or("invalid line in risk rules")?;
+            let level = level.trim().parse()?;
+            let mut words = key.split_whitespace();
+
+            let ports = words.next().ok_or("invalid line in risk rules")?;
+            let (first, last) = match ports.split_once('-') {
+                Some((first, last)) => (first.parse(), last.parse()),
+                None => (ports.parse(), ports.parse()),
+            };
+            let (Ok(first), Ok(last)) = (first, last) else {
+                return Err("invalid port in risk rules");
+            };
+            if first > last {
+                return Err("invalid port in risk rules");
+            }
+
+            let exposure = match words.next() {
+                None => Exposure::Any,
+                Some("internet") => Exposure::Internet,
+                Some("internal") => Exposure::Internal,
+                Some(_) => return Err("invalid line in risk rules"),
+            };
+            if words.next().is_some() {
+                return Err("invalid line in risk rules");
+            }
+
+            rules.push(Rule {
+                first,
+                last,
+                exposure,
+                level,
+            });
+        }
+
+        Ok(Rules { rules })
+    }
+
+    /// Assigns a risk level to each open port of a host.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The IP address that was scanned; decides whether it faces the internet.
+    /// * `ports` - The open ports of the host.
+    /// * `services` - The service reports of the host.
+    ///
+    /// # Returns
+    ///
+    /// One finding per port, in port order, whatever order the scan found them in.
+    ///
+    /// # Description
+    ///
+    /// A port gets the level of the first rule covering it, or `low` if none does. A
+    /// port whose service reports CVE hints is raised to at least `high`.
+    pub fn assess(
+        &self,
+        addr: IpAddr,
+        ports: &[u16],
+        services: &[(u16, ServiceReport)],
+    ) -> Vec<Finding> {
+        let exposure = if is_internet(addr) {
+            Exposure::Internet
+        } else {
+            Exposure::Internal
+        };
+
+        let mut findings: Vec<Finding> = ports
+            .iter()
+            .map(|&port| {
+                let mut level = self
+                    .rules
+                    .iter()
+                    .find(|rule| {
+                        (rule.first..=rule.last).contains(&port)
+                            && (rule.exposure == Exposure::Any || rule.exposure == exposure)
+                    })
+                    .map_or(DEFAULT_LEVEL, |rule| rule.level);
+
+                let hinted = services.iter().any(|(p, report)| {
+                    *p == port && report.fields.iter().any(|(name, _)| *name == "cve_hints")
+                });
+                if hinted {
+                    level = level.max(Level::High);
+                }
+
+                Finding { port, level }
+            })
+            .collect();
+        findings.sort_by_key(|finding| finding.port);
+        findings
+    }
+}
+
+/// Returns whether `addr` is globally routable, i.e. not private, loopback, link-local,
+/// shared (100.64.0.0/10), documentation, multicast or unspecified.
+pub fn is_internet(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_documentation()
+                || v4.is_multicast()
+                || v4.is_broadcast()
+                || v4.is_unspecified()
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_multicast()
+                || v6.is_unspecified()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                || first == 0x2001 && v6.segments()[1] == 0x0db8)
+        }
+    }
+}
@@ -0,0 +1,1460 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::events::{Bus, Event};
+use crate::proxy::Proxy;
+
+/// Number of events the workers may get ahead of the bus's sinks before they block,
+/// so a slow sink slows the scan down instead of letting events pile up in memory.
+const EVENT_BUFFER: usize = 1024;
+
+/// Timeout used when re-probing ports during the verification pass.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of connection attempts made per port during the verification pass.
+const VERIFY_ATTEMPTS: u32 = 2;
+
+/// Number of recent probes over which the local error ratio is measured.
+const HEALTH_WINDOW: u32 = 100;
+
+/// Fraction of probes in a window failing with local errors above which the scan is paused.
+const LOCAL_ERROR_RATIO: f64 = 0.5;
+
+/// Fraction of probes in a window reset by the target above which it is taken to be
+/// rate limiting the scan.
+const RESET_RATIO: f64 = 0.5;
+
+/// How long the scan is paused when local network errors or resets pile up.
+const LOCAL_ERROR_PAUSE: Duration = Duration::from_secs(5);
+
+/// Number of times a port is retried after a local error before it is given up on.
+const LOCAL_ERROR_RETRIES: u32 = 3;
+
+/// How much longer the timeout of the second attempt at a timed-out port is.
+const RETRANSMIT_TIMEOUT_FACTOR: u32 = 2;
+
+/// How often a paused scan checks whether it was resumed or cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// Ports connected to when measuring a host's round-trip time. Closed ports answer
+/// as quickly as open ones, so any port that is not filtered gives a sample.
+const DISCOVERY_PORTS: &[u16] = &[80, 443, 22, 445, 3389, 1];
+
+/// How long each discovery connection may take.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of consecutive timed-out probes, after the host has answered, that make the
+/// scan check whether the host is still up.
+const HOST_DOWN_STREAK: usize = 100;
+
+/// Number of ports that answered during the scan which are tried again, along with
+/// `DISCOVERY_PORTS`, when checking whether the host is still up.
+const LIVENESS_PORTS: usize = 8;
+
+/// Connect timeout as a multiple of the measured round-trip time.
+const RTT_MULTIPLIER: u32 = 4;
+
+/// Bounds of the connect timeout derived from the round-trip time.
+const MIN_TIMEOUT: Duration = Duration::from_millis(100);
+const MAX_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A port found open by the scan, along with how long the connection took.
+#[derive(Clone, Copy, Debug)]
+pub struct OpenPort {
+    pub port: u16,
+    pub latency: Duration,
+    /// How sure the scan is that the port is open.
+    pub confidence: Confidence,
+}
+
+/// What the scan concluded about a port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PortState {
+    /// The port accepted a connection.
+    Open,
+    /// The port actively refused the connection.
+    Closed,
+    /// The port did not answer, or something along the way rejected the connection.
+    Filtered,
+    /// No answer that tells an open port from a filtered one; probes that expect no
+    /// reply from open ports, unlike a TCP connect, end in this state.
+    OpenFiltered,
+    /// The port could not be probed, e.g. because of errors on our side.
+    Unknown,
+}
+
+impl PortState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+            PortState::OpenFiltered => "open|filtered",
+            PortState::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for PortState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How sure the scan is of the state it assigned to a port, from least to most.
+///
+/// # Description
+///
+/// A definite answer from the host on the first attempt, i.e. a completed handshake or
+/// a refusal, is `High`. An answer relayed by a proxy, which may word failures loosely,
+/// or one that only came after a retry is `Medium`, as is a port that stayed silent
+/// through a retransmission. A port that stayed silent to a single attempt, or could
+/// not be probed at all, is `Low`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        }
+    }
+
+    /// Returns the confidence of an answer, `High` unless it came through a proxy or
+    /// after a retry.
+    fn of_answer(retried: bool, proxied: bool) -> Confidence {
+        match retried || proxied {
+            true => Confidence::Medium,
+            false => Confidence::High,
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The outcome of scanning every port of a host.
+#[derive(Debug, Default)]
+pub struct HostScan {
+    /// Open ports, in the order they were discovered.
+    pub open: Vec<OpenPort>,
+    /// Number of ports that actively refused the connection.
+    pub closed: usize,
+    /// Number of ports that did not answer or were rejected by something along the way.
+    pub filtered: usize,
+    /// Number of ports that could not be probed because of errors on our side,
+    /// e.g. the network being down or running out of sockets.
+    pub local_errors: usize,
+    /// Whether the scan was cancelled before every port was probed.
+    pub cancelled: bool,
+    /// How long closed ports took to refuse the connection.
+    pub closed_latencies: Vec<Duration>,
+    /// Number of failed connection attempts per error, e.g. `connection refused`.
+    pub errors: BTreeMap<String, usize>,
+    /// Number of connection attempts repeated after a local error.
+    pub retries: usize,
+    /// Number of timed-out ports probed a second time with a longer timeout.
+    pub retransmits: usize,
+    /// Whether the host stopped answering partway through the scan.
+    pub host_down: bool,
+    /// Whether the scan was stopped at its deadline before every port was probed.
+    pub deadline_reached: bool,
+    /// Number of ports left unprobed because the host went down or the deadline passed.
+    pub unprobed: usize,
+    /// Number of times a worker found the event buffer full and had to wait for the
+    /// bus's sinks, i.e. the scan was limited by its output rather than the network.
+    pub sink_stalls: usize,
+    /// Total time workers spent waiting for the sinks.
+    pub sink_wait: Duration,
+    /// Number of probed ports per state and confidence.
+    pub states: BTreeMap<(PortState, Confidence), usize>,
+}
+
+/// Per-port tallies shared by the scan threads.
+#[derive(Default)]
+struct Tally {
+    closed: AtomicUsize,
+    filtered: AtomicUsize,
+    local_errors: AtomicUsize,
+    retries: AtomicUsize,
+    retransmits: AtomicUsize,
+    closed_latencies: Mutex<Vec<Duration>>,
+    errors: Mutex<BTreeMap<String, usize>>,
+    sink_stalls: AtomicUsize,
+    sink_wait: Mutex<Duration>,
+    states: Mutex<BTreeMap<(PortState, Confidence), usize>>,
+}
+
+impl Tally {
+    fn record_state(&self, state: PortState, confidence: Confidence) {
+        *self
+            .states
+            .lock()
+            .unwrap()
+            .entry((state, confidence))
+            .or_default() += 1;
+    }
+
+    fn record_error(&self, err: &io::Error) {
+        let mut errors = self.errors.lock().unwrap();
+        *errors.entry(err.kind().to_string()).or_default() += 1;
+    }
+
+    /// Sends `event` of the host at index `host` to the bus, blocking while the event buffer is full and recording
+    /// how long that took.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the receiving side is gone.
+    fn publish(&self, tx: &SyncSender<Message>, host: usize, event: Event) {
+        match tx.try_send(Message::Event(host, event)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(event)) => {
+                let started = Instant::now();
+                tx.send(event).unwrap();
+                self.sink_stalls.fetch_add(1, Ordering::Relaxed);
+                *self.sink_wait.lock().unwrap() += started.elapsed();
+            }
+            Err(TrySendError::Disconnected(_)) => panic!("scan events are no longer received"),
+        }
+    }
+}
+
+/// How the scan connects to a port.
+#[derive(Clone, Debug, Default)]
+pub struct Connector {
+    /// Connect timeout; `None` leaves it to the operating system.
+    pub timeout: Option<Duration>,
+    /// Proxy every connection is tunnelled through.
+    pub proxy: Option<Proxy>,
+}
+
+impl Connector {
+    /// Connects to `target`, directly or through the proxy.
+    pub fn connect(&self, target: SocketAddr) -> io::Result<TcpStream> {
+        match (&self.proxy, self.timeout) {
+            (Some(proxy), timeout) => proxy.connect(target, timeout),
+            (None, Some(timeout)) => TcpStream::connect_timeout(&target, timeout),
+            (None, None) => TcpStream::connect(target),
+        }
+    }
+}
+
+/// The order in which the ports of a host are probed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PortOrder {
+    /// From port 1 up to 65535.
+    #[default]
+    Sequential,
+    /// Shuffled with a seeded generator, so the same seed gives the same order.
+    Shuffled(u64),
+}
+
+impl PortOrder {
+    /// Returns every port, in this order.
+    pub fn ports(self) -> Vec<u16> {
+        let mut ports: Vec<u16> = (1..=u16::MAX).collect();
+
+        if let PortOrder::Shuffled(seed) = self {
+            let mut rng = SplitMix64(seed);
+
+            // Fisher-Yates
+            for i in (1..ports.len()).rev() {
+                let j = (rng.next() % (i as u64 + 1)) as usize;
+                ports.swap(i, j);
+            }
+        }
+        ports
+    }
+}
+
+/// The SplitMix64 generator: small, fast and good enough to shuffle ports.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// A random delay each worker waits before every probe, so probes do not leave at the
+/// perfectly regular intervals some intrusion detection systems look for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Jitter {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl FromStr for Jitter {
+    type Err = &'static str;
+
+    /// Parses a range such as `10..200ms` or `1..3s`; a unit on the lower bound too, as
+    /// in `500ms..2s`, overrides the one at the end.
+    ///
+    /// # Errors
+    ///
+    /// * "invalid jitter; must be e.g. 10..200ms" if it is not a range of durations, or
+    ///   the lower bound exceeds the upper.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "invalid jitter; must be e.g. 10..200ms";
+        let (low, high) = s.split_once("..").ok_or(invalid)?;
+
+        fn split(text: &str) -> Option<(u64, &str)> {
+            let i = text
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(text.len());
+            let (number, unit) = text.split_at(i);
+            Some((number.parse().ok()?, unit))
+        }
+        let (high, unit) = split(high).ok_or(invalid)?;
+        let (low, low_unit) = split(low).ok_or(invalid)?;
+        let duration = |number: u64, unit: &str| match unit {
+            "ms" => Ok(Duration::from_millis(number)),
+            "s" => Ok(Duration::from_secs(number)),
+            _ => Err(invalid),
+        };
+
+        let max = duration(high, unit)?;
+        let min = duration(low, if low_unit.is_empty() { unit } else { low_unit })?;
+        if min > max {
+            return Err(invalid);
+        }
+        Ok(Jitter { min, max })
+    }
+}
+
+impl Jitter {
+    /// Returns a delay between `min` and `max`, inclusive.
+    fn sample(&self, rng: &mut SplitMix64) -> Duration {
+        let (min, max) = (self.min.as_micros() as u64, self.max.as_micros() as u64);
+        Duration::from_micros(min + rng.next() % (max - min + 1))
+    }
+}
+
+/// A host to scan with `scan_hosts`.
+#[derive(Clone, Debug)]
+pub struct HostTarget {
+    pub addr: IpAddr,
+    /// How the host's ports are connected to.
+    pub connector: Connector,
+    /// When to stop probing the host; `None` scans every port.
+    pub deadline: Option<Instant>,
+    /// How long the host may be scanned for, counted from its first probe; `None` for
+    /// no limit.
+    pub timeout: Option<Duration>,
+}
+
+/// State of one host of a scan, shared by the workers.
+struct HostState {
+    target: HostTarget,
+    tally: Tally,
+    liveness: Liveness,
+    /// When the first probe of the host was sent.
+    started: OnceLock<Instant>,
+    deadline_reached: AtomicBool,
+    /// Number of the host's ports not yet probed or skipped; the worker that takes it to
+    /// zero reports the host finished.
+    remaining: AtomicUsize,
+}
+
+impl HostState {
+    /// Returns whether the host's deadline or timeout has passed, recording it if so.
+    fn past_deadline(&self) -> bool {
+        let now = Instant::now();
+        let started = *self.started.get_or_init(|| now);
+        let target = &self.target;
+
+        let past = target.deadline.is_some_and(|deadline| now >= deadline)
+            || target
+                .timeout
+                .is_some_and(|timeout| now >= started + timeout);
+        if past {
+            self.deadline_reached.store(true, Ordering::Relaxed);
+        }
+        past
+    }
+}
+
+/// Pauses and resumes a scan from another thread, e.g. when it starts to affect the
+/// network being scanned. Clones control the same scan.
+///
+/// # Usage
+///
+/// * `pause.pause()` - Stop issuing new probes; each worker finishes the probe it is on.
+/// * `pause.resume()` - Carry on where the scan stopped.
+/// * `pause.is_paused()` - Whether the scan is paused.
+#[derive(Clone, Debug, Default)]
+pub struct Pause(Arc<AtomicBool>);
+
+impl Pause {
+    pub fn pause(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Blocks while the scan is paused, returning early if it is cancelled.
+    fn wait(&self, cancelled: &AtomicBool) {
+        while self.is_paused() && !cancelled.load(Ordering::Relaxed) {
+            thread::sleep(CANCEL_POLL);
+        }
+    }
+}
+
+/// How a scan started with `scan_hosts_controlled` is steered while it runs.
+#[derive(Clone, Debug, Default)]
+pub struct Controls {
+    /// Pauses and resumes the scan from another thread.
+    pub pause: Pause,
+    /// Halve the threads probing each time local network errors or resets from the
+    /// target pile up, instead of only pausing the scan for a while.
+    pub auto_backoff: bool,
+    /// Delay each worker's probes by a random time in this range.
+    pub jitter: Option<Jitter>,
+    /// Probe only these ports, in the scan's order; `None` probes every port.
+    pub ports: Option<Vec<u16>>,
+    /// Publish a `ProbeSent` event per connection attempt, e.g. for an audit log. They
+    /// are left out otherwise, since sending one per probe slows the scan down.
+    pub probe_events: bool,
+}
+
+/// State shared by the workers of one scan.
+struct Shared {
+    hosts: Vec<HostState>,
+    health: NetworkHealth,
+    cancelled: Arc<AtomicBool>,
+    pause: Pause,
+    jitter: Option<Jitter>,
+    ports: Vec<u16>,
+    probe_events: bool,
+    /// Index of the next task: every port of the first host, then every port of the next.
+    next: AtomicUsize,
+}
+
+/// What workers send to the thread that started the scan.
+enum Message {
+    /// An event of the host at this index of `Shared::hosts`; targets may share an
+    /// address, so the address alone does not tell which one it belongs to.
+    Event(usize, Event),
+    /// Every port of the host at this index of `Shared::hosts` was probed or skipped.
+    Finished(usize),
+}
+
+/// Probes seen in the current health window.
+#[derive(Default)]
+struct Window {
+    probes: u32,
+    local_errors: u32,
+    /// The kind of the last local error.
+    kind: Option<io::ErrorKind>,
+    resets: u32,
+}
+
+/// Tracks how many recent probes failed because of our own network or were reset by
+/// the target, and pauses the scan, or backs off, when too many of them are.
+struct NetworkHealth {
+    window: Mutex<Window>,
+    paused_until: Mutex<Option<Instant>>,
+    /// Number of workers allowed to probe; workers numbered from it on stay idle.
+    threads: AtomicUsize,
+    auto_backoff: bool,
+}
+
+impl NetworkHealth {
+    fn new(threads: u16, auto_backoff: bool) -> NetworkHealth {
+        NetworkHealth {
+            window: Mutex::new(Window::default()),
+            paused_until: Mutex::new(None),
+            threads: AtomicUsize::new(threads as usize),
+            auto_backoff,
+        }
+    }
+
+    /// Records the outcome of a probe, pausing the scan if the current window has too
+    /// many local errors or resets, and halving the threads with `auto_backoff`. Pauses
+    /// and back-offs are handed to `publish` as events.
+    fn record(&self, err: Option<&io::Error>, publish: &dyn Fn(Event)) {
+        let mut window = self.window.lock().unwrap();
+        window.probes += 1;
+
+        match err {
+            Some(err) if is_local_error(err) => {
+                window.local_errors += 1;
+                window.kind = Some(err.kind());
+            }
+            Some(err) if is_reset(err) => window.resets += 1,
+            _ => {}
+        }
+
+        if window.probes < HEALTH_WINDOW {
+            return;
+        }
+
+        let full = mem::take(&mut *window);
+        let probes = full.probes as f64;
+
+        if full.local_errors as f64 / probes >= LOCAL_ERROR_RATIO {
+            publish(Event::LocalErrors {
+                errors: full.local_errors as usize,
+                probes: full.probes as usize,
+                kind: full.kind.unwrap_or(io::ErrorKind::Other),
+                pause: LOCAL_ERROR_PAUSE,
+            });
+        } else if full.resets as f64 / probes >= RESET_RATIO {
+            publish(Event::Resets {
+                resets: full.resets as usize,
+                probes: full.probes as usize,
+                pause: LOCAL_ERROR_PAUSE,
+            });
+        } else {
+            return;
+        }
+        *self.paused_until.lock().unwrap() = Some(Instant::now() + LOCAL_ERROR_PAUSE);
+
+        let threads = self.threads.load(Ordering::Relaxed);
+        if self.auto_backoff && threads > 1 {
+            let fewer = threads / 2;
+            self.threads.store(fewer, Ordering::Relaxed);
+            publish(Event::BackedOff { threads: fewer });
+        }
+    }
+
+    /// Returns whether the worker numbered `worker` may probe, or was backed off.
+    fn allows(&self, worker: usize) -> bool {
+        worker < self.threads.load(Ordering::Relaxed)
+    }
+
+    /// Blocks while the scan is paused, returning early if it is cancelled.
+    fn wait(&self, cancelled: &AtomicBool) {
+        let paused_until = *self.paused_until.lock().unwrap();
+
+        if let Some(until) = paused_until {
+            while !cancelled.load(Ordering::Relaxed) {
+                let remaining = until.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                thread::sleep(remaining.min(CANCEL_POLL));
+            }
+        }
+    }
+}
+
+/// Watches for a host that stops answering partway through the scan, e.g. because it
+/// rebooted or started blocking us.
+#[derive(Default)]
+struct Liveness {
+    /// Whether any port has answered, open or closed.
+    answered: AtomicBool,
+    /// Number of consecutive probes that timed out.
+    silent: AtomicUsize,
+    /// Ports that answered during the scan, up to `LIVENESS_PORTS` of them.
+    ports: Mutex<Vec<u16>>,
+    /// Held while the host is checked, pausing the other threads that notice the silence.
+    checking: Mutex<()>,
+    down: AtomicBool,
+}
+
+impl Liveness {
+    /// Records a port that answered, open or closed.
+    fn answer(&self, port: u16) {
+        self.answered.store(true, Ordering::Relaxed);
+        self.silent.store(0, Ordering::Relaxed);
+
+        let mut ports = self.ports.lock().unwrap();
+        if ports.len() < LIVENESS_PORTS && !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+
+    /// Records a probe that timed out, checking whether the host is still up once
+    /// `HOST_DOWN_STREAK` probes in a row have. The check and its outcome are handed to
+    /// `publish` as events.
+    fn time_out(&self, addr: IpAddr, publish: &dyn Fn(Event)) {
+        let silent = self.silent.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if !self.answered.load(Ordering::Relaxed) || silent < HOST_DOWN_STREAK {
+            return;
+        }
+
+        let _checking = self.checking.lock().unwrap();
+
+        // Another thread may have checked while this one waited.
+        if self.down.load(Ordering::Relaxed) || self.silent.load(Ordering::Relaxed) < silent {
+            return;
+        }
+
+        publish(Event::HostSilent {
+            addr,
+            probes: silent,
+        });
+
+        let ports = [DISCOVERY_PORTS, &self.ports.lock().unwrap()].concat();
+        if fastest_answer(addr, &ports).is_some() {
+            publish(Event::HostAnswers { addr });
+            self.silent.store(0, Ordering::Relaxed);
+        } else {
+            self.down.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Returns whether the target reset the connection instead of accepting or refusing it,
+/// as hosts and firewalls that rate limit connections do.
+fn is_reset(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// Returns whether `err` was caused by the scanning machine rather than the target,
+/// e.g. no route to the network, no buffer space, or too many open files.
+pub fn is_local_error(err: &io::Error) -> bool {
+    use io::ErrorKind::*;
+
+    if matches!(
+        err.kind(),
+        NetworkUnreachable | NetworkDown | AddrNotAvailable | OutOfMemory | PermissionDenied
+    ) {
+        return true;
+    }
+
+    // ENOBUFS and EMFILE have no dedicated `ErrorKind`.
+    let local_codes: &[i32] = if cfg!(windows) {
+        &[10055, 10024]
+    } else if cfg!(target_os = "macos") {
+        &[55, 24]
+    } else {
+        &[105, 24]
+    };
+
+    err.raw_os_error()
+        .is_some_and(|code| local_codes.contains(&code))
+}
+
+/// A scan running in the background, which can be paused and cancelled.
+///
+/// # Usage
+///
+/// * `ScanHandle::start(addr, threads, connector, on_open)` - Start scanning every port of `addr`.
+/// * `handle.pause()` and `handle.resume()` - Stop issuing probes for a while, keeping the
+///   results so far.
+/// * `handle.cancel()` - Ask the workers to stop; each finishes the probe it is on.
+/// * `handle.await_finished()` - Block until the workers have stopped and get the results.
+pub struct ScanHandle {
+    cancelled: Arc<AtomicBool>,
+    pause: Pause,
+    thread: JoinHandle<HostScan>,
+}
+
+impl ScanHandle {
+    /// Starts scanning every port of `addr` on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The IP address to scan.
+    /// * `num_threads` - The number of threads to use for the scan.
+    /// * `connector` - How ports are connected to.
+    /// * `on_open` - Called on the background thread for each open port, as soon as it is found.
+    pub fn start(
+        addr: IpAddr,
+        num_threads: u16,
+        connector: Connector,
+        on_open: impl FnMut(&OpenPort) + Send + 'static,
+    ) -> ScanHandle {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let token = Arc::clone(&cancelled);
+        let pause = Pause::default();
+        let switch = pause.clone();
+        let thread = thread::spawn(move || {
+            let mut bus = Bus::new();
+            bus.subscribe(open_sink(on_open));
+            let target = HostTarget {
+                addr,
+                connector,
+                deadline: None,
+                timeout: None,
+            };
+            let mut scans = run(
+                vec![target],
+                num_threads,
+                PortOrder::Sequential,
+                token,
+                Controls {
+                    pause: switch,
+                    ..Controls::default()
+                },
+                &mut bus,
+            );
+            scans.pop().unwrap_or_default()
+        });
+
+        ScanHandle {
+            cancelled,
+            pause,
+            thread,
+        }
+    }
+
+    /// Stops issuing new probes until `resume` is called. Workers check between probes,
+    /// so connection attempts in flight still complete.
+    pub fn pause(&self) {
+        self.pause.pause();
+    }
+
+    /// Resumes a paused scan.
+    pub fn resume(&self) {
+        self.pause.resume();
+    }
+
+    /// Asks the scan to stop. Workers check for cancellation between probes, so the
+    /// scan stops once every in-flight connection attempt has completed.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether every worker has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Blocks until every worker has stopped.
+    ///
+    /// # Returns
+    ///
+    /// The results gathered so far; `cancelled` is set if the scan did not complete.
+    pub fn await_finished(self) -> HostScan {
+        self.thread.join().unwrap_or_default()
+    }
+}
+
+/// Scans every port of the specified IP address using a pool of threads.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to scan.
+/// * `num_threads` - The number of threads to use for the scan.
+/// * `connector` - How ports are connected to.
+/// * `on_open` - Called on the calling thread for each open port, as soon as it is found.
+///
+/// # Returns
+///
+/// The open ports along with counts of closed, filtered and locally failed ports.
+pub fn scan_host(
+    addr: IpAddr,
+    num_threads: u16,
+    connector: Connector,
+    on_open: impl FnMut(&OpenPort),
+) -> HostScan {
+    scan_host_in_order(addr, num_threads, connector, PortOrder::Sequential, on_open)
+}
+
+/// Scans every port of the specified IP address in the given order.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to scan.
+/// * `num_threads` - The number of threads to use for the scan.
+/// * `connector` - How ports are connected to.
+/// * `order` - The order in which ports are probed.
+/// * `on_open` - Called on the calling thread for each open port, as soon as it is found.
+///
+/// # Returns
+///
+/// The open ports along with counts of closed, filtered and locally failed ports.
+pub fn scan_host_in_order(
+    addr: IpAddr,
+    num_threads: u16,
+    connector: Connector,
+    order: PortOrder,
+    on_open: impl FnMut(&OpenPort),
+) -> HostScan {
+    scan_host_until(addr, num_threads, connector, order, None, on_open)
+}
+
+/// Scans every port of the specified IP address in the given order, giving up at a deadline.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to scan.
+/// * `num_threads` - The number of threads to use for the scan.
+/// * `connector` - How ports are connected to.
+/// * `order` - The order in which ports are probed.
+/// * `deadline` - When to stop probing; `None` scans every port.
+/// * `on_open` - Called on the calling thread for each open port, as soon as it is found.
+///
+/// # Returns
+///
+/// The open ports along with counts of closed, filtered and locally failed ports. If the
+/// deadline passed first, `deadline_reached` is set and `unprobed` counts the ports left.
+///
+/// # Description
+///
+/// Workers check the deadline between probes, so the scan stops once every in-flight
+/// connection attempt has completed.
+pub fn scan_host_until(
+    addr: IpAddr,
+    num_threads: u16,
+    connector: Connector,
+    order: PortOrder,
+    deadline: Option<Instant>,
+    on_open: impl FnMut(&OpenPort),
+) -> HostScan {
+    let mut bus = Bus::new();
+    bus.subscribe(open_sink(on_open));
+    scan_host_with(addr, num_threads, connector, order, deadline, &mut bus)
+}
+
+/// Scans every port of the specified IP address, publishing what happens on a bus.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address to scan.
+/// * `num_threads` - The number of threads to use for the scan.
+/// * `connector` - How ports are connected to.
+/// * `order` - The order in which ports are probed.
+/// * `deadline` - When to stop probing; `None` scans every port.
+/// * `bus` - Receives a `PortOpen` event per open port, the pauses and liveness checks
+///   of the scan, and a final `HostDone` event, on the calling thread.
+///
+/// # Returns
+///
+/// The open ports along with counts of closed, filtered and locally failed ports, as
+/// `scan_host_until` returns them.
+pub fn scan_host_with(
+    addr: IpAddr,
+    num_threads: u16,
+    connector: Connector,
+    order: PortOrder,
+    deadline: Option<Instant>,
+    bus: &mut Bus,
+) -> HostScan {
+    let target = HostTarget {
+        addr,
+        connector,
+        deadline,
+        timeout: None,
+    };
+    let mut scans = scan_hosts(vec![target], num_threads, order, bus);
+    scans.pop().unwrap_or_default()
+}
+
+/// Scans every port of several hosts with one pool of threads, publishing what happens
+/// on a bus.
+///
+/// # Arguments
+///
+/// * `targets` - The hosts to scan, with how to connect to each and when to stop.
+/// * `num_threads` - The number of threads in the pool.
+/// * `order` - The order in which the ports of each host are probed.
+/// * `bus` - Receives a `PortOpen` event per open port, the pauses and liveness checks
+///   of the scan, and a `HostDone` event per host as soon as it is finished, on the
+///   calling thread.
+///
+/// # Returns
+///
+/// The results of each host, in the order of `targets`, as `scan_host_until` returns them.
+///
+/// # Description
+///
+/// The threads are started once and take (host, port) tasks from a shared queue that
+/// holds every port of the first host, then every port of the next, so a new host does
+/// not wait for threads to be set up and threads that run out of work on one host help
+/// finish the next. A host's timeout is counted from its first probe, not from the start
+/// of the scan. A host that goes down or passes its deadline has its remaining tasks
+/// skipped without holding up the others.
+pub fn scan_hosts(
+    targets: Vec<HostTarget>,
+    num_threads: u16,
+    order: PortOrder,
+    bus: &mut Bus,
+) -> Vec<HostScan> {
+    scan_hosts_controlled(targets, num_threads, order, &Controls::default(), bus)
+}
+
+/// Scans every port of several hosts as `scan_hosts` does, steered by `controls`.
+///
+/// # Arguments
+///
+/// * `targets` - The hosts to scan, with how to connect to each and when to stop.
+/// * `num_threads` - The number of threads in the pool.
+/// * `order` - The order in which the ports of each host are probed.
+/// * `controls` - Pauses and resumes the scan, and whether it backs off on its own.
+/// * `bus` - Receives the events of the scan, as with `scan_hosts`, and a `ProbeSent`
+///   event per connection attempt if `controls.probe_events` is set.
+///
+/// # Description
+///
+/// A paused scan keeps its queue and results and issues no new probes, so it resumes
+/// exactly where it stopped. Deadlines and host timeouts keep running while it is paused.
+///
+/// With `auto_backoff`, every health window of `HEALTH_WINDOW` probes in which local
+/// errors or resets from the target pass their ratio halves the threads probing, down
+/// to one. The threads are not raised again during the scan.
+pub fn scan_hosts_controlled(
+    targets: Vec<HostTarget>,
+    num_threads: u16,
+    order: PortOrder,
+    controls: &Controls,
+    bus: &mut Bus,
+) -> Vec<HostScan> {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    run(
+        targets,
+        num_threads,
+        order,
+        cancelled,
+        controls.clone(),
+        bus,
+    )
+}
+
+/// Adapts an `on_open` callback into a bus sink that only sees `PortOpen` events.
+fn open_sink(mut on_open: impl FnMut(&OpenPort)) -> impl FnMut(&Event) {
+    move |event| {
+        if let Event::PortOpen { open, .. } = event {
+            on_open(open);
+        }
+    }
+}
+
+/// Runs the worker threads of a scan until they finish or `cancelled` is set.
+fn run(
+    targets: Vec<HostTarget>,
+    num_threads: u16,
+    order: PortOrder,
+    cancelled: Arc<AtomicBool>,
+    controls: Controls,
+    bus: &mut Bus,
+) -> Vec<HostScan> {
+    let mut ports = order.ports();
+    if let Some(only) = &controls.ports {
+        ports.retain(|port| only.contains(port));
+    }
+    let (tx, rx) = sync_channel(EVENT_BUFFER);
+    let shared = Arc::new(Shared {
+        hosts: targets
+            .into_iter()
+            .map(|target| HostState {
+                target,
+                tally: Tally::default(),
+                liveness: Liveness::default(),
+                started: OnceLock::new(),
+                deadline_reached: AtomicBool::new(false),
+                remaining: AtomicUsize::new(ports.len()),
+            })
+            .collect(),
+        health: NetworkHealth::new(num_threads, controls.auto_backoff),
+        cancelled,
+        pause: controls.pause,
+        jitter: controls.jitter,
+        ports,
+        probe_events: controls.probe_events,
+        next: AtomicUsize::new(0),
+    });
+
+    for worker in 0..num_threads as usize {
+        let tx = tx.clone();
+        let shared = Arc::clone(&shared);
+
+        thread::spawn(move || {
+            work(tx, &shared, worker);
+        });
+    }
+
+    let mut opens: Vec<Vec<OpenPort>> = shared.hosts.iter().map(|_| vec![]).collect();
+    let mut scans: Vec<Option<HostScan>> = shared.hosts.iter().map(|_| None).collect();
+    drop(tx);
+
+    for message in rx {
+        match message {
+            Message::Event(i, event) => {
+                if let Event::PortOpen { open, .. } = event {
+                    opens[i].push(open);
+                }
+                bus.publish(&event);
+            }
+            Message::Finished(i) => {
+                let open = mem::take(&mut opens[i]);
+                scans[i] = Some(finish(&shared, i, open, bus));
+            }
+        }
+    }
+
+    // Hosts of a cancelled scan are never reported finished by the workers.
+    for (i, scan) in scans.iter_mut().enumerate() {
+        if scan.is_none() {
+            let open = mem::take(&mut opens[i]);
+            *scan = Some(finish(&shared, i, open, bus));
+        }
+    }
+    scans.into_iter().flatten().collect()
+}
+
+/// Gathers the results of the host at index `i` and publishes its `HostDone` event.
+fn finish(shared: &Shared, i: usize, open: Vec<OpenPort>, bus: &mut Bus) -> HostScan {
+    let host = &shared.hosts[i];
+    let tally = &host.tally;
+    let mut out = HostScan {
+        open,
+        ..HostScan::default()
+    };
+
+    out.closed = tally.closed.load(Ordering::Relaxed);
+    out.filtered = tally.filtered.load(Ordering::Relaxed);
+    out.local_errors = tally.local_errors.load(Ordering::Relaxed);
+    out.retries = tally.retries.load(Ordering::Relaxed);
+    out.retransmits = tally.retransmits.load(Ordering::Relaxed);
+    out.closed_latencies = mem::take(&mut *tally.closed_latencies.lock().unwrap());
+    out.errors = mem::take(&mut *tally.errors.lock().unwrap());
+    out.sink_stalls = tally.sink_stalls.load(Ordering::Relaxed);
+    out.sink_wait = *tally.sink_wait.lock().unwrap();
+    out.states = mem::take(&mut *tally.states.lock().unwrap());
+    out.cancelled = shared.cancelled.load(Ordering::Relaxed);
+    out.host_down = host.liveness.down.load(Ordering::Relaxed);
+    out.deadline_reached = host.deadline_reached.load(Ordering::Relaxed);
+    if out.host_down || out.deadline_reached {
+        let probed = out.open.len() + out.closed + out.filtered + out.local_errors;
+        out.unprobed = shared.ports.len().saturating_sub(probed);
+    }
+
+    bus.publish(&Event::HostDone {
+        addr: host.target.addr,
+        open: out.open.len(),
+        closed: out.closed,
+        filtered: out.filtered,
+    });
+    out
+}
+
+/// Takes (host, port) tasks from the shared queue until it is empty or the scan is
+/// cancelled.
+///
+/// # Arguments
+///
+/// * `tx` - A `SyncSender<Message>` to publish probes, open ports and finished hosts
+///   through.
+/// * `shared` - The hosts, network health, cancellation and the ports in the order they
+///   are probed.
+/// * `worker` - The number of this worker; backing off idles the highest numbers first.
+///
+/// # Panics
+///
+/// This function will panic if it fails to send a message through the `SyncSender`.
+fn work(tx: SyncSender<Message>, shared: &Shared, worker: usize) {
+    let num_ports = shared.ports.len();
+    if num_ports == 0 {
+        return;
+    }
+    let tasks = shared.hosts.len() * num_ports;
+    // Each worker draws its own delays, so they do not line up across workers.
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+    let mut rng = SplitMix64(nanos ^ ((process::id() as u64) << 32) ^ worker as u64);
+
+    while !shared.cancelled.load(Ordering::Relaxed) {
+        // A backed-off worker waits, in case the scan is cancelled, until the queue is
+        // taken by the others.
+        if !shared.health.allows(worker) {
+            if shared.next.load(Ordering::Relaxed) >= tasks {
+                return;
+            }
+            thread::sleep(CANCEL_POLL);
+            continue;
+        }
+
+        if let Some(jitter) = shared.jitter {
+            thread::sleep(jitter.sample(&mut rng));
+        }
+        let task = shared.next.fetch_add(1, Ordering::Relaxed);
+        let i = task / num_ports;
+        let Some(host) = shared.hosts.get(i) else {
+            return;
+        };
+
+        probe(&tx, i, shared.ports[task % num_ports], shared);
+
+        if host.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+            tx.send(Message::Finished(i)).unwrap();
+        }
+    }
+}
+
+/// Probes one port of a host.
+///
+/// # Arguments
+///
+/// * `tx` - A `SyncSender<Message>` to publish probes and open ports through.
+/// * `i` - The index of the host in `shared.hosts`, with its tallies and liveness.
+/// * `port` - The port to probe.
+/// * `shared` - The hosts, network health, pausing and cancellation.
+///
+/// # Description
+///
+/// This function attempts to connect to `port`. If the connection is successful, it
+/// sends the port number and connect latency to the provided `SyncSender` so the
+/// receiving side can report it as soon as it is found.
+///
+/// Connections failing because of our own network (see `is_local_error`) are not
+/// reported as filtered; the port is retried after the scan resumes, up to
+/// `LOCAL_ERROR_RETRIES` times.
+///
+/// A port whose connection timed out is probed once more with a timeout
+/// `RETRANSMIT_TIMEOUT_FACTOR` times longer before it is reported as filtered, since the
+/// first probe may simply have been lost. Refused connections and other definite answers
+/// are not repeated. This only applies when the connector has a timeout; the operating
+/// system's own timeout already includes its retransmissions.
+///
+/// Once the host has answered, a run of `HOST_DOWN_STREAK` timed-out probes pauses the
+/// host's probes while it is checked with the discovery ports and the ports that
+/// answered earlier. If none of them answer, the host is taken to be down and its
+/// remaining ports are left unprobed. Hosts reached through a proxy are not checked,
+/// since discovery connects directly.
+///
+/// Once the host's deadline or timeout passes, its remaining ports are left unprobed.
+///
+/// Events go through a buffer of `EVENT_BUFFER` events; while it is full the worker
+/// blocks until the sinks catch up, which is counted in `sink_stalls`.
+///
+/// # Panics
+///
+/// This function will panic if it fails to send an event through the `SyncSender`.
+fn probe(tx: &SyncSender<Message>, i: usize, port: u16, shared: &Shared) {
+    let host = &shared.hosts[i];
+    let (tally, health, liveness) = (&host.tally, &shared.health, &host.liveness);
+    let cancelled = &*shared.cancelled;
+    let (addr, base) = (host.target.addr, &host.target.connector);
+    let proxied = base.proxy.is_some();
+    let mut retransmit: Option<Connector> = None;
+    let publish = |event| tally.publish(tx, i, event);
+
+    for attempt in 0..=LOCAL_ERROR_RETRIES {
+        shared.pause.wait(cancelled);
+        health.wait(cancelled);
+
+        if cancelled.load(Ordering::Relaxed)
+            || liveness.down.load(Ordering::Relaxed)
+            || host.past_deadline()
+        {
+            return;
+        }
+
+        if shared.probe_events {
+            tally.publish(tx, i, Event::ProbeSent { addr, port });
+        }
+        let started = Instant::now();
+
+        let connector = retransmit.as_ref().unwrap_or(base);
+        let retried = attempt > 0 || retransmit.is_some();
+
+        match connector.connect(SocketAddr::new(addr, port)) {
+            Ok(_) => {
+                health.record(None, &publish);
+                liveness.answer(port);
+                let latency = started.elapsed();
+                let confidence = Confidence::of_answer(retried, proxied);
+                tally.record_state(PortState::Open, confidence);
+                let open = OpenPort {
+                    port,
+                    latency,
+                    confidence,
+                };
+                tally.publish(tx, i, Event::PortOpen { addr, open });
+            }
+            Err(err) if is_local_error(&err) => {
+                health.record(Some(&err), &publish);
+                tally.record_error(&err);
+
+                if attempt < LOCAL_ERROR_RETRIES {
+                    tally.retries.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                tally.local_errors.fetch_add(1, Ordering::Relaxed);
+                tally.record_state(PortState::Unknown, Confidence::Low);
+            }
+            Err(err) => {
+                health.record(Some(&err), &publish);
+                tally.record_error(&err);
+
+                if let (io::ErrorKind::TimedOut, None, Some(timeout)) =
+                    (err.kind(), &retransmit, base.timeout)
+                {
+                    tally.retransmits.fetch_add(1, Ordering::Relaxed);
+                    retransmit = Some(Connector {
+                        timeout: Some(timeout * RETRANSMIT_TIMEOUT_FACTOR),
+                        ..base.clone()
+                    });
+                    continue;
+                }
+
+                if err.kind() == io::ErrorKind::ConnectionRefused {
+                    liveness.answer(port);
+                    let latency = started.elapsed();
+                    tally.closed_latencies.lock().unwrap().push(latency);
+                    tally.closed.fetch_add(1, Ordering::Relaxed);
+                    tally.record_state(PortState::Closed, Confidence::of_answer(retried, proxied));
+                } else {
+                    tally.filtered.fetch_add(1, Ordering::Relaxed);
+
+                    let confidence = match err.kind() {
+                        io::ErrorKind::TimedOut if retransmit.is_some() => Confidence::Medium,
+                        io::ErrorKind::TimedOut => Confidence::Low,
+                        _ => Confidence::of_answer(retried, proxied),
+                    };
+                    tally.record_state(PortState::Filtered, confidence);
+
+                    if err.kind() == io::ErrorKind::TimedOut && !proxied {
+                        liveness.time_out(addr, &publish);
+                    }
+                }
+            }
+        }
+        break;
+    }
+}
+
+/// Measures the round-trip time to `addr` by connecting to a few common ports.
+///
+/// # Returns
+///
+/// The fastest connect time among the ports that answered, open or closed; `None`
+/// if none of them did.
+pub fn measure_rtt(addr: IpAddr) -> Option<Duration> {
+    fastest_answer(addr, DISCOVERY_PORTS)
+}
+
+/// Connects to every port of `ports` at once, waiting up to `DISCOVERY_TIMEOUT`.
+///
+/// # Returns
+///
+/// The fastest connect time among the ports that answered, open or closed; `None`
+/// if none of them did.
+fn fastest_answer(addr: IpAddr, ports: &[u16]) -> Option<Duration> {
+    thread::scope(|s| {
+        let handles: Vec<_> = ports
+            .iter()
+            .map(|&port| {
+                s.spawn(move || {
+                    let started = Instant::now();
+                    let target = SocketAddr::new(addr, port);
+
+                    match TcpStream::connect_timeout(&target, DISCOVERY_TIMEOUT) {
+                        Ok(_) => Some(started.elapsed()),
+                        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                            Some(started.elapsed())
+                        }
+                        Err(_) => None,
+                    }
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .filter_map(|h| h.join().ok().flatten())
+            .min()
+    })
+}
+
+/// Derives a connect timeout from a host's round-trip time.
+///
+/// # Returns
+///
+/// `RTT_MULTIPLIER` times `rtt`, kept between `MIN_TIMEOUT` and `MAX_TIMEOUT`. Hosts
+/// whose round-trip time could not be measured get `MAX_TIMEOUT`.
+pub fn adaptive_timeout(rtt: Option<Duration>) -> Duration {
+    match rtt {
+        Some(rtt) => (rtt * RTT_MULTIPLIER).clamp(MIN_TIMEOUT, MAX_TIMEOUT),
+        None => MAX_TIMEOUT,
+    }
+}
+
+/// Re-probes the ports reported open by the main scan to weed out transient false positives.
+///
+/// # Arguments
+///
+/// * `addr` - The IP address that was scanned.
+/// * `ports` - The open ports reported by the main scan.
+/// * `connector` - How ports are connected to; its timeout is replaced by `VERIFY_TIMEOUT`.
+///
+/// # Returns
+///
+/// A tuple of `(confirmed, dropped)` ports, each in the order they were given.
+///
+/// # Description
+///
+/// Unlike the main scan, which relies on the operating system's connect timeout, each
+/// port is re-probed with an explicit `VERIFY_TIMEOUT` and up to `VERIFY_ATTEMPTS`
+/// attempts. A port is confirmed as soon as one attempt succeeds, and then has `High`
+/// confidence since it answered twice.
+pub fn verify(
+    addr: IpAddr,
+    ports: &[OpenPort],
+    connector: &Connector,
+) -> (Vec<OpenPort>, Vec<OpenPort>) {
+    let connector = Connector {
+        timeout: Some(VERIFY_TIMEOUT),
+        ..connector.clone()
+    };
+
+    let (mut confirmed, dropped): (Vec<OpenPort>, Vec<OpenPort>) = ports.iter().partition(|open| {
+        let target = SocketAddr::new(addr, open.port);
+        (0..VERIFY_ATTEMPTS).any(|_| connector.connect(target).is_ok())
+    });
+
+    for open in &mut confirmed {
+        open.confidence = Confidence::High;
+    }
+    (confirmed, dropped)
+}
+
+/// Combines the open ports of several scans of the same host, keeping the ports that were
+/// open in at least `consensus` of them.
+///
+/// # Arguments
+///
+/// * `runs` - The open ports found by each scan.
+/// * `consensus` - How many scans a port must have been open in to be kept.
+///
+/// # Returns
+///
+/// A tuple of `(kept, observed)`: the kept ports, and every port open in any scan with the
+/// number of scans it was open in, both in port order.
+///
+/// # Description
+///
+/// A kept port has `High` confidence if it was open in every scan and `Medium` otherwise,
+/// and the latency of the first scan it was open in.
+pub fn consensus(runs: &[Vec<OpenPort>], consensus: usize) -> (Vec<OpenPort>, Vec<(u16, usize)>) {
+    let mut seen: BTreeMap<u16, (OpenPort, usize)> = BTreeMap::new();
+    for open in runs.iter().flatten() {
+        seen.entry(open.port).or_insert((*open, 0)).1 += 1;
+    }
+
+    let kept = seen
+        .values()
+        .filter(|(_, count)| *count >= consensus)
+        .map(|&(open, count)| OpenPort {
+            confidence: match count == runs.len() {
+                true => Confidence::High,
+                false => Confidence::Medium,
+            },
+            ..open
+        })
+        .collect();
+    let observed = seen
+        .iter()
+        .map(|(&port, &(_, count))| (port, count))
+        .collect();
+    (kept, observed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_jitter() {
+        let jitter = |min, max| Jitter {
+            min: Duration::from_millis(min),
+            max: Duration::from_millis(max),
+        };
+        assert_eq!("10..200ms".parse(), Ok(jitter(10, 200)));
+        assert_eq!("1..3s".parse(), Ok(jitter(1000, 3000)));
+        assert_eq!("500ms..2s".parse(), Ok(jitter(500, 2000)));
+        assert_eq!("0..0ms".parse(), Ok(jitter(0, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_jitter() {
+        for text in [
+            "200ms",
+            "200..10ms",
+            "2s..500ms",
+            "10..200",
+            "..200ms",
+            "10..ms",
+            "1..3m",
+        ] {
+            assert_eq!(
+                text.parse::<Jitter>(),
+                Err("invalid jitter; must be e.g. 10..200ms"),
+                "{}",
+                text
+            );
+        }
+    }
+
+    fn open(port: u16, millis: u64) -> OpenPort {
+        OpenPort {
+            port,
+            latency: Duration::from_millis(millis),
+            confidence: Confidence::Low,
+        }
+    }
+
+    #[test]
+    fn keeps_ports_open_in_enough_runs() {
+        let runs = vec![
+            vec![open(22, 5), open(80, 7)],
+            vec![open(22, 9), open(443, 3)],
+            vec![open(22, 4), open(80, 8)],
+        ];
+
+        let (kept, observed) = consensus(&runs, 2);
+        let kept: Vec<_> = kept
+            .iter()
+            .map(|p| (p.port, p.latency.as_millis(), p.confidence))
+            .collect();
+        assert_eq!(
+            kept,
+            [(22, 5, Confidence::High), (80, 7, Confidence::Medium)]
+        );
+        assert_eq!(observed, [(22, 3), (80, 2), (443, 1)]);
+
+        let (kept, _) = consensus(&runs, 3);
+        assert_eq!(kept.iter().map(|p| p.port).collect::<Vec<_>>(), [22]);
+        let (kept, _) = consensus(&runs, 1);
+        assert_eq!(
+            kept.iter().map(|p| p.port).collect::<Vec<_>>(),
+            [22, 80, 443]
+        );
+    }
+
+    #[test]
+    fn agrees_on_nothing_without_runs() {
+        assert_eq!(consensus(&[], 1).0.len(), 0);
+        assert_eq!(consensus(&[vec![], vec![]], 1).1, []);
+    }
+}
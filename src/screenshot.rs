@@ -0,0 +1,215 @@
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::hooks;
+use crate::probe::{self, http, Identity};
+
+/// Ports on which web services are assumed to speak HTTPS. Other open ports are only
+/// screenshotted if they answer an HTTP request.
+const TLS_PORTS: &[u16] = &[443, 8443];
+
+/// Headless browsers tried, in order, when no screenshot command is given.
+const BROWSERS: &[&str] = &["chromium", "chromium-browser", "google-chrome", "chrome"];
+
+/// How long one screenshot may take before the browser or command is killed.
+const SHOT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a running screenshot is checked for completion.
+const SHOT_POLL: Duration = Duration::from_millis(100);
+
+/// Name of the page listing the screenshots, written into the screenshot directory.
+pub const INDEX: &str = "index.html";
+
+/// A screenshot of the web service on one port.
+pub struct Shot {
+    pub port: u16,
+    pub url: String,
+    /// The image file, or `None` if the screenshot failed.
+    pub path: Option<PathBuf>,
+}
+
+/// Takes a screenshot of every web service among the open ports of `addr`.
+///
+/// # Arguments
+///
+/// * `dir` - The directory the images and `index.html` are written to; created if missing.
+/// * `addr` - The IP address that was scanned.
+/// * `ports` - The open ports of the host.
+/// * `identity` - Supplies the `User-Agent` of the HTTP requests finding web services.
+/// * `hostname` - The host name the target was given as, used in URLs instead of `addr`.
+/// * `command` - A shell command taking the screenshot, run with `IP_SNIFFER_URL` and
+///   `IP_SNIFFER_SCREENSHOT` set; a headless Chromium or Chrome is used if `None`.
+///
+/// # Returns
+///
+/// One `Shot` per web service, in port order.
+///
+/// # Errors
+///
+/// * "failed to create screenshot directory" if `dir` cannot be created.
+/// * "no headless browser found; install chromium or use --screenshot-command" if no
+///   command is given and no browser is installed.
+/// * "failed to write index.html" if the index page cannot be written.
+pub fn take(
+    dir: &str,
+    addr: IpAddr,
+    ports: &[u16],
+    identity: &Identity,
+    hostname: Option<&str>,
+    command: Option<&str>,
+) -> Result<Vec<Shot>, &'static str> {
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir).map_err(|_| "failed to create screenshot directory")?;
+
+    let urls = web_urls(addr, ports, identity, hostname);
+    let browser = match (command, urls.is_empty()) {
+        (None, false) => Some(
+            find_browser()
+                .ok_or("no headless browser found; install chromium or use --screenshot-command")?,
+        ),
+        _ => None,
+    };
+
+    let mut shots = vec![];
+    for (port, url) in urls {
+        let name = format!("{}_{}.png", addr.to_string().replace(':', "-"), port);
+        let path = dir.join(name);
+
+        let cmd = match (command, browser) {
+            (Some(command), _) => {
+                let mut cmd = hooks::shell(command);
+                cmd.env("IP_SNIFFER_URL", &url)
+                    .env("IP_SNIFFER_SCREENSHOT", &path);
+                cmd
+            }
+            (None, browser) => {
+                let mut cmd = Command::new(browser.unwrap_or(BROWSERS[0]));
+                cmd.args([
+                    "--headless",
+                    "--disable-gpu",
+                    "--hide-scrollbars",
+                    "--ignore-certificate-errors",
+                    "--window-size=1280,800",
+                ])
+                .arg(format!("--screenshot={}", path.display()))
+                .arg(&url);
+                cmd
+            }
+        };
+
+        let taken = run(cmd) && path.exists();
+        shots.push(Shot {
+            port,
+            url,
+            path: taken.then_some(path),
+        });
+    }
+
+    fs::write(dir.join(INDEX), index(addr, &shots)).map_err(|_| "failed to write index.html")?;
+    Ok(shots)
+}
+
+/// Returns the URL of every web service among `ports`, in port order.
+fn web_urls(
+    addr: IpAddr,
+    ports: &[u16],
+    identity: &Identity,
+    hostname: Option<&str>,
+) -> Vec<(u16, String)> {
+    let host = match (hostname, addr) {
+        (Some(hostname), _) => hostname.to_string(),
+        (None, IpAddr::V4(_)) => addr.to_string(),
+        (None, IpAddr::V6(_)) => format!("[{}]", addr),
+    };
+
+    probe::run_on_ports(addr, ports, |target: SocketAddr| {
+        if TLS_PORTS.contains(&target.port()) {
+            Some("https")
+        } else {
+            http::probe(target, identity, hostname).map(|_| "http")
+        }
+    })
+    .into_iter()
+    .map(|(port, scheme)| (port, format!("{}://{}:{}/", scheme, host, port)))
+    .collect()
+}
+
+/// Returns the first headless browser in `BROWSERS` that can be run.
+fn find_browser() -> Option<&'static str> {
+    BROWSERS.iter().copied().find(|browser| {
+        Command::new(browser)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+/// Runs `cmd`, killing it after `SHOT_TIMEOUT`.
+///
+/// # Returns
+///
+/// Whether it exited successfully in time.
+fn run(mut cmd: Command) -> bool {
+    let Ok(mut child) = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+    let started = Instant::now();
+
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if started.elapsed() < SHOT_TIMEOUT => thread::sleep(SHOT_POLL),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+        }
+    }
+}
+
+/// Renders the page linking every screenshot of `addr`.
+fn index(addr: IpAddr, shots: &[Shot]) -> String {
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n",
+        addr, addr
+    );
+
+    for shot in shots {
+        let url = escape(&shot.url);
+        page += &format!("<h2><a href=\"{}\">{}</a></h2>\n", url, url);
+
+        page += &match shot.path.as_ref().and_then(|path| path.file_name()) {
+            Some(name) => {
+                let name = escape(&name.to_string_lossy());
+                format!(
+                    "<a href=\"{}\"><img src=\"{}\" width=\"640\"></a>\n",
+                    name, name
+                )
+            }
+            None => "<p>screenshot failed</p>\n".to_string(),
+        };
+    }
+
+    page += "</body>\n</html>\n";
+    page
+}
+
+/// Escapes text for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
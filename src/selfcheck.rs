@@ -0,0 +1,243 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::str::FromStr;
+use std::time::Duration;
+
+use ip_sniffer::events::Bus;
+use ip_sniffer::scan::{self, Connector, PortOrder};
+
+use crate::i18n::Lang;
+use crate::target;
+
+// `self` asks a reflector outside the network what the internet sees of this machine.
+// A reflector is any HTTP server answering two requests under its URL:
+//
+// GET /ip                      the caller's address as plain text, e.g. `203.0.113.7`
+// GET /probe?ports=22,80,443   one `PORT STATE` line per port, e.g. `22 open`, after
+//                              connecting back to the caller's address on each port
+//
+// A reflector must only ever connect back to the address the request came from, so it
+// cannot be used to scan anyone else.
+
+/// How long to wait for the reflector to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the reflector may take to answer, including probing the ports.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Threads used to find the ports this machine listens on.
+const LOCAL_THREADS: u16 = 64;
+
+/// How long a local port may take to accept a connection.
+const LOCAL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Most ports sent in one probe request.
+const MAX_PROBE_PORTS: usize = 512;
+
+/// A cooperating reflector, given as `http://HOST[:PORT][/PATH]`.
+struct Reflector {
+    addr: SocketAddr,
+    host: String,
+    /// The path of the reflector's URL, without a trailing `/`.
+    path: String,
+}
+
+impl FromStr for Reflector {
+    type Err = &'static str;
+
+    /// Parses a reflector URL.
+    ///
+    /// # Errors
+    ///
+    /// * "not a valid reflector URL; must be http://HOST[:PORT][/PATH]" if it is not one.
+    /// * Any error returned by `target::parse_host_port` if the host does not resolve.
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let invalid = "not a valid reflector URL; must be http://HOST[:PORT][/PATH]";
+        let rest = url.strip_prefix("http://").ok_or(invalid)?;
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        if host.is_empty() {
+            return Err(invalid);
+        }
+
+        let with_port = match host.rsplit_once(':') {
+            Some((_, port)) if !port.contains(']') => host.to_string(),
+            _ => format!("{}:80", host),
+        };
+        Ok(Reflector {
+            addr: target::parse_host_port(&with_port)?,
+            host: host.to_string(),
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl Reflector {
+    /// Sends a GET request for `path` under the reflector's URL.
+    ///
+    /// # Returns
+    ///
+    /// The body of the response.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to reach the reflector" if it cannot be connected to or does not answer.
+    /// * "the reflector returned an error" if the status is not 200.
+    fn get(&self, path: &str) -> Result<String, &'static str> {
+        let mut stream = TcpStream::connect_timeout(&self.addr, CONNECT_TIMEOUT)
+            .map_err(|_| "failed to reach the reflector")?;
+        stream.set_read_timeout(Some(READ_TIMEOUT)).ok();
+
+        // HTTP/1.0 keeps the body from being chunked and closes the connection after it.
+        let request = format!(
+            "GET {}{} HTTP/1.0\r\nHost: {}\r\nUser-Agent: ip-sniffer/{}\r\n\r\n",
+            self.path,
+            path,
+            self.host,
+            env!("CARGO_PKG_VERSION")
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|_| "failed to reach the reflector")?;
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|_| "failed to reach the reflector")?;
+
+        match response.split_once("\r\n\r\n") {
+            Some((head, body)) if head.get(9..12) == Some("200") => Ok(body.to_string()),
+            Some(_) => Err("the reflector returned an error"),
+            None => Err("failed to reach the reflector"),
+        }
+    }
+}
+
+/// Runs the `self` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `self` on the command line.
+/// * `lang` - The language results are printed in.
+///
+/// # Returns
+///
+/// The exit code: 0 if the internet reaches none of the ports, 2 if it reaches any.
+///
+/// # Errors
+///
+/// * "missing --via URL" if no reflector is given.
+/// * "invalid port list" if `--ports` is not a comma-separated list of ports.
+/// * "too many arguments" if anything else is given.
+/// * "the reflector did not return an IP address" if `/ip` answers something else.
+/// * "unexpected answer from the reflector" if `/probe` answers something else.
+/// * Any error returned by `Reflector::from_str` or `Reflector::get`.
+///
+/// # Usage
+///
+/// * `self --via <URL>` - Check which of the ports this machine listens on the internet
+///   can reach.
+/// * `self --via <URL> --ports <LIST>` - Check the ports in LIST, e.g. `22,80,443`,
+///   instead.
+///
+/// # Description
+///
+/// The ports this machine listens on are found by scanning its loopback address, so
+/// services bound only to another address are missed unless listed with `--ports`.
+pub fn run(args: &[String], lang: Lang) -> Result<i32, &'static str> {
+    let mut via = None;
+    let mut ports = None;
+    let mut rest = args.iter();
+
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--via" => via = Some(rest.next().ok_or("missing --via URL")?),
+            "--ports" => ports = Some(parse_ports(rest.next())?),
+            _ => return Err("too many arguments"),
+        }
+    }
+
+    let reflector = Reflector::from_str(via.ok_or("missing --via URL")?)?;
+    let public = IpAddr::from_str(reflector.get("/ip")?.trim())
+        .map_err(|_| "the reflector did not return an IP address")?;
+    println!("{}", lang.format("public IP: {}", &[&public]));
+
+    let ports = match ports {
+        Some(ports) => ports,
+        None => listening(public, lang),
+    };
+    if ports.is_empty() {
+        println!("{}", lang.tr("no listening ports found"));
+        return Ok(0);
+    }
+
+    let mut reachable = vec![];
+    for chunk in ports.chunks(MAX_PROBE_PORTS) {
+        let list: Vec<String> = chunk.iter().map(u16::to_string).collect();
+        let answer = reflector.get(&format!("/probe?ports={}", list.join(",")))?;
+
+        for line in answer.lines().filter(|line| !line.trim().is_empty()) {
+            let (port, state) = line
+                .trim()
+                .split_once(' ')
+                .ok_or("unexpected answer from the reflector")?;
+            let port: u16 = port
+                .parse()
+                .map_err(|_| "unexpected answer from the reflector")?;
+            if state.trim() == "open" && chunk.contains(&port) {
+                reachable.push(port);
+            }
+        }
+    }
+
+    for port in &reachable {
+        let message = "{}:{} is reachable from the internet";
+        println!("{}", lang.format(message, &[&public, port]));
+    }
+    let message = "{} port(s) checked, {} reachable from the internet";
+    println!(
+        "{}",
+        lang.format(message, &[&ports.len(), &reachable.len()])
+    );
+    Ok(if reachable.is_empty() { 0 } else { 2 })
+}
+
+/// Returns the ports this machine listens on, found on the loopback address of the
+/// public address's family.
+fn listening(public: IpAddr, lang: Lang) -> Vec<u16> {
+    let loopback = match public {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+    };
+    let connector = Connector {
+        timeout: Some(LOCAL_TIMEOUT),
+        proxy: None,
+    };
+
+    let mut bus = Bus::new();
+    bus.subscribe(crate::health_warnings(lang, false));
+    let order = PortOrder::Sequential;
+    let mut ports: Vec<u16> =
+        scan::scan_host_with(loopback, LOCAL_THREADS, connector, order, None, &mut bus)
+            .open
+            .iter()
+            .map(|open| open.port)
+            .collect();
+    ports.sort();
+    ports
+}
+
+/// Parses the comma-separated ports given to `--ports`.
+fn parse_ports(value: Option<&String>) -> Result<Vec<u16>, &'static str> {
+    let mut ports: Vec<u16> = value
+        .ok_or("invalid port list")?
+        .split(',')
+        .map(|port| port.trim().parse().ok().filter(|&port| port > 0))
+        .collect::<Option<_>>()
+        .ok_or("invalid port list")?;
+    ports.sort();
+    ports.dedup();
+    Ok(ports)
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use ip_sniffer::scan::{Confidence, HostScan, OpenPort, PortState};
+
+use crate::i18n::Lang;
+
+/// Latency percentiles reported in the statistics block.
+pub const PERCENTILES: &[u8] = &[50, 95, 99];
+
+/// Statistics summarizing a finished scan.
+#[derive(Debug)]
+pub struct ScanStats {
+    pub open: usize,
+    pub closed: usize,
+    pub filtered: usize,
+    pub local_errors: usize,
+    /// `(percentile, latency)` over every port that answered, open or closed.
+    pub latency: Vec<(u8, Duration)>,
+    /// Number of latency samples the percentiles are computed from.
+    pub samples: usize,
+    /// Number of failed connection attempts per error.
+    pub errors: BTreeMap<String, usize>,
+    /// Number of connection attempts repeated after a local error.
+    pub retries: usize,
+    /// Number of timed-out ports probed a second time with a longer timeout.
+    pub retransmits: usize,
+    /// Number of times the scan waited for slow output sinks.
+    pub sink_stalls: usize,
+    /// Total time the scan waited for slow output sinks.
+    pub sink_wait: Duration,
+    /// Number of probed ports per state and confidence.
+    pub states: BTreeMap<(PortState, Confidence), usize>,
+}
+
+impl ScanStats {
+    /// Computes the statistics of a scan.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_scan` - The outcome of the scan.
+    /// * `open` - The open ports that are reported, after any verification.
+    /// * `filtered` - The number of filtered ports, including those dropped by verification.
+    pub fn new(host_scan: &HostScan, open: &[OpenPort], filtered: usize) -> ScanStats {
+        let mut latencies: Vec<Duration> = open
+            .iter()
+            .map(|p| p.latency)
+            .chain(host_scan.closed_latencies.iter().copied())
+            .collect();
+        latencies.sort();
+
+        ScanStats {
+            open: open.len(),
+            closed: host_scan.closed,
+            filtered,
+            local_errors: host_scan.local_errors,
+            latency: PERCENTILES
+                .iter()
+                .filter_map(|&p| Some((p, percentile(&latencies, p)?)))
+                .collect(),
+            samples: latencies.len(),
+            errors: host_scan.errors.clone(),
+            retries: host_scan.retries,
+            retransmits: host_scan.retransmits,
+            sink_stalls: host_scan.sink_stalls,
+            sink_wait: host_scan.sink_wait,
+            states: host_scan.states.clone(),
+        }
+    }
+
+    /// Formats the statistics block of the text output, in the given language.
+    pub fn text(&self, lang: Lang) -> String {
+        let message = "Statistics:\n  open: {}, closed: {}, filtered: {}, local errors: {}\n";
+        let mut out = lang.format(
+            message,
+            &[&self.open, &self.closed, &self.filtered, &self.local_errors],
+        );
+
+        if !self.latency.is_empty() {
+            let latency: Vec<String> = self
+                .latency
+                .iter()
+                .map(|(p, latency)| format!("p{} {:.2}", p, latency.as_secs_f64() * 1000.0))
+                .collect();
+            let message = "  latency (ms): {} over {} ports\n";
+            out += &lang.format(message, &[&latency.join(", "), &self.samples]);
+        }
+
+        if !self.errors.is_empty() {
+            let errors: Vec<String> = self
+                .errors
+                .iter()
+                .map(|(error, count)| format!("{} {}", error, count))
+                .collect();
+            out += &lang.format("  errors: {}\n", &[&errors.join(", ")]);
+        }
+
+        let message = "  retries: {}, retransmissions: {}\n";
+        out += &lang.format(message, &[&self.retries, &self.retransmits]);
+
+        if !self.states.is_empty() {
+            let states: Vec<String> = self
+                .states
+                .iter()
+                .map(|((state, confidence), count)| format!("{} {} {}", state, confidence, count))
+                .collect();
+            out += &lang.format("  confidence: {}\n", &[&states.join(", ")]);
+        }
+
+        if self.sink_stalls > 0 {
+            let message = "  output-limited: waited {}s for output in {} stall(s)\n";
+            let wait = format!("{:.2}", self.sink_wait.as_secs_f64());
+            out += &lang.format(message, &[&wait, &self.sink_stalls]);
+        }
+        out
+    }
+}
+
+/// Returns the nearest-rank `p`th percentile of `sorted`, or `None` if it is empty.
+fn percentile(sorted: &[Duration], p: u8) -> Option<Duration> {
+    let rank = (p as usize * sorted.len()).div_ceil(100);
+    sorted.get(rank.saturating_sub(1)).copied()
+}
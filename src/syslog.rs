@@ -0,0 +1,179 @@
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::process;
+use std::str::FromStr;
+
+/// Default port of remote syslog collectors.
+const SYSLOG_PORT: u16 = 514;
+
+/// Path of the local syslog socket.
+#[cfg(unix)]
+const LOCAL_SOCKET: &str = "/dev/log";
+
+/// Facilities messages can be logged under, with their codes from RFC 5424.
+const FACILITIES: &[(&str, u8)] = &[
+    ("kern", 0),
+    ("user", 1),
+    ("daemon", 3),
+    ("auth", 4),
+    ("syslog", 5),
+    ("security", 13),
+    ("local0", 16),
+    ("local1", 17),
+    ("local2", 18),
+    ("local3", 19),
+    ("local4", 20),
+    ("local5", 21),
+    ("local6", 22),
+    ("local7", 23),
+];
+
+/// Severities messages can be logged at, with their codes from RFC 5424.
+const SEVERITIES: &[(&str, u8)] = &[
+    ("emerg", 0),
+    ("alert", 1),
+    ("crit", 2),
+    ("err", 3),
+    ("warning", 4),
+    ("notice", 5),
+    ("info", 6),
+    ("debug", 7),
+];
+
+/// A syslog facility, e.g. `user` or `local0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Facility(u8);
+
+impl Default for Facility {
+    fn default() -> Self {
+        Facility(1)
+    }
+}
+
+impl FromStr for Facility {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FACILITIES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, code)| Facility(*code))
+            .ok_or("unknown syslog facility, e.g. user, daemon, auth or local0-local7")
+    }
+}
+
+/// A syslog severity, e.g. `notice` or `warning`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Severity(u8);
+
+impl Default for Severity {
+    fn default() -> Self {
+        Severity(5)
+    }
+}
+
+impl FromStr for Severity {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SEVERITIES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, code)| Severity(*code))
+            .ok_or("unknown syslog severity, e.g. notice, warning or err")
+    }
+}
+
+/// Where syslog messages are sent.
+enum Destination {
+    #[cfg(unix)]
+    Local(std::os::unix::net::UnixDatagram),
+    Remote(UdpSocket),
+}
+
+/// Sends findings to a local or remote syslog collector.
+pub struct Syslog {
+    destination: Destination,
+    priority: u8,
+}
+
+impl Syslog {
+    /// Connects to a syslog collector.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - `local` for the local syslog socket, or `HOST[:PORT]` for a remote
+    ///   collector listening on UDP (port 514 by default).
+    /// * `facility` - The facility messages are logged under.
+    /// * `severity` - The severity messages are logged at.
+    ///
+    /// # Errors
+    ///
+    /// * "local syslog is not available" if the local socket cannot be reached.
+    /// * "failed to resolve syslog collector" if the remote collector cannot be resolved.
+    /// * "failed to open syslog socket" if the UDP socket cannot be created.
+    pub fn connect(
+        dest: &str,
+        facility: Facility,
+        severity: Severity,
+    ) -> Result<Syslog, &'static str> {
+        let destination = if dest == "local" {
+            local()?
+        } else {
+            let target = match dest.rsplit_once(':') {
+                Some((_, port)) if port.parse::<u16>().is_ok() => dest.to_string(),
+                _ => format!("{}:{}", dest, SYSLOG_PORT),
+            };
+            let target = target
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or("failed to resolve syslog collector")?;
+
+            let bind = if target.is_ipv4() {
+                "0.0.0.0:0"
+            } else {
+                "[::]:0"
+            };
+            let socket = UdpSocket::bind(bind).map_err(|_| "failed to open syslog socket")?;
+            socket
+                .connect(target)
+                .map_err(|_| "failed to open syslog socket")?;
+            Destination::Remote(socket)
+        };
+
+        Ok(Syslog {
+            destination,
+            priority: facility.0 * 8 + severity.0,
+        })
+    }
+
+    /// Logs a message. Delivery is best effort: failures are ignored so that an
+    /// unreachable collector does not interrupt the scan.
+    pub fn log(&self, message: &str) {
+        let line = format!(
+            "<{}>ip-sniffer[{}]: {}",
+            self.priority,
+            process::id(),
+            message
+        );
+
+        let _ = match &self.destination {
+            #[cfg(unix)]
+            Destination::Local(socket) => socket.send(line.as_bytes()),
+            Destination::Remote(socket) => socket.send(line.as_bytes()),
+        };
+    }
+}
+
+#[cfg(unix)]
+fn local() -> Result<Destination, &'static str> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()
+        .and_then(|socket| socket.connect(LOCAL_SOCKET).map(|_| socket))
+        .map_err(|_| "local syslog is not available")?;
+    Ok(Destination::Local(socket))
+}
+
+#[cfg(not(unix))]
+fn local() -> Result<Destination, &'static str> {
+    Err("local syslog is not available")
+}
@@ -0,0 +1,611 @@
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+// `--uptime` reads the SYN-ACKs of the host out of the `--pcap` capture of the scan, as
+// the scanner's own connect sockets never see the TCP header:
+//
+// 12:00:00.000100 IP 192.168.1.1.22 > 192.168.1.50.40112: Flags [S.], seq 2315563213,
+//                 ... options [mss 1460,sackOK,TS val 3629145512 ecr 1130528,...]
+//
+// The timestamp values (TSval) of most systems count up at a fixed rate from boot, so
+// their rate against the capture time gives the tick rate of the host's clock and how far
+// that clock drifts from ours (its skew), and the latest value divided by the rate guesses
+// the uptime, as nmap does. Hosts that add a random offset to their timestamps, as Linux
+// does per pair of hosts, give a meaningless uptime but a usable rate and skew.
+//
+// The initial sequence numbers (ISN) of the same SYN-ACKs show how the host picks them:
+// randomly, from a clock, by a fixed increment, or always the same.
+//
+// `--dedupe-by-skew` samples every address of a `--merge-by-host` scan for a minute and
+// groups the addresses whose clocks are one: timestamps from the same counter, or the same
+// skew, which comes from the host's crystal and is not hidden by random offsets. Skews
+// are only compared when measured to within a few ppm, which a 250 or 1000 Hz clock is
+// in that minute.
+
+/// Connections made to an open port after the scan, so there are enough SYN-ACKs spread
+/// over time even when the host has a single open port.
+const PROBES: u32 = 6;
+
+/// Time between those connections; it also lets tcpdump write the last SYN-ACK before
+/// the capture is stopped.
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Rounds of connections made to every address for `--dedupe-by-skew`, and the time
+/// between them.
+const SKEW_ROUNDS: u32 = 60;
+const SKEW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How far apart the start of two counters may be, beyond two ticks, for them to be taken
+/// to be one, in seconds.
+const START_TOLERANCE: f64 = 0.05;
+
+/// Largest error, in ppm, of a skew compared with another.
+const MAX_SKEW_ERROR_PPM: f64 = 10.0;
+
+/// Tick rates of TCP timestamp clocks in use, in Hz. A measured rate within a tenth of
+/// one of them is taken to be it.
+const CLOCK_RATES: &[f64] = &[2.0, 10.0, 100.0, 200.0, 250.0, 300.0, 1000.0];
+
+/// Fewest SYN-ACKs the sequence numbers are classified from.
+const MIN_ISN_SAMPLES: usize = 3;
+
+const PCAP_INVALID: &str = "invalid pcap file";
+
+/// The TCP header fields of one SYN-ACK sent by the host.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    /// When it was captured, in seconds since the UNIX epoch.
+    time: f64,
+    /// Our end of the connection, which tells retransmits from new connections.
+    port: u16,
+    isn: u32,
+    tsval: Option<u32>,
+}
+
+/// The TCP timestamp clock of a host.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    /// Ticks per second.
+    pub hz: u32,
+    /// Time since the clock started counting, which is the uptime unless the host offsets
+    /// its timestamps.
+    pub uptime: Duration,
+    /// How much faster the host's clock runs than ours, in parts per million.
+    pub skew_ppm: f64,
+    /// Standard error of `skew_ppm`.
+    pub skew_error_ppm: f64,
+    /// When the counter was zero by our clock, in seconds since the UNIX epoch.
+    started: f64,
+}
+
+/// How a host picks the initial sequence numbers of its connections.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Isn {
+    Random,
+    /// Taken from a clock, increasing by about this much per second.
+    TimeBased(u64),
+    /// Increased by the same amount for every connection.
+    Incremental(u32),
+    Constant,
+}
+
+/// What the SYN-ACKs of a host reveal.
+#[derive(Debug)]
+pub struct Analysis {
+    /// Number of SYN-ACKs analysed.
+    pub samples: usize,
+    /// `None` if the host sends no timestamps or they do not follow a known clock rate.
+    pub clock: Option<Clock>,
+    /// `None` if there are too few SYN-ACKs.
+    pub isn: Option<Isn>,
+}
+
+impl fmt::Display for Isn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Isn::Random => write!(f, "random"),
+            Isn::TimeBased(rate) => write!(f, "time-based, about {} per second", rate),
+            Isn::Incremental(step) => write!(f, "incremented by {}", step),
+            Isn::Constant => write!(f, "constant"),
+        }
+    }
+}
+
+/// Connects to an open port of every target a few times, spaced out, so the capture
+/// holds enough SYN-ACKs. Failed connections are ignored.
+pub fn probe(targets: &[SocketAddr]) {
+    probe_rounds(targets, PROBES, PROBE_INTERVAL);
+}
+
+/// Connects to an open port of every target once a second for a minute, so their skews
+/// can be told apart.
+pub fn probe_for_skew(targets: &[SocketAddr]) {
+    probe_rounds(targets, SKEW_ROUNDS, SKEW_INTERVAL);
+}
+
+fn probe_rounds(targets: &[SocketAddr], rounds: u32, interval: Duration) {
+    for _ in 0..rounds {
+        for target in targets {
+            let _ = TcpStream::connect_timeout(target, PROBE_TIMEOUT);
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Groups addresses whose TCP timestamp clocks are the same clock.
+///
+/// # Returns
+///
+/// Every group of two or more addresses that are probably one host, in the order of
+/// `clocks`.
+pub fn same_hosts(clocks: &[(IpAddr, Clock)]) -> Vec<Vec<IpAddr>> {
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for (i, (_, clock)) in clocks.iter().enumerate() {
+        let mut group = vec![i];
+        let mut k = 0;
+        while k < groups.len() {
+            if groups[k].iter().any(|&j| clock.matches(&clocks[j].1)) {
+                group.extend(groups.remove(k));
+            } else {
+                k += 1;
+            }
+        }
+        group.sort_unstable();
+        groups.push(group);
+    }
+
+    groups.sort_by_key(|group| group[0]);
+    groups
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| group.iter().map(|&i| clocks[i].0).collect())
+        .collect()
+}
+
+impl Clock {
+    /// Returns whether this and `other` are probably the same clock: the same counter, or
+    /// the same rate and skew measured precisely enough to tell.
+    fn matches(&self, other: &Clock) -> bool {
+        if self.hz != other.hz {
+            return false;
+        }
+        let tolerance = 2.0 / self.hz as f64 + START_TOLERANCE;
+        let same_counter = (self.started - other.started).abs() <= tolerance;
+
+        let precise = self.skew_error_ppm.max(other.skew_error_ppm) <= MAX_SKEW_ERROR_PPM;
+        let error = 2.0 * (self.skew_error_ppm + other.skew_error_ppm);
+        let same_skew = (self.skew_ppm - other.skew_ppm).abs() <= error;
+
+        same_counter || (precise && same_skew)
+    }
+}
+
+/// Reads the SYN-ACKs sent by each of `addrs` from a pcap file written by tcpdump.
+///
+/// # Returns
+///
+/// The samples of each address, in the order of `addrs`: one per connection, in capture
+/// order; retransmitted SYN-ACKs, with the ISN of one already seen on the same connection,
+/// are left out.
+///
+/// # Errors
+///
+/// * "failed to read pcap file" if the file cannot be read.
+/// * "invalid pcap file" if it is not a pcap file.
+/// * "unsupported link type in pcap file" if its packets are not Ethernet, Linux cooked
+///   (`-i any`), loopback or raw IP.
+pub fn read_samples(path: &str, addrs: &[IpAddr]) -> Result<Vec<Vec<Sample>>, &'static str> {
+    let bytes = fs::read(path).map_err(|_| "failed to read pcap file")?;
+    let header = bytes.get(..24).ok_or(PCAP_INVALID)?;
+
+    let (big_endian, nanos) = match header[..4] {
+        [0xd4, 0xc3, 0xb2, 0xa1] => (false, false),
+        [0xa1, 0xb2, 0xc3, 0xd4] => (true, false),
+        [0x4d, 0x3c, 0xb2, 0xa1] => (false, true),
+        [0xa1, 0xb2, 0x3c, 0x4d] => (true, true),
+        _ => return Err(PCAP_INVALID),
+    };
+    let u32_at = |bytes: &[u8], at: usize| -> Option<u32> {
+        let word: [u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(match big_endian {
+            true => u32::from_be_bytes(word),
+            false => u32::from_le_bytes(word),
+        })
+    };
+    let link_type = u32_at(header, 20).ok_or(PCAP_INVALID)?;
+    if !matches!(link_type, 0 | 1 | 12 | 101 | 113 | 276) {
+        return Err("unsupported link type in pcap file");
+    }
+
+    let mut samples: Vec<Vec<Sample>> = vec![vec![]; addrs.len()];
+    let mut at = 24;
+    while at + 16 <= bytes.len() {
+        let secs = u32_at(&bytes, at).ok_or(PCAP_INVALID)? as f64;
+        let fraction = u32_at(&bytes, at + 4).ok_or(PCAP_INVALID)? as f64;
+        let length = u32_at(&bytes, at + 8).ok_or(PCAP_INVALID)? as usize;
+        let packet = bytes.get(at + 16..at + 16 + length).ok_or(PCAP_INVALID)?;
+        at += 16 + length;
+
+        let time = secs + fraction / if nanos { 1e9 } else { 1e6 };
+        let Some((source, port, isn, tsval)) = syn_ack(packet, link_type) else {
+            continue;
+        };
+        let Some(i) = addrs.iter().position(|addr| *addr == source) else {
+            continue;
+        };
+        let seen = |sample: &Sample| sample.port == port && sample.isn == isn;
+        if !samples[i].iter().any(seen) {
+            samples[i].push(Sample {
+                time,
+                port,
+                isn,
+                tsval,
+            });
+        }
+    }
+    Ok(samples)
+}
+
+/// Estimates the clock and sequence number pattern of a host from its SYN-ACKs.
+pub fn analyze(samples: &[Sample]) -> Analysis {
+    Analysis {
+        samples: samples.len(),
+        clock: clock(samples),
+        isn: isn(samples),
+    }
+}
+
+/// Fits the timestamps of the samples to a clock rate.
+fn clock(samples: &[Sample]) -> Option<Clock> {
+    let timed: Vec<(f64, u32)> = samples
+        .iter()
+        .filter_map(|sample| Some((sample.time, sample.tsval?)))
+        .collect();
+    let &(first_time, first_tsval) = timed.first()?;
+    let &(last_time, last_tsval) = timed.last()?;
+    if last_time - first_time < PROBE_INTERVAL.as_secs_f64() {
+        return None;
+    }
+
+    // Least squares fit of ticks since the first sample against seconds since it.
+    let points: Vec<(f64, f64)> = timed
+        .iter()
+        .map(|&(time, tsval)| (time - first_time, tsval.wrapping_sub(first_tsval) as f64))
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let variance: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let rate = covariance / variance;
+
+    let hz = CLOCK_RATES
+        .iter()
+        .copied()
+        .find(|hz| (rate - hz).abs() <= hz / 10.0)?;
+
+    // Timestamps offset at random per connection do not lie on one line.
+    let fits = points.iter().all(|&(x, y)| {
+        let expected = mean_y + rate * (x - mean_x);
+        (y - expected).abs() <= 2.0 + hz * 0.05
+    });
+    if !fits {
+        return None;
+    }
+
+    // Standard error of the rate, taking the residuals to be at least the rounding of
+    // the timestamps to whole ticks.
+    let residuals: f64 = points
+        .iter()
+        .map(|&(x, y)| (y - mean_y - rate * (x - mean_x)).powi(2))
+        .sum();
+    let spread = match points.len() {
+        0..=2 => 1.0 / 12.0,
+        n => (residuals / (n - 2) as f64).max(1.0 / 12.0),
+    };
+    let rate_error = (spread / variance).sqrt();
+
+    Some(Clock {
+        hz: hz as u32,
+        uptime: Duration::from_secs(last_tsval as u64 / hz as u64),
+        skew_ppm: (rate / hz - 1.0) * 1e6,
+        skew_error_ppm: rate_error / hz * 1e6,
+        started: last_time - last_tsval as f64 / hz,
+    })
+}
+
+/// Classifies how the initial sequence numbers of the samples were picked.
+fn isn(samples: &[Sample]) -> Option<Isn> {
+    if samples.len() < MIN_ISN_SAMPLES {
+        return None;
+    }
+
+    let steps: Vec<(u32, f64)> = samples
+        .windows(2)
+        .map(|pair| {
+            (
+                pair[1].isn.wrapping_sub(pair[0].isn),
+                pair[1].time - pair[0].time,
+            )
+        })
+        .collect();
+    if steps.iter().all(|&(step, _)| step == 0) {
+        return Some(Isn::Constant);
+    }
+    if steps.iter().all(|&(step, _)| step == steps[0].0) {
+        return Some(Isn::Incremental(steps[0].0));
+    }
+
+    let mut rates: Vec<f64> = steps
+        .iter()
+        .filter(|&&(_, elapsed)| elapsed > 0.0)
+        .map(|&(step, elapsed)| step as f64 / elapsed)
+        .collect();
+    rates.sort_by(f64::total_cmp);
+    let Some(&median) = rates.get(rates.len() / 2) else {
+        return Some(Isn::Random);
+    };
+    let steady = rates.len() == steps.len()
+        && rates
+            .iter()
+            .all(|rate| (rate - median).abs() <= median / 4.0);
+    Some(match steady {
+        true => Isn::TimeBased(median as u64),
+        false => Isn::Random,
+    })
+}
+
+/// Returns the source address, destination port, ISN and TSval of a packet if it is a
+/// SYN-ACK.
+fn syn_ack(packet: &[u8], link_type: u32) -> Option<(IpAddr, u16, u32, Option<u32>)> {
+    // Where the IP header starts, per link type; Ethernet and cooked captures also name
+    // the protocol, which VLAN tags push further in.
+    let ip = match link_type {
+        0 => packet.get(4..)?,
+        1 => {
+            let mut at = 12;
+            while packet.get(at..at + 2)? == [0x81, 0x00] {
+                at += 4;
+            }
+            packet.get(at + 2..)?
+        }
+        113 => packet.get(16..)?,
+        276 => packet.get(20..)?,
+        _ => packet,
+    };
+
+    let (source, tcp) = match ip.first()? >> 4 {
+        4 => {
+            let header = (ip[0] & 0x0f) as usize * 4;
+            if *ip.get(9)? != 6 {
+                return None;
+            }
+            let octets: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            (IpAddr::V4(Ipv4Addr::from(octets)), ip.get(header..)?)
+        }
+        6 => {
+            if *ip.get(6)? != 6 {
+                return None;
+            }
+            let octets: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            (IpAddr::V6(Ipv6Addr::from(octets)), ip.get(40..)?)
+        }
+        _ => return None,
+    };
+    if tcp.get(13)? & 0x12 != 0x12 {
+        return None;
+    }
+
+    let port = u16::from_be_bytes(tcp.get(2..4)?.try_into().ok()?);
+    let isn = u32::from_be_bytes(tcp.get(4..8)?.try_into().ok()?);
+    let header = (*tcp.get(12)? >> 4) as usize * 4;
+    let mut options = tcp.get(20..header)?;
+    let mut tsval = None;
+    while let Some(&kind) = options.first() {
+        match kind {
+            0 => break,
+            1 => options = &options[1..],
+            _ => {
+                let length = *options.get(1)? as usize;
+                if length < 2 {
+                    break;
+                }
+                if kind == 8 && length == 10 {
+                    tsval = Some(u32::from_be_bytes(options.get(2..6)?.try_into().ok()?));
+                }
+                options = options.get(length..)?;
+            }
+        }
+    }
+    Some((source, port, isn, tsval))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a raw IPv4 SYN-ACK from 10.0.0.1:80 to `port`, with a timestamp option.
+    fn packet(port: u16, isn: u32, tsval: u32) -> Vec<u8> {
+        let mut ip = vec![0x45, 0, 0, 52, 0, 0, 0x40, 0, 64, 6, 0, 0];
+        ip.extend([10, 0, 0, 1, 10, 0, 0, 2]);
+        let mut tcp = vec![0, 80];
+        tcp.extend(port.to_be_bytes());
+        tcp.extend(isn.to_be_bytes());
+        tcp.extend([0, 0, 0, 1, 0x80, 0x12, 0xff, 0xff, 0, 0, 0, 0]);
+        tcp.extend([1, 1, 8, 10]);
+        tcp.extend(tsval.to_be_bytes());
+        tcp.extend([0, 0, 0, 0]);
+        [ip, tcp].concat()
+    }
+
+    /// Writes `(seconds, packet)` records as a raw IP pcap file and returns its path.
+    fn pcap(name: &str, packets: &[(f64, Vec<u8>)]) -> String {
+        let mut bytes = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        bytes.extend([0; 8]);
+        bytes.extend(65535u32.to_le_bytes());
+        bytes.extend(101u32.to_le_bytes());
+        for (time, packet) in packets {
+            bytes.extend((*time as u32).to_le_bytes());
+            bytes.extend(((time.fract() * 1e6).round() as u32).to_le_bytes());
+            bytes.extend((packet.len() as u32).to_le_bytes());
+            bytes.extend((packet.len() as u32).to_le_bytes());
+            bytes.extend(packet);
+        }
+        let path = std::env::temp_dir().join(format!("ip-sniffer-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn keeps_one_sample_per_connection() {
+        let host: IpAddr = "10.0.0.1".parse().unwrap();
+        let path = pcap(
+            "constant.pcap",
+            &[
+                (1000.0, packet(40000, 7, 100)),
+                // A retransmit of the first SYN-ACK.
+                (1001.0, packet(40000, 7, 200)),
+                (1000.1, packet(40001, 7, 110)),
+                (1000.2, packet(40002, 7, 120)),
+            ],
+        );
+        let samples = read_samples(&path, &[host]).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(samples[0].len(), 3);
+        assert_eq!(analyze(&samples[0]).isn, Some(Isn::Constant));
+    }
+
+    fn sample(time: f64, isn: u32, tsval: Option<u32>) -> Sample {
+        Sample {
+            time,
+            port: 40000 + (time * 10.0) as u16,
+            isn,
+            tsval,
+        }
+    }
+
+    #[test]
+    fn reads_syn_acks() {
+        let host: IpAddr = "10.0.0.1".parse().unwrap();
+        let raw = packet(40000, 0xdead_beef, 123_456);
+        assert_eq!(
+            syn_ack(&raw, 101),
+            Some((host, 40000, 0xdead_beef, Some(123_456)))
+        );
+
+        // Ethernet, with a VLAN tag.
+        let mut ethernet = vec![0; 12];
+        ethernet.extend([0x81, 0x00, 0x00, 0x0a, 0x08, 0x00]);
+        ethernet.extend(&raw);
+        assert_eq!(
+            syn_ack(&ethernet, 1),
+            Some((host, 40000, 0xdead_beef, Some(123_456)))
+        );
+
+        // Without the timestamp option.
+        let mut bare = raw.clone();
+        bare[32] = 0x50;
+        assert_eq!(syn_ack(&bare, 101), Some((host, 40000, 0xdead_beef, None)));
+
+        // A bare SYN, a UDP datagram and a truncated packet.
+        let mut syn = raw.clone();
+        syn[33] = 0x02;
+        assert_eq!(syn_ack(&syn, 101), None);
+        let mut udp = raw.clone();
+        udp[9] = 17;
+        assert_eq!(syn_ack(&udp, 101), None);
+        assert_eq!(syn_ack(&raw[..30], 101), None);
+    }
+
+    #[test]
+    fn fits_clock_rates() {
+        // A 1000 Hz clock, up for a day and running 50 ppm fast.
+        let samples: Vec<Sample> = (0..100)
+            .map(|i| {
+                let time = 1000.0 + i as f64 * 10.0;
+                let ticks = 86_400_000.0 + (time - 1000.0) * 1000.0 * 1.000_05;
+                sample(time, 0, Some(ticks.round() as u32))
+            })
+            .collect();
+        let clock = clock(&samples).unwrap();
+        assert_eq!(clock.hz, 1000);
+        // The uptime is that at the last sample, 990 seconds in.
+        assert_eq!(clock.uptime.as_secs(), 87_390);
+        assert!((clock.skew_ppm - 50.0).abs() < 1.0, "{}", clock.skew_ppm);
+        assert!(clock.skew_error_ppm < MAX_SKEW_ERROR_PPM);
+        assert!((clock.started - (1000.0 - 86_400.0)).abs() < 0.1);
+    }
+
+    #[test]
+    fn rejects_unknown_clocks() {
+        // Timestamps offset at random per connection.
+        let random: Vec<Sample> = [5, 900_000, 12, 3_000_000_000, 77]
+            .iter()
+            .enumerate()
+            .map(|(i, &tsval)| sample(i as f64, 0, Some(tsval)))
+            .collect();
+        assert!(clock(&random).is_none());
+
+        // No timestamps, or too little time between them.
+        let none: Vec<Sample> = (0..5).map(|i| sample(i as f64, 0, None)).collect();
+        assert!(clock(&none).is_none());
+        let quick = [sample(0.0, 0, Some(0)), sample(0.05, 0, Some(50))];
+        assert!(clock(&quick).is_none());
+    }
+
+    #[test]
+    fn classifies_isns() {
+        let isns = |isns: &[(f64, u32)]| {
+            let samples: Vec<Sample> = isns
+                .iter()
+                .map(|&(time, isn)| sample(time, isn, None))
+                .collect();
+            isn(&samples)
+        };
+
+        assert_eq!(isns(&[(0.0, 1), (0.1, 1)]), None);
+        assert_eq!(isns(&[(0.0, 9), (0.1, 9), (0.2, 9)]), Some(Isn::Constant));
+        assert_eq!(
+            isns(&[(0.0, u32::MAX), (0.3, 63_999), (0.4, 127_999)]),
+            Some(Isn::Incremental(64_000))
+        );
+        assert_eq!(
+            isns(&[(0.0, 0), (0.1, 25_000), (0.3, 75_000), (0.4, 100_000)]),
+            Some(Isn::TimeBased(250_000))
+        );
+        assert_eq!(
+            isns(&[
+                (0.0, 0),
+                (0.1, 3_000_000_000),
+                (0.2, 1_234),
+                (0.3, 987_654_321)
+            ]),
+            Some(Isn::Random)
+        );
+    }
+
+    #[test]
+    fn groups_addresses_sharing_a_clock() {
+        let clock = |started: f64, skew_ppm: f64| Clock {
+            hz: 1000,
+            uptime: Duration::ZERO,
+            skew_ppm,
+            skew_error_ppm: 1.0,
+            started,
+        };
+        let addr = |last: u8| IpAddr::from([10, 0, 0, last]);
+        let clocks = [
+            (addr(1), clock(100.0, 20.0)),
+            (addr(2), clock(500.0, -80.0)),
+            (addr(3), clock(100.01, 200.0)),
+            (addr(4), clock(900.0, 21.0)),
+        ];
+        assert_eq!(same_hosts(&clocks), [vec![addr(1), addr(3), addr(4)]]);
+    }
+}
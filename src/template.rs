@@ -0,0 +1,726 @@
+use std::borrow::Cow;
+use std::fs;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use ip_sniffer::idna;
+use ip_sniffer::scan::OpenPort;
+
+use crate::json::Json;
+use crate::mail;
+use crate::policy::Violation;
+use crate::probe::{ServiceReport, Value};
+use crate::risk::{Finding, Level, Summary};
+
+// `--report-template` renders the results of a scan with a Handlebars template, e.g. an
+// executive summary:
+//
+// # Scan of {{target}}, {{date}}
+// {{#if note}}Requested for {{note}}.{{/if}}
+//
+// {{open_count}} open, {{closed_count}} closed, {{filtered_count}} filtered
+// {{#each open}}
+// - {{port}}{{#if service}} ({{service}}){{/if}}{{#if risk}}, {{risk}} risk{{/if}}
+// {{else}}
+// No open ports were found.
+// {{/each}}
+//
+// `{{path}}` inserts a value with HTML escaped and `{{{path}}}` inserts it as is. Blocks
+// are `{{#each}}`, `{{#if}}`, `{{#unless}}` and `{{#with}}`, each with an optional
+// `{{else}}`; inside `{{#each}}`, `{{this}}`, `{{@index}}`, `{{@first}}`, `{{@last}}` and
+// `{{@key}}` are the current item, `../name` is a value of the enclosing scope and
+// `@root.name` one of the report. `{{! comments }}` are dropped, and lines holding only a
+// block tag or comment leave no blank line behind. The values of the report are those
+// listed by `Report::context`.
+
+/// The block helpers a template may use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Helper {
+    Each,
+    If,
+    Unless,
+    With,
+}
+
+impl Helper {
+    fn parse(name: &str) -> Result<Helper, &'static str> {
+        match name {
+            "each" => Ok(Helper::Each),
+            "if" => Ok(Helper::If),
+            "unless" => Ok(Helper::Unless),
+            "with" => Ok(Helper::With),
+            _ => Err("unknown block helper in report template; must be each, if, unless or with"),
+        }
+    }
+}
+
+/// A tag or run of text, before blocks are matched up.
+#[derive(Debug)]
+enum Piece {
+    Text(String),
+    Value { path: String, escape: bool },
+    Open(Helper, String),
+    Else,
+    Close(Helper),
+    Comment,
+}
+
+impl Piece {
+    /// Whether the tag vanishes from the output, so a line holding only it is dropped.
+    fn is_standalone_kind(&self) -> bool {
+        matches!(
+            self,
+            Piece::Open(..) | Piece::Else | Piece::Close(_) | Piece::Comment
+        )
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Value {
+        path: String,
+        escape: bool,
+    },
+    Block {
+        helper: Helper,
+        path: String,
+        body: Vec<Node>,
+        inverse: Vec<Node>,
+    },
+}
+
+/// A parsed template.
+///
+/// # Usage
+///
+/// * `Template::load(path)` - Read and parse a template file.
+/// * `template.render(&report.context())` - Render it with the results of a scan.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    /// Reads and parses a template file.
+    ///
+    /// # Errors
+    ///
+    /// * "failed to read report template" if the file cannot be read.
+    /// * Any error returned by `Template::parse`.
+    pub fn load(path: &str) -> Result<Template, &'static str> {
+        let contents = fs::read_to_string(path).map_err(|_| "failed to read report template")?;
+        Template::parse(&contents)
+    }
+
+    /// Parses the contents of a template.
+    ///
+    /// # Errors
+    ///
+    /// * "unterminated tag in report template" if a `{{` is not closed.
+    /// * "unknown block helper in report template; must be each, if, unless or with" if a
+    ///   block uses another helper.
+    /// * "block without a value in report template" if a block names no value.
+    /// * "unexpected {{else}} or closing tag in report template" if they do not match an
+    ///   open block.
+    /// * "unclosed block in report template" if a block is not closed.
+    pub fn parse(contents: &str) -> Result<Template, &'static str> {
+        let pieces = standalone(tokenize(contents)?);
+        let mut pieces = pieces.into_iter();
+        let (nodes, _) = build(&mut pieces, None)?;
+        Ok(Template { nodes })
+    }
+
+    /// Renders the template with `context` as its root value.
+    pub fn render(&self, context: &Json) -> String {
+        let mut out = String::new();
+        render(&self.nodes, &[Scope::new(context)], &mut out);
+        out
+    }
+}
+
+/// Splits a template into text and tags.
+fn tokenize(contents: &str) -> Result<Vec<Piece>, &'static str> {
+    let unterminated = "unterminated tag in report template";
+    let mut pieces = vec![];
+    let mut rest = contents;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            pieces.push(Piece::Text(rest[..start].to_string()));
+        }
+        rest = &rest[start..];
+
+        if let Some(tag) = rest.strip_prefix("{{{") {
+            let end = tag.find("}}}").ok_or(unterminated)?;
+            pieces.push(Piece::Value {
+                path: tag[..end].trim().to_string(),
+                escape: false,
+            });
+            rest = &tag[end + 3..];
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("{{!--") {
+            let end = comment.find("--}}").ok_or(unterminated)?;
+            pieces.push(Piece::Comment);
+            rest = &comment[end + 4..];
+            continue;
+        }
+
+        let tag = &rest[2..];
+        let end = tag.find("}}").ok_or(unterminated)?;
+        let inside = tag[..end].trim();
+        rest = &tag[end + 2..];
+
+        pieces.push(if inside.starts_with('!') {
+            Piece::Comment
+        } else if let Some(open) = inside.strip_prefix('#') {
+            let (helper, path) = open.split_once(char::is_whitespace).unwrap_or((open, ""));
+            let path = path.trim();
+            if path.is_empty() {
+                return Err("block without a value in report template");
+            }
+            Piece::Open(Helper::parse(helper)?, path.to_string())
+        } else if let Some(close) = inside.strip_prefix('/') {
+            Piece::Close(Helper::parse(close.trim())?)
+        } else if inside == "else" {
+            Piece::Else
+        } else {
+            Piece::Value {
+                path: inside.to_string(),
+                escape: true,
+            }
+        });
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest.to_string()));
+    }
+    Ok(pieces)
+}
+
+/// Drops the indentation and line break around block tags and comments that stand alone on
+/// their line, so they leave no blank line in the output.
+fn standalone(mut pieces: Vec<Piece>) -> Vec<Piece> {
+    let blank = |text: &str| text.chars().all(|c| c == ' ' || c == '\t' || c == '\r');
+    let count = pieces.len();
+
+    // Decided on the original text, as trimming one tag's line must not affect the next.
+    let alone: Vec<bool> = (0..count)
+        .map(|i| {
+            if !pieces[i].is_standalone_kind() {
+                return false;
+            }
+            let before = match i.checked_sub(1).map(|j| &pieces[j]) {
+                None => true,
+                Some(Piece::Text(text)) => match text.rfind('\n') {
+                    Some(at) => blank(&text[at + 1..]),
+                    None => i == 1 && blank(text),
+                },
+                Some(_) => false,
+            };
+            let after = match pieces.get(i + 1) {
+                None => true,
+                Some(Piece::Text(text)) => match text.find('\n') {
+                    Some(at) => blank(&text[..at]),
+                    None => i + 2 == count && blank(text),
+                },
+                Some(_) => false,
+            };
+            before && after
+        })
+        .collect();
+
+    for (i, piece) in pieces.iter_mut().enumerate() {
+        let Piece::Text(text) = piece else {
+            continue;
+        };
+        let start = match i.checked_sub(1).is_some_and(|j| alone[j]) {
+            true => text.find('\n').map_or(text.len(), |at| at + 1),
+            false => 0,
+        };
+        let end = match alone.get(i + 1) {
+            Some(true) => text.rfind('\n').map_or(0, |at| at + 1),
+            _ => text.len(),
+        };
+        *text = text
+            .get(start..end.max(start))
+            .unwrap_or_default()
+            .to_string();
+    }
+    pieces
+}
+
+/// Matches up blocks, returning the nodes up to the close of `open`, and those after its
+/// `{{else}}`.
+fn build(
+    pieces: &mut impl Iterator<Item = Piece>,
+    open: Option<Helper>,
+) -> Result<(Vec<Node>, Vec<Node>), &'static str> {
+    let unexpected = "unexpected {{else}} or closing tag in report template";
+    let mut body = vec![];
+    let mut inverse = vec![];
+    let mut in_inverse = false;
+
+    while let Some(piece) = pieces.next() {
+        let nodes = if in_inverse { &mut inverse } else { &mut body };
+        match piece {
+            Piece::Text(text) if !text.is_empty() => nodes.push(Node::Text(text)),
+            Piece::Text(_) | Piece::Comment => {}
+            Piece::Value { path, escape } => nodes.push(Node::Value { path, escape }),
+            Piece::Open(helper, path) => {
+                let (block, block_inverse) = build(pieces, Some(helper))?;
+                nodes.push(Node::Block {
+                    helper,
+                    path,
+                    body: block,
+                    inverse: block_inverse,
+                });
+            }
+            Piece::Else if open.is_some() && !in_inverse => in_inverse = true,
+            Piece::Close(helper) if open == Some(helper) => return Ok((body, inverse)),
+            Piece::Else | Piece::Close(_) => return Err(unexpected),
+        }
+    }
+
+    match open {
+        Some(_) => Err("unclosed block in report template"),
+        None => Ok((body, inverse)),
+    }
+}
+
+/// A value templates are rendered against, with the data variables of `{{#each}}`.
+#[derive(Clone)]
+struct Scope<'a> {
+    value: &'a Json,
+    index: Option<usize>,
+    last: bool,
+    key: Option<&'a str>,
+}
+
+impl<'a> Scope<'a> {
+    fn new(value: &'a Json) -> Scope<'a> {
+        Scope {
+            value,
+            index: None,
+            last: false,
+            key: None,
+        }
+    }
+}
+
+fn render(nodes: &[Node], stack: &[Scope], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value { path, escape } => {
+                let text = display(&lookup(stack, path));
+                match escape {
+                    true => out.push_str(&escape_html(&text)),
+                    false => out.push_str(&text),
+                }
+            }
+            Node::Block {
+                helper,
+                path,
+                body,
+                inverse,
+            } => {
+                let value = lookup(stack, path);
+                match helper {
+                    Helper::Each => each(&value, body, inverse, stack, out),
+                    Helper::If if truthy(&value) => render(body, stack, out),
+                    Helper::Unless if !truthy(&value) => render(body, stack, out),
+                    Helper::With if truthy(&value) => {
+                        let mut inner = stack.to_vec();
+                        inner.push(Scope::new(&value));
+                        render(body, &inner, out);
+                    }
+                    _ => render(inverse, stack, out),
+                }
+            }
+        }
+    }
+}
+
+/// Renders `body` once per element of an array or member of an object, or `inverse` if
+/// there are none.
+fn each(value: &Json, body: &[Node], inverse: &[Node], stack: &[Scope], out: &mut String) {
+    let items: Vec<(Option<&str>, &Json)> = match value {
+        Json::Array(items) => items.iter().map(|item| (None, item)).collect(),
+        Json::Object(members) => members.iter().map(|(k, v)| (Some(k.as_str()), v)).collect(),
+        _ => vec![],
+    };
+    if items.is_empty() {
+        render(inverse, stack, out);
+        return;
+    }
+
+    let count = items.len();
+    for (index, (key, item)) in items.into_iter().enumerate() {
+        let mut inner = stack.to_vec();
+        inner.push(Scope {
+            value: item,
+            index: Some(index),
+            last: index + 1 == count,
+            key,
+        });
+        render(body, &inner, out);
+    }
+}
+
+/// Finds the value of `path`: `this`, a dotted member path, `../` to leave a scope,
+/// `@root.` to start from the report, or a data variable such as `@index`.
+fn lookup<'a>(stack: &[Scope<'a>], path: &str) -> Cow<'a, Json> {
+    let Some(mut scope) = stack.len().checked_sub(1) else {
+        return Cow::Owned(Json::Null);
+    };
+    let mut path = path;
+
+    while let Some(rest) = path.strip_prefix("../") {
+        scope = scope.saturating_sub(1);
+        path = rest;
+    }
+    let current = &stack[scope];
+    match path {
+        "@index" => {
+            return Cow::Owned(current.index.map_or(Json::Null, |i| Json::Number(i as f64)))
+        }
+        "@first" => return Cow::Owned(Json::Bool(current.index == Some(0))),
+        "@last" => return Cow::Owned(Json::Bool(current.last)),
+        "@key" => {
+            return Cow::Owned(
+                current
+                    .key
+                    .map_or(Json::Null, |k| Json::String(k.to_string())),
+            )
+        }
+        _ => {}
+    }
+
+    let (mut value, path) = match path.strip_prefix("@root") {
+        Some(rest) => (stack[0].value, rest.trim_start_matches('.')),
+        None => (current.value, path),
+    };
+    let path = path
+        .strip_prefix("this")
+        .map_or(path, |rest| rest.trim_start_matches('.'));
+    for name in path.split(['.', '/']).filter(|name| !name.is_empty()) {
+        value = match value {
+            Json::Array(items) => match name.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                Some(item) => item,
+                None => return Cow::Owned(Json::Null),
+            },
+            _ => match value.get(name) {
+                Some(member) => member,
+                None => return Cow::Owned(Json::Null),
+            },
+        };
+    }
+    Cow::Borrowed(value)
+}
+
+/// Whether a value passes `{{#if}}`: anything but `null`, `false`, `0`, `""` and `[]`.
+fn truthy(value: &Json) -> bool {
+    match value {
+        Json::Null => false,
+        Json::Bool(b) => *b,
+        Json::Number(n) => *n != 0.0,
+        Json::String(s) => !s.is_empty(),
+        Json::Array(items) => !items.is_empty(),
+        Json::Object(_) => true,
+    }
+}
+
+/// Formats a value for the output; arrays are joined by commas and objects are left out.
+fn display(value: &Json) -> String {
+    match value {
+        Json::Null | Json::Object(_) => String::new(),
+        Json::Bool(b) => b.to_string(),
+        Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => (*n as i64).to_string(),
+        Json::Number(n) => n.to_string(),
+        Json::String(s) => s.clone(),
+        Json::Array(items) => {
+            let items: Vec<String> = items.iter().map(display).collect();
+            items.join(",")
+        }
+    }
+}
+
+/// Escapes the characters Handlebars escapes in `{{path}}`.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            '`' => out.push_str("&#x60;"),
+            '=' => out.push_str("&#x3D;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The results of a single scan, as templates see them.
+pub struct Report<'a> {
+    /// The hostname, in its ASCII form, or address given on the command line.
+    pub target: String,
+    pub addrs: &'a [IpAddr],
+    pub note: Option<&'a str>,
+    /// When the scan finished, in seconds since the UNIX epoch.
+    pub finished: u64,
+    pub duration: Duration,
+    /// The open ports, by address.
+    pub open: &'a [(IpAddr, Vec<OpenPort>)],
+    pub closed: usize,
+    pub filtered: usize,
+    /// What probes found, by address.
+    pub services: &'a [(IpAddr, Vec<(u16, ServiceReport)>)],
+    /// The findings of `--risk`, by address, or `None` without it.
+    pub risks: Option<&'a [(IpAddr, Vec<Finding>)]>,
+    pub violations: &'a [Violation],
+    /// Why the host looks like a honeypot, if it does.
+    pub honeypot: &'a [String],
+}
+
+impl Report<'_> {
+    /// Builds the root value templates are rendered against.
+    ///
+    /// # Returns
+    ///
+    /// An object with:
+    ///
+    /// * `tool`, `version` - `ip-sniffer` and its version.
+    /// * `target`, `addresses` - What was scanned; an internationalized hostname is in
+    ///   its Unicode form, with the ASCII form it was resolved by in `target_ascii`.
+    /// * `note` - The `--note`, or `null`.
+    /// * `date`, `finished_at` - When the scan finished, as an RFC 5322 date and a UNIX
+    ///   timestamp.
+    /// * `duration_secs` - How long it took.
+    /// * `open_count`, `closed_count`, `filtered_count` - The totals.
+    /// * `open` - The open ports, each with the `address` it was first found open on,
+    ///   `port`, `confidence`, and `service` and `risk` on that address if known.
+    /// * `services` - What probes found, each with `address`, `port`, `transport`,
+    ///   `service` and `fields`, a list of `name` and `value`.
+    /// * `risk` - With `--risk`, the `highest` level and the number of findings at each
+    ///   level, e.g. `critical`; otherwise `null`.
+    /// * `violations` - Policy violations, each with `port` and `kind`, `unexpected_open`
+    ///   or `expected_closed`.
+    /// * `honeypot` - Why the host looks like a honeypot, if it does.
+    pub fn context(&self) -> Json {
+        let text = |s: &str| Json::String(s.to_string());
+        let number = |n: usize| Json::Number(n as f64);
+        let findings: Vec<Finding> = self
+            .risks
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|(_, findings)| findings.iter().copied())
+            .collect();
+
+        let services: Vec<(IpAddr, u16, &ServiceReport)> = self
+            .services
+            .iter()
+            .flat_map(|(addr, reports)| reports.iter().map(|(port, report)| (*addr, *port, report)))
+            .collect();
+
+        // A port open on several addresses is listed once, under the first.
+        let mut open: Vec<(IpAddr, &OpenPort)> = vec![];
+        for (addr, ports) in self.open {
+            for p in ports {
+                if !open.iter().any(|(_, o)| o.port == p.port) {
+                    open.push((*addr, p));
+                }
+            }
+        }
+        open.sort_by_key(|(_, p)| p.port);
+        let open_count = open.len();
+        let open = open
+            .into_iter()
+            .map(|(addr, p)| {
+                let service = services
+                    .iter()
+                    .find(|(a, port, _)| *a == addr && *port == p.port);
+                let risk = self
+                    .risks
+                    .unwrap_or_default()
+                    .iter()
+                    .filter(|(a, _)| *a == addr)
+                    .flat_map(|(_, findings)| findings)
+                    .find(|f| f.port == p.port);
+                Json::Object(vec![
+                    ("address".to_string(), text(&addr.to_string())),
+                    ("port".to_string(), number(p.port.into())),
+                    ("confidence".to_string(), text(p.confidence.as_str())),
+                    (
+                        "service".to_string(),
+                        service.map_or(Json::Null, |(_, _, s)| text(s.service)),
+                    ),
+                    (
+                        "risk".to_string(),
+                        risk.map_or(Json::Null, |f| text(f.level.as_str())),
+                    ),
+                ])
+            })
+            .collect();
+
+        let services = services
+            .iter()
+            .map(|(addr, port, report)| {
+                let fields = report
+                    .fields
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = match value {
+                            Value::Text(s) => s.clone(),
+                            Value::List(items) => items.join(", "),
+                            Value::Map(pairs) => {
+                                let pairs: Vec<String> =
+                                    pairs.iter().map(|(k, v)| format!("{} {}", k, v)).collect();
+                                pairs.join(", ")
+                            }
+                        };
+                        Json::Object(vec![
+                            ("name".to_string(), text(name)),
+                            ("value".to_string(), Json::String(value)),
+                        ])
+                    })
+                    .collect();
+                Json::Object(vec![
+                    ("address".to_string(), text(&addr.to_string())),
+                    ("port".to_string(), number((*port).into())),
+                    ("transport".to_string(), text(report.transport)),
+                    ("service".to_string(), text(report.service)),
+                    ("fields".to_string(), Json::Array(fields)),
+                ])
+            })
+            .collect();
+
+        let risk = self.risks.map_or(Json::Null, |_| {
+            let summary = Summary::of(&findings);
+            let mut members = vec![(
+                "highest".to_string(),
+                summary.highest().map_or(Json::Null, |l| text(l.as_str())),
+            )];
+            for level in Level::ALL {
+                members.push((level.to_string(), number(summary.count(level))));
+            }
+            Json::Object(members)
+        });
+
+        let violations = self
+            .violations
+            .iter()
+            .map(|violation| {
+                let kind = match violation {
+                    Violation::UnexpectedOpen(_) => "unexpected_open",
+                    Violation::ExpectedClosed(_) => "expected_closed",
+                };
+                Json::Object(vec![
+                    ("port".to_string(), number(violation.port().into())),
+                    ("kind".to_string(), text(kind)),
+                ])
+            })
+            .collect();
+
+        Json::Object(vec![
+            ("tool".to_string(), text("ip-sniffer")),
+            ("version".to_string(), text(env!("CARGO_PKG_VERSION"))),
+            (
+                "target".to_string(),
+                Json::String(idna::to_unicode(&self.target)),
+            ),
+            ("target_ascii".to_string(), text(&self.target)),
+            (
+                "addresses".to_string(),
+                Json::Array(self.addrs.iter().map(|a| text(&a.to_string())).collect()),
+            ),
+            ("note".to_string(), self.note.map_or(Json::Null, text)),
+            ("date".to_string(), Json::String(mail::date(self.finished))),
+            (
+                "finished_at".to_string(),
+                Json::Number(self.finished as f64),
+            ),
+            (
+                "duration_secs".to_string(),
+                Json::Number((self.duration.as_secs_f64() * 10.0).round() / 10.0),
+            ),
+            ("open_count".to_string(), number(open_count)),
+            ("closed_count".to_string(), number(self.closed)),
+            ("filtered_count".to_string(), number(self.filtered)),
+            ("open".to_string(), Json::Array(open)),
+            ("services".to_string(), Json::Array(services)),
+            ("risk".to_string(), risk),
+            ("violations".to_string(), Json::Array(violations)),
+            (
+                "honeypot".to_string(),
+                Json::Array(self.honeypot.iter().map(|r| text(r)).collect()),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, context: &str) -> String {
+        let context = Json::parse(context).unwrap();
+        Template::parse(template).unwrap().render(&context)
+    }
+
+    #[test]
+    fn renders_values_and_blocks() {
+        let context = r#"{"name":"<a&b>","ports":[22,80],"empty":[],"note":null}"#;
+        assert_eq!(
+            render("{{name}} {{{name}}}", context),
+            "&lt;a&amp;b&gt; <a&b>"
+        );
+        assert_eq!(
+            render(
+                "{{#each ports}}{{@index}}:{{this}}{{#unless @last}},{{/unless}}{{/each}}",
+                context
+            ),
+            "0:22,1:80"
+        );
+        assert_eq!(
+            render("{{#each empty}}x{{else}}none{{/each}}", context),
+            "none"
+        );
+        assert_eq!(render("{{#if note}}yes{{else}}no{{/if}}", context), "no");
+        assert_eq!(
+            render("{{#each ports}}{{../name}}{{/each}}", context),
+            "&lt;a&amp;b&gt;&lt;a&amp;b&gt;"
+        );
+        assert_eq!(
+            render("a{{! dropped }}b{{!-- {{also}} --}}c", context),
+            "abc"
+        );
+    }
+
+    #[test]
+    fn trims_standalone_tags() {
+        let template = "ports:\n  {{#each ports}}\n- {{this}}\n  {{/each}}\n{{! end }}\ndone\n";
+        assert_eq!(
+            render(template, r#"{"ports":[22,80]}"#),
+            "ports:\n- 22\n- 80\ndone\n"
+        );
+
+        // A tag that shares its line with text or a value is not standalone.
+        let template = "{{#if on}}on{{/if}}\n{{x}} {{#if on}}\nnext\n{{/if}}\n";
+        assert_eq!(render(template, r#"{"on":true,"x":1}"#), "on\n1 \nnext\n");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(Template::parse("{{name").is_err());
+        assert!(Template::parse("{{#loop items}}{{/loop}}").is_err());
+        assert!(Template::parse("{{#if}}{{/if}}").is_err());
+        assert!(Template::parse("{{/if}}").is_err());
+        assert!(Template::parse("{{#if a}}{{/each}}").is_err());
+        assert!(Template::parse("{{#each a}}").is_err());
+        assert!(Template::parse("{{else}}").is_err());
+    }
+}
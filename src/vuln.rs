@@ -0,0 +1,194 @@
+use std::fs;
+
+use crate::probe::{ServiceReport, Value};
+
+// Vulnerability data files hold one advisory per line: the product, the first affected
+// version, the first fixed version and the CVE ID, separated by whitespace:
+//
+// # product  affected  fixed    id
+// apache     2.4.49    2.4.50   CVE-2021-41773
+// openssh    8.5       9.8      CVE-2024-6387
+//
+// Products are matched case-insensitively against the names probes report, e.g. the
+// `Apache/2.4.49` of an HTTP `Server` header or the `OpenSSH_8.9p1` of an SSH banner.
+
+/// Advisories used when no data file is given, as `(product, affected, fixed, id)`.
+const BUILTIN: &[(&str, &str, &str, &str)] = &[
+    ("apache", "2.4.49", "2.4.50", "CVE-2021-41773"),
+    ("apache", "2.4.49", "2.4.51", "CVE-2021-42013"),
+    ("exim", "4.87", "4.92", "CVE-2019-10149"),
+    ("microsoft-iis", "6.0", "6.1", "CVE-2017-7269"),
+    ("mysql", "5.1", "5.1.63", "CVE-2012-2122"),
+    ("mysql", "5.5", "5.5.24", "CVE-2012-2122"),
+    ("nginx", "0.6.18", "1.20.1", "CVE-2021-23017"),
+    ("openssh", "2.3", "7.8", "CVE-2018-15473"),
+    ("openssh", "8.5", "9.8", "CVE-2024-6387"),
+    ("openssl", "1.0.1", "1.0.1g", "CVE-2014-0160"),
+];
+
+/// Report fields whose text names products, e.g. `nginx/1.18.0 (Ubuntu)`.
+const PRODUCT_FIELDS: &[&str] = &["server", "powered_by", "banner"];
+
+/// A known vulnerability of a range of versions of a product.
+#[derive(Debug)]
+pub struct Advisory {
+    /// Lowercase product name, e.g. `openssh`.
+    pub product: String,
+    /// The first affected version.
+    pub affected: Version,
+    /// The first version that is no longer affected.
+    pub fixed: Version,
+    /// The CVE ID, e.g. `CVE-2021-41773`.
+    pub id: String,
+}
+
+/// A version split into its numbers and words, e.g. `7.4p1` into `7`, `4`, `p` and `1`,
+/// so that `7.4p1` sorts after `7.4` and before `7.10`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(Vec<Part>);
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Part {
+    Number(u64),
+    Word(String),
+}
+
+impl Version {
+    /// Parses a version; separators such as `.`, `-` and `_` are dropped.
+    ///
+    /// # Returns
+    ///
+    /// `None` if `text` does not start with a digit.
+    pub fn parse(text: &str) -> Option<Version> {
+        if !text.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut parts = vec![];
+        let mut rest = text;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+            rest = &rest[start..];
+            let digits = rest.starts_with(|c: char| c.is_ascii_digit());
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() || c.is_ascii_digit() != digits)
+                .unwrap_or(rest.len());
+
+            parts.push(match digits {
+                true => Part::Number(rest[..end].parse().unwrap_or(u64::MAX)),
+                false => Part::Word(rest[..end].to_ascii_lowercase()),
+            });
+            rest = &rest[end..];
+        }
+        Some(Version(parts))
+    }
+}
+
+/// Returns the built-in advisories.
+pub fn builtin() -> Vec<Advisory> {
+    BUILTIN
+        .iter()
+        .filter_map(|(product, affected, fixed, id)| {
+            Some(Advisory {
+                product: product.to_string(),
+                affected: Version::parse(affected)?,
+                fixed: Version::parse(fixed)?,
+                id: id.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Loads advisories from a data file, used with `--vuln-data` instead of the built-in ones.
+///
+/// # Errors
+///
+/// * "failed to read vulnerability data file" if the file cannot be read.
+/// * "invalid line in vulnerability data file" if a line is not a product, two versions
+///   and an ID.
+/// * "vulnerability data file is empty" if it holds no advisories.
+pub fn load(path: &str) -> Result<Vec<Advisory>, &'static str> {
+    let contents =
+        fs::read_to_string(path).map_err(|_| "failed to read vulnerability data file")?;
+    let mut advisories = vec![];
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let [product, affected, fixed, id] = words[..] else {
+            return Err("invalid line in vulnerability data file");
+        };
+        advisories.push(Advisory {
+            product: product.to_ascii_lowercase(),
+            affected: Version::parse(affected).ok_or("invalid line in vulnerability data file")?,
+            fixed: Version::parse(fixed).ok_or("invalid line in vulnerability data file")?,
+            id: id.to_string(),
+        });
+    }
+
+    if advisories.is_empty() {
+        return Err("vulnerability data file is empty");
+    }
+    Ok(advisories)
+}
+
+/// Returns the IDs of the advisories that may apply to the products named in `report`.
+///
+/// # Description
+///
+/// Products and versions are taken from the `version` field, which names the version of
+/// the reported service itself, and from `name/version`, `name_version` and
+/// `name version` words of the `server`, `powered_by` and `banner` fields. A match only
+/// means the reported version falls in an affected range: backported fixes and banners
+/// that lie are not detected, so the IDs are hints to follow up, not findings.
+pub fn hints(report: &ServiceReport, advisories: &[Advisory]) -> Vec<String> {
+    let products = products(report);
+    let mut ids: Vec<String> = vec![];
+
+    for advisory in advisories {
+        let applies = products.iter().any(|(product, version)| {
+            *product == advisory.product
+                && advisory.affected <= *version
+                && *version < advisory.fixed
+        });
+        if applies && !ids.contains(&advisory.id) {
+            ids.push(advisory.id.clone());
+        }
+    }
+    ids
+}
+
+/// Returns the lowercase products and their versions named in `report`.
+fn products(report: &ServiceReport) -> Vec<(String, Version)> {
+    let mut products = vec![];
+
+    for (name, value) in &report.fields {
+        let Value::Text(text) = value else {
+            continue;
+        };
+
+        if *name == "version" {
+            if let Some(version) = Version::parse(text) {
+                products.push((report.service.to_string(), version));
+            }
+        } else if PRODUCT_FIELDS.contains(name) {
+            let text = text.strip_prefix("SSH-2.0-").unwrap_or(text);
+            let words: Vec<&str> = text.split([' ', ',', '(', ')']).collect();
+
+            for (i, word) in words.iter().enumerate() {
+                let named = word
+                    .split_once(['/', '_'])
+                    .or_else(|| words.get(i + 1).map(|next| (*word, *next)));
+
+                if let Some((product, version)) = named {
+                    if let Some(version) = Version::parse(version) {
+                        products.push((product.to_ascii_lowercase(), version));
+                    }
+                }
+            }
+        }
+    }
+    products
+}
@@ -0,0 +1,105 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::check::{self, Status};
+use crate::i18n::Lang;
+use crate::target;
+
+/// How long to wait for the port when `--timeout` is not given.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait between attempts when `--interval` is not given.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a single connection attempt may take.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Runs the `wait` subcommand.
+///
+/// # Arguments
+///
+/// * `args` - The arguments following `wait` on the command line.
+/// * `lang` - The language progress and results are printed in.
+///
+/// # Returns
+///
+/// The exit code: 0 once the port is open, 1 if it did not open in time.
+///
+/// # Errors
+///
+/// * "missing HOST:PORT" if no target is given.
+/// * "too many arguments" if more than one target is given.
+/// * "--timeout and --interval must be a number of seconds" if either value is invalid.
+/// * "not a valid HOST:PORT" if the target cannot be parsed.
+/// * Any error returned by `check::probe`.
+///
+/// # Usage
+///
+/// * `wait <HOST:PORT>` - Block until the port accepts connections, for up to 60 seconds.
+/// * `wait <HOST:PORT> --timeout <SECS> --interval <SECS>` - Wait up to `--timeout`
+///   seconds, trying every `--interval` seconds.
+///
+/// # Description
+///
+/// Hostnames are resolved again on every attempt, so waiting works for services whose
+/// DNS entry appears only once they start, as in container orchestration.
+pub fn run(args: &[String], lang: Lang) -> Result<i32, &'static str> {
+    let mut target = None;
+    let mut timeout = DEFAULT_TIMEOUT;
+    let mut interval = DEFAULT_INTERVAL;
+    let mut rest = args.iter();
+
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--timeout" => timeout = parse_secs(rest.next())?,
+            "--interval" => interval = parse_secs(rest.next())?,
+            value if target.is_none() => target = Some(value),
+            _ => return Err("too many arguments"),
+        }
+    }
+
+    let target = target.ok_or("missing HOST:PORT")?;
+    let started = Instant::now();
+
+    let message = "waiting up to {}s for {}";
+    eprintln!("{}", lang.format(message, &[&timeout.as_secs(), &target]));
+
+    loop {
+        let remaining = timeout.saturating_sub(started.elapsed());
+
+        let open = match target::parse_host_port(target) {
+            Ok(addr) => {
+                let attempt = ATTEMPT_TIMEOUT.min(remaining).max(Duration::from_millis(1));
+                check::probe(addr, attempt)?.0 == Status::Open
+            }
+            // The name may not exist yet.
+            Err("failed to resolve hostname") => false,
+            Err(err) => return Err(err),
+        };
+
+        if open {
+            let elapsed = format!("{:.1}", started.elapsed().as_secs_f64());
+            println!(
+                "{}",
+                lang.format("{} is open after {}s", &[&target, &elapsed])
+            );
+            return Ok(0);
+        }
+
+        let remaining = timeout.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            let message = "{} did not open within {}s";
+            println!("{}", lang.format(message, &[&target, &timeout.as_secs()]));
+            return Ok(1);
+        }
+        thread::sleep(interval.min(remaining));
+    }
+}
+
+/// Parses a number of seconds given to `--timeout` or `--interval`.
+fn parse_secs(value: Option<&String>) -> Result<Duration, &'static str> {
+    value
+        .and_then(|v| v.trim_end_matches('s').parse().ok())
+        .map(Duration::from_secs)
+        .ok_or("--timeout and --interval must be a number of seconds")
+}
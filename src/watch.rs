@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ip_sniffer::events::Bus;
+use ip_sniffer::scan::{self, Connector, PortOrder};
+
+use crate::accepted::Accepted;
+use crate::alert::Alert;
+use crate::i18n::Lang;
+use crate::mail::Mailer;
+use crate::metrics::Metrics;
+use crate::notify::{Message, Notifier};
+use crate::output::{self, OutputFormat};
+use crate::risk::Rules;
+use crate::syslog::Syslog;
+
+/// How a port changed between two scans in watch mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Opened,
+    Closed,
+}
+
+impl Change {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Change::Opened => "opened",
+            Change::Closed => "closed",
+        }
+    }
+}
+
+/// When a port was seen open, from the scan it opened in to the scan it closed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeline {
+    /// The first scan that found it open since it last opened, in seconds since the UNIX
+    /// epoch.
+    pub first_seen: u64,
+    /// The latest scan that found it open.
+    pub last_seen: u64,
+    /// The scan that found it closed, if it has closed since.
+    pub closed_at: Option<u64>,
+}
+
+impl Timeline {
+    /// Updates the timelines of every port with the ports found open by a scan at `time`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timelines` - The timelines so far, by port; a port may be any key, such as an
+    ///   address and port.
+    /// * `open` - The ports found open by the scan.
+    /// * `time` - When the scan ran, in seconds since the UNIX epoch.
+    ///
+    /// # Description
+    ///
+    /// A port that reopens after closing starts a new timeline, so `first_seen` is always
+    /// the time it last opened.
+    pub fn track<K: Ord + Copy>(
+        timelines: &mut BTreeMap<K, Timeline>,
+        open: &BTreeSet<K>,
+        time: u64,
+    ) {
+        for &port in open {
+            let timeline = timelines.entry(port).or_insert(Timeline {
+                first_seen: time,
+                last_seen: time,
+                closed_at: None,
+            });
+            if timeline.closed_at.is_some() {
+                timeline.first_seen = time;
+                timeline.closed_at = None;
+            }
+            timeline.last_seen = time;
+        }
+
+        for (port, timeline) in timelines.iter_mut() {
+            if timeline.closed_at.is_none() && !open.contains(port) {
+                timeline.closed_at = Some(time);
+            }
+        }
+    }
+}
+
+/// Settings of watch mode.
+pub struct Watch<'a> {
+    pub addr: IpAddr,
+    pub threads: u16,
+    pub connector: Connector,
+    pub verify: bool,
+    pub interval: Duration,
+    pub output: OutputFormat,
+    pub metrics: Option<&'a Metrics>,
+    pub syslog: Option<&'a Syslog>,
+    pub accepted: Option<&'a Accepted>,
+    pub mailer: Option<&'a Mailer>,
+    /// Whether scans without changes are left out of the mailed reports.
+    pub email_on_change: bool,
+    pub notifiers: &'a [Notifier],
+    /// The rules ports are assessed by for `alerts`.
+    pub rules: Option<&'a Rules>,
+    pub alerts: &'a [Alert],
+    /// The language the text output is printed in.
+    pub lang: Lang,
+}
+
+impl Watch<'_> {
+    /// Scans the host every `interval` until the process is stopped, printing the
+    /// ports that opened or closed since the previous scan.
+    ///
+    /// # Description
+    ///
+    /// The first scan prints the open ports as a baseline; later scans only print
+    /// changes, with when the port was first and last seen open. Each scan's totals are
+    /// added to `metrics`, and open ports and changes are sent to `syslog`, if given.
+    /// Changes of `accepted` findings are only counted. Each scan's report is mailed with
+    /// `mailer`, if given; with `email_on_change`, only the first scan and scans with
+    /// changes are. Those are also posted to `notifiers`. Ports of the first scan and
+    /// ports that open raise `alerts`, and ports that close resolve them.
+    pub fn run(&self) -> ! {
+        let mut previous: Option<BTreeSet<u16>> = None;
+        let mut timelines = BTreeMap::new();
+
+        loop {
+            let started = Instant::now();
+            let mut bus = Bus::new();
+            bus.subscribe(crate::health_warnings(self.lang, false));
+            let host_scan = scan::scan_host_with(
+                self.addr,
+                self.threads,
+                self.connector.clone(),
+                PortOrder::Sequential,
+                None,
+                &mut bus,
+            );
+            let mut open = host_scan.open.clone();
+
+            if self.verify {
+                open = scan::verify(self.addr, &open, &self.connector).0;
+            }
+            let now: BTreeSet<u16> = open.iter().map(|p| p.port).collect();
+            Timeline::track(&mut timelines, &now, timestamp());
+            let mut changed = vec![];
+
+            if let Some(metrics) = self.metrics {
+                metrics.record(self.addr, &host_scan, now.len(), started.elapsed());
+            }
+
+            match &previous {
+                None => self.baseline(&now),
+                Some(before) => {
+                    let changes = now
+                        .difference(before)
+                        .map(|&port| (port, Change::Opened))
+                        .chain(before.difference(&now).map(|&port| (port, Change::Closed)));
+                    let mut suppressed = 0;
+
+                    for (port, change) in changes {
+                        match self.accepted {
+                            Some(accepted) if accepted.contains(self.addr, port) => suppressed += 1,
+                            _ => {
+                                self.report(port, change, &timelines[&port]);
+                                changed.push((port, change));
+                            }
+                        }
+                    }
+                    if suppressed > 0 {
+                        self.report_accepted(suppressed);
+                    }
+                }
+            }
+            self.deliver(&now, &changed, &timelines, previous.is_none());
+            if previous.is_none() {
+                let baseline: Vec<(u16, Change)> = now
+                    .iter()
+                    .filter(|&&port| !self.accepted.is_some_and(|a| a.contains(self.addr, port)))
+                    .map(|&port| (port, Change::Opened))
+                    .collect();
+                self.escalate(&baseline);
+            } else {
+                self.escalate(&changed);
+            }
+            previous = Some(now);
+
+            thread::sleep(self.interval.saturating_sub(started.elapsed()));
+        }
+    }
+
+    /// Prints the open ports found by the first scan.
+    fn baseline(&self, open: &BTreeSet<u16>) {
+        if let Some(syslog) = self.syslog {
+            for port in open {
+                syslog.log(&format!("{}: port {} is open", self.addr, port));
+            }
+        }
+
+        match self.output {
+            OutputFormat::Text => {
+                let ports: Vec<String> = open.iter().map(u16::to_string).collect();
+                let message = "watching {} every {}s; open: {}";
+                let interval = self.interval.as_secs();
+                let ports = ports.join(", ");
+                println!(
+                    "{}",
+                    self.lang.format(message, &[&self.addr, &interval, &ports])
+                );
+            }
+            OutputFormat::Jsonl => {
+                for &port in open {
+                    println!("{}", output::jsonl_record(self.addr, port));
+                }
+            }
+        }
+    }
+
+    /// Mails the report of a scan, and posts it to `notifiers` if ports changed or it is the
+    /// first scan. Delivery failures are printed and the watch goes on.
+    fn deliver(
+        &self,
+        open: &BTreeSet<u16>,
+        changed: &[(u16, Change)],
+        timelines: &BTreeMap<u16, Timeline>,
+        baseline: bool,
+    ) {
+        let quiet = !baseline && changed.is_empty();
+        let (subject, body) = self.summarize(open, changed, timelines, baseline);
+
+        if let (Some(mailer), false) = (self.mailer, quiet && self.email_on_change) {
+            if let Err(err) = mailer.send(&subject, &body) {
+                let message = "warning: failed to send email report: {}";
+                eprintln!("{}", self.lang.format(message, &[&self.lang.tr(err)]));
+            }
+        }
+
+        if quiet {
+            return;
+        }
+        let message = Message {
+            title: subject,
+            text: body,
+            alert: changed.iter().any(|(_, change)| *change == Change::Opened),
+        };
+        for notifier in self.notifiers {
+            if let Err(err) = notifier.send(&message) {
+                let message = "warning: failed to send notification: {}";
+                eprintln!("{}", self.lang.format(message, &[&self.lang.tr(err)]));
+            }
+        }
+    }
+
+    /// Opens an incident for every port that opened at or above the level of an alert, and
+    /// resolves those of ports that closed. Failures are printed and the watch goes on.
+    fn escalate(&self, changed: &[(u16, Change)]) {
+        let Some(rules) = self.rules else {
+            return;
+        };
+        let ports: Vec<u16> = changed.iter().map(|&(port, _)| port).collect();
+        let findings = rules.assess(self.addr, &ports, &[]);
+
+        // Findings come in port order rather than in the order of `changed`.
+        for finding in &findings {
+            let Some(&(_, change)) = changed.iter().find(|&&(port, _)| port == finding.port) else {
+                continue;
+            };
+            for alert in self.alerts.iter().filter(|a| a.applies_to(finding)) {
+                let (message, result) = match change {
+                    Change::Opened => (
+                        "warning: failed to open incident: {}",
+                        alert.trigger(self.addr, finding),
+                    ),
+                    Change::Closed => (
+                        "warning: failed to resolve incident: {}",
+                        alert.resolve(self.addr, finding.port),
+                    ),
+                };
+                if let Err(err) = result {
+                    eprintln!("{}", self.lang.format(message, &[&self.lang.tr(err)]));
+                }
+            }
+        }
+    }
+
+    /// Builds the title and text of a scan's report: the open ports of the first scan, or
+    /// the changes since the previous scan with when each port was seen open.
+    fn summarize(
+        &self,
+        open: &BTreeSet<u16>,
+        changed: &[(u16, Change)],
+        timelines: &BTreeMap<u16, Timeline>,
+        baseline: bool,
+    ) -> (String, String) {
+        let (title, mut text) = if baseline {
+            (
+                format!("ip-sniffer: watching {}", self.addr),
+                format!(
+                    "watching {} every {}s\n",
+                    self.addr,
+                    self.interval.as_secs()
+                ),
+            )
+        } else if changed.is_empty() {
+            (
+                format!("ip-sniffer: no changes on {}", self.addr),
+                "no ports opened or closed since the previous scan\n".to_string(),
+            )
+        } else {
+            let mut text = String::new();
+            for &(port, change) in changed {
+                let timeline = &timelines[&port];
+                text += &match change {
+                    Change::Opened => format!("{} opened at {}\n", port, timeline.first_seen),
+                    Change::Closed => format!(
+                        "{} closed at {}; first seen open at {}, last seen open at {}\n",
+                        port,
+                        timeline.closed_at.unwrap_or_default(),
+                        timeline.first_seen,
+                        timeline.last_seen
+                    ),
+                };
+            }
+            (
+                format!(
+                    "ip-sniffer: {} port(s) changed on {}",
+                    changed.len(),
+                    self.addr
+                ),
+                text,
+            )
+        };
+        let ports: Vec<String> = open.iter().map(u16::to_string).collect();
+        text += &format!("\nopen: {}\n", ports.join(", "));
+        (title, text)
+    }
+
+    /// Prints the number of accepted findings that changed since the previous scan.
+    fn report_accepted(&self, count: usize) {
+        match self.output {
+            OutputFormat::Text => {
+                let message = "[{}] {}: {} accepted change(s) not reported";
+                let now = timestamp();
+                println!("{}", self.lang.format(message, &[&now, &self.addr, &count]))
+            }
+            OutputFormat::Jsonl => {
+                println!("{}", output::jsonl_accepted_record(self.addr, count))
+            }
+        }
+    }
+
+    /// Prints a port that changed since the previous scan.
+    fn report(&self, port: u16, change: Change, timeline: &Timeline) {
+        let timestamp = timeline.closed_at.unwrap_or(timeline.first_seen);
+
+        if let Some(syslog) = self.syslog {
+            syslog.log(&format!("{}: port {} {}", self.addr, port, change.as_str()));
+        }
+
+        match self.output {
+            OutputFormat::Text if change == Change::Closed => {
+                let message = "[{}] {}: {} closed; first seen open at {}, last seen open at {}";
+                let (first, last) = (&timeline.first_seen, &timeline.last_seen);
+                println!(
+                    "{}",
+                    self.lang
+                        .format(message, &[&timestamp, &self.addr, &port, first, last])
+                )
+            }
+            OutputFormat::Text => println!(
+                "{}",
+                self.lang
+                    .format("[{}] {}: {} opened", &[&timestamp, &self.addr, &port])
+            ),
+            OutputFormat::Jsonl => println!(
+                "{}",
+                output::jsonl_change_record(self.addr, port, change, timeline)
+            ),
+        }
+    }
+}
+
+/// Returns the current UNIX timestamp in seconds.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}